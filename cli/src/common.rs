@@ -1,7 +1,10 @@
 use crate::SharedOptions;
 
 use probe_rs::{
-    architecture::arm::ap::AccessPortError, config::TargetSelector, flashing::FileDownloadError,
+    architecture::arm::ap::AccessPortError,
+    config::{FlmError, RawFlashAlgorithm, TargetSelector},
+    debug::DebugError,
+    flashing::{option_bytes::OptionBytesError, FileDownloadError},
     DebugProbeError, Error, Probe, Session,
 };
 
@@ -30,13 +33,30 @@ pub enum CliError {
         #[from]
         FileDownloadError,
     ),
+    FlashAlgorithm(
+        #[source]
+        #[from]
+        FlmError,
+    ),
+    OptionBytes(
+        #[source]
+        #[from]
+        OptionBytesError,
+    ),
+    PermanentLock,
     MissingArgument,
+    InvalidArgument(String),
     UnableToOpenProbe(Option<&'static str>),
     ProbeRs(
         #[source]
         #[from]
         Error,
     ),
+    Debug(
+        #[source]
+        #[from]
+        DebugError,
+    ),
 }
 
 impl fmt::Display for CliError {
@@ -48,12 +68,20 @@ impl fmt::Display for CliError {
             AccessPort(ref e) => e.fmt(f),
             StdIO(ref e) => e.fmt(f),
             FileDownload(ref e) => e.fmt(f),
+            FlashAlgorithm(ref e) => e.fmt(f),
+            OptionBytes(ref e) => e.fmt(f),
+            PermanentLock => write!(
+                f,
+                "Read protection level 2 disables the debug interface permanently. Use --allow-permanent-lock if this is intended."
+            ),
             MissingArgument => write!(f, "Command expected more arguments."),
+            InvalidArgument(ref details) => write!(f, "Invalid argument: {}", details),
             UnableToOpenProbe(ref details) => match details {
                 None => write!(f, "Unable to open probe."),
                 Some(details) => write!(f, "Unable to open probe: {}", details),
             },
             ProbeRs(ref e) => e.fmt(f),
+            Debug(ref e) => e.fmt(f),
         }
     }
 }
@@ -104,5 +132,9 @@ where
 
     let session = probe.attach(target_selector)?;
 
+    if let Some(ref path) = shared_options.flash_algorithm {
+        session.add_flash_algorithm(RawFlashAlgorithm::from_flm(path)?)?;
+    }
+
     f(session)
 }
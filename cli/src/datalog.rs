@@ -0,0 +1,385 @@
+use crate::{
+    common::{with_device, CliError},
+    option_bytes::parse_value,
+    SharedOptions,
+};
+
+use probe_rs::debug::{BaseType, DebugInfo, Value};
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// The output format of the samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Format {
+    Csv,
+    JsonLines,
+    Vcd,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Format::Csv),
+            "jsonl" => Ok(Format::JsonLines),
+            "vcd" => Ok(Format::Vcd),
+            other => Err(format!(
+                "Unknown format '{}', expected csv, jsonl or vcd",
+                other
+            )),
+        }
+    }
+}
+
+/// A variable which is sampled.
+#[derive(Debug, Clone, PartialEq)]
+struct Signal {
+    name: String,
+    address: u32,
+    typ: BaseType,
+}
+
+pub(crate) struct LogOptions<'a> {
+    pub signals: &'a [String],
+    pub elf: Option<&'a Path>,
+    pub rate: f64,
+    pub format: Format,
+    pub samples: Option<u64>,
+    pub output: Option<&'a Path>,
+}
+
+/// Samples the signals while the core runs, and writes the samples with the
+/// time since the first sample.
+pub(crate) fn log_data(
+    shared_options: &SharedOptions,
+    options: &LogOptions,
+) -> Result<(), CliError> {
+    if !options.rate.is_finite() || options.rate <= 0.0 {
+        return Err(CliError::InvalidArgument(format!(
+            "The sample rate {} is not positive",
+            options.rate
+        )));
+    }
+
+    let debug_info = match options.elf {
+        Some(path) => {
+            Some(DebugInfo::from_file(path).map_err(|e| CliError::InvalidArgument(e.to_string()))?)
+        }
+        None => None,
+    };
+
+    let signals = options
+        .signals
+        .iter()
+        .map(|signal| parse_signal(signal, debug_info.as_ref()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let output: Box<dyn Write> = match options.output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(io::stdout()),
+    };
+    let mut writer = SampleWriter::new(options.format, output, &signals);
+
+    let interval = Duration::from_secs_f64(1.0 / options.rate);
+
+    with_device(shared_options, |session| {
+        let core = session.attach_to_core(0)?;
+        let memory = core.memory();
+
+        writer.write_header()?;
+
+        let start = Instant::now();
+        let mut next = start;
+        let mut count = 0;
+        let mut buffer = [0; 8];
+
+        loop {
+            let time = start.elapsed();
+
+            let mut values = Vec::with_capacity(signals.len());
+            for signal in &signals {
+                let bytes = &mut buffer[..signal.typ.size()];
+                memory.read_block8(signal.address, bytes)?;
+                values.push(signal.typ.decode(bytes));
+            }
+
+            writer.write_sample(time, &values)?;
+
+            count += 1;
+            if options.samples == Some(count) {
+                return Ok(());
+            }
+
+            // Samples which are late are skipped, so the rate stays the same.
+            next += interval;
+            let now = Instant::now();
+            if next > now {
+                sleep(next - now);
+            } else {
+                next = now;
+            }
+        }
+    })
+}
+
+/// Parses `<symbol|address>[:<type>]`. The type of a symbol is taken from its
+/// debug information if it is not given, and the type of an address is `u32`.
+fn parse_signal(signal: &str, debug_info: Option<&DebugInfo>) -> Result<Signal, CliError> {
+    // Rust paths contain colons, so only a known type is split off.
+    let (name, typ) = match signal.rfind(':') {
+        Some(index) => match signal[index + 1..].parse::<BaseType>() {
+            Ok(typ) => (&signal[..index], Some(typ)),
+            Err(_) => (signal, None),
+        },
+        None => (signal, None),
+    };
+
+    if name.starts_with("0x") || name.starts_with("0X") {
+        let address = parse_value(name)
+            .map_err(|_| CliError::InvalidArgument(format!("Invalid address '{}'", name)))?;
+
+        return Ok(Signal {
+            name: name.to_owned(),
+            address,
+            typ: typ.unwrap_or(BaseType::U32),
+        });
+    }
+
+    let debug_info = debug_info.ok_or_else(|| {
+        CliError::InvalidArgument(format!("An --elf file is needed to find '{}'", name))
+    })?;
+
+    let variable = debug_info
+        .get_static_variable(name)
+        .map_err(|e| CliError::InvalidArgument(e.to_string()))?
+        .ok_or_else(|| CliError::InvalidArgument(format!("Unknown variable '{}'", name)))?;
+
+    let typ = typ.or(variable.typ).ok_or_else(|| {
+        CliError::InvalidArgument(format!(
+            "'{}' is not a scalar, give its type as {}:<type>",
+            name, name
+        ))
+    })?;
+
+    Ok(Signal {
+        name: name.to_owned(),
+        address: variable.address,
+        typ,
+    })
+}
+
+struct SampleWriter<W: Write> {
+    format: Format,
+    output: W,
+    names: Vec<String>,
+    types: Vec<BaseType>,
+    /// VCD only contains the values which changed.
+    previous: Vec<Option<Value>>,
+}
+
+impl<W: Write> SampleWriter<W> {
+    fn new(format: Format, output: W, signals: &[Signal]) -> Self {
+        Self {
+            format,
+            output,
+            names: signals.iter().map(|signal| signal.name.clone()).collect(),
+            types: signals.iter().map(|signal| signal.typ).collect(),
+            previous: vec![None; signals.len()],
+        }
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        match self.format {
+            Format::Csv => {
+                write!(self.output, "time")?;
+                for name in &self.names {
+                    write!(self.output, ",{}", name)?;
+                }
+                writeln!(self.output)?;
+            }
+            Format::JsonLines => {}
+            Format::Vcd => {
+                writeln!(self.output, "$timescale 1 us $end")?;
+                writeln!(self.output, "$scope module target $end")?;
+                for (index, (name, typ)) in self.names.iter().zip(&self.types).enumerate() {
+                    let (kind, width) = match typ {
+                        BaseType::F32 | BaseType::F64 => ("real", 64),
+                        typ => ("wire", typ.size() * 8),
+                    };
+                    writeln!(
+                        self.output,
+                        "$var {} {} {} {} $end",
+                        kind,
+                        width,
+                        vcd_identifier(index),
+                        name
+                    )?;
+                }
+                writeln!(self.output, "$upscope $end")?;
+                writeln!(self.output, "$enddefinitions $end")?;
+            }
+        }
+
+        self.output.flush()
+    }
+
+    fn write_sample(&mut self, time: Duration, values: &[Value]) -> io::Result<()> {
+        match self.format {
+            Format::Csv => {
+                write!(self.output, "{:.6}", time.as_secs_f64())?;
+                for value in values {
+                    write!(self.output, ",{}", value)?;
+                }
+                writeln!(self.output)?;
+            }
+            Format::JsonLines => {
+                write!(self.output, "{{\"time\":{:.6}", time.as_secs_f64())?;
+                for (name, value) in self.names.iter().zip(values) {
+                    write!(self.output, ",\"{}\":", json_escape(name))?;
+                    match value {
+                        // JSON has no NaN or infinity.
+                        Value::Float(float) if !float.is_finite() => write!(self.output, "null")?,
+                        value => write!(self.output, "{}", value)?,
+                    }
+                }
+                writeln!(self.output, "}}")?;
+            }
+            Format::Vcd => {
+                let mut timestamp_written = false;
+
+                for (index, value) in values.iter().enumerate() {
+                    if self.previous[index] == Some(*value) {
+                        continue;
+                    }
+
+                    if !timestamp_written {
+                        writeln!(self.output, "#{}", time.as_micros())?;
+                        timestamp_written = true;
+                    }
+
+                    let identifier = vcd_identifier(index);
+                    match value {
+                        Value::Float(float) => writeln!(self.output, "r{} {}", float, identifier)?,
+                        Value::Signed(integer) => {
+                            let width = self.types[index].size() * 8;
+                            let bits = (*integer as u64) & (u64::MAX >> (64 - width));
+                            writeln!(self.output, "b{:b} {}", bits, identifier)?
+                        }
+                        Value::Unsigned(integer) => {
+                            writeln!(self.output, "b{:b} {}", integer, identifier)?
+                        }
+                    }
+                }
+            }
+        }
+
+        self.previous = values.iter().copied().map(Some).collect();
+
+        self.output.flush()
+    }
+}
+
+/// The short identifier of a signal in a VCD file, made of the printable
+/// characters from `!` to `~`.
+fn vcd_identifier(mut index: usize) -> String {
+    const FIRST: u8 = b'!';
+    const COUNT: usize = (b'~' - b'!' + 1) as usize;
+
+    let mut identifier = String::new();
+    loop {
+        identifier.push((FIRST + (index % COUNT) as u8) as char);
+        index /= COUNT;
+        if index == 0 {
+            return identifier;
+        }
+        index -= 1;
+    }
+}
+
+fn json_escape(string: &str) -> String {
+    string.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{vcd_identifier, Format, SampleWriter, Signal};
+    use probe_rs::debug::{BaseType, Value};
+    use std::time::Duration;
+
+    fn signals() -> Vec<Signal> {
+        vec![
+            Signal {
+                name: "COUNTER".to_owned(),
+                address: 0x2000_0000,
+                typ: BaseType::I8,
+            },
+            Signal {
+                name: "TEMPERATURE".to_owned(),
+                address: 0x2000_0004,
+                typ: BaseType::F32,
+            },
+        ]
+    }
+
+    fn write(format: Format) -> String {
+        let signals = signals();
+        let mut writer = SampleWriter::new(format, Vec::new(), &signals);
+
+        writer.write_header().unwrap();
+        writer
+            .write_sample(
+                Duration::from_millis(0),
+                &[Value::Signed(-1), Value::Float(1.5)],
+            )
+            .unwrap();
+        writer
+            .write_sample(
+                Duration::from_millis(50),
+                &[Value::Signed(2), Value::Float(1.5)],
+            )
+            .unwrap();
+
+        String::from_utf8(writer.output).unwrap()
+    }
+
+    #[test]
+    fn csv() {
+        assert_eq!(
+            write(Format::Csv),
+            "time,COUNTER,TEMPERATURE\n0.000000,-1,1.5\n0.050000,2,1.5\n"
+        );
+    }
+
+    #[test]
+    fn json_lines() {
+        assert_eq!(
+            write(Format::JsonLines),
+            "{\"time\":0.000000,\"COUNTER\":-1,\"TEMPERATURE\":1.5}\n\
+             {\"time\":0.050000,\"COUNTER\":2,\"TEMPERATURE\":1.5}\n"
+        );
+    }
+
+    #[test]
+    fn vcd_contains_changes() {
+        let vcd = write(Format::Vcd);
+        let (header, changes) = vcd.split_at(vcd.find("#0").unwrap());
+
+        assert!(header.contains("$var wire 8 ! COUNTER $end"));
+        assert!(header.contains("$var real 64 \" TEMPERATURE $end"));
+        // -1 is written in two's complement, and the unchanged float is left out.
+        assert_eq!(changes, "#0\nb11111111 !\nr1.5 \"\n#50000\nb10 !\n");
+    }
+
+    #[test]
+    fn vcd_identifiers() {
+        assert_eq!(vcd_identifier(0), "!");
+        assert_eq!(vcd_identifier(93), "~");
+        assert_eq!(vcd_identifier(94), "!!");
+    }
+}
@@ -1,11 +1,22 @@
 use crate::common::CliError;
 
 use capstone::Capstone;
-use probe_rs::architecture::arm::CortexDump;
+use probe_rs::architecture::riscv::{Mcause, Xlen};
 use probe_rs::debug::DebugInfo;
-use probe_rs::{Core, CoreRegisterAddress};
+use probe_rs::semihosting::{Semihosting, SemihostingOutcome, ADP_STOPPED_APPLICATION_EXIT};
+use probe_rs::svd::{self, SvdError};
+use probe_rs::{Architecture, BreakpointCondition, BreakpointOptions, Core, CoreStatus};
 use std::fs::File;
-use std::io::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// How long a function which is called with `call` may run.
+const CALL_TIMEOUT: Duration = Duration::from_secs(2);
+/// Set by Ctrl-C, to halt the core while `run` waits for it.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// How long `step`, `next` and `finish` wait for the core to reach the next line.
+const STEP_TIMEOUT: Duration = Duration::from_secs(5);
 
 pub struct DebugCli {
     commands: Vec<Command>,
@@ -18,12 +29,59 @@ impl DebugCli {
         };
 
         cli.add_command(Command {
-            name: "step",
+            name: "stepi",
             help_text: "Step a single instruction",
 
             function: |cli_data, _args| {
                 let cpu_info = cli_data.core.step()?;
-                println!("Core stopped at address 0x{:08x}", cpu_info.pc);
+                print_stop(cli_data, cpu_info.pc);
+
+                Ok(CliState::Continue)
+            },
+        });
+
+        cli.add_command(Command {
+            name: "step",
+            help_text: "Step to the next source line, into called functions. Steps a single instruction without debug information",
+
+            function: |cli_data, _args| {
+                let pc = match &cli_data.debug_info {
+                    Some(di) => di.step_into(&mut cli_data.core, STEP_TIMEOUT)?.pc,
+                    None => cli_data.core.step()?.pc,
+                };
+                print_stop(cli_data, pc);
+
+                Ok(CliState::Continue)
+            },
+        });
+
+        cli.add_command(Command {
+            name: "next",
+            help_text: "Step to the next source line, over called functions",
+
+            function: |cli_data, _args| {
+                let di = cli_data.debug_info.as_ref().ok_or_else(|| {
+                    CliError::InvalidArgument("No debug information loaded".into())
+                })?;
+
+                let pc = di.step_over(&mut cli_data.core, STEP_TIMEOUT)?.pc;
+                print_stop(cli_data, pc);
+
+                Ok(CliState::Continue)
+            },
+        });
+
+        cli.add_command(Command {
+            name: "finish",
+            help_text: "Run until the current function returns",
+
+            function: |cli_data, _args| {
+                let di = cli_data.debug_info.as_ref().ok_or_else(|| {
+                    CliError::InvalidArgument("No debug information loaded".into())
+                })?;
+
+                let pc = di.step_out(&mut cli_data.core, STEP_TIMEOUT)?.pc;
+                print_stop(cli_data, pc);
 
                 Ok(CliState::Continue)
             },
@@ -66,11 +124,83 @@ impl DebugCli {
 
         cli.add_command(Command {
             name: "run",
-            help_text: "Resume execution of the CPU",
+            help_text:
+                "Resume execution of the CPU, Ctrl-C halts it while waiting for a breakpoint",
 
             function: |cli_data, _args| {
                 cli_data.core.run()?;
 
+                // Wait for a breakpoint, so the core is resumed if its condition is not
+                // met, and for semihosting calls.
+                if cli_data.core.managed_breakpoints().is_empty() && cli_data.semihosting.is_none()
+                {
+                    return Ok(CliState::Continue);
+                }
+
+                INTERRUPTED.store(false, Ordering::SeqCst);
+
+                'running: loop {
+                    while !cli_data.core.poll_halted()? {
+                        if INTERRUPTED.swap(false, Ordering::SeqCst) {
+                            cli_data.core.halt()?;
+                            break 'running;
+                        }
+
+                        std::thread::sleep(Duration::from_millis(10));
+                    }
+
+                    let outcome = match &mut cli_data.semihosting {
+                        Some(semihosting) => semihosting.handle(&cli_data.core)?,
+                        None => None,
+                    };
+
+                    match outcome {
+                        Some(SemihostingOutcome::Resumed) => continue,
+                        Some(SemihostingOutcome::Exited { reason }) => {
+                            if reason == ADP_STOPPED_APPLICATION_EXIT {
+                                println!("The program exited");
+                            } else {
+                                println!("The program exited with reason {:#x}", reason);
+                            }
+
+                            return Ok(CliState::Continue);
+                        }
+                        None => break,
+                    }
+                }
+
+                let pc = cli_data
+                    .core
+                    .read_core_reg(cli_data.core.registers().program_counter())?;
+
+                match (cli_data.core.breakpoint_at(pc), cli_data.core.status()?) {
+                    (Some(id), _) => println!("Hit breakpoint {} at address {:#010x}", id, pc),
+                    (None, CoreStatus::Halted(reason)) => {
+                        println!("Core stopped at address {:#010x} ({})", pc, reason)
+                    }
+                    (None, CoreStatus::Running) => {}
+                }
+
+                Ok(CliState::Continue)
+            },
+        });
+
+        cli.add_command(Command {
+            name: "status",
+            help_text: "Show if the CPU is running, or why it is halted",
+
+            function: |cli_data, _args| {
+                match cli_data.core.status()? {
+                    CoreStatus::Running => println!("Core is running"),
+                    CoreStatus::Halted(reason) => {
+                        let pc = cli_data
+                            .core
+                            .read_core_reg(cli_data.core.registers().program_counter())?;
+
+                        println!("Core is halted at address {:#010x} ({})", pc, reason);
+                    }
+                }
+
                 Ok(CliState::Continue)
             },
         });
@@ -127,15 +257,17 @@ impl DebugCli {
 
         cli.add_command(Command {
             name: "break",
-            help_text: "Set a breakpoint at a specifc address",
+            help_text: "Set a breakpoint at a specifc address: break <address> [ignore <count>] [after <hits>] [if <register|*address> <op> <value>]",
 
             function: |cli_data, args| {
                 let address_str = args.get(0).ok_or(CliError::MissingArgument)?;
                 let address = u32::from_str_radix(address_str, 16).unwrap();
 
-                cli_data.core.set_hw_breakpoint(address)?;
+                let options = parse_breakpoint_options(&cli_data.core, &args[1..])?;
+
+                let id = cli_data.core.add_breakpoint(address, options)?;
 
-                println!("Set new breakpoint at address {:#08x}", address);
+                println!("Set new breakpoint {} at address {:#08x}", id, address);
 
                 Ok(CliState::Continue)
             },
@@ -149,7 +281,68 @@ impl DebugCli {
                 let address_str = args.get(0).ok_or(CliError::MissingArgument)?;
                 let address = u32::from_str_radix(address_str, 16).unwrap();
 
-                cli_data.core.clear_hw_breakpoint(address)?;
+                match cli_data.core.breakpoint_at(address) {
+                    Some(id) => cli_data.core.remove_breakpoint(id)?,
+                    None => cli_data.core.clear_hw_breakpoint(address)?,
+                }
+
+                Ok(CliState::Continue)
+            },
+        });
+
+        cli.add_command(Command {
+            name: "breakpoints",
+            help_text: "List the breakpoints and how often they were hit",
+
+            function: |cli_data, _args| {
+                for breakpoint in cli_data.core.managed_breakpoints() {
+                    let statistics = breakpoint.statistics();
+
+                    println!(
+                        "{}: {:#010x}, {} hits, {} halts",
+                        breakpoint.id(),
+                        breakpoint.address(),
+                        statistics.hits,
+                        statistics.halts
+                    );
+                }
+
+                Ok(CliState::Continue)
+            },
+        });
+
+        cli.add_command(Command {
+            name: "call",
+            help_text:
+                "Call a function on the halted core: call <function|address> [<argument>]...",
+
+            function: |cli_data, args| {
+                let function = args.first().ok_or(CliError::MissingArgument)?;
+
+                let address = match &cli_data.debug_info {
+                    Some(di) if !function.starts_with("0x") => di
+                        .get_function_address(function)
+                        .map_err(|e| CliError::InvalidArgument(e.to_string()))?
+                        .ok_or_else(|| {
+                            CliError::InvalidArgument(format!("Unknown function '{}'", function))
+                        })? as u32,
+                    _ => parse_number(function)?,
+                };
+
+                let arguments = args[1..]
+                    .iter()
+                    .map(|argument| parse_number(argument))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let results =
+                    cli_data
+                        .core
+                        .call_function(address, &arguments, None, CALL_TIMEOUT)?;
+
+                println!(
+                    "{} returned {:#010x} ({})",
+                    function, results[0], results[0]
+                );
 
                 Ok(CliState::Continue)
             },
@@ -188,53 +381,147 @@ impl DebugCli {
                     println!("{}: {:#010x}", register.name(), value)
                 }
 
+                for register in register_file.control_registers() {
+                    let value = cli_data.core.read_core_reg_64(register)?;
+
+                    if cli_data.core.architecture() == Architecture::RISCV
+                        && register.name() == "mcause"
+                    {
+                        let xlen = match cli_data.core.register_bits()? {
+                            64 => Xlen::X64,
+                            _ => Xlen::X32,
+                        };
+
+                        println!(
+                            "{}: {:#010x} {}",
+                            register.name(),
+                            value,
+                            Mcause::decode(value, xlen)
+                        )
+                    } else {
+                        println!("{}: {:#010x}", register.name(), value)
+                    }
+                }
+
+                if cli_data.core.fpu_present()? {
+                    for register in register_file.fpu_registers() {
+                        let value = cli_data.core.read_core_reg_64(register)?;
+
+                        println!("{}: {:#010x}", register.name(), value)
+                    }
+                }
+
                 Ok(CliState::Continue)
             },
         });
 
         cli.add_command(Command {
             name: "dump",
-            help_text: "Store a dump of the current CPU state",
+            help_text: "Store an ELF core dump of the core in dump.elf, of all RAM or the given <address> <size> ranges",
 
-            function: |cli_data, _args| {
-                // dump all relevant data, stack and regs for now..
-                //
-                // stack beginning -> assume beginning to be hardcoded
+            function: |cli_data, args| {
+                let dump = if args.is_empty() {
+                    cli_data.core.dump()?
+                } else {
+                    let mut ranges = Vec::new();
+                    let mut args = args.iter();
+
+                    while let Some(address) = args.next() {
+                        let address = parse_number(address)?;
+                        let size = parse_number(next_argument(&mut args)?)?;
+
+                        let end = address.checked_add(size).ok_or_else(|| {
+                            CliError::InvalidArgument(format!(
+                                "The range of {:#x} bytes at {:#010x} reaches beyond the address space",
+                                size, address
+                            ))
+                        })?;
+
+                        ranges.push(address..end);
+                    }
 
-                let stack_top: u32 = 0x2000_0000 + 0x4_000;
+                    cli_data.core.dump_ranges(&ranges)?
+                };
 
-                let regs = cli_data.core.registers();
+                dump.write_elf(File::create("dump.elf")?)?;
+
+                println!("Core dump written to dump.elf");
 
-                let stack_bot: u32 = cli_data.core.read_core_reg(regs.stack_pointer())?;
-                let pc: u32 = cli_data.core.read_core_reg(regs.program_counter())?;
+                Ok(CliState::Continue)
+            },
+        });
 
-                let mut stack = vec![0u8; (stack_top - stack_bot) as usize];
+        cli.add_command(Command {
+            name: "periph",
+            help_text: "Show the registers of a peripheral from the SVD file: periph <peripheral>",
 
-                cli_data
-                    .core
-                    .memory()
-                    .read_block8(stack_bot, &mut stack[..])?;
+            function: |cli_data, args| {
+                let name = args.first().ok_or(CliError::MissingArgument)?;
+                let device = svd_device(cli_data)?;
 
-                let mut dump = CortexDump::new(stack_bot, stack);
+                let peripheral = device.peripheral(name).ok_or_else(|| {
+                    probe_rs::Error::from(SvdError::UnknownPeripheral(name.to_string()))
+                })?;
 
-                for i in 0..12 {
-                    dump.regs[i as usize] =
-                        cli_data
-                            .core
-                            .read_core_reg(Into::<CoreRegisterAddress>::into(i))?;
+                match &peripheral.description {
+                    Some(description) => println!("{} ({})", peripheral.name, description),
+                    None => println!("{}", peripheral.name),
+                }
+
+                let memory = cli_data.core.memory();
+                for register in &peripheral.registers {
+                    let value = register.read(&memory)?;
+                    print_register(register, value);
                 }
 
-                dump.regs[13] = stack_bot;
-                dump.regs[14] = cli_data.core.read_core_reg(regs.return_address())?;
-                dump.regs[15] = pc;
+                Ok(CliState::Continue)
+            },
+        });
+
+        cli.add_command(Command {
+            name: "reg",
+            help_text:
+                "Show a register from the SVD file with its fields: reg <peripheral>.<register>",
+
+            function: |cli_data, args| {
+                let path = args.first().ok_or(CliError::MissingArgument)?;
+                let device = svd_device(cli_data)?;
+
+                let register = device.find_register(path).map_err(probe_rs::Error::from)?;
+                let value = register.read(&cli_data.core.memory())?;
+
+                print_register(register, value);
 
-                let serialized = ron::ser::to_string(&dump).expect("Failed to serialize dump");
+                Ok(CliState::Continue)
+            },
+        });
+
+        cli.add_command(Command {
+            name: "reg-set",
+            help_text: "Write a register or a field from the SVD file: reg-set <peripheral>.<register>[.<field>] <value>",
 
-                let mut dump_file = File::create("dump.txt").expect("Failed to create file");
+            function: |cli_data, args| {
+                let path = args.first().ok_or(CliError::MissingArgument)?;
+                let value = args.get(1).ok_or(CliError::MissingArgument)?;
+                let device = svd_device(cli_data)?;
+                let memory = cli_data.core.memory();
+
+                let register = match device.find_register(path) {
+                    Ok(register) => {
+                        register.write(&memory, parse_number(value)?)?;
+                        register
+                    }
+                    Err(_) => {
+                        let (register, field) =
+                            device.find_field(path).map_err(probe_rs::Error::from)?;
+                        let value = field.parse_value(value).map_err(probe_rs::Error::from)?;
+
+                        register.write_field(&memory, field, value)?;
+                        register
+                    }
+                };
 
-                dump_file
-                    .write_all(serialized.as_bytes())
-                    .expect("Failed to write dump file");
+                print_register(register, register.read(&memory)?);
 
                 Ok(CliState::Continue)
             },
@@ -295,10 +582,136 @@ impl DebugCli {
     }
 }
 
+fn svd_device(cli_data: &CliData) -> Result<&svd::Device, CliError> {
+    cli_data.svd.as_ref().ok_or_else(|| {
+        CliError::InvalidArgument(
+            "No SVD file is loaded, start the debugger with --svd <path>".to_owned(),
+        )
+    })
+}
+
+/// Makes Ctrl-C halt the core while the `run` command waits for a breakpoint,
+/// instead of ending the debugger.
+pub(crate) fn catch_interrupts() {
+    if let Err(e) = ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::SeqCst)) {
+        log::warn!("Ctrl-C can not interrupt the core: {}", e);
+    }
+}
+
+/// Prints where the core stopped, with the source location if it is known.
+fn print_stop(cli_data: &CliData, pc: u32) {
+    let location = cli_data
+        .debug_info
+        .as_ref()
+        .and_then(|di| di.get_source_location(u64::from(pc)));
+
+    match location {
+        Some(location) => println!(
+            "Core stopped at address 0x{:08x} ({}:{})",
+            pc,
+            location.file.as_deref().unwrap_or("<unknown>"),
+            location.line.unwrap_or(0)
+        ),
+        None => println!("Core stopped at address 0x{:08x}", pc),
+    }
+}
+
+/// Prints the value of a register, and the values of its fields with the names
+/// of their enumerated values.
+fn print_register(register: &svd::Register, value: u32) {
+    println!(
+        "{} @ {:#010x} = {:#010x}",
+        register.name, register.address, value
+    );
+
+    for field in &register.fields {
+        let field_value = field.extract(value);
+
+        match field.enumerated_value(field_value) {
+            Some(enumerated_value) => println!(
+                "    {:<16} = {:#x} ({})",
+                field.name, field_value, enumerated_value.name
+            ),
+            None => println!("    {:<16} = {:#x}", field.name, field_value),
+        }
+    }
+}
+
+/// Parses the options of the `break` command.
+fn parse_breakpoint_options(core: &Core, args: &[&str]) -> Result<BreakpointOptions, CliError> {
+    let mut options = BreakpointOptions::default();
+    let mut args = args.iter();
+
+    while let Some(keyword) = args.next() {
+        match *keyword {
+            "ignore" => options.ignore_count = parse_number(next_argument(&mut args)?)?,
+            "after" => options.hit_threshold = parse_number(next_argument(&mut args)?)?,
+            "if" => {
+                let lhs = next_argument(&mut args)?;
+                let comparison = next_argument(&mut args)?
+                    .parse()
+                    .map_err(CliError::InvalidArgument)?;
+                let value = parse_number(next_argument(&mut args)?)?;
+
+                let condition = if let Some(address) = lhs.strip_prefix('*') {
+                    BreakpointCondition::Memory {
+                        address: parse_number(address)?,
+                        comparison,
+                        value,
+                    }
+                } else {
+                    let register = core
+                        .registers()
+                        .registers()
+                        .chain(core.registers().control_registers())
+                        .find(|register| register.name().eq_ignore_ascii_case(lhs))
+                        .ok_or_else(|| {
+                            CliError::InvalidArgument(format!("Unknown register '{}'", lhs))
+                        })?;
+
+                    BreakpointCondition::Register {
+                        register: register.into(),
+                        comparison,
+                        value,
+                    }
+                };
+
+                options.condition = Some(condition);
+            }
+            other => {
+                return Err(CliError::InvalidArgument(format!(
+                    "Unknown breakpoint option '{}'",
+                    other
+                )))
+            }
+        }
+    }
+
+    Ok(options)
+}
+
+fn next_argument<'a>(args: &mut impl Iterator<Item = &'a &'a str>) -> Result<&'a str, CliError> {
+    args.next().copied().ok_or(CliError::MissingArgument)
+}
+
+/// Parses a decimal number, or a hexadecimal number with a `0x` prefix.
+fn parse_number(value: &str) -> Result<u32, CliError> {
+    let result = match value.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+
+    result.map_err(|_| CliError::InvalidArgument(format!("Invalid number '{}'", value)))
+}
+
 pub struct CliData {
     pub core: Core,
     pub debug_info: Option<DebugInfo>,
     pub capstone: Capstone,
+    /// Semihosting calls are performed while the core runs.
+    pub semihosting: Option<Semihosting>,
+    /// The peripherals of the target, for the `periph`, `reg` and `reg-set` commands.
+    pub svd: Option<svd::Device>,
 }
 
 pub enum CliState {
@@ -1,14 +1,18 @@
 mod common;
+mod datalog;
 mod debugger;
 mod info;
+mod option_bytes;
+mod rtt;
 
-use common::{with_device, CliError};
+use common::{open_probe, with_device, CliError};
 use debugger::CliState;
 
 use probe_rs::{
     debug::DebugInfo,
     flashing::{download_file, Format},
-    MemoryInterface, Probe, Session,
+    semihosting::Semihosting,
+    svd, Core, CoreDump, MemoryInterface, Probe, Session,
 };
 
 use capstone::{arch::arm::ArchMode, prelude::*, Capstone, Endian};
@@ -16,7 +20,7 @@ use rustyline::Editor;
 use structopt::StructOpt;
 
 use std::num::ParseIntError;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 
 fn parse_hex(src: &str) -> Result<u32, ParseIntError> {
@@ -56,6 +60,18 @@ enum CLI {
         #[structopt(long, parse(from_os_str))]
         /// Binary to debug
         exe: Option<PathBuf>,
+
+        #[structopt(long, parse(from_os_str))]
+        /// Debug an ELF core dump instead of an attached target
+        core_dump: Option<PathBuf>,
+
+        #[structopt(long)]
+        /// Perform the semihosting calls of the target while it runs
+        semihosting: bool,
+
+        #[structopt(long, parse(from_os_str))]
+        /// SVD file with the peripherals of the target, instead of the one referenced by the target description
+        svd: Option<PathBuf>,
     },
     /// Dump memory from attached target
     #[structopt(name = "dump")]
@@ -78,14 +94,86 @@ enum CLI {
         /// The path to the file to be downloaded to the flash
         path: String,
     },
-    #[structopt(name = "trace")]
-    Trace {
+    /// Mass erase a locked nRF52 or nRF91 chip to unlock it. This erases the entire flash, UICR and RAM!
+    #[structopt(name = "nrf-recover")]
+    NrfRecover {
+        #[structopt(flatten)]
+        shared: SharedOptions,
+    },
+    /// Read or program the option bytes of STM32 chips
+    #[structopt(name = "option-bytes")]
+    OptionBytes {
+        #[structopt(subcommand)]
+        command: OptionBytesCommand,
+    },
+    /// Sample variables of the running target, and write them as CSV, JSON lines or VCD
+    #[structopt(name = "log")]
+    Log {
         #[structopt(flatten)]
         shared: SharedOptions,
 
-        /// The address of the memory to dump from the target (in hexadecimal without 0x prefix)
-        #[structopt(parse(try_from_str = parse_hex))]
-        loc: u32,
+        /// The variables to sample, as <symbol|0x address>[:<type>] with the types i8 to u64, f32 and f64
+        #[structopt(required = true)]
+        signals: Vec<String>,
+
+        #[structopt(long, parse(from_os_str))]
+        /// Firmware with the debug information of the symbols
+        elf: Option<PathBuf>,
+
+        #[structopt(long, default_value = "20")]
+        /// Samples per second
+        rate: f64,
+
+        #[structopt(long, default_value = "csv")]
+        /// The output format: csv, jsonl or vcd
+        format: datalog::Format,
+
+        #[structopt(long)]
+        /// Stop after this many samples
+        samples: Option<u64>,
+
+        #[structopt(long, parse(from_os_str))]
+        /// Write the samples to this file instead of stdout
+        output: Option<PathBuf>,
+    },
+    /// Stream the RTT up channels of the target to stdout
+    #[structopt(name = "rtt")]
+    Rtt {
+        #[structopt(flatten)]
+        shared: SharedOptions,
+
+        #[structopt(long, parse(from_os_str))]
+        /// Firmware with a _SEGGER_RTT symbol, instead of scanning the RAM for the control block
+        elf: Option<PathBuf>,
+
+        #[structopt(long)]
+        /// Only stream this up channel
+        channel: Option<u32>,
+    },
+}
+
+#[derive(StructOpt)]
+enum OptionBytesCommand {
+    /// Show the current option bytes
+    #[structopt(name = "get")]
+    Get {
+        #[structopt(flatten)]
+        shared: SharedOptions,
+    },
+    /// Change a single field of the option bytes
+    #[structopt(name = "set")]
+    Set {
+        #[structopt(flatten)]
+        shared: SharedOptions,
+
+        /// The name of the field, as shown by `option-bytes get`
+        field: String,
+        /// The new value of the field (decimal, or hexadecimal with 0x prefix)
+        #[structopt(parse(try_from_str = option_bytes::parse_value))]
+        value: u32,
+        /// Allow setting the read protection to level 2, which disables the debug interface permanently
+        #[structopt(long)]
+        allow_permanent_lock: bool,
     },
 }
 
@@ -103,6 +191,10 @@ struct SharedOptions {
     /// Protocol to use for target connection
     #[structopt(short, long)]
     protocol: Option<String>,
+
+    /// A Keil FLM flash algorithm, which is used instead of the built-in algorithms of the target
+    #[structopt(long, parse(from_os_str))]
+    flash_algorithm: Option<PathBuf>,
 }
 
 fn main() {
@@ -115,10 +207,58 @@ fn main() {
         CLI::List {} => list_connected_devices(),
         CLI::Info { shared } => crate::info::show_info_of_device(&shared),
         CLI::Reset { shared, assert } => reset_target_of_device(&shared, assert),
-        CLI::Debug { shared, exe } => debug(&shared, exe),
+        CLI::Debug {
+            exe,
+            core_dump: Some(core_dump),
+            svd,
+            ..
+        } => debug_core_dump(&core_dump, exe, svd),
+        CLI::Debug {
+            shared,
+            exe,
+            semihosting,
+            svd,
+            ..
+        } => debug(&shared, exe, semihosting, svd),
         CLI::Dump { shared, loc, words } => dump_memory(&shared, loc, words),
         CLI::Download { shared, path } => download_program_fast(&shared, &path),
-        CLI::Trace { shared, loc } => trace_u32_on_target(&shared, loc),
+        CLI::NrfRecover { shared } => nrf_recover(&shared),
+        CLI::OptionBytes {
+            command: OptionBytesCommand::Get { shared },
+        } => option_bytes::show_option_bytes(&shared),
+        CLI::OptionBytes {
+            command:
+                OptionBytesCommand::Set {
+                    shared,
+                    field,
+                    value,
+                    allow_permanent_lock,
+                },
+        } => option_bytes::set_option_byte(&shared, &field, value, allow_permanent_lock),
+        CLI::Log {
+            shared,
+            signals,
+            elf,
+            rate,
+            format,
+            samples,
+            output,
+        } => datalog::log_data(
+            &shared,
+            &datalog::LogOptions {
+                signals: &signals,
+                elf: elf.as_deref(),
+                rate,
+                format,
+                samples,
+                output: output.as_deref(),
+            },
+        ),
+        CLI::Rtt {
+            shared,
+            elf,
+            channel,
+        } => rtt::stream_rtt(&shared, elf.as_deref(), channel),
     };
 
     if let Err(e) = cli_result {
@@ -192,105 +332,118 @@ fn reset_target_of_device(
     })
 }
 
-fn trace_u32_on_target(shared_options: &SharedOptions, loc: u32) -> Result<(), CliError> {
-    use scroll::{Pwrite, LE};
-    use std::io::prelude::*;
-    use std::thread::sleep;
-    use std::time::Duration;
+fn nrf_recover(shared_options: &SharedOptions) -> Result<(), CliError> {
+    let mut probe = open_probe(shared_options.n)?;
 
-    let mut xs = vec![];
-    let mut ys = vec![];
+    if let Some(ref protocol) = shared_options.protocol {
+        probe.select_protocol(
+            protocol
+                .parse()
+                .map_err(|_e| CliError::UnableToOpenProbe(Some("Error while parsing protocol")))?,
+        )?;
+    }
 
-    let start = Instant::now();
+    probe.nrf_recover()?;
 
-    with_device(shared_options, |session| {
-        let mut core = session.attach_to_core(0)?;
+    println!("The chip was erased and is unlocked now.");
 
-        loop {
-            // Prepare read.
-            let elapsed = start.elapsed();
-            let instant = elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis());
-
-            // Read data.
-            let value: u32 = core.read32(loc)?;
-
-            xs.push(instant);
-            ys.push(value);
-
-            // Send value to plot.py.
-            // Unwrap is safe as there is always an stdin in our case!
-            let mut buf = [0 as u8; 8];
-            // Unwrap is safe!
-            buf.pwrite_with(instant, 0, LE).unwrap();
-            buf.pwrite_with(value, 4, LE).unwrap();
-            std::io::stdout().write_all(&buf)?;
-
-            std::io::stdout().flush()?;
-
-            // Schedule next read.
-            let elapsed = start.elapsed();
-            let instant = elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis());
-            let poll_every_ms = 50;
-            let time_to_wait = poll_every_ms - instant % poll_every_ms;
-            sleep(Duration::from_millis(time_to_wait));
-        }
-    })
+    Ok(())
 }
 
-fn debug(shared_options: &SharedOptions, exe: Option<PathBuf>) -> Result<(), CliError> {
+fn debug(
+    shared_options: &SharedOptions,
+    exe: Option<PathBuf>,
+    semihosting: bool,
+    svd: Option<PathBuf>,
+) -> Result<(), CliError> {
     let runner = |session: Session| {
-        let cs = Capstone::new()
-            .arm()
-            .mode(ArchMode::Thumb)
-            .endian(Endian::Little)
-            .build()
-            .unwrap();
+        let core = session.attach_to_core(0)?;
+        let semihosting = if semihosting {
+            Some(Semihosting::new())
+        } else {
+            None
+        };
 
-        let di = exe
-            .as_ref()
-            .and_then(|path| DebugInfo::from_file(path).ok());
+        let svd = svd.or_else(|| session.svd_path().map(PathBuf::from));
 
-        let cli = debugger::DebugCli::new();
+        run_debugger(core, exe.as_ref(), semihosting, svd.as_deref())
+    };
 
-        let core = session.attach_to_core(0)?;
+    with_device(shared_options, runner)
+}
 
-        let mut cli_data = debugger::CliData {
-            core,
-            debug_info: di,
-            capstone: cs,
-        };
+/// Runs the debugger on a core dump, which does not need a probe.
+fn debug_core_dump(
+    path: &Path,
+    exe: Option<PathBuf>,
+    svd: Option<PathBuf>,
+) -> Result<(), CliError> {
+    let dump = CoreDump::from_elf(&std::fs::read(path)?).map_err(probe_rs::Error::from)?;
 
-        let mut rl = Editor::<()>::new();
+    let core = Core::from_dump(dump).map_err(probe_rs::Error::from)?;
 
-        loop {
-            let readline = rl.readline(">> ");
-            match readline {
-                Ok(line) => {
-                    let history_entry: &str = line.as_ref();
-                    rl.add_history_entry(history_entry);
-                    let cli_state = cli.handle_line(&line, &mut cli_data)?;
+    run_debugger(core, exe.as_ref(), None, svd.as_deref())
+}
 
-                    match cli_state {
-                        CliState::Continue => (),
-                        CliState::Stop => return Ok(()),
-                    }
+fn run_debugger(
+    core: Core,
+    exe: Option<&PathBuf>,
+    semihosting: Option<Semihosting>,
+    svd: Option<&Path>,
+) -> Result<(), CliError> {
+    let cs = Capstone::new()
+        .arm()
+        .mode(ArchMode::Thumb)
+        .endian(Endian::Little)
+        .build()
+        .unwrap();
+
+    let di = exe.and_then(|path| DebugInfo::from_file(path).ok());
+
+    let svd = match svd {
+        Some(path) => Some(svd::Device::from_file(path).map_err(probe_rs::Error::from)?),
+        None => None,
+    };
+
+    let cli = debugger::DebugCli::new();
+    debugger::catch_interrupts();
+
+    let mut cli_data = debugger::CliData {
+        core,
+        debug_info: di,
+        capstone: cs,
+        semihosting,
+        svd,
+    };
+
+    let mut rl = Editor::<()>::new();
+
+    loop {
+        let readline = rl.readline(">> ");
+        match readline {
+            Ok(line) => {
+                let history_entry: &str = line.as_ref();
+                rl.add_history_entry(history_entry);
+                let cli_state = cli.handle_line(&line, &mut cli_data)?;
+
+                match cli_state {
+                    CliState::Continue => (),
+                    CliState::Stop => return Ok(()),
                 }
-                Err(e) => {
-                    use rustyline::error::ReadlineError;
-
-                    match e {
-                        // For end of file and ctrl-c, we just quit
-                        ReadlineError::Eof | ReadlineError::Interrupted => return Ok(()),
-                        actual_error => {
-                            // Show error message and quit
-                            println!("Error handling input: {:?}", actual_error);
-                            return Ok(());
-                        }
+            }
+            Err(e) => {
+                use rustyline::error::ReadlineError;
+
+                match e {
+                    // For end of file and ctrl-c, we just quit
+                    ReadlineError::Eof | ReadlineError::Interrupted => return Ok(()),
+                    actual_error => {
+                        // Show error message and quit
+                        println!("Error handling input: {:?}", actual_error);
+                        return Ok(());
                     }
                 }
             }
         }
-    };
-
-    with_device(shared_options, runner)
+    }
 }
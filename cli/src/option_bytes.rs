@@ -0,0 +1,70 @@
+use crate::{
+    common::{with_device, CliError},
+    SharedOptions,
+};
+
+use probe_rs::flashing::option_bytes::{OptionBytesError, OptionBytesFamily, ReadProtection};
+use probe_rs::Session;
+
+use std::num::ParseIntError;
+
+/// Parses a decimal or a `0x` prefixed hexadecimal number.
+pub(crate) fn parse_value(src: &str) -> Result<u32, ParseIntError> {
+    if src.starts_with("0x") || src.starts_with("0X") {
+        u32::from_str_radix(&src[2..], 16)
+    } else {
+        src.parse()
+    }
+}
+
+fn option_bytes_family(session: &Session) -> Result<OptionBytesFamily, CliError> {
+    session
+        .option_bytes_family()
+        .ok_or_else(|| OptionBytesError::Unsupported.into())
+}
+
+pub(crate) fn show_option_bytes(shared_options: &SharedOptions) -> Result<(), CliError> {
+    with_device(shared_options, |session| {
+        let family = option_bytes_family(&session)?;
+        let memory = session.attach_to_memory(0)?;
+
+        let option_bytes = family.read(&memory)?;
+
+        print!("{}", option_bytes);
+
+        Ok(())
+    })
+}
+
+pub(crate) fn set_option_byte(
+    shared_options: &SharedOptions,
+    field: &str,
+    value: u32,
+    allow_permanent_lock: bool,
+) -> Result<(), CliError> {
+    with_device(shared_options, |session| {
+        let family = option_bytes_family(&session)?;
+        let memory = session.attach_to_memory(0)?;
+
+        let mut option_bytes = family.read(&memory)?;
+        let previous_protection = option_bytes.read_protection();
+
+        option_bytes.set_field(field, value)?;
+
+        if option_bytes.read_protection() == ReadProtection::Level2 && !allow_permanent_lock {
+            return Err(CliError::PermanentLock);
+        }
+
+        if previous_protection != ReadProtection::Level0
+            && option_bytes.read_protection() == ReadProtection::Level0
+        {
+            println!("Removing the read protection, this erases the entire flash.");
+        }
+
+        option_bytes.write(&memory)?;
+
+        println!("Option bytes programmed, the changes take effect after the next reset.");
+
+        Ok(())
+    })
+}
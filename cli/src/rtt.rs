@@ -0,0 +1,107 @@
+use crate::{
+    common::{with_device, CliError},
+    SharedOptions,
+};
+
+use probe_rs::rtt::{self, Rtt, RttError, ScanRegion};
+use probe_rs::Error;
+
+use std::io::Write;
+use std::path::Path;
+use std::thread::sleep;
+use std::time::Duration;
+
+/// How often the up channels are read.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How often attaching is tried, because the target might not have initialized
+/// the control block yet.
+const ATTACH_RETRIES: usize = 10;
+
+const ATTACH_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Streams the up channels of the target to stdout, until the program is stopped.
+pub(crate) fn stream_rtt(
+    shared_options: &SharedOptions,
+    elf: Option<&Path>,
+    channel: Option<u32>,
+) -> Result<(), CliError> {
+    let region = match elf {
+        Some(path) => {
+            let elf = std::fs::read(path)?;
+
+            match rtt::control_block_address(&elf).map_err(Error::from)? {
+                Some(address) => ScanRegion::Exact(address),
+                None => {
+                    return Err(CliError::InvalidArgument(format!(
+                        "{} has no _SEGGER_RTT symbol",
+                        path.display()
+                    )))
+                }
+            }
+        }
+        None => ScanRegion::Ram,
+    };
+
+    with_device(shared_options, |session| {
+        let core = session.attach_to_core(0)?;
+        let memory_map = session.memory_map();
+
+        let mut retries = 0;
+        let rtt = loop {
+            match Rtt::attach(core.memory(), &memory_map, &region) {
+                Err(Error::Rtt(RttError::ControlBlockNotFound)) if retries < ATTACH_RETRIES => {
+                    retries += 1;
+                    sleep(ATTACH_RETRY_INTERVAL);
+                }
+                result => break result?,
+            }
+        };
+
+        eprintln!("Found an RTT control block at {:#010x}", rtt.address());
+        for up_channel in rtt.up_channels() {
+            eprintln!(
+                "Up channel {}: {} ({} bytes)",
+                up_channel.number(),
+                up_channel.name().unwrap_or("unnamed"),
+                up_channel.buffer_size()
+            );
+        }
+
+        let up_channels: Vec<_> = match channel {
+            Some(number) => match rtt.up_channel(number) {
+                Some(up_channel) => vec![up_channel],
+                None => {
+                    return Err(CliError::InvalidArgument(format!(
+                        "The target has no up channel {}",
+                        number
+                    )))
+                }
+            },
+            None => rtt.up_channels().iter().collect(),
+        };
+
+        let stdout = std::io::stdout();
+        let mut stdout = stdout.lock();
+        let mut buffer = vec![0; 1024];
+
+        loop {
+            let mut idle = true;
+
+            for up_channel in &up_channels {
+                let count = up_channel.read_data(&mut buffer)?;
+
+                if count > 0 {
+                    stdout.write_all(&buffer[..count])?;
+                    idle = false;
+                }
+            }
+
+            stdout.flush()?;
+
+            if idle {
+                sleep(POLL_INTERVAL);
+            }
+        }
+    })
+}
@@ -7,7 +7,7 @@ use std::{
 };
 use structopt::StructOpt;
 
-use probe_rs::{config::TargetSelector, Probe};
+use probe_rs::{config::TargetSelector, CoreDump, Probe};
 
 #[derive(Debug, StructOpt)]
 struct Opt {
@@ -31,6 +31,12 @@ struct Opt {
         help = "Use this flag to override the default GDB connection string (localhost:1337)."
     )]
     gdb_connection_string: Option<String>,
+    #[structopt(
+        name = "core-dump",
+        long = "core-dump",
+        help = "Serve an ELF core dump instead of an attached target, no probe is needed for this."
+    )]
+    core_dump: Option<String>,
 }
 
 fn main() {
@@ -70,6 +76,17 @@ fn main_try() -> Result<(), failure::Error> {
     // Get commandline options.
     let opt = Opt::from_iter(std::env::args());
 
+    if let Some(path) = opt.core_dump {
+        let dump = CoreDump::from_elf(&std::fs::read(path)?)?;
+
+        if let Err(e) = probe_rs_gdb_server::run_core_dump(opt.gdb_connection_string, dump) {
+            eprintln!("During the execution of GDB an error was encountered:");
+            eprintln!("{:?}", e);
+        }
+
+        return Ok(());
+    }
+
     let probe = open_probe(None)?;
 
     let target_selector = match opt.chip {
@@ -7,7 +7,7 @@ use async_std::{
 };
 use futures::channel::mpsc;
 use gdb_protocol::packet::CheckedPacket;
-use probe_rs::Session;
+use probe_rs::{Core, CoreDump, Session};
 use std::sync::atomic::AtomicUsize;
 use std::sync::{Arc, Mutex};
 
@@ -19,15 +19,32 @@ const CONNECTION_STRING: &str = "127.0.0.1:1337";
 
 /// This is the main entrypoint which we will call to start the GDB stub.
 pub fn run(connection_string: Option<impl AsRef<str>>, session: Arc<Mutex<Session>>) -> Result<()> {
+    serve(connection_string, || {
+        session.lock().unwrap().attach_to_core(0)
+    })
+}
+
+/// Starts the GDB stub for a core dump, so GDB can inspect it without a probe.
+pub fn run_core_dump(connection_string: Option<impl AsRef<str>>, dump: CoreDump) -> Result<()> {
+    serve(connection_string, || Ok(Core::from_dump(dump.clone())?))
+}
+
+fn serve(
+    connection_string: Option<impl AsRef<str>>,
+    attach: impl Fn() -> std::result::Result<Core, probe_rs::Error>,
+) -> Result<()> {
     let connection_string = connection_string
         .map(|cs| cs.as_ref().to_owned())
         .unwrap_or_else(|| CONNECTION_STRING.to_owned());
     println!("GDB stub listening on {}", connection_string);
-    task::block_on(accept_loop(connection_string, session))
+    task::block_on(accept_loop(connection_string, attach))
 }
 
-/// This function accepts any incomming connection.
-async fn accept_loop(addr: impl ToSocketAddrs, session: Arc<Mutex<Session>>) -> Result<()> {
+/// This function accepts any incomming connection, and attaches to a core for it.
+async fn accept_loop(
+    addr: impl ToSocketAddrs,
+    attach: impl Fn() -> std::result::Result<Core, probe_rs::Error>,
+) -> Result<()> {
     let listener = TcpListener::bind(addr).await?;
 
     let mut incoming = listener.incoming();
@@ -37,15 +54,25 @@ async fn accept_loop(addr: impl ToSocketAddrs, session: Arc<Mutex<Session>>) ->
         let (tbd_sender, tbd_receiver) = mpsc::unbounded();
         let stream = Arc::new(stream?);
 
+        // A failed attach only closes this connection, the next one can try again.
+        let core = match attach() {
+            Ok(core) => core,
+            Err(e) => {
+                eprintln!(
+                    "The core could not be attached for the new connection. It has been closed."
+                );
+                eprintln!("{:?}", e);
+                continue;
+            }
+        };
+
         let inbound_broker_handle = task::spawn(inbound_broker_loop(
             Arc::clone(&stream),
             tbd_sender,
             packet_stream_receiver,
             acks_due,
         ));
-        if let Err(e) =
-            super::worker::worker(tbd_receiver, packet_stream_sender, session.clone()).await
-        {
+        if let Err(e) = super::worker::worker(tbd_receiver, packet_stream_sender, core).await {
             eprintln!(
                 "An error with the current connection has been encountered. It has been closed."
             );
@@ -1,9 +1,17 @@
-use probe_rs::Core;
+use crate::target_description::{register_address, register_bits, target_description};
+use probe_rs::{Core, CoreStatus, HaltReason, WatchKind};
 use recap::Recap;
 use serde::Deserialize;
 
-pub(crate) fn q_supported() -> Option<String> {
-    Some("PacketSize=2048;swbreak-;hwbreak+;vContSupported+;qXfer:memory-map:read+".into())
+pub(crate) fn q_supported(core: &Core) -> Option<String> {
+    let mut features =
+        String::from("PacketSize=2048;swbreak+;hwbreak+;vContSupported+;qXfer:memory-map:read+");
+
+    if target_description(core).is_some() {
+        features.push_str(";qXfer:features:read+");
+    }
+
+    Some(features)
 }
 
 pub(crate) fn reply_empty() -> Option<String> {
@@ -14,8 +22,49 @@ pub(crate) fn q_attached() -> Option<String> {
     Some("1".into())
 }
 
-pub(crate) fn halt_reason() -> Option<String> {
-    Some("S05".into())
+pub(crate) fn halt_reason(core: &Core) -> Option<String> {
+    Some(stop_reply(core))
+}
+
+/// The stop reply for the reason why the core is halted.
+pub(crate) fn stop_reply(core: &Core) -> String {
+    match core.status() {
+        Ok(CoreStatus::Halted(HaltReason::Breakpoint)) => breakpoint_stop_reply(core),
+        Ok(CoreStatus::Halted(HaltReason::Watchpoint { address, kind })) => {
+            watchpoint_stop_reply(address, kind)
+        }
+        // SIGINT, which GDB expects after an interrupt.
+        Ok(CoreStatus::Halted(HaltReason::Request)) => "S02".into(),
+        // SIGSEGV, for a fault.
+        Ok(CoreStatus::Halted(HaltReason::Exception)) => "S0b".into(),
+        _ => "S05".into(),
+    }
+}
+
+/// Software breakpoints are reported as `swbreak`, all others as `hwbreak`.
+fn breakpoint_stop_reply(core: &Core) -> String {
+    let pc = core.read_core_reg(core.registers().program_counter());
+
+    match pc {
+        Ok(pc) if core.has_sw_breakpoint(pc) => "T05swbreak:;",
+        _ => "T05hwbreak:;",
+    }
+    .into()
+}
+
+/// The address of the watchpoint is reported if the core knows it, otherwise
+/// only `SIGTRAP`.
+fn watchpoint_stop_reply(address: Option<u32>, kind: WatchKind) -> String {
+    let kind = match kind {
+        WatchKind::Read => "rwatch",
+        WatchKind::Write => "watch",
+        WatchKind::Access => "awatch",
+    };
+
+    match address {
+        Some(address) => format!("T05{}:{:x};", kind, address),
+        None => "T05".into(),
+    }
 }
 
 pub(crate) fn read_general_registers() -> Option<String> {
@@ -34,25 +83,23 @@ pub(crate) fn read_register(packet_string: String, core: &Core) -> Option<String
     let _ = core.halt();
     core.wait_for_core_halted().unwrap();
 
-    let value = core
-        .read_core_reg(u16::from_str_radix(&p.reg, 16).unwrap())
-        .unwrap();
+    let address = register_address(core, u16::from_str_radix(&p.reg, 16).unwrap());
+    let bytes = register_bits(core, address) as usize / 8;
 
-    format!(
-        "{}{}{}{}",
-        value as u8,
-        (value >> 8) as u8,
-        (value >> 16) as u8,
-        (value >> 24) as u8
-    );
+    // Registers which can not be read, e.g. because they are not part of a
+    // core dump, are reported as unavailable.
+    let value = match core.read_core_reg_64(address) {
+        Ok(value) => value,
+        Err(_) => return Some("xx".repeat(bytes)),
+    };
 
-    Some(format!(
-        "{:02x}{:02x}{:02x}{:02x}",
-        value as u8,
-        (value >> 8) as u8,
-        (value >> 16) as u8,
-        (value >> 24) as u8
-    ))
+    // The value is sent in the byte order of the target, which is little endian.
+    Some(
+        value.to_le_bytes()[..bytes]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect(),
+    )
 }
 
 pub(crate) fn read_memory(packet_string: String, core: &Core) -> Option<String> {
@@ -66,12 +113,16 @@ pub(crate) fn read_memory(packet_string: String, core: &Core) -> Option<String>
     let m = packet_string.parse::<M>().unwrap();
 
     let mut readback_data = vec![0u8; usize::from_str_radix(&m.length, 16).unwrap()];
-    core.memory()
+    if core
+        .memory()
         .read_block8(
             u32::from_str_radix(&m.addr, 16).unwrap(),
             &mut readback_data,
         )
-        .unwrap();
+        .is_err()
+    {
+        return Some("E01".into());
+    }
 
     Some(
         readback_data
@@ -87,7 +138,10 @@ pub(crate) fn vcont_supported() -> Option<String> {
 }
 
 pub(crate) fn run(core: &Core, awaits_halt: &mut bool) -> Option<String> {
-    core.run().unwrap();
+    // A core dump can not be resumed.
+    if core.run().is_err() {
+        return Some("E01".into());
+    }
     *awaits_halt = true;
     None
 }
@@ -100,7 +154,9 @@ pub(crate) fn stop(core: &Core, awaits_halt: &mut bool) -> Option<String> {
 }
 
 pub(crate) fn step(core: &Core, awaits_halt: &mut bool) -> Option<String> {
-    core.step().unwrap();
+    if core.step().is_err() {
+        return Some("E01".into());
+    }
     *awaits_halt = false;
     Some("S05".into())
 }
@@ -143,6 +199,54 @@ pub(crate) fn remove_hardware_break(packet_string: String, core: &mut Core) -> O
     Some("OK".into())
 }
 
+pub(crate) fn insert_software_break(packet_string: String, core: &mut Core) -> Option<String> {
+    #[derive(Debug, Deserialize, PartialEq, Recap)]
+    #[recap(regex = r#"Z0,(?P<addr>\w+),(?P<kind>\w+)"#)]
+    struct Z0 {
+        addr: String,
+        kind: String,
+    }
+
+    let z0 = packet_string.parse::<Z0>().unwrap();
+
+    let addr = u32::from_str_radix(&z0.addr, 16).unwrap();
+
+    // A breakpoint unit is used if one is free, otherwise the code is patched.
+    match core.add_breakpoint(addr, Default::default()) {
+        Ok(_) => Some("OK".into()),
+        Err(e) => {
+            log::warn!("Unable to set breakpoint at {:#010x}: {}", addr, e);
+            Some("E01".into())
+        }
+    }
+}
+
+pub(crate) fn remove_software_break(packet_string: String, core: &mut Core) -> Option<String> {
+    #[derive(Debug, Deserialize, PartialEq, Recap)]
+    #[recap(regex = r#"z0,(?P<addr>\w+),(?P<kind>\w+)"#)]
+    struct Z0 {
+        addr: String,
+        kind: String,
+    }
+
+    let z0 = packet_string.parse::<Z0>().unwrap();
+
+    let addr = u32::from_str_radix(&z0.addr, 16).unwrap();
+
+    let result = match core.breakpoint_at(addr) {
+        Some(id) => core.remove_breakpoint(id),
+        None => core.clear_breakpoint(addr),
+    };
+
+    match result {
+        Ok(()) => Some("OK".into()),
+        Err(e) => {
+            log::warn!("Unable to clear breakpoint at {:#010x}: {}", addr, e);
+            Some("E01".into())
+        }
+    }
+}
+
 pub(crate) fn write_memory(packet_string: String, data: &[u8], core: &Core) -> Option<String> {
     #[derive(Debug, Deserialize, PartialEq, Recap)]
     #[recap(regex = r#"X(?P<addr>\w+),(?P<length>\w+):(?P<data>[01]*)"#)]
@@ -178,11 +282,54 @@ pub(crate) fn get_memory_map() -> Option<String> {
     )
 }
 
+pub(crate) fn read_target_description(packet_string: String, core: &Core) -> Option<String> {
+    #[derive(Debug, Deserialize, PartialEq, Recap)]
+    #[recap(regex = r#"qXfer:features:read:target.xml:(?P<offset>\w+),(?P<length>\w+)"#)]
+    struct Qxfer {
+        offset: String,
+        length: String,
+    }
+
+    let qxfer = packet_string.parse::<Qxfer>().ok()?;
+
+    let xml = target_description(core)?;
+
+    Some(
+        std::str::from_utf8(&gdb_sanitize_file(
+            xml.into_bytes(),
+            u32::from_str_radix(&qxfer.offset, 16).unwrap(),
+            u32::from_str_radix(&qxfer.length, 16).unwrap(),
+        ))
+        .unwrap()
+        .to_string(),
+    )
+}
+
+/// Lists the breakpoints with their hit statistics, for `monitor breakpoints`.
+pub(crate) fn list_breakpoints(core: &Core) -> Option<String> {
+    let mut output = String::new();
+
+    for breakpoint in core.managed_breakpoints() {
+        let statistics = breakpoint.statistics();
+
+        output.push_str(&format!(
+            "{}: {:#010x}, {} hits, {} halts\n",
+            breakpoint.id(),
+            breakpoint.address(),
+            statistics.hits,
+            statistics.halts
+        ));
+    }
+
+    // The output of monitor commands is hex encoded.
+    Some(output.bytes().map(|byte| format!("{:02x}", byte)).collect())
+}
+
 pub(crate) fn user_halt(core: &Core, awaits_halt: &mut bool) -> Option<String> {
     let _ = core.halt();
     core.wait_for_core_halted().unwrap();
     *awaits_halt = false;
-    Some("T05hwbreak:;".into())
+    Some(stop_reply(core))
 }
 
 pub(crate) fn detach(break_due: &mut bool) -> Option<String> {
@@ -216,3 +363,26 @@ fn gdb_sanitize_file(mut data: Vec<u8>, offset: u32, len: u32) -> Vec<u8> {
         trimmed_data
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::watchpoint_stop_reply;
+    use probe_rs::WatchKind;
+
+    #[test]
+    fn watchpoint_stop_replies() {
+        assert_eq!(
+            watchpoint_stop_reply(Some(0x2000_0040), WatchKind::Read),
+            "T05rwatch:20000040;"
+        );
+        assert_eq!(
+            watchpoint_stop_reply(Some(0x2000_0040), WatchKind::Write),
+            "T05watch:20000040;"
+        );
+        assert_eq!(
+            watchpoint_stop_reply(Some(0x2000_0040), WatchKind::Access),
+            "T05awatch:20000040;"
+        );
+        assert_eq!(watchpoint_stop_reply(None, WatchKind::Access), "T05");
+    }
+}
@@ -3,7 +3,8 @@
 mod gdb_server_async;
 mod handlers;
 mod reader;
+mod target_description;
 mod worker;
 mod writer;
 
-pub use gdb_server_async::run;
+pub use gdb_server_async::{run, run_core_dump};
@@ -0,0 +1,190 @@
+//! The target description, which tells GDB the registers of a core.
+//!
+//! Only RISC-V cores have a description. GDB uses its built-in register
+//! layout for ARM cores.
+
+use probe_rs::{Architecture, Core, CoreRegisterAddress};
+use std::fmt::Write;
+
+/// The GDB register number of the first floating point register.
+const RISCV_FIRST_FPR: u16 = 33;
+
+/// The GDB register number of the first CSR, CSRs are numbered by their address.
+const RISCV_FIRST_CSR: u16 = 65;
+
+/// Returns the target description of the core, or `None` if GDB should use its default.
+pub(crate) fn target_description(core: &Core) -> Option<String> {
+    if core.architecture() != Architecture::RISCV {
+        return None;
+    }
+
+    let registers = core.registers();
+    let xlen = core.register_bits().unwrap_or(32);
+    let flen = core.fpu_register_bits().unwrap_or(32);
+
+    let mut xml = format!(
+        r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+<architecture>riscv:rv{}</architecture>
+<feature name="org.gnu.gdb.riscv.cpu">
+"#,
+        xlen
+    );
+
+    for register in registers.registers() {
+        write_register(
+            &mut xml,
+            register.name(),
+            register.into(),
+            xlen,
+            "int",
+            None,
+        );
+    }
+    write_register(
+        &mut xml,
+        registers.program_counter().name(),
+        registers.program_counter().into(),
+        xlen,
+        "code_ptr",
+        None,
+    );
+    xml.push_str("</feature>\n");
+
+    // The registers are not shown if the FPU can not be detected.
+    if core.fpu_present().unwrap_or(false) {
+        xml.push_str("<feature name=\"org.gnu.gdb.riscv.fpu\">\n");
+        for register in registers.fpu_registers() {
+            let address: CoreRegisterAddress = register.into();
+            let (bits, kind) = match (is_fpr(address), flen) {
+                (true, 64) => (64, "ieee_double"),
+                (true, _) => (32, "ieee_single"),
+                // fflags, frm and fcsr
+                (false, _) => (32, "int"),
+            };
+
+            write_register(
+                &mut xml,
+                register.name(),
+                address,
+                bits,
+                kind,
+                Some("float"),
+            );
+        }
+        xml.push_str("</feature>\n");
+    }
+
+    xml.push_str("<feature name=\"org.gnu.gdb.riscv.csr\">\n");
+    for register in registers.control_registers() {
+        // dpc is already described as the program counter.
+        if register.name() == "dpc" {
+            continue;
+        }
+
+        write_register(
+            &mut xml,
+            register.name(),
+            register.into(),
+            xlen,
+            "int",
+            Some("csr"),
+        );
+    }
+    xml.push_str("</feature>\n</target>");
+
+    Some(xml)
+}
+
+fn write_register(
+    xml: &mut String,
+    name: &str,
+    address: CoreRegisterAddress,
+    bits: u32,
+    kind: &str,
+    group: Option<&str>,
+) {
+    let _ = write!(
+        xml,
+        r#"<reg name="{}" bitsize="{}" regnum="{}" type="{}""#,
+        name,
+        bits,
+        riscv_register_number(address),
+        kind
+    );
+
+    if let Some(group) = group {
+        let _ = write!(xml, r#" group="{}""#, group);
+    }
+
+    xml.push_str("/>\n");
+}
+
+fn is_fpr(address: CoreRegisterAddress) -> bool {
+    (0x1020..0x1040).contains(&address.0)
+}
+
+/// The number of bits GDB expects for the value of a register, which matches
+/// the sizes in the target description.
+pub(crate) fn register_bits(core: &Core, address: CoreRegisterAddress) -> u32 {
+    if core.architecture() != Architecture::RISCV {
+        return 32;
+    }
+
+    if is_fpr(address) {
+        core.fpu_register_bits().unwrap_or(32)
+    } else if (0x1..=0x3).contains(&address.0) {
+        // fflags, frm and fcsr
+        32
+    } else {
+        core.register_bits().unwrap_or(32)
+    }
+}
+
+/// The GDB register number of a RISC-V register.
+fn riscv_register_number(address: CoreRegisterAddress) -> u16 {
+    match address.0 {
+        // x0 to x31
+        0x1000..=0x101f => address.0 - 0x1000,
+        // The program counter is read from dpc.
+        0x7b1 => 32,
+        // f0 to f31
+        0x1020..=0x103f => address.0 - 0x1020 + RISCV_FIRST_FPR,
+        csr => csr + RISCV_FIRST_CSR,
+    }
+}
+
+/// The register address of a GDB register number, which is the inverse of the
+/// numbering in the target description.
+pub(crate) fn register_address(core: &Core, number: u16) -> CoreRegisterAddress {
+    if core.architecture() != Architecture::RISCV {
+        return CoreRegisterAddress(number);
+    }
+
+    let address = match number {
+        0..=31 => 0x1000 + number,
+        32 => 0x7b1,
+        n if n < RISCV_FIRST_CSR => 0x1020 + n - RISCV_FIRST_FPR,
+        n => n - RISCV_FIRST_CSR,
+    };
+
+    CoreRegisterAddress(address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::riscv_register_number;
+    use probe_rs::CoreRegisterAddress;
+
+    #[test]
+    fn riscv_register_numbers() {
+        assert_eq!(riscv_register_number(CoreRegisterAddress(0x1002)), 2);
+        assert_eq!(riscv_register_number(CoreRegisterAddress(0x7b1)), 32);
+        assert_eq!(riscv_register_number(CoreRegisterAddress(0x1021)), 34);
+        assert_eq!(
+            riscv_register_number(CoreRegisterAddress(0x342)),
+            0x342 + 65
+        );
+    }
+}
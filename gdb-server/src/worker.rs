@@ -4,8 +4,6 @@ use futures::future::FutureExt;
 use futures::select;
 use gdb_protocol::packet::{CheckedPacket, Kind as PacketKind};
 use probe_rs::Core;
-use probe_rs::Session;
-use std::sync::{Arc, Mutex};
 
 use crate::handlers;
 
@@ -17,9 +15,8 @@ type Receiver<T> = mpsc::UnboundedReceiver<T>;
 pub async fn worker(
     mut input_stream: Receiver<CheckedPacket>,
     output_stream: Sender<CheckedPacket>,
-    session: Arc<Mutex<Session>>,
+    mut core: Core,
 ) -> ServerResult<()> {
-    let mut core = session.lock().unwrap().attach_to_core(0).unwrap();
     let mut awaits_halt = false;
 
     loop {
@@ -52,7 +49,7 @@ pub async fn handler(
         let packet_string = String::from_utf8_lossy(&packet.data).to_string();
         #[allow(clippy::if_same_then_else)]
         let response: Option<String> = if packet.data.starts_with(b"qSupported") {
-            handlers::q_supported()
+            handlers::q_supported(core)
         } else if packet.data.starts_with(b"vMustReplyEmpty") {
             handlers::reply_empty()
         } else if packet.data.starts_with(b"qTStatus") {
@@ -62,7 +59,7 @@ pub async fn handler(
         } else if packet.data.starts_with(b"qAttached") {
             handlers::q_attached()
         } else if packet.data.starts_with(b"?") {
-            handlers::halt_reason()
+            handlers::halt_reason(core)
         } else if packet.data.starts_with(b"g") {
             handlers::read_general_registers()
         } else if packet.data.starts_with(b"p") {
@@ -88,19 +85,25 @@ pub async fn handler(
         } else if packet.data.starts_with(b"vContb;s") || packet.data.starts_with(b"s") {
             handlers::step(&core, awaits_halt)
         } else if packet.data.starts_with(b"Z0") {
-            handlers::reply_empty()
+            handlers::insert_software_break(packet_string, core)
+        } else if packet.data.starts_with(b"z0") {
+            handlers::remove_software_break(packet_string, core)
         } else if packet.data.starts_with(b"Z1") {
             handlers::insert_hardware_break(packet_string, core)
         } else if packet.data.starts_with(b"z1") {
             handlers::remove_hardware_break(packet_string, core)
         } else if packet.data.starts_with(b"X") {
             handlers::write_memory(packet_string, &packet.data, core)
+        } else if packet.data.starts_with(b"qXfer:features:read") {
+            handlers::read_target_description(packet_string, core)
         } else if packet.data.starts_with(b"qXfer:memory-mapb:read") {
             handlers::get_memory_map()
         } else if packet.data.starts_with(&[0x03]) {
             handlers::user_halt(&core, awaits_halt)
         } else if packet.data.starts_with(b"D") {
             handlers::detach(&mut break_due)
+        } else if packet.data.starts_with(b"qRcmd,627265616b706f696e7473") {
+            handlers::list_breakpoints(core)
         } else if packet.data.starts_with(b"qRcmdb,7265736574") {
             handlers::reset_halt(&core)
         } else if packet.data.starts_with(b"qTfV") {
@@ -125,9 +128,10 @@ pub async fn handler(
 }
 
 pub async fn await_halt(core: &Core, output_stream: Sender<CheckedPacket>, await_halt: bool) {
-    if await_halt && core.core_halted().unwrap() {
+    // Breakpoints whose condition is not met resume the core, and are not reported.
+    if await_halt && core.poll_halted().unwrap() {
         let response =
-            CheckedPacket::from_data(PacketKind::Packet, "T05hwbreak:;".to_string().into_bytes());
+            CheckedPacket::from_data(PacketKind::Packet, handlers::stop_reply(core).into_bytes());
 
         let mut bytes = Vec::new();
         response.encode(&mut bytes).unwrap();
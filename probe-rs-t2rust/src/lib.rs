@@ -304,6 +304,17 @@ fn extract_variants(chip_family: &serde_yaml::Value) -> Vec<proc_macro2::TokenSt
                     .get("part")
                     .and_then(|v| v.as_u64().map(|v| v as u16)),
             );
+            let idcode = quote_option(
+                variant
+                    .get("idcode")
+                    .and_then(|v| v.as_u64().map(|v| v as u32)),
+            );
+            let svd = quote_option(
+                variant
+                    .get("svd")
+                    .and_then(|v| v.as_str())
+                    .map(|svd| quote::quote! { Cow::Borrowed(#svd) }),
+            );
 
             // Extract all the memory regions into a Vec of TookenStreams.
             let memory_map = extract_memory_map(&variant);
@@ -318,6 +329,8 @@ fn extract_variants(chip_family: &serde_yaml::Value) -> Vec<proc_macro2::TokenSt
                 Chip {
                     name: Cow::Borrowed(#name),
                     part: #part,
+                    idcode: #idcode,
+                    svd: #svd,
                     memory_map: Cow::Borrowed(&[
                         #(#memory_map,)*
                     ]),
@@ -354,6 +367,18 @@ fn extract_chip_family(
         .unwrap()
         .to_ascii_lowercase();
     let manufacturer = quote_option(extract_manufacturer(&chip_family));
+    let debug_sequence = quote_option(
+        chip_family
+            .get("debug_sequence")
+            .and_then(|v| v.as_str())
+            .map(|v| quote::quote! { Cow::Borrowed(#v) }),
+    );
+    let option_bytes = quote_option(
+        chip_family
+            .get("option_bytes")
+            .and_then(|v| v.as_str())
+            .map(|v| quote::quote! { Cow::Borrowed(#v) }),
+    );
 
     // Quote the chip.
     let chip_family = quote::quote! {
@@ -367,6 +392,8 @@ fn extract_chip_family(
                 #(#variants,)*
             ]),
             core: Cow::Borrowed(#core),
+            debug_sequence: #debug_sequence,
+            option_bytes: #option_bytes,
         }
     };
 
@@ -64,3 +64,60 @@ define_ap_register!(
     },
     if value.APPROTECTSTATUS { 1 } else { 0 }
 );
+
+// Debug mailbox
+// The debug mailbox is NXP's custom access port on LPC55xx devices. It is used to request
+// a debug session from the boot ROM, e.g. when the flash is empty or debugging is disabled.
+define_ap!(DebugMailboxAp);
+
+define_ap_register!(
+    /// Control and status register of the debug mailbox
+    DebugMailboxAp,
+    Dmcsw,
+    0x000,
+    [
+        (RESYNCH_REQ: bool),
+        (REQ_PENDING: bool),
+        (DBG_OR_ERR: bool),
+        (AHB_OR_ERR: bool),
+        (SOFT_RESET: bool),
+        (CHIP_RESET_REQ: bool),
+    ],
+    value,
+    Dmcsw {
+        RESYNCH_REQ: value & 0x01 != 0,
+        REQ_PENDING: value & 0x02 != 0,
+        DBG_OR_ERR: value & 0x04 != 0,
+        AHB_OR_ERR: value & 0x08 != 0,
+        SOFT_RESET: value & 0x10 != 0,
+        CHIP_RESET_REQ: value & 0x20 != 0,
+    },
+    u32::from(value.RESYNCH_REQ)
+        | (u32::from(value.REQ_PENDING) << 1)
+        | (u32::from(value.DBG_OR_ERR) << 2)
+        | (u32::from(value.AHB_OR_ERR) << 3)
+        | (u32::from(value.SOFT_RESET) << 4)
+        | (u32::from(value.CHIP_RESET_REQ) << 5)
+);
+
+define_ap_register!(
+    /// Request register of the debug mailbox, a command is started by writing to it
+    DebugMailboxAp,
+    Request,
+    0x004,
+    [(REQUEST: u32),],
+    value,
+    Request { REQUEST: value },
+    value.REQUEST
+);
+
+define_ap_register!(
+    /// Return register of the debug mailbox, holds the status of the last command
+    DebugMailboxAp,
+    Return,
+    0x008,
+    [(RETURN: u32),],
+    value,
+    Return { RETURN: value },
+    value.RETURN
+);
@@ -1,7 +1,7 @@
 use super::{
     ap::{
-        valid_access_ports, APAccess, APClass, APRegister, AccessPort, BaseaddrFormat, GenericAP,
-        MemoryAP, BASE, BASE2, IDR,
+        valid_access_ports, APAccess, APClass, APRegister, AccessPort, BaseaddrFormat, MemoryAP,
+        BASE, BASE2, IDR,
     },
     dp::{
         Abort, Ctrl, DPAccess, DPBankSel, DPRegister, DebugPortError, DebugPortId,
@@ -460,60 +460,24 @@ impl DPAccess for InnerArmCommunicationInterface {
     }
 }
 
-impl<R> APAccess<MemoryAP, R> for ArmCommunicationInterface
+impl<AP, R> APAccess<AP, R> for ArmCommunicationInterface
 where
-    R: APRegister<MemoryAP>,
+    AP: AccessPort,
+    R: APRegister<AP>,
 {
     type Error = DebugProbeError;
 
-    fn read_ap_register(&mut self, port: MemoryAP, register: R) -> Result<R, Self::Error> {
+    fn read_ap_register(&mut self, port: AP, register: R) -> Result<R, Self::Error> {
         self.inner.borrow_mut().read_ap_register(port, register)
     }
 
-    fn write_ap_register(&mut self, port: MemoryAP, register: R) -> Result<(), Self::Error> {
+    fn write_ap_register(&mut self, port: AP, register: R) -> Result<(), Self::Error> {
         self.inner.borrow_mut().write_ap_register(port, register)
     }
 
     fn write_ap_register_repeated(
         &mut self,
-        port: MemoryAP,
-        register: R,
-        values: &[u32],
-    ) -> Result<(), Self::Error> {
-        self.inner
-            .borrow_mut()
-            .write_ap_register_repeated(port, register, values)
-    }
-
-    fn read_ap_register_repeated(
-        &mut self,
-        port: MemoryAP,
-        register: R,
-        values: &mut [u32],
-    ) -> Result<(), Self::Error> {
-        self.inner
-            .borrow_mut()
-            .read_ap_register_repeated(port, register, values)
-    }
-}
-
-impl<R> APAccess<GenericAP, R> for ArmCommunicationInterface
-where
-    R: APRegister<GenericAP>,
-{
-    type Error = DebugProbeError;
-
-    fn read_ap_register(&mut self, port: GenericAP, register: R) -> Result<R, Self::Error> {
-        self.inner.borrow_mut().read_ap_register(port, register)
-    }
-
-    fn write_ap_register(&mut self, port: GenericAP, register: R) -> Result<(), Self::Error> {
-        self.inner.borrow_mut().write_ap_register(port, register)
-    }
-
-    fn write_ap_register_repeated(
-        &mut self,
-        port: GenericAP,
+        port: AP,
         register: R,
         values: &[u32],
     ) -> Result<(), Self::Error> {
@@ -524,7 +488,7 @@ where
 
     fn read_ap_register_repeated(
         &mut self,
-        port: GenericAP,
+        port: AP,
         register: R,
         values: &mut [u32],
     ) -> Result<(), Self::Error> {
@@ -600,14 +564,6 @@ impl ArmChipInfo {
                 }
             }
         }
-        // log::info!(
-        //     "{}\n{}\n{}\n{}",
-        //     "If you are using a Nordic chip, it might be locked to debug access".yellow(),
-        //     "Run cargo flash with --nrf-recover to unlock".yellow(),
-        //     "WARNING: --nrf-recover will erase the entire code".yellow(),
-        //     "flash and UICR area of the device, in addition to the entire RAM".yellow()
-        // );
-
         Ok(None)
     }
 }
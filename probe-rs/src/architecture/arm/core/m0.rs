@@ -1,8 +1,8 @@
-use super::ARM_REGISTER_FILE;
+use super::{ArmVersion, HaltReasonDecoder, ARM_REGISTER_FILE};
 use crate::core::RegisterDescription;
 use crate::core::{
-    Architecture, CoreInformation, CoreInterface, CoreRegister, CoreRegisterAddress, RegisterFile,
-    RegisterKind,
+    Architecture, CoreInformation, CoreInterface, CoreRegister, CoreRegisterAddress, CoreStatus,
+    RegisterFile, RegisterKind,
 };
 use crate::error::Error;
 use crate::memory::Memory;
@@ -279,6 +279,8 @@ pub struct M0 {
     memory: Memory,
 
     hw_breakpoints_enabled: bool,
+
+    halt_reason: HaltReasonDecoder,
 }
 
 impl M0 {
@@ -286,6 +288,7 @@ impl M0 {
         Self {
             memory,
             hw_breakpoints_enabled: false,
+            halt_reason: HaltReasonDecoder::new(ArmVersion::Armv6m),
         }
     }
 
@@ -327,6 +330,18 @@ impl CoreInterface for M0 {
         }
     }
 
+    fn status(&self) -> Result<CoreStatus, Error> {
+        let dhcsr = Dhcsr(self.memory.read32(Dhcsr::ADDRESS)?);
+
+        if !dhcsr.s_halt() {
+            return Ok(CoreStatus::Running);
+        }
+
+        let reason = self.halt_reason.read(self, &self.memory, dhcsr.c_step())?;
+
+        Ok(CoreStatus::Halted(reason))
+    }
+
     fn read_core_reg(&self, addr: CoreRegisterAddress) -> Result<u32, Error> {
         // Write the DCRSR value to select the register we want to read.
         let mut dcrsr_val = Dcrsr(0);
@@ -377,6 +392,8 @@ impl CoreInterface for M0 {
     }
 
     fn run(&self) -> Result<(), Error> {
+        self.halt_reason.clear(&self.memory)?;
+
         let mut value = Dhcsr(0);
         value.set_c_halt(false);
         value.set_c_debugen(true);
@@ -388,6 +405,8 @@ impl CoreInterface for M0 {
     }
 
     fn step(&self) -> Result<CoreInformation, Error> {
+        self.halt_reason.clear(&self.memory)?;
+
         let mut value = Dhcsr(0);
         // Leave halted state.
         // Step one instruction.
@@ -439,6 +458,7 @@ impl CoreInterface for M0 {
             self.memory.write32(Demcr::ADDRESS, demcr_enabled.into())?;
         }
 
+        self.halt_reason.clear(&self.memory)?;
         self.reset()?;
 
         self.wait_for_core_halted()?;
@@ -2,7 +2,7 @@
 //!
 
 use crate::core::{
-    CoreInformation, CoreInterface, CoreRegister, CoreRegisterAddress, RegisterFile,
+    CoreInformation, CoreInterface, CoreRegister, CoreRegisterAddress, CoreStatus, RegisterFile,
 };
 use crate::error::Error;
 use crate::memory::Memory;
@@ -12,7 +12,7 @@ use crate::architecture::arm::core::register;
 
 use bitfield::bitfield;
 
-use super::ARM_REGISTER_FILE;
+use super::{ArmVersion, HaltReasonDecoder, ARM_REGISTER_FILE};
 use crate::core::Architecture;
 use std::mem::size_of;
 
@@ -20,6 +20,8 @@ pub struct M33 {
     memory: Memory,
 
     hw_breakpoints_enabled: bool,
+
+    halt_reason: HaltReasonDecoder,
 }
 
 impl M33 {
@@ -27,6 +29,7 @@ impl M33 {
         Self {
             memory,
             hw_breakpoints_enabled: false,
+            halt_reason: HaltReasonDecoder::new(ArmVersion::Armv8m),
         }
     }
 
@@ -67,6 +70,18 @@ impl CoreInterface for M33 {
         }
     }
 
+    fn status(&self) -> Result<CoreStatus, Error> {
+        let dhcsr = Dhcsr(self.memory.read32(Dhcsr::ADDRESS)?);
+
+        if !dhcsr.s_halt() {
+            return Ok(CoreStatus::Running);
+        }
+
+        let reason = self.halt_reason.read(self, &self.memory, dhcsr.c_step())?;
+
+        Ok(CoreStatus::Halted(reason))
+    }
+
     fn halt(&self) -> Result<CoreInformation, Error> {
         let mut value = Dhcsr(0);
         value.set_c_halt(true);
@@ -84,6 +99,8 @@ impl CoreInterface for M33 {
         Ok(CoreInformation { pc: pc_value })
     }
     fn run(&self) -> Result<(), Error> {
+        self.halt_reason.clear(&self.memory)?;
+
         let mut value = Dhcsr(0);
         value.set_c_halt(false);
         value.set_c_debugen(true);
@@ -124,6 +141,7 @@ impl CoreInterface for M33 {
             self.memory.write32(Demcr::ADDRESS, demcr_enabled.into())?;
         }
 
+        self.halt_reason.clear(&self.memory)?;
         self.reset()?;
 
         self.wait_for_core_halted()?;
@@ -144,6 +162,8 @@ impl CoreInterface for M33 {
     }
 
     fn step(&self) -> Result<CoreInformation, Error> {
+        self.halt_reason.clear(&self.memory)?;
+
         let mut value = Dhcsr(0);
         // Leave halted state.
         // Step one instruction.
@@ -1,12 +1,12 @@
 use crate::core::{
-    CoreInformation, CoreInterface, CoreRegister, CoreRegisterAddress, RegisterFile,
+    CoreInformation, CoreInterface, CoreRegister, CoreRegisterAddress, CoreStatus, RegisterFile,
 };
 use crate::error::Error;
 use crate::memory::Memory;
 use crate::DebugProbeError;
 use bitfield::bitfield;
 
-use super::{register, ARM_REGISTER_FILE};
+use super::{register, ArmVersion, HaltReasonDecoder, ARM_REGISTER_FILE};
 use crate::core::Architecture;
 use std::mem::size_of;
 
@@ -287,6 +287,8 @@ pub struct M4 {
     memory: Memory,
 
     hw_breakpoints_enabled: bool,
+
+    halt_reason: HaltReasonDecoder,
 }
 
 impl M4 {
@@ -294,6 +296,7 @@ impl M4 {
         Self {
             memory,
             hw_breakpoints_enabled: false,
+            halt_reason: HaltReasonDecoder::new(ArmVersion::Armv7m),
         }
     }
 
@@ -334,6 +337,18 @@ impl CoreInterface for M4 {
         }
     }
 
+    fn status(&self) -> Result<CoreStatus, Error> {
+        let dhcsr = Dhcsr(self.memory.read32(Dhcsr::ADDRESS)?);
+
+        if !dhcsr.s_halt() {
+            return Ok(CoreStatus::Running);
+        }
+
+        let reason = self.halt_reason.read(self, &self.memory, dhcsr.c_step())?;
+
+        Ok(CoreStatus::Halted(reason))
+    }
+
     fn read_core_reg(&self, addr: CoreRegisterAddress) -> Result<u32, Error> {
         // Write the DCRSR value to select the register we want to read.
         let mut dcrsr_val = Dcrsr(0);
@@ -384,6 +399,8 @@ impl CoreInterface for M4 {
     }
 
     fn run(&self) -> Result<(), Error> {
+        self.halt_reason.clear(&self.memory)?;
+
         let mut value = Dhcsr(0);
         value.set_c_halt(false);
         value.set_c_debugen(true);
@@ -395,6 +412,8 @@ impl CoreInterface for M4 {
     }
 
     fn step(&self) -> Result<CoreInformation, Error> {
+        self.halt_reason.clear(&self.memory)?;
+
         let mut value = Dhcsr(0);
         // Leave halted state.
         // Step one instruction.
@@ -445,6 +464,7 @@ impl CoreInterface for M4 {
             self.memory.write32(Demcr::ADDRESS, demcr_enabled.into())?;
         }
 
+        self.halt_reason.clear(&self.memory)?;
         self.reset()?;
 
         self.wait_for_core_halted()?;
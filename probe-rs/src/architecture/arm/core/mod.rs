@@ -1,7 +1,14 @@
-use crate::core::CoreRegisterAddress;
 use crate::core::RegisterDescription;
 use crate::core::RegisterFile;
 use crate::core::RegisterKind;
+use crate::core::{
+    Architecture, CoreDump, CoreInterface, CoreRegister, CoreRegisterAddress, HaltReason,
+    MemorySegment, WatchKind,
+};
+use crate::error::Error;
+use crate::memory::Memory;
+use bitfield::bitfield;
+use std::cell::Cell;
 
 pub mod m0;
 pub mod m33;
@@ -24,6 +31,197 @@ impl CortexDump {
     }
 }
 
+/// Converts the dump, so it can be loaded with [`Core::from_dump`](crate::Core::from_dump).
+///
+/// `xPSR` is not part of a `CortexDump`, and is zero.
+impl From<CortexDump> for CoreDump {
+    fn from(dump: CortexDump) -> Self {
+        let mut registers = dump.regs.to_vec();
+        // xPSR and orig_r0
+        registers.extend_from_slice(&[0, 0]);
+
+        CoreDump::new(
+            Architecture::ARM,
+            registers,
+            vec![MemorySegment {
+                address: dump.stack_addr,
+                data: dump.stack,
+            }],
+        )
+    }
+}
+
+bitfield! {
+    /// The Debug Fault Status Register, which tells why the core halted.
+    ///
+    /// The bits are sticky, and are cleared by writing one to them.
+    #[derive(Copy, Clone)]
+    pub struct Dfsr(u32);
+    impl Debug;
+    pub external, _: 4;
+    pub vcatch, _: 3;
+    pub dwttrap, _: 2;
+    pub bkpt, _: 1;
+    pub halted, _: 0;
+}
+
+impl Dfsr {
+    /// Decodes the halt reason. `stepping` is set if `DHCSR.C_STEP` is set,
+    /// and `ipsr` is the number of the active exception.
+    fn halt_reason(self, stepping: bool, ipsr: u32) -> HaltReason {
+        if self.bkpt() {
+            HaltReason::Breakpoint
+        } else if self.dwttrap() {
+            // The comparator which matched is found by `HaltReasonDecoder`.
+            HaltReason::Watchpoint {
+                address: None,
+                kind: WatchKind::Access,
+            }
+        } else if self.vcatch() && ipsr == 0 {
+            // The reset vector catch halts the core before the first
+            // instruction, which runs in thread mode.
+            HaltReason::VectorCatch
+        } else if self.vcatch() {
+            HaltReason::Exception
+        } else if self.external() {
+            HaltReason::Request
+        } else if self.halted() && stepping {
+            HaltReason::Step
+        } else if self.halted() {
+            HaltReason::Request
+        } else {
+            HaltReason::Unknown
+        }
+    }
+}
+
+impl From<u32> for Dfsr {
+    fn from(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Dfsr> for u32 {
+    fn from(value: Dfsr) -> Self {
+        value.0
+    }
+}
+
+impl CoreRegister for Dfsr {
+    const ADDRESS: u32 = 0xE000_ED30;
+    const NAME: &'static str = "DFSR";
+}
+
+const DWT_CTRL: u32 = 0xe000_1000;
+const DWT_COMP0: u32 = 0xe000_1020;
+const DWT_FUNCTION0: u32 = 0xe000_1028;
+/// The distance between the registers of two comparators.
+const DWT_COMPARATOR_STRIDE: u32 = 0x10;
+/// `DWT_FUNCTIONn.MATCHED`, which is cleared when the register is read.
+const DWT_MATCHED: u32 = 1 << 24;
+
+/// The version of the M profile architecture, which decides how the DWT
+/// comparators are configured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ArmVersion {
+    Armv6m,
+    Armv7m,
+    Armv8m,
+}
+
+impl ArmVersion {
+    /// The kind of a watchpoint, from `DWT_FUNCTIONn`.
+    fn watch_kind(self, function: u32) -> WatchKind {
+        match (self, function & 0xf) {
+            (ArmVersion::Armv6m, 0b0101) | (ArmVersion::Armv7m, 0b0101) => WatchKind::Read,
+            (ArmVersion::Armv6m, 0b0110) | (ArmVersion::Armv7m, 0b0110) => WatchKind::Write,
+            // ARMv8-M swaps the encodings, `MATCH` is 0b0100 for any access.
+            (ArmVersion::Armv8m, 0b0101) => WatchKind::Write,
+            (ArmVersion::Armv8m, 0b0110) => WatchKind::Read,
+            _ => WatchKind::Access,
+        }
+    }
+}
+
+/// Reads why a halted core is halted, and remembers the watchpoint which
+/// halted it.
+///
+/// Reading `DWT_FUNCTIONn` clears `MATCHED`, so the comparator which matched
+/// can only be found once after each halt.
+pub(crate) struct HaltReasonDecoder {
+    version: ArmVersion,
+    watchpoint: Cell<Option<HaltReason>>,
+}
+
+impl HaltReasonDecoder {
+    pub(crate) fn new(version: ArmVersion) -> Self {
+        Self {
+            version,
+            watchpoint: Cell::new(None),
+        }
+    }
+
+    /// Reads why the core is halted. `stepping` is set if `DHCSR.C_STEP` is set.
+    pub(crate) fn read(
+        &self,
+        core: &impl CoreInterface,
+        memory: &Memory,
+        stepping: bool,
+    ) -> Result<HaltReason, Error> {
+        let dfsr = Dfsr(memory.read32(Dfsr::ADDRESS)?);
+
+        let ipsr = if dfsr.vcatch() {
+            core.read_core_reg(register::XPSR.address)? & 0x1ff
+        } else {
+            0
+        };
+
+        match dfsr.halt_reason(stepping, ipsr) {
+            HaltReason::Watchpoint { .. } => {
+                if let Some(watchpoint) = self.watchpoint.get() {
+                    return Ok(watchpoint);
+                }
+
+                let watchpoint = read_watchpoint(memory, self.version)?;
+                self.watchpoint.set(Some(watchpoint));
+
+                Ok(watchpoint)
+            }
+            reason => Ok(reason),
+        }
+    }
+
+    /// Clears the halt reason, before the core is resumed or reset.
+    pub(crate) fn clear(&self, memory: &Memory) -> Result<(), Error> {
+        self.watchpoint.set(None);
+
+        memory.write32(Dfsr::ADDRESS, 0b1_1111)
+    }
+}
+
+/// Finds the DWT comparator which halted the core. The address is not known
+/// if no comparator has `MATCHED` set.
+fn read_watchpoint(memory: &Memory, version: ArmVersion) -> Result<HaltReason, Error> {
+    let comparators = memory.read32(DWT_CTRL)? >> 28;
+
+    for n in 0..comparators {
+        let offset = n * DWT_COMPARATOR_STRIDE;
+        let function = memory.read32(DWT_FUNCTION0 + offset)?;
+
+        if function & DWT_MATCHED != 0 {
+            return Ok(HaltReason::Watchpoint {
+                address: Some(memory.read32(DWT_COMP0 + offset)?),
+                kind: version.watch_kind(function),
+            });
+        }
+    }
+
+    Ok(HaltReason::Watchpoint {
+        address: None,
+        kind: WatchKind::Access,
+    })
+}
+
 pub(crate) mod register {
     use crate::{
         core::{RegisterDescription, RegisterKind},
@@ -55,7 +253,7 @@ pub(crate) mod register {
     };
 }
 
-static ARM_REGISTER_FILE: RegisterFile = RegisterFile {
+pub(crate) static ARM_REGISTER_FILE: RegisterFile = RegisterFile {
     platform_registers: &[
         RegisterDescription {
             name: "R0",
@@ -178,4 +376,88 @@ static ARM_REGISTER_FILE: RegisterFile = RegisterFile {
             address: CoreRegisterAddress(1),
         },
     ],
+
+    control_registers: &[],
+
+    fpu_registers: &[],
 };
+
+#[cfg(test)]
+mod tests {
+    use super::{read_watchpoint, ArmVersion, Dfsr, DWT_CTRL, DWT_MATCHED};
+    use crate::test_support::RamImage;
+    use crate::{HaltReason, Memory, WatchKind};
+
+    #[test]
+    fn decode_halt_reason() {
+        assert_eq!(Dfsr(0b0_0010).halt_reason(false, 0), HaltReason::Breakpoint);
+        // A breakpoint which is hit by a step is reported as a breakpoint.
+        assert_eq!(Dfsr(0b0_0011).halt_reason(true, 0), HaltReason::Breakpoint);
+        assert_eq!(Dfsr(0b0_0001).halt_reason(true, 0), HaltReason::Step);
+        assert_eq!(Dfsr(0b0_0001).halt_reason(false, 0), HaltReason::Request);
+        assert_eq!(
+            Dfsr(0b0_1000).halt_reason(false, 0),
+            HaltReason::VectorCatch
+        );
+        // HardFault
+        assert_eq!(Dfsr(0b0_1000).halt_reason(false, 3), HaltReason::Exception);
+        assert_eq!(Dfsr(0).halt_reason(false, 0), HaltReason::Unknown);
+    }
+
+    /// A DWT with two comparators, of which the second one watches 0x2000_0040.
+    fn dwt(function: u32) -> Memory {
+        let mut data = vec![0; 0x40];
+        data[..4].copy_from_slice(&(2u32 << 28).to_le_bytes());
+        data[0x30..0x34].copy_from_slice(&0x2000_0040u32.to_le_bytes());
+        data[0x38..0x3c].copy_from_slice(&function.to_le_bytes());
+
+        Memory::new(RamImage {
+            base: DWT_CTRL,
+            data,
+        })
+    }
+
+    #[test]
+    fn decode_watchpoint() {
+        let watchpoint =
+            |version, function| read_watchpoint(&dwt(DWT_MATCHED | function), version).unwrap();
+
+        assert_eq!(
+            watchpoint(ArmVersion::Armv7m, 0b0101),
+            HaltReason::Watchpoint {
+                address: Some(0x2000_0040),
+                kind: WatchKind::Read
+            }
+        );
+        assert_eq!(
+            watchpoint(ArmVersion::Armv6m, 0b0110),
+            HaltReason::Watchpoint {
+                address: Some(0x2000_0040),
+                kind: WatchKind::Write
+            }
+        );
+        assert_eq!(
+            watchpoint(ArmVersion::Armv8m, 0b0101),
+            HaltReason::Watchpoint {
+                address: Some(0x2000_0040),
+                kind: WatchKind::Write
+            }
+        );
+        assert_eq!(
+            watchpoint(ArmVersion::Armv8m, 0b0100),
+            HaltReason::Watchpoint {
+                address: Some(0x2000_0040),
+                kind: WatchKind::Access
+            }
+        );
+
+        // No comparator has matched.
+        assert_eq!(
+            read_watchpoint(&dwt(0b0111), ArmVersion::Armv7m).unwrap(),
+            HaltReason::Watchpoint {
+                address: None,
+                kind: WatchKind::Access
+            }
+        );
+    }
+}
@@ -338,6 +338,40 @@ where
         Ok(())
     }
 
+    /// Write a 16bit word at `addr`.
+    ///
+    /// The address where the write should be performed at has to be half word aligned.
+    /// Returns `AccessPortError::MemoryNotAligned` if this does not hold true.
+    pub fn write16(&mut self, address: u32, data: u16) -> Result<(), AccessPortError> {
+        if (address & 1) != 0 {
+            return Err(AccessPortError::alignment_error(address, 2));
+        }
+
+        let aligned = aligned_range(address, 2)?;
+
+        // Offset of the half word in the word (little endian)
+        let bit_offset = (address - aligned.start) * 8;
+
+        if self.only_32bit_data_size {
+            // Read the existing 32-bit word and insert the half word at the correct bit offset
+            let word = self.read32(aligned.start)?;
+            let word = word & !(0xFFFF << bit_offset) | (u32::from(data) << bit_offset);
+
+            self.write32(aligned.start, word)?;
+        } else {
+            let csw = self.build_csw_register(DataSize::U16);
+            let drw = DRW {
+                data: u32::from(data) << bit_offset,
+            };
+            let tar = TAR { address };
+            self.write_ap_register(csw)?;
+            self.write_ap_register(tar)?;
+            self.write_ap_register(drw)?;
+        }
+
+        Ok(())
+    }
+
     /// Write an 8bit word at `addr`.
     pub fn write8(&mut self, address: u32, data: u8) -> Result<(), AccessPortError> {
         let aligned = aligned_range(address, 1)?;
@@ -552,6 +586,10 @@ where
         ADIMemoryInterface::write32(self, address, data).map_err(Error::architecture_specific)
     }
 
+    fn write16(&mut self, address: u32, data: u16) -> Result<(), Error> {
+        ADIMemoryInterface::write16(self, address, data).map_err(Error::architecture_specific)
+    }
+
     fn write8(&mut self, address: u32, data: u8) -> Result<(), Error> {
         ADIMemoryInterface::write8(self, address, data).map_err(Error::architecture_specific)
     }
@@ -644,6 +682,26 @@ mod tests {
         }
     }
 
+    #[test]
+    fn write16() {
+        for &address in &[0, 2, 4, 6] {
+            let mock = MockMemoryAP::with_pattern();
+            let mut mi = ADIMemoryInterface::<MockMemoryAP>::new(mock, 0x0);
+
+            let mut expected = Vec::from(mi.mock_memory());
+            expected[address..address + 2].copy_from_slice(&DATA8[..2]);
+
+            mi.write16(address as u32, u16::from_le_bytes([DATA8[0], DATA8[1]]))
+                .unwrap_or_else(|e| panic!("write16 failed, address = {}: {}", address, e));
+            assert_eq!(
+                mi.mock_memory(),
+                expected.as_slice(),
+                "address = {}",
+                address
+            );
+        }
+    }
+
     #[test]
     fn read_block32() {
         let mut mock = MockMemoryAP::with_pattern();
@@ -3,6 +3,7 @@ pub(crate) mod communication_interface;
 pub(crate) mod core;
 pub mod dp;
 pub mod memory;
+pub mod sequences;
 
 pub use communication_interface::{ArmChipInfo, ArmCommunicationInterface, DAPAccess, DapError};
 pub use communication_interface::{PortType, Register};
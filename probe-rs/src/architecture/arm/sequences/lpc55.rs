@@ -0,0 +1,94 @@
+//! Debug sequence for LPC55xx chips
+
+use super::DebugSequence;
+use crate::architecture::arm::ap::custom_ap::{DebugMailboxAp, Dmcsw, Request, Return};
+use crate::architecture::arm::ap::{access_port_is_valid, APAccess, GenericAP};
+use crate::architecture::arm::ArmCommunicationInterface;
+use crate::{DebugProbeError, Error};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The debug mailbox is always located at AP 2.
+const DEBUG_MAILBOX_AP: u8 = 2;
+
+/// Command to request a debug session from the boot ROM.
+const START_DEBUG_SESSION: u32 = 0x07;
+
+/// Requests a debug session through the debug mailbox of LPC55xx chips.
+///
+/// If the flash of the chip is empty or contains an invalid image, the boot ROM
+/// does not enable the core access port. In that case a debug session has to be
+/// requested through the debug mailbox.
+#[derive(Debug, Clone, Copy)]
+pub struct Lpc55;
+
+impl Lpc55 {
+    const TIMEOUT: Duration = Duration::from_millis(500);
+
+    /// The time between two reads of the mailbox while waiting for the boot ROM.
+    const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+    fn enable_debug_mailbox(interface: &mut ArmCommunicationInterface) -> Result<(), Error> {
+        let mailbox = DebugMailboxAp::new(DEBUG_MAILBOX_AP);
+
+        log::debug!("Requesting debug session through the debug mailbox");
+
+        // Resynchronize the mailbox and reset the chip.
+        interface.write_ap_register(
+            mailbox,
+            Dmcsw {
+                RESYNCH_REQ: true,
+                CHIP_RESET_REQ: true,
+                ..Default::default()
+            },
+        )?;
+
+        let deadline = Instant::now() + Self::TIMEOUT;
+        loop {
+            // Reads can fail while the chip is being reset, so errors are ignored here.
+            if let Ok(csw) = interface.read_ap_register(mailbox, Dmcsw::default()) {
+                if !csw.RESYNCH_REQ && !csw.CHIP_RESET_REQ {
+                    break;
+                }
+            }
+
+            if Instant::now() > deadline {
+                return Err(DebugProbeError::Timeout.into());
+            }
+
+            thread::sleep(Self::POLL_INTERVAL);
+        }
+
+        interface.write_ap_register(
+            mailbox,
+            Request {
+                REQUEST: START_DEBUG_SESSION,
+            },
+        )?;
+
+        let deadline = Instant::now() + Self::TIMEOUT;
+        loop {
+            let ret = interface.read_ap_register(mailbox, Return::default())?;
+            if ret.RETURN & 0xFFFF == 0 {
+                return Ok(());
+            }
+
+            if Instant::now() > deadline {
+                return Err(DebugProbeError::Timeout.into());
+            }
+
+            thread::sleep(Self::POLL_INTERVAL);
+        }
+    }
+}
+
+impl DebugSequence for Lpc55 {
+    fn debug_port_setup(&self, interface: &mut ArmCommunicationInterface) -> Result<(), Error> {
+        // The core access port is only missing if the boot ROM did not enable debugging.
+        if access_port_is_valid(interface, GenericAP::new(0)) {
+            return Ok(());
+        }
+
+        Self::enable_debug_mailbox(interface)
+    }
+}
@@ -0,0 +1,85 @@
+//! Debug sequences for chip specific attach and reset handling
+//!
+//! Some chips require additional steps when a debugger connects to them or
+//! resets them, e.g. unlocking the debug port or enabling debug support in
+//! low power modes. These steps are modeled after the debug sequences found
+//! in CMSIS-Packs.
+//!
+//! A sequence is selected by the `debug_sequence` field of a chip family
+//! definition. All hooks have a default implementation which matches the
+//! behaviour of a chip without a custom sequence.
+
+pub mod lpc55;
+pub mod nrf;
+pub mod stm32;
+
+use super::ArmCommunicationInterface;
+use crate::{CoreInterface, Error, Memory};
+use std::sync::Arc;
+
+/// Chip specific logic which is executed when attaching to or resetting a chip.
+///
+/// The hooks are named after their CMSIS-Pack counterparts.
+pub trait DebugSequence: std::fmt::Debug + Send + Sync {
+    /// `DebugPortSetup`: Called after the debug port was powered up,
+    /// before any access port is used.
+    fn debug_port_setup(&self, _interface: &mut ArmCommunicationInterface) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// `DebugDeviceUnlock`: Called after the debug port setup, can be used
+    /// to check if the device is locked and to unlock it.
+    fn debug_device_unlock(&self, _interface: &mut ArmCommunicationInterface) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// `DebugCoreStart`: Called when attaching to a core and after the core was reset and halted.
+    fn debug_core_start(&self, _memory: &Memory) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// `ResetSystem`: Resets the whole system.
+    ///
+    /// The default implementation uses the reset of the core, which
+    /// requests a system reset using `AIRCR.SYSRESETREQ`.
+    fn reset_system(&self, core: &dyn CoreInterface) -> Result<(), Error> {
+        core.reset()
+    }
+}
+
+/// Returns the debug sequence with the given name, or `None` if
+/// no sequence with this name exists.
+pub fn get_debug_sequence(name: impl AsRef<str>) -> Option<Arc<dyn DebugSequence>> {
+    let sequence: Arc<dyn DebugSequence> = match &name.as_ref().to_ascii_lowercase()[..] {
+        "stm32f0" => Arc::new(stm32::Stm32::stm32f0()),
+        "stm32f1" => Arc::new(stm32::Stm32::stm32f1()),
+        "stm32f4" => Arc::new(stm32::Stm32::stm32f4()),
+        "stm32g0" => Arc::new(stm32::Stm32::stm32g0()),
+        "stm32h7" => Arc::new(stm32::Stm32::stm32h7()),
+        "stm32l0" => Arc::new(stm32::Stm32::stm32l0()),
+        "stm32l4" => Arc::new(stm32::Stm32::stm32l4()),
+        "stm32wb" => Arc::new(stm32::Stm32::stm32wb()),
+        "lpc55" => Arc::new(lpc55::Lpc55),
+        "nrf" => Arc::new(nrf::Nrf),
+        _ => return None,
+    };
+
+    Some(sequence)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::get_debug_sequence;
+
+    #[test]
+    fn lookup_known_sequences() {
+        assert!(get_debug_sequence("stm32f4").is_some());
+        assert!(get_debug_sequence("LPC55").is_some());
+        assert!(get_debug_sequence("nrf").is_some());
+    }
+
+    #[test]
+    fn lookup_unknown_sequence() {
+        assert!(get_debug_sequence("does-not-exist").is_none());
+    }
+}
@@ -0,0 +1,314 @@
+//! Debug sequence for nRF52 and nRF91 chips
+
+use super::DebugSequence;
+use crate::architecture::arm::ap::custom_ap::{
+    CtrlAP, APPROTECTSTATUS, ERASEALL, ERASEALLSTATUS, RESET,
+};
+use crate::architecture::arm::ap::{get_ap_by_idr, APAccess, GenericAP, IDR};
+use crate::architecture::arm::ArmCommunicationInterface;
+use crate::{DebugProbeError, Error};
+use std::thread;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// The IDR of the CTRL-AP, ignoring the revision.
+const CTRL_AP_IDR: u32 = 0x0288_0000;
+
+/// The maximum time a mass erase through the CTRL-AP is allowed to take.
+const ERASE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// The time between two reads of `ERASEALLSTATUS` while the mass erase runs.
+const ERASE_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Error)]
+pub enum NrfError {
+    #[error("The chip is locked by APPROTECT, so the core cannot be accessed. Use the `nrf-recover` command to unlock it. WARNING: This erases the entire flash, UICR and RAM of the chip.")]
+    ApProtected,
+    #[error("No CTRL-AP was found, this is probably not an nRF chip.")]
+    CtrlApNotFound,
+    #[error("The mass erase did not finish in time, the chip might still be locked.")]
+    EraseTimeout,
+}
+
+/// Access to the registers of the CTRL-AP, and to the IDR of all access ports to find it.
+pub trait CtrlApAccess:
+    APAccess<GenericAP, IDR, Error = DebugProbeError>
+    + APAccess<CtrlAP, RESET, Error = DebugProbeError>
+    + APAccess<CtrlAP, ERASEALL, Error = DebugProbeError>
+    + APAccess<CtrlAP, ERASEALLSTATUS, Error = DebugProbeError>
+    + APAccess<CtrlAP, APPROTECTSTATUS, Error = DebugProbeError>
+{
+}
+
+impl<T> CtrlApAccess for T where
+    T: APAccess<GenericAP, IDR, Error = DebugProbeError>
+        + APAccess<CtrlAP, RESET, Error = DebugProbeError>
+        + APAccess<CtrlAP, ERASEALL, Error = DebugProbeError>
+        + APAccess<CtrlAP, ERASEALLSTATUS, Error = DebugProbeError>
+        + APAccess<CtrlAP, APPROTECTSTATUS, Error = DebugProbeError>
+{
+}
+
+fn find_ctrl_ap(interface: &mut impl CtrlApAccess) -> Option<CtrlAP> {
+    get_ap_by_idr(interface, |idr| u32::from(idr) & 0x0FFF_FFFF == CTRL_AP_IDR).map(CtrlAP::from)
+}
+
+/// Checks if the connected nRF chip is locked by APPROTECT.
+///
+/// Returns [`NrfError::CtrlApNotFound`] if the chip has no CTRL-AP.
+///
+/// [`NrfError::CtrlApNotFound`]: enum.NrfError.html#variant.CtrlApNotFound
+pub fn is_locked(interface: &mut impl CtrlApAccess) -> Result<bool, Error> {
+    let ctrl_ap = find_ctrl_ap(interface)
+        .ok_or_else(|| Error::architecture_specific(NrfError::CtrlApNotFound))?;
+
+    let status = interface.read_ap_register(ctrl_ap, APPROTECTSTATUS::default())?;
+
+    // A value of `0` means the protection is enabled.
+    Ok(!status.APPROTECTSTATUS)
+}
+
+/// Mass erases the connected nRF chip through the CTRL-AP, which also disables APPROTECT.
+///
+/// This erases the entire flash, UICR and RAM of the chip.
+pub fn recover(interface: &mut impl CtrlApAccess) -> Result<(), Error> {
+    mass_erase(interface, ERASE_TIMEOUT)
+}
+
+fn mass_erase(interface: &mut impl CtrlApAccess, timeout: Duration) -> Result<(), Error> {
+    let ctrl_ap = find_ctrl_ap(interface)
+        .ok_or_else(|| Error::architecture_specific(NrfError::CtrlApNotFound))?;
+
+    log::info!("Starting mass erase...");
+
+    // Reset first
+    interface.write_ap_register(ctrl_ap, RESET { RESET: true })?;
+    interface.write_ap_register(ctrl_ap, RESET { RESET: false })?;
+
+    interface.write_ap_register(ctrl_ap, ERASEALL { ERASEALL: true })?;
+
+    let deadline = Instant::now() + timeout;
+    let timed_out = loop {
+        let status = interface.read_ap_register(ctrl_ap, ERASEALLSTATUS::default())?;
+        if !status.ERASEALLSTATUS {
+            break false;
+        }
+        if Instant::now() > deadline {
+            break true;
+        }
+
+        thread::sleep(ERASE_POLL_INTERVAL);
+    };
+
+    // Reset again, so the new APPROTECT state is applied.
+    interface.write_ap_register(ctrl_ap, RESET { RESET: true })?;
+    interface.write_ap_register(ctrl_ap, RESET { RESET: false })?;
+    interface.write_ap_register(ctrl_ap, ERASEALL { ERASEALL: false })?;
+
+    if timed_out {
+        return Err(Error::architecture_specific(NrfError::EraseTimeout));
+    }
+
+    if is_locked(interface)? {
+        return Err(Error::architecture_specific(NrfError::ApProtected));
+    }
+
+    log::info!("Mass erase completed, chip unlocked");
+
+    Ok(())
+}
+
+/// Checks the access port protection through the CTRL-AP of nRF chips.
+///
+/// If the protection is enabled, all other access ports are disabled and
+/// accessing them results in errors.
+#[derive(Debug, Clone, Copy)]
+pub struct Nrf;
+
+impl DebugSequence for Nrf {
+    fn debug_device_unlock(&self, interface: &mut ArmCommunicationInterface) -> Result<(), Error> {
+        let ctrl_ap = match find_ctrl_ap(interface) {
+            Some(ctrl_ap) => ctrl_ap,
+            None => {
+                log::debug!("No CTRL-AP found, skipping APPROTECT check.");
+                return Ok(());
+            }
+        };
+
+        let status = interface.read_ap_register(ctrl_ap, APPROTECTSTATUS::default())?;
+
+        // A value of `0` means the protection is enabled.
+        if status.APPROTECTSTATUS {
+            Ok(())
+        } else {
+            Err(Error::architecture_specific(NrfError::ApProtected))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_locked, mass_erase, NrfError, CTRL_AP_IDR};
+    use crate::architecture::arm::ap::custom_ap::{CtrlAP, ERASEALL, ERASEALLSTATUS};
+    use crate::architecture::arm::ap::{APAccess, APRegister, AccessPort, GenericAP, IDR};
+    use crate::architecture::arm::Register;
+    use crate::{DebugProbeError, Error};
+    use std::time::Duration;
+
+    const CTRL_AP_PORT: u8 = 1;
+
+    /// A chip with an AHB-AP at port 0 and, if `has_ctrl_ap` is set, the CTRL-AP at port 1.
+    struct MockCtrlAp {
+        has_ctrl_ap: bool,
+        protected: bool,
+        /// How often `ERASEALLSTATUS` reads as busy after the erase started,
+        /// or `None` if the erase never finishes.
+        busy_polls: Option<usize>,
+        erasing: bool,
+    }
+
+    impl MockCtrlAp {
+        fn new(protected: bool, busy_polls: Option<usize>) -> Self {
+            Self {
+                has_ctrl_ap: true,
+                protected,
+                busy_polls,
+                erasing: false,
+            }
+        }
+    }
+
+    impl<R: APRegister<GenericAP>> APAccess<GenericAP, R> for MockCtrlAp {
+        type Error = DebugProbeError;
+
+        fn read_ap_register(&mut self, port: GenericAP, _register: R) -> Result<R, Self::Error> {
+            assert_eq!(R::ADDRESS, IDR::ADDRESS);
+
+            match port.get_port_number() {
+                // AHB-AP
+                0 => Ok(R::from(0x2477_0011)),
+                CTRL_AP_PORT if self.has_ctrl_ap => Ok(R::from(CTRL_AP_IDR)),
+                _ => Err(DebugProbeError::Unknown),
+            }
+        }
+
+        fn read_ap_register_repeated(
+            &mut self,
+            _port: GenericAP,
+            _register: R,
+            _values: &mut [u32],
+        ) -> Result<(), Self::Error> {
+            unimplemented!()
+        }
+
+        fn write_ap_register(&mut self, _port: GenericAP, _register: R) -> Result<(), Self::Error> {
+            unimplemented!()
+        }
+
+        fn write_ap_register_repeated(
+            &mut self,
+            _port: GenericAP,
+            _register: R,
+            _values: &[u32],
+        ) -> Result<(), Self::Error> {
+            unimplemented!()
+        }
+    }
+
+    impl<R: APRegister<CtrlAP>> APAccess<CtrlAP, R> for MockCtrlAp {
+        type Error = DebugProbeError;
+
+        fn read_ap_register(&mut self, port: CtrlAP, _register: R) -> Result<R, Self::Error> {
+            assert_eq!(port.get_port_number(), CTRL_AP_PORT);
+
+            let value = if R::ADDRESS == ERASEALLSTATUS::ADDRESS {
+                match self.busy_polls {
+                    Some(0) if self.erasing => {
+                        self.erasing = false;
+                        self.protected = false;
+                        0
+                    }
+                    Some(ref mut polls) if self.erasing => {
+                        *polls -= 1;
+                        1
+                    }
+                    None if self.erasing => 1,
+                    _ => 0,
+                }
+            } else {
+                // APPROTECTSTATUS, `0` means locked.
+                u32::from(!self.protected)
+            };
+
+            Ok(R::from(value))
+        }
+
+        fn read_ap_register_repeated(
+            &mut self,
+            _port: CtrlAP,
+            _register: R,
+            _values: &mut [u32],
+        ) -> Result<(), Self::Error> {
+            unimplemented!()
+        }
+
+        fn write_ap_register(&mut self, port: CtrlAP, register: R) -> Result<(), Self::Error> {
+            assert_eq!(port.get_port_number(), CTRL_AP_PORT);
+
+            if R::ADDRESS == ERASEALL::ADDRESS && register.into() == 1 {
+                self.erasing = true;
+            }
+
+            Ok(())
+        }
+
+        fn write_ap_register_repeated(
+            &mut self,
+            _port: CtrlAP,
+            _register: R,
+            _values: &[u32],
+        ) -> Result<(), Self::Error> {
+            unimplemented!()
+        }
+    }
+
+    fn nrf_error(error: Error) -> NrfError {
+        match error {
+            Error::ArchitectureSpecific(error) => *error.downcast::<NrfError>().unwrap(),
+            error => panic!("Unexpected error: {}", error),
+        }
+    }
+
+    #[test]
+    fn locked_chip() {
+        assert!(is_locked(&mut MockCtrlAp::new(true, None)).unwrap());
+        assert!(!is_locked(&mut MockCtrlAp::new(false, None)).unwrap());
+    }
+
+    #[test]
+    fn missing_ctrl_ap() {
+        let mut interface = MockCtrlAp::new(true, None);
+        interface.has_ctrl_ap = false;
+
+        let error = is_locked(&mut interface).unwrap_err();
+        assert!(matches!(nrf_error(error), NrfError::CtrlApNotFound));
+    }
+
+    #[test]
+    fn recover_unlocks_chip() {
+        let mut interface = MockCtrlAp::new(true, Some(3));
+
+        mass_erase(&mut interface, Duration::from_secs(1)).unwrap();
+
+        assert!(!interface.protected);
+        assert_eq!(interface.busy_polls, Some(0));
+    }
+
+    #[test]
+    fn recover_times_out() {
+        let mut interface = MockCtrlAp::new(true, None);
+
+        let error = mass_erase(&mut interface, Duration::from_millis(50)).unwrap_err();
+        assert!(matches!(nrf_error(error), NrfError::EraseTimeout));
+    }
+}
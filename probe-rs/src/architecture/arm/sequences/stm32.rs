@@ -0,0 +1,100 @@
+//! Debug sequence for STM32 chips
+
+use super::DebugSequence;
+use crate::{Error, Memory};
+
+/// Enables debugging in the low power modes of STM32 chips.
+///
+/// Without this, the debug connection is lost as soon as the chip
+/// enters sleep, stop or standby mode. This is done by setting the
+/// corresponding bits in the `DBGMCU_CR` register.
+#[derive(Debug, Clone, Copy)]
+pub struct Stm32 {
+    /// Address of the `DBGMCU_CR` register.
+    dbgmcu_cr: u32,
+    /// Bits which are set in the `DBGMCU_CR` register.
+    enable_mask: u32,
+    /// Address and bit mask of the RCC register which enables the clock
+    /// of the DBGMCU, on chips where the DBGMCU sits on a peripheral bus.
+    clock_enable: Option<(u32, u32)>,
+}
+
+impl Stm32 {
+    /// DBG_SLEEP, DBG_STOP and DBG_STANDBY
+    const SLEEP_STOP_STANDBY: u32 = 0b111;
+    /// DBG_STOP and DBG_STANDBY, the sleep mode is always debuggable.
+    const STOP_STANDBY: u32 = 0b110;
+
+    pub fn new(dbgmcu_cr: u32, enable_mask: u32) -> Self {
+        Self {
+            dbgmcu_cr,
+            enable_mask,
+            clock_enable: None,
+        }
+    }
+
+    /// Enable the DBGMCU clock by setting `mask` in the RCC register at `address`
+    /// before accessing the `DBGMCU_CR` register.
+    pub fn with_clock_enable(mut self, address: u32, mask: u32) -> Self {
+        self.clock_enable = Some((address, mask));
+        self
+    }
+
+    pub fn stm32f0() -> Self {
+        // RCC_APB2ENR.DBGMCUEN
+        Self::new(0x4001_5804, Self::STOP_STANDBY).with_clock_enable(0x4002_1018, 1 << 22)
+    }
+
+    pub fn stm32f1() -> Self {
+        Self::new(0xE004_2004, Self::SLEEP_STOP_STANDBY)
+    }
+
+    pub fn stm32f4() -> Self {
+        Self::new(0xE004_2004, Self::SLEEP_STOP_STANDBY)
+    }
+
+    pub fn stm32g0() -> Self {
+        // RCC_APBENR1.DBGEN
+        Self::new(0x4001_5804, Self::STOP_STANDBY).with_clock_enable(0x4002_103C, 1 << 27)
+    }
+
+    pub fn stm32h7() -> Self {
+        // DBGSLEEP_D1, DBGSTOP_D1 and DBGSTBY_D1 as well as
+        // TRACECLKEN, D1DBGCKEN and D3DBGCKEN, which are required
+        // to access the debug components.
+        Self::new(0x5C00_1004, 0x0070_0000 | Self::SLEEP_STOP_STANDBY)
+    }
+
+    pub fn stm32l0() -> Self {
+        // RCC_APB2ENR.DBGEN
+        Self::new(0x4001_5804, Self::SLEEP_STOP_STANDBY).with_clock_enable(0x4002_1034, 1 << 22)
+    }
+
+    pub fn stm32l4() -> Self {
+        Self::new(0xE004_2004, Self::SLEEP_STOP_STANDBY)
+    }
+
+    pub fn stm32wb() -> Self {
+        Self::new(0xE004_2004, Self::SLEEP_STOP_STANDBY)
+    }
+}
+
+impl DebugSequence for Stm32 {
+    fn debug_core_start(&self, memory: &Memory) -> Result<(), Error> {
+        if let Some((address, mask)) = self.clock_enable {
+            let value = memory.read32(address)?;
+            memory.write32(address, value | mask)?;
+        }
+
+        let value = memory.read32(self.dbgmcu_cr)?;
+
+        log::debug!(
+            "Setting DBGMCU_CR (0x{:08x}) from 0x{:08x} to 0x{:08x}",
+            self.dbgmcu_cr,
+            value,
+            value | self.enable_mask
+        );
+
+        memory.write32(self.dbgmcu_cr, value | self.enable_mask)
+    }
+}
@@ -0,0 +1,120 @@
+//! Encoding of the instructions which are executed in the program buffer.
+//!
+//! Only the few instructions needed to access registers and memory of a
+//! halted hart are supported. Registers are given by their number, e.g. `8` for `s0`.
+
+use super::communication_interface::RiscvBusAccess;
+
+/// The `ebreak` instruction, which returns control to the debug module.
+pub(crate) const EBREAK: u32 = 0x0010_0073;
+
+const OPCODE_LOAD: u32 = 0b0000011;
+const OPCODE_STORE: u32 = 0b0100011;
+const OPCODE_SYSTEM: u32 = 0b1110011;
+const OPCODE_OP_FP: u32 = 0b1010011;
+
+const FUNCT3_CSRRW: u32 = 0b001;
+const FUNCT3_CSRRS: u32 = 0b010;
+
+fn i_type(opcode: u32, rd: u8, funct3: u32, rs1: u8, imm: u16) -> u32 {
+    u32::from(imm & 0xfff) << 20
+        | u32::from(rs1 & 0x1f) << 15
+        | funct3 << 12
+        | u32::from(rd & 0x1f) << 7
+        | opcode
+}
+
+fn s_type(opcode: u32, funct3: u32, rs1: u8, rs2: u8, imm: u16) -> u32 {
+    let imm = u32::from(imm & 0xfff);
+
+    (imm >> 5) << 25
+        | u32::from(rs2 & 0x1f) << 20
+        | u32::from(rs1 & 0x1f) << 15
+        | funct3 << 12
+        | (imm & 0x1f) << 7
+        | opcode
+}
+
+/// Loads `width` bits from `offset(base)` into `rd`.
+///
+/// The loaded value is sign extended, so only the lower `width` bits are valid.
+pub(crate) fn load(rd: u8, base: u8, offset: u16, width: RiscvBusAccess) -> u32 {
+    i_type(OPCODE_LOAD, rd, width as u32, base, offset)
+}
+
+/// Stores the lower `width` bits of `rs` at `offset(base)`.
+pub(crate) fn store(rs: u8, base: u8, offset: u16, width: RiscvBusAccess) -> u32 {
+    s_type(OPCODE_STORE, width as u32, base, rs, offset)
+}
+
+/// Reads the CSR at `csr` into `rd`, encoded as `csrrs rd, csr, x0`.
+pub(crate) fn csrr(rd: u8, csr: u16) -> u32 {
+    i_type(OPCODE_SYSTEM, rd, FUNCT3_CSRRS, 0, csr)
+}
+
+/// Writes `rs` to the CSR at `csr`, encoded as `csrrw x0, csr, rs`.
+pub(crate) fn csrw(csr: u16, rs: u8) -> u32 {
+    i_type(OPCODE_SYSTEM, 0, FUNCT3_CSRRW, rs, csr)
+}
+
+/// Moves the single precision floating point register `fs` into `rd`.
+pub(crate) fn fmv_x_w(rd: u8, fs: u8) -> u32 {
+    0b1110000 << 25 | u32::from(fs & 0x1f) << 15 | u32::from(rd & 0x1f) << 7 | OPCODE_OP_FP
+}
+
+/// Moves `rs` into the single precision floating point register `fd`.
+pub(crate) fn fmv_w_x(fd: u8, rs: u8) -> u32 {
+    0b1111000 << 25 | u32::from(rs & 0x1f) << 15 | u32::from(fd & 0x1f) << 7 | OPCODE_OP_FP
+}
+
+/// Moves the double precision floating point register `fs` into `rd`, which
+/// requires a 64 bit hart.
+pub(crate) fn fmv_x_d(rd: u8, fs: u8) -> u32 {
+    0b1110001 << 25 | u32::from(fs & 0x1f) << 15 | u32::from(rd & 0x1f) << 7 | OPCODE_OP_FP
+}
+
+/// Moves `rs` into the double precision floating point register `fd`, which
+/// requires a 64 bit hart.
+pub(crate) fn fmv_d_x(fd: u8, rs: u8) -> u32 {
+    0b1111001 << 25 | u32::from(rs & 0x1f) << 15 | u32::from(fd & 0x1f) << 7 | OPCODE_OP_FP
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const S0: u8 = 8;
+    const S1: u8 = 9;
+
+    #[test]
+    fn encode_memory_access() {
+        // lw s1, 0(s0)
+        assert_eq!(load(S1, S0, 0, RiscvBusAccess::A32), 0x0004_2483);
+        // lb s0, 4(s0)
+        assert_eq!(load(S0, S0, 4, RiscvBusAccess::A8), 0x0044_0403);
+        // sw s1, 0(s0)
+        assert_eq!(store(S1, S0, 0, RiscvBusAccess::A32), 0x0094_2023);
+        // sh s1, 36(s0)
+        assert_eq!(store(S1, S0, 36, RiscvBusAccess::A16), 0x0294_1223);
+    }
+
+    #[test]
+    fn encode_csr_access() {
+        // csrr s0, mstatus
+        assert_eq!(csrr(S0, 0x300), 0x3000_2473);
+        // csrw mstatus, s0
+        assert_eq!(csrw(0x300, S0), 0x3004_1073);
+    }
+
+    #[test]
+    fn encode_float_moves() {
+        // fmv.x.w s0, ft1
+        assert_eq!(fmv_x_w(S0, 1), 0xE000_8453);
+        // fmv.w.x ft1, s0
+        assert_eq!(fmv_w_x(1, S0), 0xF004_00D3);
+        // fmv.x.d s0, ft1
+        assert_eq!(fmv_x_d(S0, 1), 0xE200_8453);
+        // fmv.d.x ft1, s0
+        assert_eq!(fmv_d_x(1, S0), 0xF204_00D3);
+    }
+}
@@ -4,14 +4,14 @@
 //! Debug Module, as described in the RISCV debug
 //! specification v0.13.2 .
 
-use super::{register, Dmcontrol, Dmstatus};
-use crate::architecture::riscv::{Abstractcs, Command, Data0, Progbuf0, Progbuf1};
+use super::{assembly, register, Dmcontrol, Dmstatus, Hawindow, Hawindowsel};
+use crate::architecture::riscv::{Abstractcs, Command, Data0, Data1};
 use crate::DebugProbeError;
 use crate::{Memory, MemoryInterface, Probe};
 
 use crate::{CoreRegisterAddress, Error as ProbeRsError};
 
-use std::cell::RefCell;
+use std::cell::{RefCell, RefMut};
 use std::rc::Rc;
 
 use std::{
@@ -36,6 +36,14 @@ pub(crate) enum RiscvError {
     RequestNotAcknowledged,
     #[error("The version '{0}' of the debug module is currently not supported.")]
     UnsupportedDebugModuleVersion(u8),
+    #[error("The program buffer is too small, {required} words are required but only {available} are available.")]
+    ProgramBufferTooSmall { required: usize, available: usize },
+    #[error("Error during system bus access: {0:?}")]
+    SystemBusAccess(SystemBusErrorKind),
+    #[error("Hart {0} does not exist.")]
+    HartNotFound(u32),
+    #[error("Memory accesses with a width of {0:?} are not supported.")]
+    UnsupportedBusAccessWidth(RiscvBusAccess),
 }
 
 impl From<RiscvError> for ProbeRsError {
@@ -78,6 +86,54 @@ impl AbstractCommandErrorKind {
     }
 }
 
+/// Errors which can occur during a system bus access, reported in `sbcs.sberror`.
+#[derive(Debug)]
+pub(crate) enum SystemBusErrorKind {
+    Timeout = 1,
+    BadAddress = 2,
+    Alignment = 3,
+    UnsupportedSize = 4,
+    Other = 7,
+}
+
+impl SystemBusErrorKind {
+    fn parse(value: u8) -> Self {
+        use SystemBusErrorKind::*;
+
+        match value {
+            1 => Timeout,
+            2 => BadAddress,
+            3 => Alignment,
+            4 => UnsupportedSize,
+            _ => Other,
+        }
+    }
+}
+
+/// The width of the general purpose registers of a hart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Xlen {
+    X32,
+    X64,
+}
+
+impl Xlen {
+    /// The number of bits in a register.
+    pub fn bits(self) -> u32 {
+        match self {
+            Xlen::X32 => 32,
+            Xlen::X64 => 64,
+        }
+    }
+
+    fn access(self) -> RiscvBusAccess {
+        match self {
+            Xlen::X32 => RiscvBusAccess::A32,
+            Xlen::X64 => RiscvBusAccess::A64,
+        }
+    }
+}
+
 /// List of all debug module versions.
 ///
 /// The version of the debug module can be read from the version field of the `dmstatus`
@@ -95,9 +151,14 @@ enum DebugModuleVersion {
     NonConforming = 15,
 }
 
+/// The interface to a debug module, which is used to access one of its harts.
+///
+/// All clones share the same debug module. Each clone accesses its own hart,
+/// which is selected in the debug module before every access.
 #[derive(Clone, Debug)]
 pub struct RiscvCommunicationInterface {
     inner: Rc<RefCell<InnerRiscvCommunicationInterface>>,
+    hart: u32,
 }
 
 impl RiscvCommunicationInterface {
@@ -106,36 +167,99 @@ impl RiscvCommunicationInterface {
             inner: Rc::new(RefCell::new(InnerRiscvCommunicationInterface::build(
                 probe,
             )?)),
+            hart: 0,
         })
     }
 
-    pub(super) fn read_dm_register<R: DebugRegister>(&self) -> Result<R, RiscvError> {
-        self.inner.borrow_mut().read_dm_register()
+    /// Returns an interface to the hart with index `hart`.
+    pub fn for_hart(&self, hart: u32) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            hart,
+        }
     }
 
-    pub(super) fn write_dm_register(&self, register: impl DebugRegister) -> Result<(), RiscvError> {
-        self.inner.borrow_mut().write_dm_register(register)
+    /// The index of the hart which is accessed through this interface.
+    pub fn hart(&self) -> u32 {
+        self.hart
     }
 
-    pub(crate) fn execute_abstract_command(&self, command: u32) -> Result<(), RiscvError> {
-        self.inner.borrow_mut().execute_abstract_command(command)
+    /// The number of harts of the debug module.
+    pub fn num_harts(&self) -> u32 {
+        self.inner.borrow().num_harts
     }
 
-    pub(crate) fn abstract_cmd_register_read(
+    /// Selects the hart of this interface in the debug module.
+    fn select_hart(&self) -> Result<RefMut<'_, InnerRiscvCommunicationInterface>, RiscvError> {
+        let mut inner = self.inner.borrow_mut();
+        inner.select_hart(self.hart)?;
+
+        Ok(inner)
+    }
+
+    /// Halts the harts in `harts` at the same time.
+    ///
+    /// The harts are selected with the hart array mask, so they are halted by
+    /// a single request. If the debug module does not support the hart array mask,
+    /// the harts are halted one after another.
+    pub fn halt_harts(&self, harts: &[u32]) -> Result<(), crate::Error> {
+        let mut inner = self.inner.borrow_mut();
+
+        let mut dmcontrol = Dmcontrol(0);
+        dmcontrol.set_dmactive(true);
+        dmcontrol.set_haltreq(true);
+
+        inner.request_harts(harts, dmcontrol, |status| status.allhalted())?;
+
+        Ok(())
+    }
+
+    /// Resumes the harts in `harts` at the same time.
+    ///
+    /// See [`halt_harts`](Self::halt_harts) for how the harts are selected.
+    pub fn resume_harts(&self, harts: &[u32]) -> Result<(), crate::Error> {
+        let mut inner = self.inner.borrow_mut();
+
+        let mut dmcontrol = Dmcontrol(0);
+        dmcontrol.set_dmactive(true);
+        dmcontrol.set_resumereq(true);
+
+        inner.request_harts(harts, dmcontrol, |status| status.allresumeack())?;
+
+        Ok(())
+    }
+
+    pub(super) fn read_dm_register<R: DebugRegister>(&self) -> Result<R, RiscvError> {
+        self.select_hart()?.read_dm_register()
+    }
+
+    pub(super) fn write_dm_register(&self, register: impl DebugRegister) -> Result<(), RiscvError> {
+        self.select_hart()?.write_dm_register(register)
+    }
+
+    /// Reads a core register, using the program buffer if the debug module
+    /// does not support abstract access to it.
+    pub(crate) fn read_register(
         &self,
         regno: impl Into<CoreRegisterAddress>,
-    ) -> Result<u32, RiscvError> {
-        self.inner.borrow_mut().abstract_cmd_register_read(regno)
+    ) -> Result<u64, RiscvError> {
+        self.select_hart()?.read_register(regno.into())
     }
 
-    pub(crate) fn abstract_cmd_register_write(
+    /// Writes a core register, using the program buffer if the debug module
+    /// does not support abstract access to it.
+    pub(crate) fn write_register(
         &self,
         regno: impl Into<CoreRegisterAddress>,
-        value: u32,
+        value: u64,
     ) -> Result<(), RiscvError> {
-        self.inner
-            .borrow_mut()
-            .abstract_cmd_register_write(regno, value)
+        self.select_hart()?.write_register(regno.into(), value)
+    }
+
+    /// The register width of the hart, which is detected on the first register access.
+    /// The hart has to be halted for this.
+    pub(crate) fn xlen(&self) -> Result<Xlen, RiscvError> {
+        self.select_hart()?.xlen()
     }
 
     /// Read the IDCODE register
@@ -144,9 +268,11 @@ impl RiscvCommunicationInterface {
     }
 
     pub fn close(self) -> Result<Probe, Self> {
+        let hart = self.hart;
+
         Rc::try_unwrap(self.inner)
             .map(|cell| cell.into_inner().probe)
-            .map_err(|e| RiscvCommunicationInterface { inner: e })
+            .map_err(|e| RiscvCommunicationInterface { inner: e, hart })
     }
 
     pub fn memory(&self) -> Memory {
@@ -156,28 +282,31 @@ impl RiscvCommunicationInterface {
 
 impl MemoryInterface for RiscvCommunicationInterface {
     fn read32(&mut self, address: u32) -> Result<u32, crate::Error> {
-        self.inner.borrow_mut().read32(address)
+        self.select_hart()?.read32(address)
     }
     fn read8(&mut self, address: u32) -> Result<u8, crate::Error> {
-        self.inner.borrow_mut().read8(address)
+        self.select_hart()?.read8(address)
     }
     fn read_block32(&mut self, address: u32, data: &mut [u32]) -> Result<(), crate::Error> {
-        self.inner.borrow_mut().read_block32(address, data)
+        self.select_hart()?.read_block32(address, data)
     }
     fn read_block8(&mut self, address: u32, data: &mut [u8]) -> Result<(), crate::Error> {
-        self.inner.borrow_mut().read_block8(address, data)
+        self.select_hart()?.read_block8(address, data)
     }
     fn write32(&mut self, addr: u32, data: u32) -> Result<(), crate::Error> {
-        self.inner.borrow_mut().write32(addr, data)
+        self.select_hart()?.write32(addr, data)
+    }
+    fn write16(&mut self, addr: u32, data: u16) -> Result<(), crate::Error> {
+        self.select_hart()?.write16(addr, data)
     }
     fn write8(&mut self, addr: u32, data: u8) -> Result<(), crate::Error> {
-        self.inner.borrow_mut().write8(addr, data)
+        self.select_hart()?.write8(addr, data)
     }
     fn write_block32(&mut self, addr: u32, data: &[u32]) -> Result<(), crate::Error> {
-        self.inner.borrow_mut().write_block32(addr, data)
+        self.select_hart()?.write_block32(addr, data)
     }
     fn write_block8(&mut self, addr: u32, data: &[u8]) -> Result<(), crate::Error> {
-        self.inner.borrow_mut().write_block8(addr, data)
+        self.select_hart()?.write_block8(addr, data)
     }
 }
 
@@ -185,6 +314,25 @@ impl MemoryInterface for RiscvCommunicationInterface {
 struct InnerRiscvCommunicationInterface {
     probe: Probe,
     abits: u32,
+    /// The number of words in the program buffer.
+    progbuf_size: usize,
+    /// The debug module executes an `ebreak` after the last word of the program buffer.
+    implicit_ebreak: bool,
+    /// The system bus capabilities, if a system bus master is available.
+    system_bus: Option<Sbcs>,
+    /// Cleared when the debug module rejects an abstract command for a CSR or FPR.
+    abstract_register_access: bool,
+    /// The number of harts, found by selecting harts until one does not exist.
+    num_harts: u32,
+    /// The hart which is selected in `dmcontrol`.
+    hartsel: u32,
+    /// The debug module supports the hart array mask, so several harts can be selected.
+    hart_array_mask: bool,
+    /// The register width of each hart, detected on the first register access.
+    xlen: Vec<Option<Xlen>>,
+    /// If each hart has 64 bit floating point registers which are moved with
+    /// `fmv.x.d`, detected on the first access with the program buffer.
+    double_float: Vec<Option<bool>>,
 }
 
 /// Timeout for RISCV operations.
@@ -212,7 +360,19 @@ impl InnerRiscvCommunicationInterface {
         // Setup the number of idle cycles between JTAG accesses
         jtag_interface.set_idle_cycles(idle_cycles as u8);
 
-        let mut interface = InnerRiscvCommunicationInterface { probe, abits };
+        let mut interface = InnerRiscvCommunicationInterface {
+            probe,
+            abits,
+            progbuf_size: 0,
+            implicit_ebreak: false,
+            system_bus: None,
+            abstract_register_access: true,
+            num_harts: 1,
+            hartsel: 0,
+            hart_array_mask: false,
+            xlen: vec![],
+            double_float: vec![],
+        };
 
         // Reset error bits from previous connections
         interface.dmi_reset()?;
@@ -235,9 +395,165 @@ impl InnerRiscvCommunicationInterface {
 
         interface.write_dm_register(control)?;
 
+        let abstractcs: Abstractcs = interface.read_dm_register()?;
+        interface.progbuf_size = abstractcs.progbufsize() as usize;
+        interface.implicit_ebreak = status.impebreak();
+
+        log::debug!(
+            "Program buffer: {} words, implicit ebreak: {}",
+            interface.progbuf_size,
+            interface.implicit_ebreak
+        );
+
+        // Only version 1 of the system bus interface is supported.
+        let sbcs: Sbcs = interface.read_dm_register()?;
+        if sbcs.sbversion() == 1 && sbcs.sbasize() >= 32 {
+            log::debug!("System bus access is available: {:?}", sbcs);
+            interface.system_bus = Some(sbcs);
+        }
+
+        interface.enumerate_harts()?;
+
         Ok(interface)
     }
 
+    /// Finds the number of harts, and whether the hart array mask is supported.
+    fn enumerate_harts(&mut self) -> Result<(), RiscvError> {
+        // The unsupported bits of hartsel are hardwired to zero, so writing all ones
+        // gives the highest hart index which can be selected.
+        let mut control = Dmcontrol(0);
+        control.set_dmactive(true);
+        control.set_hasel(true);
+        control.set_hartsel(0xfffff);
+        self.write_dm_register_untyped(Dmcontrol::ADDRESS, control.0)?;
+
+        let control: Dmcontrol = self.read_dm_register()?;
+        let max_hartsel = control.hartsel();
+        self.hart_array_mask = control.hasel();
+
+        let mut control = Dmcontrol(0);
+        control.set_dmactive(true);
+
+        let mut num_harts = 0;
+
+        while num_harts <= max_hartsel {
+            self.hartsel = num_harts;
+            self.write_dm_register(Dmcontrol(control.0))?;
+
+            let status: Dmstatus = self.read_dm_register()?;
+            if status.anynonexistent() {
+                break;
+            }
+
+            num_harts += 1;
+        }
+
+        log::debug!(
+            "Found {} harts, hart array mask supported: {}",
+            num_harts,
+            self.hart_array_mask
+        );
+
+        // A debug module always has at least one hart, even if it could not be found.
+        self.num_harts = num_harts.max(1);
+        self.xlen = vec![None; self.num_harts as usize];
+        self.double_float = vec![None; self.num_harts as usize];
+
+        self.hartsel = 0;
+        self.write_dm_register(control)
+    }
+
+    /// Selects the hart with index `hart` in `dmcontrol`, if it is not selected already.
+    fn select_hart(&mut self, hart: u32) -> Result<(), RiscvError> {
+        if hart >= self.num_harts {
+            return Err(RiscvError::HartNotFound(hart));
+        }
+
+        if self.hartsel != hart {
+            self.hartsel = hart;
+
+            let mut control = Dmcontrol(0);
+            control.set_dmactive(true);
+            self.write_dm_register(control)?;
+        }
+
+        Ok(())
+    }
+
+    /// Selects all `harts` in the hart array mask, clearing all others.
+    fn write_hart_array_mask(&mut self, harts: &[u32]) -> Result<(), RiscvError> {
+        // Each window covers 32 harts.
+        for window in 0..=(self.num_harts - 1) / 32 {
+            let mask = harts
+                .iter()
+                .filter(|hart| **hart / 32 == window)
+                .fold(0, |mask, hart| mask | 1 << (hart % 32));
+
+            self.write_dm_register(Hawindowsel(window))?;
+            self.write_dm_register(Hawindow(mask))?;
+        }
+
+        Ok(())
+    }
+
+    /// Sends the halt or resume request in `request` to all `harts`, and waits until
+    /// `done` returns true for `dmstatus`. The request is cleared afterwards.
+    fn request_harts(
+        &mut self,
+        harts: &[u32],
+        request: Dmcontrol,
+        done: impl Fn(&Dmstatus) -> bool,
+    ) -> Result<(), RiscvError> {
+        if let Some(hart) = harts.iter().find(|hart| **hart >= self.num_harts) {
+            return Err(RiscvError::HartNotFound(*hart));
+        }
+
+        if self.hart_array_mask {
+            self.write_hart_array_mask(harts)?;
+
+            let mut request = Dmcontrol(request.0);
+            request.set_hasel(true);
+
+            self.request_selected_harts(request, &done)
+        } else {
+            for hart in harts {
+                self.select_hart(*hart)?;
+                self.request_selected_harts(Dmcontrol(request.0), &done)?;
+            }
+
+            Ok(())
+        }
+    }
+
+    fn request_selected_harts(
+        &mut self,
+        request: Dmcontrol,
+        done: &impl Fn(&Dmstatus) -> bool,
+    ) -> Result<(), RiscvError> {
+        self.write_dm_register(request)?;
+
+        let start_time = Instant::now();
+
+        let result = loop {
+            let status: Dmstatus = self.read_dm_register()?;
+
+            if done(&status) {
+                break Ok(());
+            }
+
+            if start_time.elapsed() > RISCV_TIMEOUT {
+                break Err(RiscvError::RequestNotAcknowledged);
+            }
+        };
+
+        // Clear the request, and the hart array selection.
+        let mut control = Dmcontrol(0);
+        control.set_dmactive(true);
+        self.write_dm_register(control)?;
+
+        result
+    }
+
     fn dmi_reset(&mut self) -> Result<(), RiscvError> {
         let mut dtmcs = Dtmcs(0);
 
@@ -370,7 +686,14 @@ impl InnerRiscvCommunicationInterface {
     ) -> Result<(), RiscvError> {
         // write write command to dmi register
 
-        let data = register.into();
+        let mut data = register.into();
+
+        // The selected hart is kept for every write to dmcontrol.
+        if R::ADDRESS == Dmcontrol::ADDRESS {
+            let mut control = Dmcontrol(data);
+            control.set_hartsel(self.hartsel);
+            data = control.0;
+        }
 
         log::debug!(
             "Write DM register '{}' at {:#010x} = {:#010x}",
@@ -379,9 +702,14 @@ impl InnerRiscvCommunicationInterface {
             data
         );
 
+        self.write_dm_register_untyped(R::ADDRESS, data)
+    }
+
+    /// Write a DM register by its address, used for register arrays like the program buffer.
+    fn write_dm_register_untyped(&mut self, address: u8, value: u32) -> Result<(), RiscvError> {
         self.dmi_register_access_with_timeout(
-            R::ADDRESS as u64,
-            data,
+            address as u64,
+            value,
             DmiOperation::Write,
             RISCV_TIMEOUT,
         )?;
@@ -389,153 +717,388 @@ impl InnerRiscvCommunicationInterface {
         Ok(())
     }
 
-    /// Perfrom memory read from a single location using the program buffer.
-    /// Only reads up to a width of 32 bits are currently supported.
+    /// Writes `instructions` to the program buffer, followed by an `ebreak`
+    /// unless the debug module executes one implicitly.
+    fn setup_program_buffer(&mut self, instructions: &[u32]) -> Result<(), RiscvError> {
+        let required = if self.implicit_ebreak {
+            instructions.len()
+        } else {
+            instructions.len() + 1
+        };
+
+        if required > self.progbuf_size {
+            return Err(RiscvError::ProgramBufferTooSmall {
+                required,
+                available: self.progbuf_size,
+            });
+        }
+
+        let ebreak = if required > instructions.len() {
+            Some(assembly::EBREAK)
+        } else {
+            None
+        };
+
+        for (index, instruction) in instructions.iter().chain(ebreak.iter()).enumerate() {
+            self.write_dm_register_untyped(PROGBUF0_ADDRESS + index as u8, *instruction)?;
+        }
+
+        Ok(())
+    }
+
+    /// Runs `instructions` in the program buffer.
+    ///
+    /// If `regno` is given, the `data` registers are first transferred into that register,
+    /// which is how a value is passed to the instructions.
+    fn execute_program_buffer(
+        &mut self,
+        instructions: &[u32],
+        regno: Option<CoreRegisterAddress>,
+    ) -> Result<(), RiscvError> {
+        self.setup_program_buffer(instructions)?;
+
+        let xlen = self.xlen()?;
+
+        let mut command = AccessRegisterCommand(0);
+        command.set_cmd_type(0);
+        command.set_aarsize(xlen.access());
+        command.set_postexec(true);
+
+        if let Some(regno) = regno {
+            command.set_transfer(true);
+            command.set_write(true);
+            command.set_regno(regno.0 as u32);
+        }
+
+        self.execute_abstract_command(command.0)
+    }
+
+    /// Returns true if the system bus supports accesses of the given width.
+    fn system_bus_supports(&self, width: RiscvBusAccess) -> bool {
+        self.system_bus
+            .as_ref()
+            .map(|sbcs| match width {
+                RiscvBusAccess::A8 => sbcs.sbaccess8(),
+                RiscvBusAccess::A16 => sbcs.sbaccess16(),
+                RiscvBusAccess::A32 => sbcs.sbaccess32(),
+                RiscvBusAccess::A64 => sbcs.sbaccess64(),
+                RiscvBusAccess::A128 => sbcs.sbaccess128(),
+            })
+            .unwrap_or(false)
+    }
+
+    /// Perform a memory read from a single location, through the system bus if it
+    /// supports the width, and using the program buffer otherwise.
     /// For widths smaller than u32, the higher bits have to be discarded manually.
     fn perform_memory_read(
         &mut self,
         address: u32,
         width: RiscvBusAccess,
     ) -> Result<u32, RiscvError> {
-        // assemble
-        //  lb s1, 0(s0)
+        if self.system_bus_supports(width) {
+            Ok(self.perform_memory_read_sysbus(address, width)? as u32)
+        } else if self.system_bus_covers_with_64_bits(address, width) {
+            let value = self.perform_memory_read_sysbus(address & !0x7, RiscvBusAccess::A64)?;
+
+            Ok((value >> ((address & 0x7) * 8)) as u32)
+        } else {
+            self.perform_memory_read_progbuf(address, width)
+        }
+    }
 
-        // Backup registers s0 and s1
-        let s0 = self.abstract_cmd_register_read(&register::S0)?;
+    /// Perform a memory write to a single location, through the system bus if it
+    /// supports the width, and using the program buffer otherwise.
+    fn perform_memory_write(
+        &mut self,
+        address: u32,
+        width: RiscvBusAccess,
+        data: u32,
+    ) -> Result<(), RiscvError> {
+        if self.system_bus_supports(width) {
+            self.perform_memory_write_sysbus(address, width, data.into())
+        } else if self.system_bus_covers_with_64_bits(address, width) {
+            // Only 64 bit accesses are supported, so the other bytes are written back unchanged.
+            let aligned = address & !0x7;
+
+            let value = self.perform_memory_read_sysbus(aligned, RiscvBusAccess::A64)?;
+            let value = insert_into_u64(value, address, width, data);
+
+            self.perform_memory_write_sysbus(aligned, RiscvBusAccess::A64, value)
+        } else {
+            self.perform_memory_write_progbuf(address, width, data)
+        }
+    }
 
-        //let o = 0; // offset = 0
-        //let b = 9; // base register -> s0
-        //let w = 0; // width
-        //let d = 9; // dest register -> s0
-        //let l = 0b11;
+    /// Returns true if an access can be done as part of an aligned 64 bit
+    /// system bus access, for buses which do not support smaller accesses.
+    fn system_bus_covers_with_64_bits(&self, address: u32, width: RiscvBusAccess) -> bool {
+        let size = 1 << width as u32;
 
-        let mut lw_command: u32 = 0b000000000000_01000_000_01000_0000011;
+        self.system_bus_supports(RiscvBusAccess::A64) && size < 8 && address & (size - 1) == 0
+    }
 
-        // verify the width is supported
-        // 0 ==  8 bit
-        // 1 == 16 bit
-        // 2 == 32 bit
-        assert!((width as u32) < 3, "Width larger than 3 not supported yet");
+    fn perform_memory_read_sysbus(
+        &mut self,
+        address: u32,
+        width: RiscvBusAccess,
+    ) -> Result<u64, RiscvError> {
+        let mut sbcs = Sbcs(0);
+        sbcs.set_sbaccess(width as u32);
+        sbcs.set_sbreadonaddr(true);
+        // Clear errors of previous accesses.
+        sbcs.set_sbbusyerror(true);
+        sbcs.set_sberror(0x7);
 
-        lw_command |= (width as u32) << 12;
+        self.write_dm_register(sbcs)?;
+        self.clear_upper_system_bus_address()?;
 
-        let ebreak_cmd = 0b000000000001_00000_000_00000_1110011;
+        // Writing the address starts the read.
+        self.write_dm_register(Sbaddress0(address))?;
 
-        self.write_dm_register(Progbuf0(lw_command))?;
-        self.write_dm_register(Progbuf1(ebreak_cmd))?;
+        self.wait_for_system_bus()?;
 
-        self.write_dm_register(Data0(address))?;
+        let low: Sbdata0 = self.read_dm_register()?;
+        let low = u64::from(u32::from(low));
 
-        // Write s0, then execute program buffer
-        let mut command = AccessRegisterCommand(0);
-        command.set_cmd_type(0);
-        command.set_transfer(true);
-        command.set_write(true);
+        if let RiscvBusAccess::A64 = width {
+            let high: Sbdata1 = self.read_dm_register()?;
 
-        // registers are 32 bit, so we have size 2 here
-        command.set_aarsize(RiscvBusAccess::A32);
-        command.set_postexec(true);
+            Ok(u64::from(u32::from(high)) << 32 | low)
+        } else {
+            Ok(low)
+        }
+    }
 
-        // register s0, ie. 0x1008
-        command.set_regno((register::S0).address.0 as u32);
+    fn perform_memory_write_sysbus(
+        &mut self,
+        address: u32,
+        width: RiscvBusAccess,
+        data: u64,
+    ) -> Result<(), RiscvError> {
+        let mut sbcs = Sbcs(0);
+        sbcs.set_sbaccess(width as u32);
+        // Clear errors of previous accesses.
+        sbcs.set_sbbusyerror(true);
+        sbcs.set_sberror(0x7);
 
-        self.write_dm_register(command)?;
+        self.write_dm_register(sbcs)?;
+        self.clear_upper_system_bus_address()?;
 
-        let status: Abstractcs = self.read_dm_register()?;
+        self.write_dm_register(Sbaddress0(address))?;
 
-        if status.cmderr() != 0 {
-            return Err(RiscvError::AbstractCommand(
-                AbstractCommandErrorKind::parse(status.cmderr() as u8),
-            ));
+        if let RiscvBusAccess::A64 = width {
+            self.write_dm_register(Sbdata1((data >> 32) as u32))?;
+        }
+
+        // Writing the data starts the write.
+        self.write_dm_register(Sbdata0(data as u32))?;
+
+        self.wait_for_system_bus()
+    }
+
+    /// Addresses are only 32 bits wide, so the upper bits of a wider system bus are zero.
+    fn clear_upper_system_bus_address(&mut self) -> Result<(), RiscvError> {
+        let sbasize = self
+            .system_bus
+            .as_ref()
+            .map(|sbcs| sbcs.sbasize())
+            .unwrap_or(0);
+
+        if sbasize > 32 {
+            self.write_dm_register(Sbaddress1(0))?;
+        }
+
+        Ok(())
+    }
+
+    /// Waits until the current system bus access is done, and checks it for errors.
+    fn wait_for_system_bus(&mut self) -> Result<(), RiscvError> {
+        let start_time = Instant::now();
+
+        loop {
+            let sbcs: Sbcs = self.read_dm_register()?;
+
+            if sbcs.sberror() != 0 {
+                return Err(RiscvError::SystemBusAccess(SystemBusErrorKind::parse(
+                    sbcs.sberror() as u8,
+                )));
+            }
+
+            if !sbcs.sbbusy() {
+                return Ok(());
+            }
+
+            if start_time.elapsed() > RISCV_TIMEOUT {
+                return Err(RiscvError::Timeout);
+            }
         }
+    }
 
-        // Read back s0
-        let value = self.abstract_cmd_register_read(&register::S0)?;
+    /// Perform a memory read from a single location using the program buffer.
+    /// Only reads up to a width of 32 bits are currently supported.
+    fn perform_memory_read_progbuf(
+        &mut self,
+        address: u32,
+        width: RiscvBusAccess,
+    ) -> Result<u32, RiscvError> {
+        verify_progbuf_width(width)?;
 
+        // Backup register s0
+        let s0 = self.abstract_cmd_register_read(&register::S0)?;
+
+        // lw s0, 0(s0)
+        let program = [assembly::load(S0, S0, 0, width)];
+
+        // Write the address into s0, then execute the program buffer
+        let result = self
+            .write_data(address.into())
+            .and_then(|_| self.execute_program_buffer(&program, Some(register::S0.address)))
+            .and_then(|_| self.abstract_cmd_register_read(&register::S0));
+
+        // Restore register s0, even if the read failed
         self.abstract_cmd_register_write(&register::S0, s0)?;
 
-        Ok(value)
+        result.map(|value| value as u32)
     }
 
-    /// Perform memory write to a single location using the program buffer.
+    /// Perform a memory write to a single location using the program buffer.
     /// Only writes up to a width of 32 bits are currently supported.
-    fn perform_memory_write(
+    fn perform_memory_write_progbuf(
         &mut self,
         address: u32,
         width: RiscvBusAccess,
         data: u32,
     ) -> Result<(), RiscvError> {
+        verify_progbuf_width(width)?;
+
         // Backup registers s0 and s1
         let s0 = self.abstract_cmd_register_read(&register::S0)?;
         let s1 = self.abstract_cmd_register_read(&register::S1)?;
 
-        // assemble
-        //  lb s0, 0(s0)
+        // sw s1, 0(s0)
+        let program = [assembly::store(S1, S0, 0, width)];
 
-        //let o = 0; // offset = 0
-        //let b = 9; // base register -> s0
-        //let w = 0; // width
-        //let d = 9; // dest register -> s0
-        //let l = 0b11;
+        // Write the address into s0 and the data into s1, then execute the program buffer
+        let result = (|| {
+            self.abstract_cmd_register_write(&register::S0, address.into())?;
+            self.write_data(data.into())?;
+            self.execute_program_buffer(&program, Some(register::S1.address))
+        })();
 
-        //let lw_command = bitpack!("oooooooooooobbbbb_www_ddddd_lllllll");
-        let mut sw_command: u32 = 0b0000000_01001_01000_000_00000_0100011;
+        // Restore registers s0 and s1, even if the write failed
+        let restored_s0 = self.abstract_cmd_register_write(&register::S0, s0);
+        let restored_s1 = self.abstract_cmd_register_write(&register::S1, s1);
 
-        // sw command -> sb s1, 0(s0)
+        result.and(restored_s0).and(restored_s1)
+    }
 
-        // verify the width is supported
-        // 0 ==  8 bit
-        // 1 == 16 bit
-        // 2 == 32 bit
+    /// Reads a CSR or FPR with the program buffer, by moving it into s0.
+    fn register_read_progbuf(&mut self, regno: CoreRegisterAddress) -> Result<u64, RiscvError> {
+        let instruction = match regno.0 {
+            0..=0xfff => assembly::csrr(S0, regno.0),
+            FPR_FIRST..=FPR_LAST if self.double_float()? => {
+                assembly::fmv_x_d(S0, (regno.0 - FPR_FIRST) as u8)
+            }
+            FPR_FIRST..=FPR_LAST => assembly::fmv_x_w(S0, (regno.0 - FPR_FIRST) as u8),
+            _ => {
+                return Err(RiscvError::AbstractCommand(
+                    AbstractCommandErrorKind::NotSupported,
+                ))
+            }
+        };
 
-        assert!((width as u32) < 3, "Width larger than 3 not supported yet");
+        let s0 = self.abstract_cmd_register_read(&register::S0)?;
 
-        sw_command |= (width as u32) << 12;
+        let result = self
+            .execute_program_buffer(&[instruction], None)
+            .and_then(|_| self.abstract_cmd_register_read(&register::S0));
 
-        //if width == 2 {
-        //    sw_command = 0xc004;
-        //}
+        self.abstract_cmd_register_write(&register::S0, s0)?;
+
+        result
+    }
 
-        let ebreak_cmd = 0b000000000001_00000_000_00000_1110011;
+    /// Writes a CSR or FPR with the program buffer, by moving it from s0.
+    fn register_write_progbuf(
+        &mut self,
+        regno: CoreRegisterAddress,
+        value: u64,
+    ) -> Result<(), RiscvError> {
+        let instruction = match regno.0 {
+            0..=0xfff => assembly::csrw(regno.0, S0),
+            FPR_FIRST..=FPR_LAST if self.double_float()? => {
+                assembly::fmv_d_x((regno.0 - FPR_FIRST) as u8, S0)
+            }
+            FPR_FIRST..=FPR_LAST => assembly::fmv_w_x((regno.0 - FPR_FIRST) as u8, S0),
+            _ => {
+                return Err(RiscvError::AbstractCommand(
+                    AbstractCommandErrorKind::NotSupported,
+                ))
+            }
+        };
 
-        self.write_dm_register(Progbuf0(sw_command))?;
-        self.write_dm_register(Progbuf1(ebreak_cmd))?;
+        let s0 = self.abstract_cmd_register_read(&register::S0)?;
 
-        // write value into s0
-        self.abstract_cmd_register_write(&register::S0, address)?;
+        self.write_data(value)?;
+        let result = self.execute_program_buffer(&[instruction], Some(register::S0.address));
 
-        // write address into data 0
-        self.write_dm_register(Data0(data))?;
+        self.abstract_cmd_register_write(&register::S0, s0)?;
 
-        // Write s0, then execute program buffer
-        let mut command = AccessRegisterCommand(0);
-        command.set_cmd_type(0);
-        command.set_transfer(true);
-        command.set_write(true);
+        result
+    }
 
-        // registers are 32 bit, so we have size 2 here
-        command.set_aarsize(RiscvBusAccess::A32);
-        command.set_postexec(true);
+    /// Reads a register with an abstract command. GPRs are always accessible this way,
+    /// for other registers the program buffer is used if the abstract command is not supported.
+    fn read_register(&mut self, regno: CoreRegisterAddress) -> Result<u64, RiscvError> {
+        if is_gpr(regno) {
+            return self.abstract_cmd_register_read(regno);
+        }
 
-        // register s0, ie. 0x1008
-        command.set_regno((register::S1).address.0 as u32);
+        if self.abstract_register_access {
+            match self.abstract_cmd_register_read(regno) {
+                Err(RiscvError::AbstractCommand(AbstractCommandErrorKind::NotSupported)) => {
+                    log::debug!(
+                        "Abstract access to register {:#x} is not supported, using the program buffer",
+                        regno.0
+                    );
+                }
+                result => return result,
+            }
 
-        self.write_dm_register(command)?;
+            let value = self.register_read_progbuf(regno)?;
+            self.abstract_register_access = false;
 
-        let status: Abstractcs = self.read_dm_register()?;
+            Ok(value)
+        } else {
+            self.register_read_progbuf(regno)
+        }
+    }
 
-        if status.cmderr() != 0 {
-            return Err(RiscvError::AbstractCommand(
-                AbstractCommandErrorKind::parse(status.cmderr() as u8),
-            ));
+    /// Writes a register with an abstract command. GPRs are always accessible this way,
+    /// for other registers the program buffer is used if the abstract command is not supported.
+    fn write_register(&mut self, regno: CoreRegisterAddress, value: u64) -> Result<(), RiscvError> {
+        if is_gpr(regno) {
+            return self.abstract_cmd_register_write(regno, value);
         }
 
-        // Restore register s0 and s1
+        if self.abstract_register_access {
+            match self.abstract_cmd_register_write(regno, value) {
+                Err(RiscvError::AbstractCommand(AbstractCommandErrorKind::NotSupported)) => {
+                    log::debug!(
+                        "Abstract access to register {:#x} is not supported, using the program buffer",
+                        regno.0
+                    );
+                }
+                result => return result,
+            }
 
-        self.abstract_cmd_register_write(&register::S0, s0)?;
-        self.abstract_cmd_register_write(&register::S1, s1)?;
+            self.register_write_progbuf(regno, value)?;
+            self.abstract_register_access = false;
 
-        Ok(())
+            Ok(())
+        } else {
+            self.register_write_progbuf(regno, value)
+        }
     }
 
     pub(crate) fn execute_abstract_command(&mut self, command: u32) -> Result<(), RiscvError> {
@@ -596,44 +1159,118 @@ impl InnerRiscvCommunicationInterface {
         Ok(())
     }
 
-    // Read a core register using an abstract command
+    /// Returns the register width of the hart, and detects it on the first call.
+    ///
+    /// The width is found by reading s0 with a 64 bit access, which is
+    /// not supported by 32 bit harts. This requires a halted hart.
+    fn xlen(&mut self) -> Result<Xlen, RiscvError> {
+        let hart = self.hartsel as usize;
+
+        if let Some(xlen) = self.xlen[hart] {
+            return Ok(xlen);
+        }
+
+        let xlen = match self.abstract_cmd_register_read_sized(&register::S0, Xlen::X64) {
+            Ok(_) => Xlen::X64,
+            Err(RiscvError::AbstractCommand(AbstractCommandErrorKind::NotSupported)) => Xlen::X32,
+            Err(e) => return Err(e),
+        };
+
+        log::debug!(
+            "Detected register width of hart {}: {} bits",
+            hart,
+            xlen.bits()
+        );
+
+        self.xlen[hart] = Some(xlen);
+
+        Ok(xlen)
+    }
+
+    /// Returns if the floating point registers of the hart are moved with their
+    /// full 64 bits, which requires the D extension and a 64 bit hart.
+    fn double_float(&mut self) -> Result<bool, RiscvError> {
+        let hart = self.hartsel as usize;
+
+        if let Some(double_float) = self.double_float[hart] {
+            return Ok(double_float);
+        }
+
+        let double_float = self.xlen()? == Xlen::X64 && {
+            let misa = self.read_register(CoreRegisterAddress(register::MISA))?;
+
+            // The D extension, which is bit 3.
+            misa & 1 << 3 != 0
+        };
+
+        self.double_float[hart] = Some(double_float);
+
+        Ok(double_float)
+    }
+
+    /// Write a value to the `data` registers, for the transfer of a register.
+    fn write_data(&mut self, value: u64) -> Result<(), RiscvError> {
+        if self.xlen()? == Xlen::X64 {
+            self.write_dm_register(Data1((value >> 32) as u32))?;
+        }
+
+        self.write_dm_register(Data0(value as u32))
+    }
+
+    /// Read a core register using an abstract command, with the full width of the register.
     pub(crate) fn abstract_cmd_register_read(
         &mut self,
         regno: impl Into<CoreRegisterAddress>,
-    ) -> Result<u32, RiscvError> {
-        // GPR
+    ) -> Result<u64, RiscvError> {
+        let xlen = self.xlen()?;
 
-        // read from data0
+        self.abstract_cmd_register_read_sized(regno, xlen)
+    }
+
+    fn abstract_cmd_register_read_sized(
+        &mut self,
+        regno: impl Into<CoreRegisterAddress>,
+        xlen: Xlen,
+    ) -> Result<u64, RiscvError> {
+        // read from data0, and data1 for the upper bits
         let mut command = AccessRegisterCommand(0);
         command.set_cmd_type(0);
         command.set_transfer(true);
-        command.set_aarsize(RiscvBusAccess::A32);
+        command.set_aarsize(xlen.access());
 
         command.set_regno(regno.into().0 as u32);
 
         self.execute_abstract_command(command.0)?;
 
-        let register_value: Data0 = self.read_dm_register()?;
+        let low: Data0 = self.read_dm_register()?;
+        let low = u64::from(u32::from(low));
+
+        if xlen == Xlen::X64 {
+            let high: Data1 = self.read_dm_register()?;
 
-        Ok(register_value.into())
+            Ok(u64::from(u32::from(high)) << 32 | low)
+        } else {
+            Ok(low)
+        }
     }
 
+    /// Write a core register using an abstract command, with the full width of the register.
     pub(crate) fn abstract_cmd_register_write(
         &mut self,
         regno: impl Into<CoreRegisterAddress>,
-        value: u32,
+        value: u64,
     ) -> Result<(), RiscvError> {
-        // write to data0
+        let xlen = self.xlen()?;
+
         let mut command = AccessRegisterCommand(0);
         command.set_cmd_type(0);
         command.set_transfer(true);
         command.set_write(true);
-        command.set_aarsize(RiscvBusAccess::A32);
+        command.set_aarsize(xlen.access());
 
         command.set_regno(regno.into().0 as u32);
 
-        // write data0
-        self.write_dm_register(Data0(value))?;
+        self.write_data(value)?;
 
         self.execute_abstract_command(command.0)?;
 
@@ -655,6 +1292,24 @@ impl MemoryInterface for InnerRiscvCommunicationInterface {
     }
 
     fn read_block32(&mut self, address: u32, data: &mut [u32]) -> Result<(), crate::Error> {
+        // Two words at a time, if the system bus supports 64 bit accesses.
+        if self.system_bus_supports(RiscvBusAccess::A64) && address & 0x7 == 0 {
+            for (offset, words) in data.chunks_mut(2).enumerate() {
+                let address = address + (offset * 8) as u32;
+
+                if let [low, high] = words {
+                    let value = self.perform_memory_read_sysbus(address, RiscvBusAccess::A64)?;
+
+                    *low = value as u32;
+                    *high = (value >> 32) as u32;
+                } else {
+                    words[0] = self.read32(address)?;
+                }
+            }
+
+            return Ok(());
+        }
+
         for (offset, word) in data.iter_mut().enumerate() {
             *word = self.read32(address + ((offset * 4) as u32))?;
         }
@@ -676,12 +1331,35 @@ impl MemoryInterface for InnerRiscvCommunicationInterface {
         Ok(())
     }
 
+    fn write16(&mut self, address: u32, data: u16) -> Result<(), crate::Error> {
+        self.perform_memory_write(address, RiscvBusAccess::A16, data as u32)?;
+
+        Ok(())
+    }
+
     fn write8(&mut self, address: u32, data: u8) -> Result<(), crate::Error> {
         self.perform_memory_write(address, RiscvBusAccess::A8, data as u32)?;
 
         Ok(())
     }
     fn write_block32(&mut self, address: u32, data: &[u32]) -> Result<(), crate::Error> {
+        // Two words at a time, if the system bus supports 64 bit accesses.
+        if self.system_bus_supports(RiscvBusAccess::A64) && address & 0x7 == 0 {
+            for (offset, words) in data.chunks(2).enumerate() {
+                let address = address + (offset * 8) as u32;
+
+                if let [low, high] = words {
+                    let value = u64::from(*high) << 32 | u64::from(*low);
+
+                    self.perform_memory_write_sysbus(address, RiscvBusAccess::A64, value)?;
+                } else {
+                    self.write32(address, words[0])?;
+                }
+            }
+
+            return Ok(());
+        }
+
         for (offset, word) in data.iter().enumerate() {
             self.write32(address + ((offset * 4) as u32), *word)?;
         }
@@ -697,6 +1375,40 @@ impl MemoryInterface for InnerRiscvCommunicationInterface {
     }
 }
 
+/// Address of the first program buffer register, `progbuf0`.
+const PROGBUF0_ADDRESS: u8 = 0x20;
+
+/// The number of the s0 GPR, as used in instructions.
+const S0: u8 = 8;
+
+/// The number of the s1 GPR, as used in instructions.
+const S1: u8 = 9;
+
+/// The abstract register numbers of the floating point registers.
+const FPR_FIRST: u16 = 0x1020;
+const FPR_LAST: u16 = 0x103f;
+
+/// Returns true for the abstract register numbers of the GPRs.
+fn is_gpr(regno: CoreRegisterAddress) -> bool {
+    (0x1000..=0x101f).contains(&regno.0)
+}
+
+/// Replaces the bytes at `address` in the aligned 64 bit word `value` with `data`.
+fn insert_into_u64(value: u64, address: u32, width: RiscvBusAccess, data: u32) -> u64 {
+    let shift = (address & 0x7) * 8;
+    let mask = (u64::MAX >> (64 - 8 * (1 << width as u32))) << shift;
+
+    value & !mask | (u64::from(data) << shift) & mask
+}
+
+/// Checks that a memory access with the program buffer has a width of at most 32 bits.
+fn verify_progbuf_width(width: RiscvBusAccess) -> Result<(), RiscvError> {
+    match width {
+        RiscvBusAccess::A8 | RiscvBusAccess::A16 | RiscvBusAccess::A32 => Ok(()),
+        _ => Err(RiscvError::UnsupportedBusAccessWidth(width)),
+    }
+}
+
 /// Access width for bus access.
 /// This is used both for system bus access (`sbcs` register),
 /// as well for abstract commands.
@@ -920,3 +1632,35 @@ enum DmiOperation {
     Write = 2,
     _Reserved = 3,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{insert_into_u64, verify_progbuf_width, RiscvBusAccess, RiscvError};
+
+    #[test]
+    fn insert_bytes_into_double_word() {
+        let value = 0x0011_2233_4455_6677;
+
+        assert_eq!(
+            insert_into_u64(value, 0x1004, RiscvBusAccess::A32, 0xAABB_CCDD),
+            0xAABB_CCDD_4455_6677
+        );
+        assert_eq!(
+            insert_into_u64(value, 0x1002, RiscvBusAccess::A16, 0xAABB_CCDD),
+            0x0011_2233_CCDD_6677
+        );
+        assert_eq!(
+            insert_into_u64(value, 0x1007, RiscvBusAccess::A8, 0xAABB_CCDD),
+            0xDD11_2233_4455_6677
+        );
+    }
+
+    #[test]
+    fn progbuf_accesses_are_at_most_32_bits_wide() {
+        assert!(verify_progbuf_width(RiscvBusAccess::A32).is_ok());
+        assert!(matches!(
+            verify_progbuf_width(RiscvBusAccess::A64),
+            Err(RiscvError::UnsupportedBusAccessWidth(RiscvBusAccess::A64))
+        ));
+    }
+}
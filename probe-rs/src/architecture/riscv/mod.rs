@@ -5,20 +5,26 @@
 use crate::core::Architecture;
 use crate::CoreInterface;
 use communication_interface::{
-    AbstractCommandErrorKind, AccessRegisterCommand, DebugRegister, RiscvCommunicationInterface,
-    RiscvError,
+    AbstractCommandErrorKind, DebugRegister, RiscvCommunicationInterface, RiscvError,
 };
 
-use crate::core::{CoreInformation, RegisterFile};
+use crate::core::{CoreInformation, CoreStatus, HaltReason, RegisterFile};
 use crate::CoreRegisterAddress;
 use bitfield::bitfield;
-use register::RISCV_REGISTERS;
+use jep106::JEP106Code;
 
 #[macro_use]
 mod register;
 
+mod assembly;
+
 pub mod communication_interface;
 
+pub use communication_interface::Xlen;
+pub use register::Mcause;
+pub(crate) use register::RISCV_REGISTERS;
+
+/// A RISC-V hart, with 32 or 64 bit registers.
 #[derive(Clone)]
 pub struct Riscv32 {
     interface: RiscvCommunicationInterface,
@@ -29,85 +35,106 @@ impl Riscv32 {
         Self { interface }
     }
 
+    /// Reads the lower 32 bits of a CSR.
     fn read_csr(&self, address: u16) -> Result<u32, RiscvError> {
-        let s0 = self.interface.abstract_cmd_register_read(&register::S0)?;
+        self.read_csr_64(address).map(|value| value as u32)
+    }
 
-        // We need to perform the csrr instruction, which reads a CSR.
-        // This is a pseudo instruction, which actually is encoded as a
-        // csrrs instruction, with the rs1 register being x0,
-        // so no bits are changed in the CSR, but the CSR is read into rd, i.e. s0.
-        //
-        // csrrs,
-        // with rd  = s0
-        //      rs1 = x0
-        //      csr = address
+    fn write_csr(&self, address: u16, value: u32) -> Result<(), RiscvError> {
+        self.write_csr_64(address, value.into())
+    }
 
-        let mut csrrs_cmd: u32 = 0b_00000_010_01000_1110011;
-        csrrs_cmd |= ((address as u32) & 0xfff) << 20;
-        let ebreak_cmd = 0b000000000001_00000_000_00000_1110011;
+    /// Reads a CSR with the full register width.
+    fn read_csr_64(&self, address: u16) -> Result<u64, RiscvError> {
+        self.interface.read_register(CoreRegisterAddress(address))
+    }
 
-        // write progbuf0: csrr xxxxxx s0, (address) // lookup correct command
-        self.interface.write_dm_register(Progbuf0(csrrs_cmd))?;
+    fn write_csr_64(&self, address: u16, value: u64) -> Result<(), RiscvError> {
+        self.interface
+            .write_register(CoreRegisterAddress(address), value)
+    }
 
-        // write progbuf1: ebreak
-        self.interface.write_dm_register(Progbuf1(ebreak_cmd))?;
+    /// Reads the type of the selected trigger, which is in the upper four bits of `tdata1`.
+    fn read_trigger_type(&self, xlen: Xlen) -> Result<u64, RiscvError> {
+        let tdata1 = self.read_csr_64(0x7a1)?;
 
-        // command: postexec
-        let mut postexec_cmd = AccessRegisterCommand(0);
-        postexec_cmd.set_postexec(true);
+        Ok(tdata1 >> (xlen.bits() - 4))
+    }
 
-        self.interface.execute_abstract_command(postexec_cmd.0)?;
+    /// Makes `ebreak` instructions enter debug mode in all privilege modes,
+    /// instead of raising an exception. This is how a called function, e.g.
+    /// of a flash algorithm, returns control to the debugger.
+    fn enable_ebreak_debug_entry(&self) -> Result<(), RiscvError> {
+        let mut dcsr = Dcsr(self.read_csr(0x7b0)?);
 
-        // read the s0 value
-        let reg_value = self.interface.abstract_cmd_register_read(&register::S0)?;
+        if !(dcsr.ebreakm() && dcsr.ebreaks() && dcsr.ebreaku()) {
+            dcsr.set_ebreakm(true);
+            dcsr.set_ebreaks(true);
+            dcsr.set_ebreaku(true);
 
-        // restore original value in s0
-        self.interface
-            .abstract_cmd_register_write(&register::S0, s0)?;
+            self.write_csr(0x7b0, dcsr.0)?;
+        }
 
-        Ok(reg_value)
+        Ok(())
     }
+}
 
-    fn write_csr(&self, address: u16, value: u32) -> Result<(), RiscvError> {
-        // Backup register s0
-        let s0 = self.interface.abstract_cmd_register_read(&register::S0)?;
-
-        // We need to perform the csrw instruction, which writes a CSR.
-        // This is a pseudo instruction, which actually is encoded as a
-        // csrrw instruction, with the destination register being x0,
-        // so the read is ignored.
-        //
-        // csrrw,
-        // with rd  = x0
-        //      rs1 = s0
-        //      csr = address
-
-        // Write value into s0
-        self.interface
-            .abstract_cmd_register_write(&register::S0, value)?;
-
-        let mut csrrw_cmd: u32 = 0b_01000_001_00000_1110011;
-        csrrw_cmd |= ((address as u32) & 0xfff) << 20;
-        let ebreak_cmd = 0b000000000001_00000_000_00000_1110011;
-
-        // write progbuf0: csrr xxxxxx s0, (address) // lookup correct command
-        self.interface.write_dm_register(Progbuf0(csrrw_cmd))?;
-
-        // write progbuf1: ebreak
-        self.interface.write_dm_register(Progbuf1(ebreak_cmd))?;
+/// The information used to find a RISC-V chip in the registry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RiscvChipInfo {
+    /// The JTAG `IDCODE` of the debug transport module.
+    pub idcode: u32,
+    /// The machine identification CSRs, if they could be read.
+    pub machine_ids: Option<MachineIds>,
+}
 
-        // command: postexec
-        let mut postexec_cmd = AccessRegisterCommand(0);
-        postexec_cmd.set_postexec(true);
+impl RiscvChipInfo {
+    /// Reads the chip information.
+    ///
+    /// The CSRs can only be read while the hart is halted, so they are left out
+    /// if it is running. Autodetection must not disturb a running target.
+    pub fn read(interface: &RiscvCommunicationInterface) -> Result<Self, crate::Error> {
+        let idcode = interface.read_idcode()?;
+
+        let core = Riscv32::new(interface.clone());
+
+        let machine_ids = if core.core_halted()? {
+            Some(MachineIds {
+                mvendorid: core.read_csr(0xf11)?,
+                marchid: core.read_csr(0xf12)?,
+                mimpid: core.read_csr(0xf13)?,
+            })
+        } else {
+            log::debug!("The hart is running, the machine ID CSRs are not read.");
+            None
+        };
 
-        self.interface.execute_abstract_command(postexec_cmd.0)?;
+        Ok(Self {
+            idcode,
+            machine_ids,
+        })
+    }
+}
 
-        // command: transfer, regno = 0x1008
-        // restore original value in s0
-        self.interface
-            .abstract_cmd_register_write(&register::S0, s0)?;
+/// The `mvendorid`, `marchid` and `mimpid` CSRs of a hart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MachineIds {
+    pub mvendorid: u32,
+    pub marchid: u32,
+    pub mimpid: u32,
+}
 
-        Ok(())
+impl MachineIds {
+    /// The JEP106 code of the vendor, or `None` if the CSR is not implemented.
+    pub fn vendor(&self) -> Option<JEP106Code> {
+        if self.mvendorid == 0 {
+            None
+        } else {
+            Some(JEP106Code::new(
+                (self.mvendorid >> 7) as u8,
+                (self.mvendorid & 0x7f) as u8,
+            ))
+        }
     }
 }
 
@@ -135,6 +162,16 @@ impl CoreInterface for Riscv32 {
         Ok(dmstatus.allhalted())
     }
 
+    fn status(&self) -> Result<CoreStatus, crate::Error> {
+        if !self.core_halted()? {
+            return Ok(CoreStatus::Running);
+        }
+
+        let dcsr = Dcsr(self.read_core_reg(CoreRegisterAddress(0x7b0))?);
+
+        Ok(CoreStatus::Halted(dcsr.halt_reason()))
+    }
+
     fn halt(&self) -> Result<CoreInformation, crate::Error> {
         // write 1 to the haltreq register, which is part
         // of the dmcontrol register
@@ -159,6 +196,8 @@ impl CoreInterface for Riscv32 {
 
         self.interface.write_dm_register(dmcontrol)?;
 
+        self.enable_ebreak_debug_entry()?;
+
         let pc = self.read_core_reg(CoreRegisterAddress(0x7b1))?;
 
         Ok(CoreInformation { pc })
@@ -305,6 +344,8 @@ impl CoreInterface for Riscv32 {
 
         self.interface.write_dm_register(dmcontrol)?;
 
+        self.enable_ebreak_debug_entry()?;
+
         let pc = self.read_core_reg(CoreRegisterAddress(0x7b1))?;
 
         Ok(CoreInformation { pc })
@@ -334,23 +375,15 @@ impl CoreInterface for Riscv32 {
     }
 
     fn read_core_reg(&self, address: crate::CoreRegisterAddress) -> Result<u32, crate::Error> {
-        // We need to sue the "Access Register Command",
-        // which has cmdtype 0
-
-        // write needs to be clear
-        // transfer has to be set
+        Ok(self.read_core_reg_64(address)? as u32)
+    }
 
+    fn read_core_reg_64(&self, address: crate::CoreRegisterAddress) -> Result<u64, crate::Error> {
         log::debug!("Reading core register at address {:#x}", address.0);
 
-        // if it is a gpr (general purpose register) read using an abstract command,
-        // otherwise, use the program buffer
-        if address.0 >= 0x1000 && address.0 <= 0x101f {
-            let value = self.interface.abstract_cmd_register_read(address)?;
-            Ok(value)
-        } else {
-            let reg_value = self.read_csr(address.0)?;
-            Ok(reg_value)
-        }
+        let value = self.interface.read_register(address)?;
+
+        Ok(value)
     }
 
     fn write_core_reg(
@@ -358,11 +391,16 @@ impl CoreInterface for Riscv32 {
         address: crate::CoreRegisterAddress,
         value: u32,
     ) -> Result<(), crate::Error> {
-        if address.0 >= 0x1000 && address.0 <= 0x101f {
-            self.interface.abstract_cmd_register_write(address, value)?;
-        } else {
-            self.write_csr(address.0, value)?;
-        }
+        self.write_core_reg_64(address, value.into())
+    }
+
+    fn write_core_reg_64(
+        &self,
+        address: crate::CoreRegisterAddress,
+        value: u64,
+    ) -> Result<(), crate::Error> {
+        self.interface.write_register(address, value)?;
+
         Ok(())
     }
 
@@ -372,7 +410,6 @@ impl CoreInterface for Riscv32 {
         log::debug!("Determining number of HW breakpoints supported");
 
         let tselect = 0x7a0;
-        let tdata1 = 0x7a1;
         let tinfo = 0x7a4;
 
         let mut tselect_index = 0;
@@ -408,12 +445,7 @@ impl CoreInterface for Riscv32 {
                 }
                 Err(RiscvError::AbstractCommand(AbstractCommandErrorKind::Exception)) => {
                     // An exception means we have to read tdata1 to discover the type
-                    let tdata_val = self.read_csr(tdata1)?;
-
-                    // TODO: Proper handle xlen
-                    let xlen = 32;
-
-                    let trigger_type = tdata_val >> (xlen - 4);
+                    let trigger_type = self.read_trigger_type(self.interface.xlen()?)?;
 
                     if trigger_type == 0 {
                         break;
@@ -449,11 +481,11 @@ impl CoreInterface for Riscv32 {
 
         // verify the trigger has the correct type
 
-        let tdata_value = Mcontrol(self.read_csr(tdata1)?);
+        let xlen = self.interface.xlen()?;
 
         // This should not happen
         assert_eq!(
-            tdata_value.type_(),
+            self.read_trigger_type(xlen)?,
             2,
             "Error: Incorrect trigger type for address breakpoint"
         );
@@ -470,9 +502,10 @@ impl CoreInterface for Riscv32 {
 
         instruction_breakpoint.set_execute(true);
 
-        instruction_breakpoint.set_dmode(true);
+        // The dmode bit is below the type, at the top of the register.
+        let dmode = 1 << (xlen.bits() - 5);
 
-        self.write_csr(tdata1, instruction_breakpoint.0)?;
+        self.write_csr_64(tdata1, u64::from(instruction_breakpoint.0) | dmode)?;
         self.write_csr(tdata2, addr)?;
 
         Ok(())
@@ -490,6 +523,29 @@ impl CoreInterface for Riscv32 {
         Ok(())
     }
 
+    fn fpu_present(&self) -> Result<bool, crate::Error> {
+        let misa = self.read_csr(register::MISA)?;
+
+        // The F and D extensions, which are bits 5 and 3.
+        Ok(misa & (1 << 5 | 1 << 3) != 0)
+    }
+
+    fn register_bits(&self) -> Result<u32, crate::Error> {
+        Ok(self.interface.xlen()?.bits())
+    }
+
+    fn fpu_register_bits(&self) -> Result<u32, crate::Error> {
+        let misa = self.read_csr(register::MISA)?;
+
+        // The D extension, which is bit 3. The registers are only transferred
+        // with their full width on harts with 64 bit general purpose registers.
+        if misa & 1 << 3 != 0 && self.interface.xlen()? == Xlen::X64 {
+            Ok(64)
+        } else {
+            Ok(32)
+        }
+    }
+
     fn registers(&self) -> &'static RegisterFile {
         &RISCV_REGISTERS
     }
@@ -527,6 +583,18 @@ bitfield! {
     dmactive, set_dmactive: 0;
 }
 
+impl Dmcontrol {
+    /// The index of the selected hart, which is split into `hartselhi` and `hartsello`.
+    pub fn hartsel(&self) -> u32 {
+        self.hartselhi() << 10 | self.hartsello()
+    }
+
+    pub fn set_hartsel(&mut self, hartsel: u32) {
+        self.set_hartsello(hartsel & 0x3ff);
+        self.set_hartselhi((hartsel >> 10) & 0x3ff);
+    }
+}
+
 impl DebugRegister for Dmcontrol {
     const ADDRESS: u8 = 0x10;
     const NAME: &'static str = "dmcontrol";
@@ -606,6 +674,23 @@ bitfield! {
         prv, set_prv: 1,0;
 }
 
+impl Dcsr {
+    /// Decodes `cause`, the reason why the hart entered debug mode.
+    fn halt_reason(&self) -> HaltReason {
+        match self.cause() {
+            1 => HaltReason::Breakpoint,
+            // Triggers are only used for hardware breakpoints.
+            2 => HaltReason::Breakpoint,
+            3 => HaltReason::Request,
+            4 => HaltReason::Step,
+            5 => HaltReason::VectorCatch,
+            // A halt of another hart in the same halt group.
+            6 => HaltReason::Request,
+            _ => HaltReason::Unknown,
+        }
+    }
+}
+
 bitfield! {
     pub struct Abstractcs(u32);
     impl Debug;
@@ -648,8 +733,8 @@ data_register! { pub Data11, 0x0f, "data11" }
 
 data_register! { Command, 0x17, "command" }
 
-data_register! { pub Progbuf0, 0x20, "progbuf0" }
-data_register! { pub Progbuf1, 0x21, "progbuf1" }
+data_register! { pub Hawindowsel, 0x14, "hawindowsel" }
+data_register! { pub Hawindow, 0x15, "hawindow" }
 
 bitfield! {
     struct Mcontrol(u32);
@@ -672,3 +757,31 @@ bitfield! {
     store, set_store: 1;
     load, set_load: 0;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Dcsr, Dmcontrol};
+    use crate::HaltReason;
+
+    #[test]
+    fn hartsel_is_split_into_two_fields() {
+        let mut dmcontrol = Dmcontrol(0);
+        dmcontrol.set_dmactive(true);
+        dmcontrol.set_hartsel(0x4_0003);
+
+        assert_eq!(dmcontrol.hartsello(), 3);
+        assert_eq!(dmcontrol.hartselhi(), 0x100);
+        assert_eq!(dmcontrol.hartsel(), 0x4_0003);
+        assert_eq!(dmcontrol.0, 0x0003_4001);
+    }
+
+    #[test]
+    fn halt_reason_from_dcsr_cause() {
+        // xdebugver 4, ebreakm, cause ebreak
+        assert_eq!(Dcsr(0x4000_8043).halt_reason(), HaltReason::Breakpoint);
+        // cause step
+        assert_eq!(Dcsr(0x4000_8107).halt_reason(), HaltReason::Step);
+        // cause haltreq
+        assert_eq!(Dcsr(0x4000_80c3).halt_reason(), HaltReason::Request);
+    }
+}
@@ -1,8 +1,10 @@
+use super::Xlen;
 use crate::core::RegisterDescription;
 use crate::{
     core::{RegisterFile, RegisterKind},
     CoreRegisterAddress,
 };
+use std::fmt;
 
 macro_rules! data_register {
     ($i:ident, $addr:expr, $name:expr) => {
@@ -51,39 +53,38 @@ macro_rules! data_register {
 static PC: RegisterDescription = RegisterDescription {
     name: "pc",
     kind: RegisterKind::PC,
-    /// This is a CSR register
+    // This is a CSR register
     address: CoreRegisterAddress(0x7b1),
 };
 
 static RA: RegisterDescription = RegisterDescription {
     name: "ra",
     kind: RegisterKind::General,
-    /// This is a CSR register
     address: CoreRegisterAddress(0x1001),
 };
 
 static SP: RegisterDescription = RegisterDescription {
     name: "sp",
     kind: RegisterKind::General,
-    /// This is a CSR register
     address: CoreRegisterAddress(0x1002),
 };
 
 pub static S0: RegisterDescription = RegisterDescription {
     name: "s0",
     kind: RegisterKind::General,
-    /// This is a CSR register
     address: CoreRegisterAddress(0x1008),
 };
 
 pub static S1: RegisterDescription = RegisterDescription {
     name: "s1",
     kind: RegisterKind::General,
-    /// This is a CSR register
     address: CoreRegisterAddress(0x1009),
 };
 
-pub(super) static RISCV_REGISTERS: RegisterFile = RegisterFile {
+/// The address of the `misa` CSR, which lists the supported extensions.
+pub(super) const MISA: u16 = 0x301;
+
+pub(crate) static RISCV_REGISTERS: RegisterFile = RegisterFile {
     platform_registers: &[
         RegisterDescription {
             name: "x0",
@@ -138,13 +139,113 @@ pub(super) static RISCV_REGISTERS: RegisterFile = RegisterFile {
         RegisterDescription {
             name: "x10",
             kind: RegisterKind::General,
-            address: CoreRegisterAddress(0x1010),
+            address: CoreRegisterAddress(0x100a),
         },
         RegisterDescription {
             name: "x11",
             kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x100b),
+        },
+        RegisterDescription {
+            name: "x12",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x100c),
+        },
+        RegisterDescription {
+            name: "x13",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x100d),
+        },
+        RegisterDescription {
+            name: "x14",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x100e),
+        },
+        RegisterDescription {
+            name: "x15",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x100f),
+        },
+        RegisterDescription {
+            name: "x16",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x1010),
+        },
+        RegisterDescription {
+            name: "x17",
+            kind: RegisterKind::General,
             address: CoreRegisterAddress(0x1011),
         },
+        RegisterDescription {
+            name: "x18",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x1012),
+        },
+        RegisterDescription {
+            name: "x19",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x1013),
+        },
+        RegisterDescription {
+            name: "x20",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x1014),
+        },
+        RegisterDescription {
+            name: "x21",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x1015),
+        },
+        RegisterDescription {
+            name: "x22",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x1016),
+        },
+        RegisterDescription {
+            name: "x23",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x1017),
+        },
+        RegisterDescription {
+            name: "x24",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x1018),
+        },
+        RegisterDescription {
+            name: "x25",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x1019),
+        },
+        RegisterDescription {
+            name: "x26",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x101a),
+        },
+        RegisterDescription {
+            name: "x27",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x101b),
+        },
+        RegisterDescription {
+            name: "x28",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x101c),
+        },
+        RegisterDescription {
+            name: "x29",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x101d),
+        },
+        RegisterDescription {
+            name: "x30",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x101e),
+        },
+        RegisterDescription {
+            name: "x31",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x101f),
+        },
     ],
 
     program_counter: &PC,
@@ -157,42 +258,42 @@ pub(super) static RISCV_REGISTERS: RegisterFile = RegisterFile {
         RegisterDescription {
             name: "a0",
             kind: RegisterKind::General,
-            address: CoreRegisterAddress(0x1010),
+            address: CoreRegisterAddress(0x100a),
         },
         RegisterDescription {
             name: "a1",
             kind: RegisterKind::General,
-            address: CoreRegisterAddress(0x1011),
+            address: CoreRegisterAddress(0x100b),
         },
         RegisterDescription {
             name: "a2",
             kind: RegisterKind::General,
-            address: CoreRegisterAddress(0x1012),
+            address: CoreRegisterAddress(0x100c),
         },
         RegisterDescription {
             name: "a3",
             kind: RegisterKind::General,
-            address: CoreRegisterAddress(0x1013),
+            address: CoreRegisterAddress(0x100d),
         },
         RegisterDescription {
             name: "a4",
             kind: RegisterKind::General,
-            address: CoreRegisterAddress(0x1014),
+            address: CoreRegisterAddress(0x100e),
         },
         RegisterDescription {
             name: "a5",
             kind: RegisterKind::General,
-            address: CoreRegisterAddress(0x1015),
+            address: CoreRegisterAddress(0x100f),
         },
         RegisterDescription {
             name: "a6",
             kind: RegisterKind::General,
-            address: CoreRegisterAddress(0x1016),
+            address: CoreRegisterAddress(0x1010),
         },
         RegisterDescription {
             name: "a7",
             kind: RegisterKind::General,
-            address: CoreRegisterAddress(0x1017),
+            address: CoreRegisterAddress(0x1011),
         },
     ],
 
@@ -200,12 +301,349 @@ pub(super) static RISCV_REGISTERS: RegisterFile = RegisterFile {
         RegisterDescription {
             name: "a0",
             kind: RegisterKind::General,
-            address: CoreRegisterAddress(0x1010),
+            address: CoreRegisterAddress(0x100a),
         },
         RegisterDescription {
             name: "a1",
             kind: RegisterKind::General,
-            address: CoreRegisterAddress(0x1011),
+            address: CoreRegisterAddress(0x100b),
+        },
+    ],
+
+    control_registers: &[
+        RegisterDescription {
+            name: "mstatus",
+            kind: RegisterKind::Control,
+            address: CoreRegisterAddress(0x300),
+        },
+        RegisterDescription {
+            name: "misa",
+            kind: RegisterKind::Control,
+            address: CoreRegisterAddress(0x301),
+        },
+        RegisterDescription {
+            name: "mie",
+            kind: RegisterKind::Control,
+            address: CoreRegisterAddress(0x304),
+        },
+        RegisterDescription {
+            name: "mtvec",
+            kind: RegisterKind::Control,
+            address: CoreRegisterAddress(0x305),
+        },
+        RegisterDescription {
+            name: "mepc",
+            kind: RegisterKind::Control,
+            address: CoreRegisterAddress(0x341),
+        },
+        RegisterDescription {
+            name: "mcause",
+            kind: RegisterKind::Control,
+            address: CoreRegisterAddress(0x342),
+        },
+        RegisterDescription {
+            name: "mtval",
+            kind: RegisterKind::Control,
+            address: CoreRegisterAddress(0x343),
+        },
+        RegisterDescription {
+            name: "mip",
+            kind: RegisterKind::Control,
+            address: CoreRegisterAddress(0x344),
+        },
+        RegisterDescription {
+            name: "dcsr",
+            kind: RegisterKind::Control,
+            address: CoreRegisterAddress(0x7b0),
+        },
+        RegisterDescription {
+            name: "dpc",
+            kind: RegisterKind::Control,
+            address: CoreRegisterAddress(0x7b1),
+        },
+    ],
+
+    fpu_registers: &[
+        RegisterDescription {
+            name: "f0",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x1020),
+        },
+        RegisterDescription {
+            name: "f1",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x1021),
+        },
+        RegisterDescription {
+            name: "f2",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x1022),
+        },
+        RegisterDescription {
+            name: "f3",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x1023),
+        },
+        RegisterDescription {
+            name: "f4",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x1024),
+        },
+        RegisterDescription {
+            name: "f5",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x1025),
+        },
+        RegisterDescription {
+            name: "f6",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x1026),
+        },
+        RegisterDescription {
+            name: "f7",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x1027),
+        },
+        RegisterDescription {
+            name: "f8",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x1028),
+        },
+        RegisterDescription {
+            name: "f9",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x1029),
+        },
+        RegisterDescription {
+            name: "f10",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x102a),
+        },
+        RegisterDescription {
+            name: "f11",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x102b),
+        },
+        RegisterDescription {
+            name: "f12",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x102c),
+        },
+        RegisterDescription {
+            name: "f13",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x102d),
+        },
+        RegisterDescription {
+            name: "f14",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x102e),
+        },
+        RegisterDescription {
+            name: "f15",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x102f),
+        },
+        RegisterDescription {
+            name: "f16",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x1030),
+        },
+        RegisterDescription {
+            name: "f17",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x1031),
+        },
+        RegisterDescription {
+            name: "f18",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x1032),
+        },
+        RegisterDescription {
+            name: "f19",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x1033),
+        },
+        RegisterDescription {
+            name: "f20",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x1034),
+        },
+        RegisterDescription {
+            name: "f21",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x1035),
+        },
+        RegisterDescription {
+            name: "f22",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x1036),
+        },
+        RegisterDescription {
+            name: "f23",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x1037),
+        },
+        RegisterDescription {
+            name: "f24",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x1038),
+        },
+        RegisterDescription {
+            name: "f25",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x1039),
+        },
+        RegisterDescription {
+            name: "f26",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x103a),
+        },
+        RegisterDescription {
+            name: "f27",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x103b),
+        },
+        RegisterDescription {
+            name: "f28",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x103c),
+        },
+        RegisterDescription {
+            name: "f29",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x103d),
+        },
+        RegisterDescription {
+            name: "f30",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x103e),
+        },
+        RegisterDescription {
+            name: "f31",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x103f),
+        },
+        RegisterDescription {
+            name: "fflags",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x1),
+        },
+        RegisterDescription {
+            name: "frm",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x2),
+        },
+        RegisterDescription {
+            name: "fcsr",
+            kind: RegisterKind::Fpu,
+            address: CoreRegisterAddress(0x3),
         },
     ],
 };
+
+/// The decoded value of the `mcause` CSR, which holds the cause of the last trap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mcause {
+    /// The trap was caused by an interrupt, and not by an exception.
+    pub interrupt: bool,
+    /// The exception or interrupt code.
+    pub code: u64,
+}
+
+impl Mcause {
+    /// Decodes a value of `mcause` of a hart with the register width `xlen`.
+    ///
+    /// The interrupt flag is the highest bit of the register.
+    pub fn decode(value: u64, xlen: Xlen) -> Self {
+        let interrupt_bit = xlen.bits() - 1;
+
+        Self {
+            interrupt: value & (1 << interrupt_bit) != 0,
+            code: value & !(1 << interrupt_bit),
+        }
+    }
+
+    /// The description of the cause, if it is defined by the privileged specification.
+    pub fn description(&self) -> Option<&'static str> {
+        let description = if self.interrupt {
+            match self.code {
+                1 => "Supervisor software interrupt",
+                3 => "Machine software interrupt",
+                5 => "Supervisor timer interrupt",
+                7 => "Machine timer interrupt",
+                9 => "Supervisor external interrupt",
+                11 => "Machine external interrupt",
+                _ => return None,
+            }
+        } else {
+            match self.code {
+                0 => "Instruction address misaligned",
+                1 => "Instruction access fault",
+                2 => "Illegal instruction",
+                3 => "Breakpoint",
+                4 => "Load address misaligned",
+                5 => "Load access fault",
+                6 => "Store/AMO address misaligned",
+                7 => "Store/AMO access fault",
+                8 => "Environment call from U-mode",
+                9 => "Environment call from S-mode",
+                11 => "Environment call from M-mode",
+                12 => "Instruction page fault",
+                13 => "Load page fault",
+                15 => "Store/AMO page fault",
+                _ => return None,
+            }
+        };
+
+        Some(description)
+    }
+}
+
+impl fmt::Display for Mcause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = if self.interrupt {
+            "interrupt"
+        } else {
+            "exception"
+        };
+
+        match self.description() {
+            Some(description) => write!(f, "{} ({} {})", description, kind, self.code),
+            None => write!(f, "Unknown {} {}", kind, self.code),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Mcause, Xlen};
+
+    #[test]
+    fn decode_mcause() {
+        let exception = Mcause::decode(2, Xlen::X32);
+        assert!(!exception.interrupt);
+        assert_eq!(exception.to_string(), "Illegal instruction (exception 2)");
+
+        let interrupt = Mcause::decode(0x8000_0007, Xlen::X32);
+        assert!(interrupt.interrupt);
+        assert_eq!(interrupt.code, 7);
+
+        let interrupt = Mcause::decode(0x8000_0000_0000_000b, Xlen::X64);
+        assert!(interrupt.interrupt);
+        assert_eq!(
+            interrupt.to_string(),
+            "Machine external interrupt (interrupt 11)"
+        );
+
+        // Bit 31 is part of the exception code on RV64 harts.
+        let exception = Mcause::decode(0x8000_0002, Xlen::X64);
+        assert!(!exception.interrupt);
+        assert_eq!(exception.code, 0x8000_0002);
+
+        assert_eq!(
+            Mcause::decode(24, Xlen::X32).to_string(),
+            "Unknown exception 24"
+        );
+    }
+}
@@ -12,6 +12,13 @@ pub struct Chip {
     /// The `PART` register of the chip.
     /// This value can be determined via the `cli info` command.
     pub part: Option<u16>,
+    /// The JTAG `IDCODE` of a RISC-V chip, which is used to detect it.
+    /// The version in the upper four bits is ignored.
+    #[serde(default)]
+    pub idcode: Option<u32>,
+    /// The path of the CMSIS-SVD file which describes the peripherals of the chip.
+    #[serde(default)]
+    pub svd: Option<Cow<'static, str>>,
     /// The memory regions available on the chip.
     pub memory_map: Cow<'static, [MemoryRegion]>,
 
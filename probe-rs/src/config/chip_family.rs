@@ -21,6 +21,14 @@ pub struct ChipFamily {
     /// The name of the core type.
     /// E.g. `M0` or `M4`.
     pub core: Cow<'static, str>,
+    /// The name of the debug sequence used for this family, if any.
+    /// E.g. `stm32f4` or `nrf`.
+    #[serde(default)]
+    pub debug_sequence: Option<Cow<'static, str>>,
+    /// The name of the option bytes layout of this family, if any.
+    /// E.g. `stm32f4`.
+    #[serde(default)]
+    pub option_bytes: Option<Cow<'static, str>>,
 }
 
 impl ChipFamily {
@@ -1,8 +1,10 @@
 use crate::architecture::arm::ArmChipInfo;
+use crate::architecture::riscv::RiscvChipInfo;
 
 #[derive(Debug)]
 pub enum ChipInfo {
     Arm(ArmChipInfo),
+    Riscv(RiscvChipInfo),
 }
 
 impl From<ArmChipInfo> for ChipInfo {
@@ -10,3 +12,9 @@ impl From<ArmChipInfo> for ChipInfo {
         ChipInfo::Arm(info)
     }
 }
+
+impl From<RiscvChipInfo> for ChipInfo {
+    fn from(info: RiscvChipInfo) -> Self {
+        ChipInfo::Riscv(info)
+    }
+}
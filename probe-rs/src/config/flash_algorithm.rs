@@ -1,5 +1,6 @@
 use super::flash_properties::FlashProperties;
 use super::memory::{PageInfo, RamRegion, SectorInfo};
+use crate::core::Architecture;
 use std::{borrow::Cow, convert::TryInto};
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
@@ -22,8 +23,8 @@ pub struct FlashAlgorithm {
     pub pc_erase_sector: u32,
     /// Address of the `EraseAll()` entry point. Optional.
     pub pc_erase_all: Option<u32>,
-    /// Initial value of the R9 register for calling flash algo entry points, which
-    /// determines where the position-independent data resides.
+    /// Initial value of the static base register (R9 on ARM, gp on RISC-V) for calling
+    /// flash algo entry points, which determines where the position-independent data resides.
     pub static_base: u32,
     /// Initial value of the stack pointer when calling any flash algo API.
     pub begin_stack: u32,
@@ -152,10 +153,12 @@ where
 }
 
 impl RawFlashAlgorithm {
-    const FLASH_BLOB_HEADER_SIZE: u32 = 8 * 4;
     const FLASH_ALGO_STACK_SIZE: u32 = 512;
     const FLASH_ALGO_STACK_DECREMENT: u32 = 64;
-    const FLASH_BLOB_HEADER: [u32; Self::FLASH_BLOB_HEADER_SIZE as usize / 4] = [
+
+    /// The header placed in front of the algorithm on ARM. It starts with a `BKPT`,
+    /// which the algorithm returns to.
+    const FLASH_BLOB_HEADER_ARM: [u32; 8] = [
         0xE00A_BE00,
         0x062D_780D,
         0x2408_4068,
@@ -166,9 +169,21 @@ impl RawFlashAlgorithm {
         0x0477_0D1F,
     ];
 
+    /// The header placed in front of the algorithm on RISC-V, an `ebreak`
+    /// which the algorithm returns to.
+    const FLASH_BLOB_HEADER_RISCV: [u32; 1] = [0x0010_0073];
+
     /// Constructs a complete flash algorithm, tailored to the flash and RAM sizes given.
-    pub fn assemble(&self, ram_region: &RamRegion) -> FlashAlgorithm {
-        let mut instructions = Self::FLASH_BLOB_HEADER.to_vec();
+    ///
+    /// The algorithm is prefixed with a header for the `architecture`, which halts the core
+    /// when an algorithm function returns.
+    pub fn assemble(&self, ram_region: &RamRegion, architecture: Architecture) -> FlashAlgorithm {
+        let header: &[u32] = match architecture {
+            Architecture::ARM => &Self::FLASH_BLOB_HEADER_ARM,
+            Architecture::RISCV => &Self::FLASH_BLOB_HEADER_RISCV,
+        };
+
+        let mut instructions = header.to_vec();
 
         let assembled_instructions = (&self.instructions)
             .chunks(4)
@@ -210,7 +225,7 @@ impl RawFlashAlgorithm {
             vec![addr_data]
         };
 
-        let code_start = addr_load + Self::FLASH_BLOB_HEADER_SIZE;
+        let code_start = addr_load + header.len() as u32 * 4;
 
         let name = self.name.clone().into_owned();
 
@@ -346,3 +361,31 @@ fn flash_sector_multiple_sizes() {
     assert_eq!(expected_b, config.sector_info(0x801_0000).unwrap());
     assert_eq!(expected_c, config.sector_info(0x80A_0000).unwrap());
 }
+
+#[test]
+fn assemble_riscv_algorithm() {
+    let raw = RawFlashAlgorithm {
+        instructions: Cow::Borrowed(&[0x13, 0x00, 0x00, 0x00]),
+        pc_program_page: 0x0,
+        pc_erase_sector: 0x0,
+        flash_properties: FlashProperties {
+            page_size: 0x100,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let ram = RamRegion {
+        range: 0x2000_0000..0x2000_8000,
+        is_boot_memory: false,
+    };
+
+    let algorithm = raw.assemble(&ram, Architecture::RISCV);
+
+    // The functions return to the `ebreak` in front of the code.
+    assert_eq!(algorithm.instructions, vec![0x0010_0073, 0x0000_0013]);
+    assert_eq!(algorithm.pc_program_page, algorithm.load_address + 4);
+
+    let algorithm = raw.assemble(&ram, Architecture::ARM);
+    assert_eq!(algorithm.pc_program_page, algorithm.load_address + 32);
+}
@@ -0,0 +1,294 @@
+//! Parsing of Keil FLM flash algorithms.
+//!
+//! An FLM file is an ELF file with the position independent code of the algorithm in the
+//! `PrgCode` section, its data in the `PrgData` section and a `FlashDevice` struct in the
+//! `DevDscr` section, which describes the flash the algorithm is used for.
+
+use super::flash_algorithm::RawFlashAlgorithm;
+use super::flash_properties::FlashProperties;
+use super::memory::SectorDescription;
+use goblin::elf::{section_header::SHT_NOBITS, Elf};
+use scroll::{Pread, LE};
+use std::borrow::Cow;
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum FlmError {
+    #[error("Reading the flash algorithm failed: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Parsing the flash algorithm failed: {0}")]
+    Elf(#[from] goblin::error::Error),
+    #[error("The flash algorithm '{0}' is invalid: {1}")]
+    InvalidAlgorithm(String, String),
+    #[error("The flash algorithm '{0}' covers only a part of the flash region {1:#010x?}")]
+    PartialFlashRegion(String, std::ops::Range<u32>),
+}
+
+/// The largest size of the code and data of an algorithm, which has to fit into RAM.
+const MAX_ALGORITHM_SIZE: usize = 0x10_0000;
+
+/// Offsets of the fields in the `FlashDevice` struct, as defined in `FlashOS.h`.
+mod flash_device {
+    pub const NAME: usize = 2;
+    pub const NAME_LENGTH: usize = 128;
+    pub const DEVICE_ADDRESS: usize = 132;
+    pub const DEVICE_SIZE: usize = 136;
+    pub const PAGE_SIZE: usize = 140;
+    pub const ERASED_VALUE: usize = 148;
+    pub const PROGRAM_PAGE_TIMEOUT: usize = 152;
+    pub const ERASE_SECTOR_TIMEOUT: usize = 156;
+    pub const SECTORS: usize = 160;
+    /// The list of sectors is terminated by a sector with this size and address.
+    pub const SECTOR_END: u32 = 0xFFFF_FFFF;
+}
+
+impl RawFlashAlgorithm {
+    /// Loads the Keil FLM flash algorithm at `path`.
+    ///
+    /// The algorithm is named after the file, and is marked as the default algorithm,
+    /// so it is preferred over built-in algorithms for the same flash.
+    pub fn from_flm(path: impl AsRef<Path>) -> Result<Self, FlmError> {
+        let path = path.as_ref();
+        let data = std::fs::read(path)?;
+
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+
+        Self::from_flm_data(&name, &data, true)
+    }
+
+    /// Parses the FLM file in `data` into a flash algorithm called `name`.
+    pub fn from_flm_data(name: &str, data: &[u8], default: bool) -> Result<Self, FlmError> {
+        let elf = Elf::parse(data)?;
+
+        let invalid = |reason: &str| FlmError::InvalidAlgorithm(name.to_owned(), reason.to_owned());
+
+        // Copy code and data into a single blob, starting at address 0.
+        let mut instructions = Vec::new();
+        let mut data_section_offset = None;
+
+        for section in &elf.section_headers {
+            let section_name = elf.shdr_strtab.get(section.sh_name).and_then(Result::ok);
+
+            let is_data = match section_name {
+                Some("PrgCode") => false,
+                Some("PrgData") => true,
+                _ => continue,
+            };
+
+            let start = section.sh_addr as usize;
+            let end = start
+                .checked_add(section.sh_size as usize)
+                .filter(|end| *end <= MAX_ALGORITHM_SIZE)
+                .ok_or_else(|| invalid("a section does not fit into the algorithm"))?;
+
+            if instructions.len() < end {
+                instructions.resize(end, 0);
+            }
+
+            // Zero initialized data is not stored in the file, and is already zero in the blob.
+            if section.sh_type != SHT_NOBITS {
+                let offset = section.sh_offset as usize;
+                let contents = offset
+                    .checked_add(section.sh_size as usize)
+                    .and_then(|contents_end| data.get(offset..contents_end))
+                    .ok_or_else(|| invalid("section contents are out of bounds"))?;
+                instructions[start..end].copy_from_slice(contents);
+            }
+
+            if is_data {
+                data_section_offset = Some(
+                    data_section_offset.map_or(section.sh_addr as u32, |o: u32| {
+                        o.min(section.sh_addr as u32)
+                    }),
+                );
+            }
+        }
+
+        if instructions.is_empty() {
+            return Err(invalid("no PrgCode section was found"));
+        }
+
+        // The instructions are loaded as 32 bit words.
+        while instructions.len() % 4 != 0 {
+            instructions.push(0);
+        }
+
+        let symbol = |symbol_name: &str| {
+            elf.syms
+                .iter()
+                .find(|sym| elf.strtab.get(sym.st_name).and_then(Result::ok) == Some(symbol_name))
+                .map(|sym| sym.st_value as u32)
+        };
+
+        let flash_device_address =
+            symbol("FlashDevice").ok_or_else(|| invalid("no FlashDevice symbol"))?;
+        let flash_device = elf
+            .section_headers
+            .iter()
+            .find(|sh| {
+                let address = u64::from(flash_device_address);
+                sh.sh_type != SHT_NOBITS
+                    && address >= sh.sh_addr
+                    && address - sh.sh_addr < sh.sh_size
+            })
+            .and_then(|sh| {
+                let offset = sh
+                    .sh_offset
+                    .checked_add(u64::from(flash_device_address) - sh.sh_addr)?;
+                data.get(offset as usize..)
+            })
+            .ok_or_else(|| invalid("the FlashDevice struct is out of bounds"))?;
+
+        let read_u32 = |offset: usize| -> Result<u32, FlmError> {
+            flash_device
+                .pread_with::<u32>(offset, LE)
+                .map_err(|_| invalid("the FlashDevice struct is truncated"))
+        };
+
+        let description = flash_device
+            .get(flash_device::NAME..flash_device::NAME + flash_device::NAME_LENGTH)
+            .map(|name| {
+                let end = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+                String::from_utf8_lossy(&name[..end]).into_owned()
+            })
+            .ok_or_else(|| invalid("the FlashDevice struct is truncated"))?;
+
+        let device_address = read_u32(flash_device::DEVICE_ADDRESS)?;
+        let device_size = read_u32(flash_device::DEVICE_SIZE)?;
+        let device_end = device_address
+            .checked_add(device_size)
+            .ok_or_else(|| invalid("the flash device ends beyond the address space"))?;
+
+        let mut sectors = Vec::new();
+        let mut offset = flash_device::SECTORS;
+        loop {
+            let size = read_u32(offset)?;
+            let address = read_u32(offset + 4)?;
+
+            if size == flash_device::SECTOR_END && address == flash_device::SECTOR_END {
+                break;
+            }
+
+            sectors.push(SectorDescription { size, address });
+            offset += 8;
+        }
+
+        Ok(RawFlashAlgorithm {
+            name: Cow::Owned(name.to_owned()),
+            description: Cow::Owned(description),
+            default,
+            instructions: Cow::Owned(instructions),
+            pc_init: symbol("Init"),
+            pc_uninit: symbol("UnInit"),
+            pc_program_page: symbol("ProgramPage")
+                .ok_or_else(|| invalid("no ProgramPage symbol"))?,
+            pc_erase_sector: symbol("EraseSector")
+                .ok_or_else(|| invalid("no EraseSector symbol"))?,
+            pc_erase_all: symbol("EraseChip"),
+            data_section_offset: data_section_offset
+                .ok_or_else(|| invalid("no PrgData section"))?,
+            flash_properties: FlashProperties {
+                address_range: device_address..device_end,
+                page_size: read_u32(flash_device::PAGE_SIZE)?,
+                erased_byte_value: flash_device
+                    .get(flash_device::ERASED_VALUE)
+                    .copied()
+                    .ok_or_else(|| invalid("the FlashDevice struct is truncated"))?,
+                program_page_timeout: read_u32(flash_device::PROGRAM_PAGE_TIMEOUT)?,
+                erase_sector_timeout: read_u32(flash_device::ERASE_SECTOR_TIMEOUT)?,
+                sectors: Cow::Owned(sectors),
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{FlmError, RawFlashAlgorithm, SectorDescription};
+
+    const TEST_ALGORITHM: &[u8] = include_bytes!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/TEST_64.FLM"
+    ));
+
+    /// Offsets of fields in `TEST_64.FLM`.
+    const PRG_CODE_SIZE: usize = 0x6bc + 40 + 20;
+    const DEVICE_SIZE: usize = 0x474 + 136;
+
+    fn patched(offset: usize, value: u32) -> Vec<u8> {
+        let mut data = TEST_ALGORITHM.to_vec();
+        data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn parse_test_algorithm() {
+        let algorithm = RawFlashAlgorithm::from_flm(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/TEST_64.FLM"
+        ))
+        .unwrap();
+
+        assert_eq!(algorithm.name, "test_64");
+        assert!(algorithm.default);
+
+        assert_eq!(algorithm.description, "Test Flash 64kB");
+        assert_eq!(algorithm.instructions.len() % 4, 0);
+        assert_eq!(algorithm.pc_init, Some(0x1));
+        assert_eq!(algorithm.pc_uninit, Some(0x5));
+        assert_eq!(algorithm.pc_erase_all, Some(0x9));
+        assert_eq!(algorithm.pc_erase_sector, 0xd);
+        assert_eq!(algorithm.pc_program_page, 0x11);
+        assert_eq!(algorithm.data_section_offset, 0x20);
+
+        let properties = &algorithm.flash_properties;
+        assert_eq!(properties.address_range, 0x0800_0000..0x0801_0000);
+        assert_eq!(properties.page_size, 0x400);
+        assert_eq!(properties.erased_byte_value, 0xFF);
+        assert_eq!(properties.program_page_timeout, 100);
+        assert_eq!(properties.erase_sector_timeout, 3000);
+        assert_eq!(
+            &properties.sectors[..],
+            &[
+                SectorDescription {
+                    size: 0x400,
+                    address: 0x0,
+                },
+                SectorDescription {
+                    size: 0x800,
+                    address: 0x8000,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn truncated_algorithm_is_rejected() {
+        for length in (0..TEST_ALGORITHM.len()).step_by(7) {
+            assert!(
+                RawFlashAlgorithm::from_flm_data("test", &TEST_ALGORITHM[..length], true).is_err(),
+                "truncated to {} bytes",
+                length
+            );
+        }
+    }
+
+    #[test]
+    fn malformed_algorithm_is_rejected() {
+        let oversized_section = patched(PRG_CODE_SIZE, 0xFFFF_FFF0);
+        assert!(matches!(
+            RawFlashAlgorithm::from_flm_data("test", &oversized_section, true),
+            Err(FlmError::InvalidAlgorithm(..))
+        ));
+
+        let overflowing_device = patched(DEVICE_SIZE, 0xFFFF_0000);
+        assert!(matches!(
+            RawFlashAlgorithm::from_flm_data("test", &overflowing_device, true),
+            Err(FlmError::InvalidAlgorithm(..))
+        ));
+    }
+}
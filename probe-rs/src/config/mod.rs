@@ -3,6 +3,7 @@ mod chip_family;
 mod chip_info;
 mod flash_algorithm;
 mod flash_properties;
+mod flm;
 mod memory;
 pub mod registry;
 mod target;
@@ -12,6 +13,7 @@ pub use chip_family::ChipFamily;
 pub use chip_info::ChipInfo;
 pub use flash_algorithm::{FlashAlgorithm, RawFlashAlgorithm};
 pub use flash_properties::FlashProperties;
+pub use flm::FlmError;
 pub use memory::{
     FlashRegion, MemoryRange, MemoryRegion, PageInfo, RamRegion, SectorDescription, SectorInfo,
 };
@@ -1,6 +1,8 @@
 use super::target::Target;
+use crate::architecture::arm::sequences::get_debug_sequence;
 use crate::config::{Chip, ChipFamily, ChipInfo};
 use crate::core::CoreType;
+use crate::flashing::option_bytes::OptionBytesFamily;
 use lazy_static::lazy_static;
 use std::fs::File;
 use std::path::Path;
@@ -15,6 +17,9 @@ lazy_static! {
         Arc::new(Mutex::new(Registry::from_builtin_families()));
 }
 
+/// The bits of a JTAG `IDCODE` which are compared, everything but the version.
+const IDCODE_MASK: u32 = 0x0FFF_FFFF;
+
 #[derive(Debug, Error)]
 pub enum RegistryError {
     #[error("The requested chip was not found.")]
@@ -33,6 +38,10 @@ pub enum RegistryError {
     Io(#[from] std::io::Error),
     #[error("Deserializing the yaml encountered an error: {0}")]
     Yaml(#[from] serde_yaml::Error),
+    #[error("The requested debug sequence '{0}' was not found.")]
+    DebugSequenceNotFound(String),
+    #[error("The requested option bytes layout '{0}' was not found.")]
+    OptionBytesNotFound(String),
     #[error("Unable to lock registry")]
     LockUnavailable,
 }
@@ -50,11 +59,15 @@ const GENERIC_TARGETS: [ChipFamily; 5] = [
         variants: Cow::Borrowed(&[Chip {
             name: Cow::Borrowed("cortex-m0"),
             part: None,
+            idcode: None,
+            svd: None,
             memory_map: Cow::Borrowed(&[]),
             flash_algorithms: Cow::Borrowed(&[]),
         }]),
         flash_algorithms: Cow::Borrowed(&[]),
         core: Cow::Borrowed("M0"),
+        debug_sequence: None,
+        option_bytes: None,
     },
     ChipFamily {
         name: Cow::Borrowed("Generic Cortex-M4"),
@@ -62,11 +75,15 @@ const GENERIC_TARGETS: [ChipFamily; 5] = [
         variants: Cow::Borrowed(&[Chip {
             name: Cow::Borrowed("cortex-m4"),
             part: None,
+            idcode: None,
+            svd: None,
             memory_map: Cow::Borrowed(&[]),
             flash_algorithms: Cow::Borrowed(&[]),
         }]),
         flash_algorithms: Cow::Borrowed(&[]),
         core: Cow::Borrowed("M4"),
+        debug_sequence: None,
+        option_bytes: None,
     },
     ChipFamily {
         name: Cow::Borrowed("Generic Cortex-M3"),
@@ -74,11 +91,15 @@ const GENERIC_TARGETS: [ChipFamily; 5] = [
         variants: Cow::Borrowed(&[Chip {
             name: Cow::Borrowed("cortex-m3"),
             part: None,
+            idcode: None,
+            svd: None,
             memory_map: Cow::Borrowed(&[]),
             flash_algorithms: Cow::Borrowed(&[]),
         }]),
         flash_algorithms: Cow::Borrowed(&[]),
         core: Cow::Borrowed("M3"),
+        debug_sequence: None,
+        option_bytes: None,
     },
     ChipFamily {
         name: Cow::Borrowed("Generic Cortex-M33"),
@@ -86,11 +107,15 @@ const GENERIC_TARGETS: [ChipFamily; 5] = [
         variants: Cow::Borrowed(&[Chip {
             name: Cow::Borrowed("cortex-m33"),
             part: None,
+            idcode: None,
+            svd: None,
             memory_map: Cow::Borrowed(&[]),
             flash_algorithms: Cow::Borrowed(&[]),
         }]),
         flash_algorithms: Cow::Borrowed(&[]),
         core: Cow::Borrowed("M33"),
+        debug_sequence: None,
+        option_bytes: None,
     },
     ChipFamily {
         name: Cow::Borrowed("Generic Riscv"),
@@ -98,11 +123,15 @@ const GENERIC_TARGETS: [ChipFamily; 5] = [
         variants: Cow::Borrowed(&[Chip {
             name: Cow::Borrowed("riscv"),
             part: None,
+            idcode: None,
+            svd: None,
             memory_map: Cow::Borrowed(&[]),
             flash_algorithms: Cow::Borrowed(&[]),
         }]),
         flash_algorithms: Cow::Borrowed(&[]),
         core: Cow::Borrowed("riscv"),
+        debug_sequence: None,
+        option_bytes: None,
     },
 ];
 
@@ -195,6 +224,35 @@ impl Registry {
 
                     (family, chip)
                 }
+                ChipInfo::Riscv(chip_info) => {
+                    // The version of the IDCODE changes with every revision of the silicon.
+                    let idcode = chip_info.idcode & IDCODE_MASK;
+
+                    let candidates: Vec<_> = self
+                        .families
+                        .iter()
+                        .flat_map(|family| family.variants.iter().map(move |v| (family, v)))
+                        .filter(|(_, variant)| {
+                            variant
+                                .idcode
+                                .map(|i| i & IDCODE_MASK == idcode)
+                                .unwrap_or(false)
+                        })
+                        .collect();
+
+                    // Chips using the same debug module are told apart by the vendor of the hart.
+                    let vendor = chip_info.machine_ids.and_then(|ids| ids.vendor());
+                    let preferred = candidates
+                        .iter()
+                        .find(|(family, _)| vendor.is_some() && family.manufacturer == vendor);
+
+                    let (family, chip) = preferred
+                        .or_else(|| candidates.first())
+                        .copied()
+                        .ok_or(RegistryError::ChipAutodetectFailed)?;
+
+                    (family, chip)
+                }
             }
         };
         self.get_target(family, chip)
@@ -218,7 +276,24 @@ impl Registry {
             .cloned()
             .collect();
 
-        Ok(Target::new(chip, chip_algorithms, core))
+        let debug_sequence =
+            match &family.debug_sequence {
+                Some(name) => Some(get_debug_sequence(name).ok_or_else(|| {
+                    RegistryError::DebugSequenceNotFound(name.clone().into_owned())
+                })?),
+                None => None,
+            };
+
+        let mut target = Target::new(chip, chip_algorithms, core, debug_sequence);
+
+        if let Some(name) = &family.option_bytes {
+            target.option_bytes =
+                Some(OptionBytesFamily::from_name(name).ok_or_else(|| {
+                    RegistryError::OptionBytesNotFound(name.clone().into_owned())
+                })?);
+        }
+
+        Ok(target)
     }
 
     fn add_target_from_yaml(&mut self, path_to_yaml: &Path) -> Result<(), RegistryError> {
@@ -296,4 +371,57 @@ mod tests {
         let registry = Registry::from_builtin_families();
         assert!(registry.get_target_by_name("nrf51822_Xxaa").is_ok());
     }
+
+    #[test]
+    fn fetch_debug_sequence() {
+        let registry = Registry::from_builtin_families();
+        let target = registry.get_target_by_name("STM32F401CBUx").unwrap();
+        assert!(target.debug_sequence.is_some());
+
+        let target = registry.get_target_by_name("nrf51822_Xxaa").unwrap();
+        assert!(target.debug_sequence.is_none());
+    }
+
+    #[test]
+    fn fetch_option_bytes() {
+        let registry = Registry::from_builtin_families();
+        let target = registry.get_target_by_name("STM32F401CBUx").unwrap();
+        assert_eq!(target.option_bytes, Some(OptionBytesFamily::Stm32F4));
+
+        let target = registry.get_target_by_name("nrf51822_Xxaa").unwrap();
+        assert!(target.option_bytes.is_none());
+    }
+
+    #[test]
+    fn fetch_riscv_by_idcode() {
+        use crate::architecture::riscv::RiscvChipInfo;
+
+        let registry = Registry::from_builtin_families();
+
+        // A newer revision of the FE310-G002 still matches.
+        let target = registry
+            .get_target_by_chip_info(ChipInfo::Riscv(RiscvChipInfo {
+                idcode: 0x3000_0913,
+                machine_ids: None,
+            }))
+            .unwrap();
+        assert_eq!(target.identifier.chip_name, "FE310-G002");
+
+        // All GD32VF103 parts have the same IDCODE, so the generic variant with
+        // the smallest flash and RAM is used.
+        let target = registry
+            .get_target_by_chip_info(ChipInfo::Riscv(RiscvChipInfo {
+                idcode: 0x1000_563D,
+                machine_ids: None,
+            }))
+            .unwrap();
+        assert_eq!(target.identifier.chip_name, "GD32VF103");
+
+        assert!(registry
+            .get_target_by_chip_info(ChipInfo::Riscv(RiscvChipInfo {
+                idcode: 0x1234_5677,
+                machine_ids: None,
+            }))
+            .is_err());
+    }
 }
@@ -1,8 +1,12 @@
 use super::chip::Chip;
 use super::flash_algorithm::RawFlashAlgorithm;
-use super::memory::MemoryRegion;
+use super::flm::FlmError;
+use super::memory::{FlashRegion, MemoryRange, MemoryRegion};
 use super::registry::TargetIdentifier;
+use crate::architecture::arm::sequences::DebugSequence;
 use crate::core::{Architecture, CoreType};
+use crate::flashing::option_bytes::OptionBytesFamily;
+use std::sync::Arc;
 
 /// This describes a complete target with a fixed chip model and variant.
 #[derive(Clone)]
@@ -15,6 +19,12 @@ pub struct Target {
     pub core_type: CoreType,
     /// The memory map of the target.
     pub memory_map: Vec<MemoryRegion>,
+    /// The debug sequence used when attaching to and resetting the target.
+    pub debug_sequence: Option<Arc<dyn DebugSequence>>,
+    /// The layout of the option bytes of the target, if they are supported.
+    pub option_bytes: Option<OptionBytesFamily>,
+    /// The path of the CMSIS-SVD file of the target.
+    pub svd: Option<String>,
 }
 
 impl std::fmt::Debug for Target {
@@ -25,8 +35,16 @@ impl std::fmt::Debug for Target {
             identifier: {:?},
             flash_algorithms: {:?},
             memory_map: {:?},
+            debug_sequence: {:?},
+            option_bytes: {:?},
+            svd: {:?},
         }}",
-            self.identifier, self.flash_algorithms, self.memory_map
+            self.identifier,
+            self.flash_algorithms,
+            self.memory_map,
+            self.debug_sequence,
+            self.option_bytes,
+            self.svd
         )
     }
 }
@@ -38,6 +56,7 @@ impl Target {
         chip: &Chip,
         flash_algorithms: Vec<RawFlashAlgorithm>,
         core_type: CoreType,
+        debug_sequence: Option<Arc<dyn DebugSequence>>,
     ) -> Target {
         Target {
             identifier: TargetIdentifier {
@@ -46,9 +65,65 @@ impl Target {
             flash_algorithms,
             core_type,
             memory_map: chip.memory_map.clone().into_owned(),
+            debug_sequence,
+            option_bytes: None,
+            svd: chip.svd.as_ref().map(|svd| svd.clone().into_owned()),
         }
     }
 
+    /// Adds a flash algorithm to the target, which is used instead of the existing
+    /// algorithms for the same flash.
+    ///
+    /// If the flash of the algorithm is not part of the memory map, for example
+    /// because it is an external flash, it is added to the memory map. An algorithm
+    /// which covers only a part of a flash region of the memory map is rejected,
+    /// because it could not be used to program the whole region.
+    pub fn add_flash_algorithm(
+        &mut self,
+        mut algorithm: RawFlashAlgorithm,
+    ) -> Result<(), FlmError> {
+        let range = algorithm.flash_properties.address_range.clone();
+
+        let flash_regions = self.memory_map.iter().filter_map(|region| match region {
+            MemoryRegion::Flash(flash) => Some(&flash.range),
+            _ => None,
+        });
+
+        let mut is_mapped = false;
+        for region in flash_regions {
+            if range.contains_range(region) {
+                is_mapped = true;
+            } else if region.intersects_range(&range) {
+                return Err(FlmError::PartialFlashRegion(
+                    algorithm.name.clone().into_owned(),
+                    region.clone(),
+                ));
+            }
+        }
+
+        algorithm.default = true;
+        for existing in &mut self.flash_algorithms {
+            if existing
+                .flash_properties
+                .address_range
+                .intersects_range(&range)
+            {
+                existing.default = false;
+            }
+        }
+
+        if !is_mapped {
+            self.memory_map.push(MemoryRegion::Flash(FlashRegion {
+                range,
+                is_boot_memory: false,
+            }));
+        }
+
+        self.flash_algorithms.insert(0, algorithm);
+
+        Ok(())
+    }
+
     pub fn architecture(&self) -> Architecture {
         match &self.core_type {
             CoreType::M0 => Architecture::ARM,
@@ -97,3 +172,92 @@ impl From<Target> for TargetSelector {
         TargetSelector::Specified(target)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Target;
+    use crate::config::{
+        Chip, FlashProperties, FlashRegion, FlmError, MemoryRegion, RamRegion, RawFlashAlgorithm,
+    };
+    use crate::core::CoreType;
+    use std::borrow::Cow;
+
+    fn algorithm(name: &'static str, range: std::ops::Range<u32>) -> RawFlashAlgorithm {
+        RawFlashAlgorithm {
+            name: Cow::Borrowed(name),
+            default: true,
+            flash_properties: FlashProperties {
+                address_range: range,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn target() -> Target {
+        let chip = Chip {
+            name: Cow::Borrowed("test"),
+            part: None,
+            idcode: None,
+            svd: None,
+            memory_map: Cow::Owned(vec![
+                MemoryRegion::Flash(FlashRegion {
+                    range: 0x0800_0000..0x0810_0000,
+                    is_boot_memory: true,
+                }),
+                MemoryRegion::Ram(RamRegion {
+                    range: 0x2000_0000..0x2002_0000,
+                    is_boot_memory: false,
+                }),
+            ]),
+            flash_algorithms: Cow::Borrowed(&[]),
+        };
+
+        Target::new(
+            &chip,
+            vec![algorithm("internal", 0x0800_0000..0x0810_0000)],
+            CoreType::M4,
+            None,
+        )
+    }
+
+    #[test]
+    fn added_algorithm_replaces_default() {
+        let mut target = target();
+        target
+            .add_flash_algorithm(algorithm("custom", 0x0800_0000..0x0810_0000))
+            .unwrap();
+
+        assert_eq!(target.flash_algorithms[0].name, "custom");
+        assert!(target.flash_algorithms[0].default);
+        assert!(!target.flash_algorithms[1].default);
+        assert_eq!(target.memory_map.len(), 2);
+    }
+
+    #[test]
+    fn added_algorithm_for_part_of_a_region_is_rejected() {
+        let mut target = target();
+        let result = target.add_flash_algorithm(algorithm("custom", 0x0800_0000..0x0808_0000));
+
+        assert!(matches!(result, Err(FlmError::PartialFlashRegion(..))));
+        assert_eq!(target.flash_algorithms.len(), 1);
+        assert!(target.flash_algorithms[0].default);
+    }
+
+    #[test]
+    fn added_algorithm_for_external_flash_is_mapped() {
+        let mut target = target();
+        target
+            .add_flash_algorithm(algorithm("qspi", 0x9000_0000..0x9100_0000))
+            .unwrap();
+
+        assert!(target.flash_algorithms[1].default);
+        assert_eq!(
+            target.memory_map[2],
+            MemoryRegion::Flash(FlashRegion {
+                range: 0x9000_0000..0x9100_0000,
+                is_boot_memory: false,
+            })
+        );
+    }
+}
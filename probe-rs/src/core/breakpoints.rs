@@ -0,0 +1,271 @@
+//! Breakpoints with conditions and hit counts, and software breakpoints,
+//! which replace an instruction in RAM with a breakpoint instruction.
+
+use super::{Architecture, BreakpointId, CoreRegisterAddress};
+use std::str::FromStr;
+
+/// `BKPT #0`, which halts Cortex-M cores.
+const ARM_BKPT: [u8; 2] = [0x00, 0xbe];
+
+/// `ebreak`, which halts a RISC-V hart if `dcsr.ebreakm` is set.
+const RISCV_EBREAK: [u8; 4] = [0x73, 0x00, 0x10, 0x00];
+
+/// `c.ebreak`, which replaces a compressed instruction.
+const RISCV_C_EBREAK: [u8; 2] = [0x02, 0x90];
+
+/// A breakpoint which is set by patching the code at its address.
+#[derive(Debug, Clone)]
+pub(crate) struct SoftwareBreakpoint {
+    pub(crate) address: u32,
+    /// The code which was replaced by `patch`.
+    pub(crate) original: Vec<u8>,
+    /// The breakpoint instruction.
+    pub(crate) patch: &'static [u8],
+}
+
+impl SoftwareBreakpoint {
+    /// Creates a breakpoint for the instruction at the start of `code`.
+    ///
+    /// The breakpoint instruction has to be the same size as the replaced
+    /// instruction on RISC-V, so compressed instructions are replaced by `c.ebreak`.
+    pub(crate) fn new(architecture: Architecture, address: u32, code: [u8; 4]) -> Self {
+        let patch: &'static [u8] = match architecture {
+            // A 32 bit Thumb instruction is never executed, so replacing
+            // its first half word is enough.
+            Architecture::ARM => &ARM_BKPT,
+            Architecture::RISCV if code[0] & 0b11 != 0b11 => &RISCV_C_EBREAK,
+            Architecture::RISCV => &RISCV_EBREAK,
+        };
+
+        Self {
+            address,
+            original: code[..patch.len()].to_vec(),
+            patch,
+        }
+    }
+}
+
+/// How a value is compared in a [`BreakpointCondition`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    /// Compares `lhs` with `rhs`, as unsigned values.
+    pub fn compare(self, lhs: u32, rhs: u32) -> bool {
+        match self {
+            Comparison::Equal => lhs == rhs,
+            Comparison::NotEqual => lhs != rhs,
+            Comparison::Less => lhs < rhs,
+            Comparison::LessOrEqual => lhs <= rhs,
+            Comparison::Greater => lhs > rhs,
+            Comparison::GreaterOrEqual => lhs >= rhs,
+        }
+    }
+}
+
+impl FromStr for Comparison {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "==" => Ok(Comparison::Equal),
+            "!=" => Ok(Comparison::NotEqual),
+            "<" => Ok(Comparison::Less),
+            "<=" => Ok(Comparison::LessOrEqual),
+            ">" => Ok(Comparison::Greater),
+            ">=" => Ok(Comparison::GreaterOrEqual),
+            _ => Err(format!("Unknown comparison '{}'", s)),
+        }
+    }
+}
+
+/// A condition which is evaluated by the host when the core halts at a breakpoint.
+#[derive(Debug, Clone)]
+pub enum BreakpointCondition {
+    /// Compares a core register with `value`.
+    Register {
+        register: CoreRegisterAddress,
+        comparison: Comparison,
+        value: u32,
+    },
+    /// Compares the 32 bit word at `address` with `value`.
+    Memory {
+        address: u32,
+        comparison: Comparison,
+        value: u32,
+    },
+}
+
+/// Decides which hits of a breakpoint halt the core.
+#[derive(Debug, Clone, Default)]
+pub struct BreakpointOptions {
+    /// The number of hits which are ignored, before the hit threshold is checked.
+    pub ignore_count: u32,
+    /// The core only stays halted once the breakpoint has been hit this many times
+    /// after the ignored hits.
+    pub hit_threshold: u32,
+    /// Hits are only counted if the condition is met.
+    pub condition: Option<BreakpointCondition>,
+}
+
+/// How often a breakpoint was hit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BreakpointStatistics {
+    /// The number of times the core reached the breakpoint and the condition was met.
+    pub hits: u32,
+    /// The number of times the core stayed halted at the breakpoint.
+    pub halts: u32,
+}
+
+/// A breakpoint which is managed by a [`Core`](crate::Core).
+#[derive(Debug, Clone)]
+pub struct ManagedBreakpoint {
+    id: BreakpointId,
+    address: u32,
+    options: BreakpointOptions,
+    statistics: BreakpointStatistics,
+    /// The hits which still have to be ignored.
+    ignores_left: u32,
+    /// The hits after the ignored ones, which are checked against the hit threshold.
+    counted_hits: u32,
+}
+
+impl ManagedBreakpoint {
+    pub(crate) fn new(id: BreakpointId, address: u32, options: BreakpointOptions) -> Self {
+        Self {
+            id,
+            address,
+            ignores_left: options.ignore_count,
+            counted_hits: 0,
+            options,
+            statistics: BreakpointStatistics::default(),
+        }
+    }
+
+    pub fn id(&self) -> BreakpointId {
+        self.id
+    }
+
+    pub fn address(&self) -> u32 {
+        self.address
+    }
+
+    pub fn options(&self) -> &BreakpointOptions {
+        &self.options
+    }
+
+    pub fn statistics(&self) -> BreakpointStatistics {
+        self.statistics
+    }
+
+    /// Counts a hit whose condition is met, and returns if the core should stay halted.
+    pub(crate) fn hit(&mut self) -> bool {
+        self.statistics.hits += 1;
+
+        if self.ignores_left > 0 {
+            self.ignores_left -= 1;
+            return false;
+        }
+
+        self.counted_hits += 1;
+        if self.counted_hits < self.options.hit_threshold {
+            return false;
+        }
+
+        self.statistics.halts += 1;
+
+        true
+    }
+}
+
+/// The breakpoints of a core, which are checked when it halts.
+#[derive(Debug, Default)]
+pub(crate) struct BreakpointManager {
+    pub(crate) breakpoints: Vec<ManagedBreakpoint>,
+    next_id: usize,
+    /// Set once the current halt was checked against the breakpoints,
+    /// and cleared when the core is resumed.
+    pub(crate) halt_checked: bool,
+}
+
+impl BreakpointManager {
+    pub(crate) fn next_id(&mut self) -> BreakpointId {
+        let id = BreakpointId::new(self.next_id);
+        self.next_id += 1;
+
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        BreakpointOptions, ManagedBreakpoint, SoftwareBreakpoint, RISCV_C_EBREAK, RISCV_EBREAK,
+    };
+    use crate::{Architecture, BreakpointId};
+
+    #[test]
+    fn arm_breakpoint_replaces_a_half_word() {
+        // ldr r0, [pc, #8]
+        let breakpoint =
+            SoftwareBreakpoint::new(Architecture::ARM, 0x2000_0100, [0x02, 0x48, 0x00, 0x00]);
+
+        assert_eq!(breakpoint.original, [0x02, 0x48]);
+        assert_eq!(breakpoint.patch, [0x00, 0xbe]);
+    }
+
+    #[test]
+    fn riscv_breakpoint_matches_instruction_size() {
+        // addi a0, a0, 1
+        let breakpoint =
+            SoftwareBreakpoint::new(Architecture::RISCV, 0x8000_0000, [0x13, 0x05, 0x15, 0x00]);
+        assert_eq!(breakpoint.original, [0x13, 0x05, 0x15, 0x00]);
+        assert_eq!(breakpoint.patch, RISCV_EBREAK);
+
+        // c.addi a0, 1
+        let breakpoint =
+            SoftwareBreakpoint::new(Architecture::RISCV, 0x8000_0000, [0x05, 0x05, 0x13, 0x05]);
+        assert_eq!(breakpoint.original, [0x05, 0x05]);
+        assert_eq!(breakpoint.patch, RISCV_C_EBREAK);
+    }
+
+    #[test]
+    fn ignore_count_and_hit_threshold() {
+        let options = BreakpointOptions {
+            ignore_count: 1,
+            hit_threshold: 3,
+            condition: None,
+        };
+        let mut breakpoint = ManagedBreakpoint::new(BreakpointId::new(0), 0x100, options);
+
+        assert!(!breakpoint.hit());
+        assert!(!breakpoint.hit());
+        assert!(!breakpoint.hit());
+        assert!(breakpoint.hit());
+        assert!(breakpoint.hit());
+
+        assert_eq!(breakpoint.statistics().hits, 5);
+        assert_eq!(breakpoint.statistics().halts, 2);
+    }
+
+    #[test]
+    fn hit_threshold_counts_after_ignored_hits() {
+        let options = BreakpointOptions {
+            ignore_count: 2,
+            hit_threshold: 3,
+            condition: None,
+        };
+        let mut breakpoint = ManagedBreakpoint::new(BreakpointId::new(0), 0x100, options);
+
+        let halts: Vec<bool> = (0..6).map(|_| breakpoint.hit()).collect();
+
+        assert_eq!(halts, [false, false, false, false, true, true]);
+    }
+}
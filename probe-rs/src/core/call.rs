@@ -0,0 +1,313 @@
+//! Calls of target functions from the host.
+//!
+//! The arguments are placed in the argument registers, and on the stack if there
+//! are more of them than registers. The function returns to a breakpoint
+//! instruction on the stack, the trampoline, which halts the core. The registers
+//! are saved before the call and restored after it, so the program continues
+//! as if the call never happened.
+
+use super::{Architecture, Core, CoreRegisterAddress};
+use crate::error;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// `BKPT 0` and two bytes of padding.
+const ARM_TRAMPOLINE: [u8; 4] = [0x00, 0xbe, 0x00, 0x00];
+
+/// `ebreak`
+const RISCV_TRAMPOLINE: [u8; 4] = [0x73, 0x00, 0x10, 0x00];
+
+/// The program status register of ARM cores.
+const ARM_XPSR: CoreRegisterAddress = CoreRegisterAddress(16);
+
+/// The Thumb bit in xPSR, which has to be set to execute code.
+const ARM_XPSR_THUMB: u32 = 1 << 24;
+
+/// The exception number in xPSR, which the call keeps.
+const ARM_XPSR_EXCEPTION: u32 = 0x1ff;
+
+/// The zero register of RISC-V, which can not be written.
+const RISCV_ZERO: u16 = 0x1000;
+
+/// Both ABIs require an 8 byte aligned stack at a call.
+const STACK_ALIGNMENT: u32 = 8;
+
+/// How long to wait between checks if the function has returned.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+#[derive(Error, Debug)]
+pub enum CallError {
+    #[error("The core has to be halted to call a function")]
+    NotHalted,
+    #[error("The function did not return within {0:?}")]
+    Timeout(Duration),
+    #[error("The core halted at {0:#010x} before the function returned")]
+    UnexpectedHalt(u32),
+    #[error("The stack at {0:#010x} is too small for the call")]
+    StackTooSmall(u32),
+}
+
+/// Where the registers and the stack are set up for a call.
+#[derive(Debug, PartialEq, Eq)]
+struct CallFrame {
+    program_counter: u32,
+    stack_pointer: u32,
+    return_address: u32,
+    trampoline: u32,
+    /// The arguments which are passed in registers.
+    register_arguments: Vec<u32>,
+    /// The arguments which are passed on the stack, starting at the stack pointer.
+    stack_arguments: Vec<u32>,
+}
+
+impl CallFrame {
+    fn new(
+        architecture: Architecture,
+        address: u32,
+        arguments: &[u32],
+        register_count: usize,
+        stack: u32,
+    ) -> Result<Self, CallError> {
+        let split = arguments.len().min(register_count);
+        let (register_arguments, stack_arguments) = arguments.split_at(split);
+
+        let stack_size = STACK_ALIGNMENT + 4 * stack_arguments.len() as u32;
+        let trampoline = (stack & !(STACK_ALIGNMENT - 1))
+            .checked_sub(STACK_ALIGNMENT)
+            .ok_or(CallError::StackTooSmall(stack))?;
+        let stack_pointer = (stack & !(STACK_ALIGNMENT - 1))
+            .checked_sub(stack_size)
+            .ok_or(CallError::StackTooSmall(stack))?
+            & !(STACK_ALIGNMENT - 1);
+
+        let (program_counter, return_address) = match architecture {
+            // Only the return address has the Thumb bit set.
+            Architecture::ARM => (address & !1, trampoline | 1),
+            Architecture::RISCV => (address, trampoline),
+        };
+
+        Ok(Self {
+            program_counter,
+            stack_pointer,
+            return_address,
+            trampoline,
+            register_arguments: register_arguments.to_vec(),
+            stack_arguments: stack_arguments.to_vec(),
+        })
+    }
+}
+
+/// Calls the function at `address`, and returns the values of the result registers.
+pub(crate) fn call_function(
+    core: &Core,
+    address: u32,
+    arguments: &[u32],
+    stack: Option<u32>,
+    timeout: Duration,
+) -> Result<Vec<u32>, error::Error> {
+    if !core.core_halted()? {
+        return Err(CallError::NotHalted.into());
+    }
+
+    let architecture = core.architecture();
+    let registers = core.registers();
+
+    let saved = save_registers(core)?;
+
+    let stack = match stack {
+        Some(stack) => stack,
+        None => core.read_core_reg(registers.stack_pointer())?,
+    };
+
+    let frame = CallFrame::new(
+        architecture,
+        address,
+        arguments,
+        registers.argument_registers.len(),
+        stack,
+    )?;
+
+    log::debug!(
+        "Calling the function at {:#010x} with the arguments {:x?} and the stack at {:#010x}",
+        address,
+        arguments,
+        frame.stack_pointer
+    );
+
+    let result = run_call(core, &frame, timeout);
+
+    // The registers are restored even if the call failed.
+    let restored = restore_registers(core, &saved);
+
+    let values = result?;
+    restored?;
+
+    Ok(values)
+}
+
+fn run_call(core: &Core, frame: &CallFrame, timeout: Duration) -> Result<Vec<u32>, error::Error> {
+    let architecture = core.architecture();
+    let registers = core.registers();
+
+    let trampoline = match architecture {
+        Architecture::ARM => ARM_TRAMPOLINE,
+        Architecture::RISCV => RISCV_TRAMPOLINE,
+    };
+    core.write_8(frame.trampoline, &trampoline)?;
+
+    if !frame.stack_arguments.is_empty() {
+        core.write_32(frame.stack_pointer, &frame.stack_arguments)?;
+    }
+
+    for (index, argument) in frame.register_arguments.iter().enumerate() {
+        core.write_core_reg(registers.argument_register(index).into(), *argument)?;
+    }
+
+    core.write_core_reg(registers.stack_pointer().into(), frame.stack_pointer)?;
+    core.write_core_reg(registers.return_address().into(), frame.return_address)?;
+    core.write_core_reg(registers.program_counter().into(), frame.program_counter)?;
+
+    if architecture == Architecture::ARM {
+        // The IT bits of an interrupted instruction must not apply to the function.
+        let xpsr = core.read_core_reg(ARM_XPSR)?;
+        core.write_core_reg(ARM_XPSR, (xpsr & ARM_XPSR_EXCEPTION) | ARM_XPSR_THUMB)?;
+    }
+
+    core.run()?;
+
+    let start = Instant::now();
+    while !core.core_halted()? {
+        if start.elapsed() > timeout {
+            core.halt()?;
+            return Err(CallError::Timeout(timeout).into());
+        }
+
+        sleep(POLL_INTERVAL);
+    }
+
+    let pc = core.read_core_reg(registers.program_counter())?;
+    if pc != frame.trampoline {
+        return Err(CallError::UnexpectedHalt(pc).into());
+    }
+
+    registers
+        .result_registers
+        .iter()
+        .map(|register| core.read_core_reg(register))
+        .collect()
+}
+
+/// The registers which are saved: the general purpose registers, the program
+/// counter, the program status on ARM and the floating point registers.
+fn saved_registers(core: &Core) -> Result<Vec<CoreRegisterAddress>, error::Error> {
+    let registers = core.registers();
+
+    let mut addresses: Vec<CoreRegisterAddress> = registers
+        .registers()
+        .map(|register| register.address)
+        .filter(|address| address.0 != RISCV_ZERO || core.architecture() != Architecture::RISCV)
+        .collect();
+
+    match core.architecture() {
+        // The program counter is a platform register on ARM.
+        Architecture::ARM => addresses.push(ARM_XPSR),
+        Architecture::RISCV => addresses.push(registers.program_counter().address),
+    }
+
+    if core.fpu_present()? {
+        addresses.extend(registers.fpu_registers().map(|register| register.address));
+    }
+
+    Ok(addresses)
+}
+
+/// Saves the registers with their full width, which is 64 bits on RV64 cores.
+fn save_registers(core: &Core) -> Result<Vec<(CoreRegisterAddress, u64)>, error::Error> {
+    saved_registers(core)?
+        .into_iter()
+        .map(|address| Ok((address, core.read_core_reg_64(address)?)))
+        .collect()
+}
+
+fn restore_registers(
+    core: &Core,
+    saved: &[(CoreRegisterAddress, u64)],
+) -> Result<(), error::Error> {
+    for (address, value) in saved {
+        core.write_core_reg_64(*address, *value)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{restore_registers, save_registers, CallError, CallFrame};
+    use crate::test_support::{MockCore, RamImage};
+    use crate::{Architecture, Memory};
+
+    #[test]
+    fn arm_frame() {
+        let frame =
+            CallFrame::new(Architecture::ARM, 0x0800_0101, &[1, 2], 4, 0x2000_1004).unwrap();
+
+        assert_eq!(
+            frame,
+            CallFrame {
+                program_counter: 0x0800_0100,
+                stack_pointer: 0x2000_0ff8,
+                return_address: 0x2000_0ff9,
+                trampoline: 0x2000_0ff8,
+                register_arguments: vec![1, 2],
+                stack_arguments: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn stack_arguments() {
+        let frame =
+            CallFrame::new(Architecture::ARM, 0x100, &[1, 2, 3, 4, 5], 4, 0x2000_1000).unwrap();
+
+        assert_eq!(frame.register_arguments, vec![1, 2, 3, 4]);
+        assert_eq!(frame.stack_arguments, vec![5]);
+        // The argument is below the trampoline, and the stack stays aligned.
+        assert_eq!(frame.trampoline, 0x2000_0ff8);
+        assert_eq!(frame.stack_pointer, 0x2000_0ff0);
+    }
+
+    #[test]
+    fn riscv_frame() {
+        let frame = CallFrame::new(Architecture::RISCV, 0x2000_0102, &[7], 8, 0x8000_0100).unwrap();
+
+        assert_eq!(frame.program_counter, 0x2000_0102);
+        assert_eq!(frame.return_address, frame.trampoline);
+        assert_eq!(frame.register_arguments, vec![7]);
+    }
+
+    #[test]
+    fn registers_keep_their_high_bits() {
+        let memory = Memory::new(RamImage {
+            base: 0,
+            data: vec![],
+        });
+        let (core, state) = MockCore::start(&[], memory);
+        state
+            .borrow_mut()
+            .registers
+            .insert(4, 0x1234_5678_9abc_def0);
+
+        let saved = save_registers(&core).unwrap();
+        core.write_core_reg(4.into(), 7).unwrap();
+        restore_registers(&core, &saved).unwrap();
+
+        assert_eq!(state.borrow().register_64(4), 0x1234_5678_9abc_def0);
+    }
+
+    #[test]
+    fn stack_too_small() {
+        let result = CallFrame::new(Architecture::ARM, 0x100, &[], 4, 4);
+        assert!(matches!(result, Err(CallError::StackTooSmall(4))));
+    }
+}
@@ -0,0 +1,698 @@
+//! Core dumps in the ELF core file format.
+//!
+//! A dump holds the general purpose registers in a `NT_PRSTATUS` note, with the
+//! same layout as a Linux core file, and a `PT_LOAD` segment for every dumped
+//! memory region. GDB reads it together with the firmware ELF, e.g. with
+//! `arm-none-eabi-gdb firmware.elf dump.elf`.
+//!
+//! A saved dump can also be loaded with [`Core::from_dump`], which serves the
+//! registers and memory of the dump like a halted core, without a probe.
+
+use super::{
+    Architecture, Core, CoreInformation, CoreInterface, CoreRegisterAddress, CoreStatus,
+    HaltReason, RegisterFile,
+};
+use crate::architecture::{arm::core::ARM_REGISTER_FILE, riscv::RISCV_REGISTERS};
+use crate::config::{MemoryRegion, RamRegion};
+use crate::{error, Memory, MemoryInterface};
+use goblin::elf::{header, note, program_header, Elf};
+use std::{
+    convert::{TryFrom, TryInto},
+    io,
+    ops::Range,
+    rc::Rc,
+};
+use thiserror::Error;
+
+const ELF_HEADER_SIZE: usize = 52;
+const PROGRAM_HEADER_SIZE: usize = 32;
+
+const ET_CORE: u16 = 4;
+const EM_ARM: u16 = 40;
+const EM_RISCV: u16 = 243;
+
+/// Version 5 of the ARM EABI.
+const EF_ARM_EABI_VER5: u32 = 0x0500_0000;
+
+const PT_LOAD: u32 = 1;
+const PT_NOTE: u32 = 4;
+
+/// Readable, writable and executable.
+const PF_RWX: u32 = 0b111;
+
+const NT_PRSTATUS: u32 = 1;
+
+/// The size of `struct elf_prstatus` up to `pr_reg`, on 32 bit targets.
+const PRSTATUS_REGISTERS_OFFSET: usize = 72;
+
+/// The offset of `pr_cursig`, which GDB shows as the signal which stopped the program.
+const PRSTATUS_CURSIG_OFFSET: usize = 12;
+
+/// `SIGTRAP`, the core was halted by the debugger.
+const SIGTRAP: u16 = 5;
+
+/// The registers of an ARM `elf_gregset_t` are `r0` to `r15`, `cpsr` and
+/// `orig_r0`. `xPSR` is stored instead of `cpsr`.
+const ARM_REGISTER_COUNT: usize = 18;
+
+/// The address of `xPSR` in the ARM register file.
+const ARM_XPSR: u16 = 0b1_0000;
+
+/// The registers of a RISC-V `elf_gregset_t` are `pc` and `x1` to `x31`.
+const RISCV_REGISTER_COUNT: usize = 32;
+
+/// The program counter of a halted hart is read from `dpc`.
+const RISCV_DPC: u16 = 0x7b1;
+
+/// `x0`, which is always zero and not stored in a dump.
+const RISCV_ZERO: u16 = 0x1000;
+
+#[derive(Error, Debug)]
+pub enum CoreDumpError {
+    #[error("The core dump is not a valid ELF file")]
+    InvalidElf(#[from] goblin::error::Error),
+    #[error("The ELF file is not a core file")]
+    NotACoreFile,
+    #[error("Core dumps of the ELF machine {0} are not supported")]
+    UnsupportedMachine(u16),
+    #[error("The core dump has no NT_PRSTATUS note with the registers")]
+    MissingRegisters,
+    #[error("A segment of the core dump is outside of the file")]
+    Truncated,
+    #[error("The memory at {0:#010x} is not part of the core dump")]
+    MemoryNotDumped(u32),
+    #[error("The register {0:#x} is not part of the core dump")]
+    RegisterNotDumped(u16),
+    #[error("{0} is not possible with a core dump")]
+    NotSupported(&'static str),
+    #[error("The segment at {0:#010x} reaches beyond the 32 bit address space")]
+    SegmentOutOfRange(u32),
+}
+
+/// A region of memory which is part of a [`CoreDump`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemorySegment {
+    pub address: u32,
+    pub data: Vec<u8>,
+}
+
+/// The registers and memory of a halted core.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoreDump {
+    architecture: Architecture,
+    /// The registers in the order of `elf_gregset_t`.
+    registers: Vec<u32>,
+    segments: Vec<MemorySegment>,
+}
+
+impl CoreDump {
+    /// Reads the registers of the halted core and the memory in `ranges`.
+    pub(crate) fn read(core: &Core, ranges: &[Range<u32>]) -> Result<Self, error::Error> {
+        let architecture = core.architecture();
+
+        let registers = register_addresses(architecture)
+            .into_iter()
+            .map(|address| match address {
+                Some(address) => core.read_core_reg(address),
+                None => Ok(0),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut segments = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            let mut data = vec![0; (range.end - range.start) as usize];
+            core.read_8(range.start, &mut data)?;
+
+            segments.push(MemorySegment {
+                address: range.start,
+                data,
+            });
+        }
+
+        Ok(Self {
+            architecture,
+            registers,
+            segments,
+        })
+    }
+
+    /// Creates a dump from registers in the order of `elf_gregset_t`.
+    pub(crate) fn new(
+        architecture: Architecture,
+        registers: Vec<u32>,
+        segments: Vec<MemorySegment>,
+    ) -> Self {
+        Self {
+            architecture,
+            registers,
+            segments,
+        }
+    }
+
+    /// Parses an ELF core file, as written by [`CoreDump::write_elf`].
+    pub fn from_elf(data: &[u8]) -> Result<Self, CoreDumpError> {
+        let elf = Elf::parse(data)?;
+
+        if elf.header.e_type != header::ET_CORE {
+            return Err(CoreDumpError::NotACoreFile);
+        }
+
+        let (architecture, register_count) = match elf.header.e_machine {
+            header::EM_ARM => (Architecture::ARM, ARM_REGISTER_COUNT),
+            header::EM_RISCV => (Architecture::RISCV, RISCV_REGISTER_COUNT),
+            machine => return Err(CoreDumpError::UnsupportedMachine(machine)),
+        };
+
+        let prstatus = elf
+            .iter_note_headers(data)
+            .into_iter()
+            .flatten()
+            .filter_map(Result::ok)
+            .find(|note| note.name == "CORE" && note.n_type == note::NT_PRSTATUS)
+            .ok_or(CoreDumpError::MissingRegisters)?;
+
+        let registers = prstatus
+            .desc
+            .get(PRSTATUS_REGISTERS_OFFSET..PRSTATUS_REGISTERS_OFFSET + register_count * 4)
+            .ok_or(CoreDumpError::MissingRegisters)?
+            .chunks_exact(4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect();
+
+        let mut segments = Vec::new();
+        for header in &elf.program_headers {
+            if header.p_type != program_header::PT_LOAD {
+                continue;
+            }
+
+            let start = header.p_offset as usize;
+            let data = start
+                .checked_add(header.p_filesz as usize)
+                .and_then(|end| data.get(start..end))
+                .ok_or(CoreDumpError::Truncated)?;
+
+            segments.push(MemorySegment {
+                address: header.p_vaddr as u32,
+                data: data.to_vec(),
+            });
+        }
+
+        Ok(Self {
+            architecture,
+            registers,
+            segments,
+        })
+    }
+
+    pub fn architecture(&self) -> Architecture {
+        self.architecture
+    }
+
+    /// The registers, in the order in which they are stored in the `NT_PRSTATUS` note.
+    pub fn registers(&self) -> &[u32] {
+        &self.registers
+    }
+
+    pub fn segments(&self) -> &[MemorySegment] {
+        &self.segments
+    }
+
+    /// Encodes the dump as an ELF core file.
+    pub fn to_elf(&self) -> Vec<u8> {
+        let note = self.prstatus_note();
+
+        let phnum = 1 + self.segments.len();
+        let note_offset = ELF_HEADER_SIZE + phnum * PROGRAM_HEADER_SIZE;
+
+        let mut elf = Vec::new();
+        self.write_elf_header(&mut elf, phnum as u16);
+
+        write_program_header(&mut elf, PT_NOTE, note_offset, 0, note.len(), 0, 4);
+
+        let mut offset = note_offset + note.len();
+        for segment in &self.segments {
+            write_program_header(
+                &mut elf,
+                PT_LOAD,
+                offset,
+                segment.address,
+                segment.data.len(),
+                PF_RWX,
+                1,
+            );
+            offset += segment.data.len();
+        }
+
+        elf.extend_from_slice(&note);
+        for segment in &self.segments {
+            elf.extend_from_slice(&segment.data);
+        }
+
+        elf
+    }
+
+    /// Writes the dump as an ELF core file.
+    pub fn write_elf(&self, mut writer: impl io::Write) -> io::Result<()> {
+        writer.write_all(&self.to_elf())
+    }
+
+    fn write_elf_header(&self, elf: &mut Vec<u8>, phnum: u16) {
+        let (machine, flags) = match self.architecture {
+            Architecture::ARM => (EM_ARM, EF_ARM_EABI_VER5),
+            Architecture::RISCV => (EM_RISCV, 0),
+        };
+
+        // 32 bit, little endian, version 1, System V ABI.
+        elf.extend_from_slice(&[0x7f, b'E', b'L', b'F', 1, 1, 1, 0]);
+        elf.extend_from_slice(&[0; 8]);
+
+        put_u16(elf, ET_CORE);
+        put_u16(elf, machine);
+        put_u32(elf, 1);
+        // Entry point
+        put_u32(elf, 0);
+        put_u32(elf, ELF_HEADER_SIZE as u32);
+        // There are no section headers.
+        put_u32(elf, 0);
+        put_u32(elf, flags);
+        put_u16(elf, ELF_HEADER_SIZE as u16);
+        put_u16(elf, PROGRAM_HEADER_SIZE as u16);
+        put_u16(elf, phnum);
+        put_u16(elf, 0);
+        put_u16(elf, 0);
+        put_u16(elf, 0);
+    }
+
+    fn prstatus_note(&self) -> Vec<u8> {
+        let mut prstatus = vec![0; PRSTATUS_REGISTERS_OFFSET];
+        prstatus[PRSTATUS_CURSIG_OFFSET..PRSTATUS_CURSIG_OFFSET + 2]
+            .copy_from_slice(&SIGTRAP.to_le_bytes());
+
+        for register in &self.registers {
+            put_u32(&mut prstatus, *register);
+        }
+        // pr_fpvalid
+        put_u32(&mut prstatus, 0);
+
+        let mut note = Vec::new();
+        put_u32(&mut note, 5);
+        put_u32(&mut note, prstatus.len() as u32);
+        put_u32(&mut note, NT_PRSTATUS);
+        // The name is padded to a multiple of four bytes.
+        note.extend_from_slice(b"CORE\0\0\0\0");
+        note.extend_from_slice(&prstatus);
+
+        note
+    }
+
+    /// Reads a register of the dump, `x0` of a RISC-V hart is always zero.
+    fn read_register(&self, address: CoreRegisterAddress) -> Result<u32, CoreDumpError> {
+        if self.architecture == Architecture::RISCV && address.0 == RISCV_ZERO {
+            return Ok(0);
+        }
+
+        register_addresses(self.architecture)
+            .iter()
+            .position(|slot| matches!(slot, Some(slot) if slot.0 == address.0))
+            .and_then(|index| self.registers.get(index).copied())
+            .ok_or(CoreDumpError::RegisterNotDumped(address.0))
+    }
+
+    /// Reads `data.len()` bytes at `address`, which have to be in a single segment.
+    fn read_memory(&self, address: u32, data: &mut [u8]) -> Result<(), CoreDumpError> {
+        let bytes = self
+            .segments
+            .iter()
+            .find_map(|segment| {
+                let start = address.checked_sub(segment.address)? as usize;
+                segment.data.get(start..start + data.len())
+            })
+            .ok_or(CoreDumpError::MemoryNotDumped(address))?;
+
+        data.copy_from_slice(bytes);
+
+        Ok(())
+    }
+}
+
+impl Core {
+    /// Creates a core which serves the registers and memory of a dump.
+    ///
+    /// The core is always halted. It can not be resumed, and its registers and
+    /// memory can not be written.
+    ///
+    /// Segments which reach beyond the 32 bit address space are rejected.
+    pub fn from_dump(dump: CoreDump) -> Result<Core, CoreDumpError> {
+        let memory_map = dump
+            .segments
+            .iter()
+            .map(|segment| {
+                let end = u32::try_from(segment.data.len())
+                    .ok()
+                    .and_then(|length| segment.address.checked_add(length))
+                    .ok_or(CoreDumpError::SegmentOutOfRange(segment.address))?;
+
+                Ok(MemoryRegion::Ram(RamRegion {
+                    range: segment.address..end,
+                    is_boot_memory: false,
+                }))
+            })
+            .collect::<Result<_, CoreDumpError>>()?;
+
+        let mut core = Core::new(DumpCore {
+            dump: Rc::new(dump),
+        });
+        core.set_memory_map(memory_map);
+
+        Ok(core)
+    }
+}
+
+/// The core of [`Core::from_dump`].
+struct DumpCore {
+    dump: Rc<CoreDump>,
+}
+
+impl DumpCore {
+    fn program_counter(&self) -> Result<u32, error::Error> {
+        self.read_core_reg(self.registers().program_counter().into())
+    }
+}
+
+impl CoreInterface for DumpCore {
+    fn wait_for_core_halted(&self) -> Result<(), error::Error> {
+        Ok(())
+    }
+
+    fn core_halted(&self) -> Result<bool, error::Error> {
+        Ok(true)
+    }
+
+    fn status(&self) -> Result<CoreStatus, error::Error> {
+        Ok(CoreStatus::Halted(HaltReason::Unknown))
+    }
+
+    fn halt(&self) -> Result<CoreInformation, error::Error> {
+        Ok(CoreInformation {
+            pc: self.program_counter()?,
+        })
+    }
+
+    fn run(&self) -> Result<(), error::Error> {
+        Err(CoreDumpError::NotSupported("Running the core").into())
+    }
+
+    fn reset(&self) -> Result<(), error::Error> {
+        Err(CoreDumpError::NotSupported("Resetting the core").into())
+    }
+
+    fn reset_and_halt(&self) -> Result<CoreInformation, error::Error> {
+        Err(CoreDumpError::NotSupported("Resetting the core").into())
+    }
+
+    fn step(&self) -> Result<CoreInformation, error::Error> {
+        Err(CoreDumpError::NotSupported("Stepping the core").into())
+    }
+
+    fn read_core_reg(&self, address: CoreRegisterAddress) -> Result<u32, error::Error> {
+        Ok(self.dump.read_register(address)?)
+    }
+
+    fn write_core_reg(
+        &self,
+        _address: CoreRegisterAddress,
+        _value: u32,
+    ) -> Result<(), error::Error> {
+        Err(CoreDumpError::NotSupported("Writing a register").into())
+    }
+
+    fn get_available_breakpoint_units(&self) -> Result<u32, error::Error> {
+        Ok(0)
+    }
+
+    fn enable_breakpoints(&mut self, _state: bool) -> Result<(), error::Error> {
+        Err(CoreDumpError::NotSupported("Setting a breakpoint").into())
+    }
+
+    fn set_breakpoint(&self, _bp_unit_index: usize, _addr: u32) -> Result<(), error::Error> {
+        Err(CoreDumpError::NotSupported("Setting a breakpoint").into())
+    }
+
+    fn clear_breakpoint(&self, _unit_index: usize) -> Result<(), error::Error> {
+        Err(CoreDumpError::NotSupported("Setting a breakpoint").into())
+    }
+
+    fn registers(&self) -> &'static RegisterFile {
+        match self.dump.architecture {
+            Architecture::ARM => &ARM_REGISTER_FILE,
+            Architecture::RISCV => &RISCV_REGISTERS,
+        }
+    }
+
+    fn memory(&self) -> Memory {
+        Memory::new(DumpMemory {
+            dump: self.dump.clone(),
+        })
+    }
+
+    fn hw_breakpoints_enabled(&self) -> bool {
+        false
+    }
+
+    fn architecture(&self) -> Architecture {
+        self.dump.architecture
+    }
+}
+
+/// The memory of a [`DumpCore`].
+struct DumpMemory {
+    dump: Rc<CoreDump>,
+}
+
+impl MemoryInterface for DumpMemory {
+    fn read32(&mut self, address: u32) -> Result<u32, error::Error> {
+        let mut bytes = [0; 4];
+        self.dump.read_memory(address, &mut bytes)?;
+
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn read8(&mut self, address: u32) -> Result<u8, error::Error> {
+        let mut byte = [0];
+        self.dump.read_memory(address, &mut byte)?;
+
+        Ok(byte[0])
+    }
+
+    fn read_block32(&mut self, address: u32, data: &mut [u32]) -> Result<(), error::Error> {
+        for (offset, word) in data.iter_mut().enumerate() {
+            *word = self.read32(address + offset as u32 * 4)?;
+        }
+
+        Ok(())
+    }
+
+    fn read_block8(&mut self, address: u32, data: &mut [u8]) -> Result<(), error::Error> {
+        Ok(self.dump.read_memory(address, data)?)
+    }
+
+    fn write32(&mut self, _address: u32, _data: u32) -> Result<(), error::Error> {
+        Err(CoreDumpError::NotSupported("Writing memory").into())
+    }
+
+    fn write16(&mut self, _address: u32, _data: u16) -> Result<(), error::Error> {
+        Err(CoreDumpError::NotSupported("Writing memory").into())
+    }
+
+    fn write8(&mut self, _address: u32, _data: u8) -> Result<(), error::Error> {
+        Err(CoreDumpError::NotSupported("Writing memory").into())
+    }
+
+    fn write_block32(&mut self, _address: u32, _data: &[u32]) -> Result<(), error::Error> {
+        Err(CoreDumpError::NotSupported("Writing memory").into())
+    }
+
+    fn write_block8(&mut self, _address: u32, _data: &[u8]) -> Result<(), error::Error> {
+        Err(CoreDumpError::NotSupported("Writing memory").into())
+    }
+}
+
+/// The register addresses in the order of `elf_gregset_t`,
+/// `None` for slots which are not read from the core.
+fn register_addresses(architecture: Architecture) -> Vec<Option<CoreRegisterAddress>> {
+    match architecture {
+        Architecture::ARM => {
+            let mut addresses: Vec<_> = (0..16).map(|n| Some(CoreRegisterAddress(n))).collect();
+            addresses.push(Some(CoreRegisterAddress(ARM_XPSR)));
+            // orig_r0 is only used by Linux.
+            addresses.push(None);
+
+            debug_assert_eq!(addresses.len(), ARM_REGISTER_COUNT);
+            addresses
+        }
+        Architecture::RISCV => {
+            let mut addresses = vec![Some(CoreRegisterAddress(RISCV_DPC))];
+            addresses.extend((1..32).map(|n| Some(CoreRegisterAddress(0x1000 + n))));
+
+            debug_assert_eq!(addresses.len(), RISCV_REGISTER_COUNT);
+            addresses
+        }
+    }
+}
+
+/// The RAM regions of a memory map, which are dumped by default.
+pub(crate) fn ram_ranges(memory_map: &[MemoryRegion]) -> Vec<Range<u32>> {
+    memory_map
+        .iter()
+        .filter_map(|region| match region {
+            MemoryRegion::Ram(ram) => Some(ram.range.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn write_program_header(
+    elf: &mut Vec<u8>,
+    kind: u32,
+    offset: usize,
+    address: u32,
+    size: usize,
+    flags: u32,
+    align: u32,
+) {
+    put_u32(elf, kind);
+    put_u32(elf, offset as u32);
+    // Virtual and physical address
+    put_u32(elf, address);
+    put_u32(elf, address);
+    // File and memory size
+    put_u32(elf, size as u32);
+    put_u32(elf, size as u32);
+    put_u32(elf, flags);
+    put_u32(elf, align);
+}
+
+fn put_u16(buffer: &mut Vec<u8>, value: u16) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buffer: &mut Vec<u8>, value: u32) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Architecture, CoreDump, CoreDumpError, MemorySegment, ARM_REGISTER_COUNT, ELF_HEADER_SIZE,
+        PROGRAM_HEADER_SIZE,
+    };
+    use crate::{Core, CoreRegisterAddress};
+    use goblin::elf::{program_header::PT_LOAD, program_header::PT_NOTE, Elf};
+
+    fn arm_dump() -> CoreDump {
+        CoreDump {
+            architecture: Architecture::ARM,
+            registers: (0..ARM_REGISTER_COUNT as u32).collect(),
+            segments: vec![
+                MemorySegment {
+                    address: 0x2000_0000,
+                    data: vec![0xaa; 16],
+                },
+                MemorySegment {
+                    address: 0x1000_0000,
+                    data: vec![0x55; 8],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn elf_core_file_layout() {
+        let elf = arm_dump().to_elf();
+        let parsed = Elf::parse(&elf).unwrap();
+
+        assert_eq!(parsed.header.e_type, goblin::elf::header::ET_CORE);
+        assert_eq!(parsed.header.e_machine, goblin::elf::header::EM_ARM);
+        assert!(!parsed.is_64);
+
+        let headers = &parsed.program_headers;
+        assert_eq!(headers.len(), 3);
+        assert_eq!(headers[0].p_type, PT_NOTE);
+        assert_eq!(headers[1].p_type, PT_LOAD);
+        assert_eq!(headers[1].p_vaddr, 0x2000_0000);
+        assert_eq!(headers[2].p_vaddr, 0x1000_0000);
+
+        let segment = headers[2].p_offset as usize;
+        assert_eq!(&elf[segment..segment + 8], &[0x55; 8]);
+    }
+
+    #[test]
+    fn prstatus_note_holds_registers() {
+        let elf = arm_dump().to_elf();
+        let parsed = Elf::parse(&elf).unwrap();
+
+        let note = parsed
+            .iter_note_headers(&elf)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(note.name, "CORE");
+        assert_eq!(note.n_type, goblin::elf::note::NT_PRSTATUS);
+        // The Linux layout of elf_prstatus on 32 bit ARM.
+        assert_eq!(note.desc.len(), 148);
+        // pc is r15
+        assert_eq!(&note.desc[72 + 15 * 4..72 + 16 * 4], &15u32.to_le_bytes());
+    }
+
+    #[test]
+    fn parse_written_dump() {
+        let dump = arm_dump();
+
+        assert_eq!(CoreDump::from_elf(&dump.to_elf()).unwrap(), dump);
+    }
+
+    #[test]
+    fn segment_outside_of_file_is_rejected() {
+        let mut elf = arm_dump().to_elf();
+
+        // p_offset and p_filesz of the first PT_LOAD header.
+        let header = ELF_HEADER_SIZE + PROGRAM_HEADER_SIZE;
+        elf[header + 4..header + 8].copy_from_slice(&0xffff_fff0u32.to_le_bytes());
+        elf[header + 16..header + 20].copy_from_slice(&0xffff_fff0u32.to_le_bytes());
+
+        assert!(matches!(
+            CoreDump::from_elf(&elf),
+            Err(CoreDumpError::Truncated)
+        ));
+    }
+
+    #[test]
+    fn core_from_dump() {
+        let core = Core::from_dump(arm_dump()).unwrap();
+
+        assert!(core.core_halted().unwrap());
+        assert_eq!(core.halt().unwrap().pc, 15);
+        assert_eq!(core.read_core_reg(CoreRegisterAddress(13)).unwrap(), 13);
+        assert_eq!(core.read_word_32(0x2000_0004).unwrap(), 0xaaaa_aaaa);
+
+        // Reads outside of the dumped memory, and changes to the core fail.
+        assert!(core.read_word_32(0x2000_000e).is_err());
+        assert!(core.run().is_err());
+    }
+
+    #[test]
+    fn segment_beyond_address_space_is_rejected() {
+        let mut dump = arm_dump();
+        dump.segments.push(MemorySegment {
+            address: 0xffff_fffc,
+            data: vec![0; 8],
+        });
+
+        assert!(matches!(
+            Core::from_dump(dump),
+            Err(CoreDumpError::SegmentOutOfRange(0xffff_fffc))
+        ));
+    }
+}
@@ -1,18 +1,27 @@
+mod breakpoints;
+mod call;
 pub(crate) mod communication_interface;
+mod dump;
 
+pub use breakpoints::{
+    BreakpointCondition, BreakpointOptions, BreakpointStatistics, Comparison, ManagedBreakpoint,
+};
+pub use call::CallError;
 pub use communication_interface::CommunicationInterface;
+pub use dump::{CoreDump, CoreDumpError, MemorySegment};
 
-use crate::config::TargetSelector;
+use crate::config::{MemoryRegion, TargetSelector};
 use crate::error;
 use crate::{
     architecture::{
-        arm::{memory::ADIMemoryInterface, ArmCommunicationInterface},
+        arm::{memory::ADIMemoryInterface, sequences::DebugSequence, ArmCommunicationInterface},
         riscv::{communication_interface::RiscvCommunicationInterface, Riscv32},
     },
     Error, MemoryInterface,
 };
 use crate::{DebugProbeError, Memory, Probe};
-use std::{cell::RefCell, rc::Rc};
+use breakpoints::{BreakpointManager, SoftwareBreakpoint};
+use std::{cell::RefCell, convert::TryFrom, ops::Range, rc::Rc, sync::Arc, time::Duration};
 
 pub trait CoreRegister: Clone + From<u32> + Into<u32> + Sized + std::fmt::Debug {
     const ADDRESS: u32;
@@ -38,6 +47,75 @@ pub struct CoreInformation {
     pub pc: u32,
 }
 
+/// The reason why a core is halted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HaltReason {
+    /// A breakpoint instruction or a hardware breakpoint was hit.
+    Breakpoint,
+    /// A watchpoint was hit. The address is the one the watchpoint is set to,
+    /// if the watchpoint which halted the core could be found.
+    Watchpoint {
+        address: Option<u32>,
+        kind: WatchKind,
+    },
+    /// A single instruction was stepped.
+    Step,
+    /// The debugger or an external signal requested the halt.
+    Request,
+    /// The core was halted on reset.
+    VectorCatch,
+    /// The core was halted on entry to an exception handler.
+    Exception,
+    /// The reason is not known.
+    Unknown,
+}
+
+/// The kind of data access a watchpoint halts on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    /// Reads and writes, or an unknown kind of access.
+    Access,
+}
+
+impl std::fmt::Display for WatchKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            WatchKind::Read => "read",
+            WatchKind::Write => "write",
+            WatchKind::Access => "access",
+        })
+    }
+}
+
+impl std::fmt::Display for HaltReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let description = match self {
+            HaltReason::Breakpoint => "breakpoint",
+            HaltReason::Watchpoint {
+                address: Some(address),
+                kind,
+            } => return write!(f, "{} watchpoint at {:#010x}", kind, address),
+            HaltReason::Watchpoint { address: None, .. } => "watchpoint",
+            HaltReason::Step => "step",
+            HaltReason::Request => "halt request",
+            HaltReason::VectorCatch => "vector catch",
+            HaltReason::Exception => "exception",
+            HaltReason::Unknown => "unknown reason",
+        };
+
+        f.write_str(description)
+    }
+}
+
+/// The state of a core.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoreStatus {
+    Running,
+    Halted(HaltReason),
+}
+
 #[derive(Debug, Clone)]
 pub struct RegisterDescription {
     pub(crate) name: &'static str,
@@ -67,6 +145,10 @@ impl From<&RegisterDescription> for CoreRegisterAddress {
 pub(crate) enum RegisterKind {
     General,
     PC,
+    /// A control and status register.
+    Control,
+    /// A floating point register, only available if the core has an FPU.
+    Fpu,
 }
 
 /// Register description for a core.
@@ -83,6 +165,11 @@ pub struct RegisterFile {
 
     pub(crate) argument_registers: &'static [RegisterDescription],
     pub(crate) result_registers: &'static [RegisterDescription],
+
+    pub(crate) control_registers: &'static [RegisterDescription],
+
+    /// Only available if [`CoreInterface::fpu_present`] is true.
+    pub(crate) fpu_registers: &'static [RegisterDescription],
 }
 
 impl RegisterFile {
@@ -125,6 +212,16 @@ impl RegisterFile {
     pub fn get_platform_register(&self, index: usize) -> Option<&RegisterDescription> {
         self.platform_registers.get(index)
     }
+
+    /// The control and status registers of the core.
+    pub fn control_registers(&self) -> impl Iterator<Item = &RegisterDescription> {
+        self.control_registers.iter()
+    }
+
+    /// The floating point registers, which are only available if the core has an FPU.
+    pub fn fpu_registers(&self) -> impl Iterator<Item = &RegisterDescription> {
+        self.fpu_registers.iter()
+    }
 }
 
 pub trait CoreInterface {
@@ -140,6 +237,9 @@ pub trait CoreInterface {
     /// [`CoreError::Timeout`]: ../probe/debug_probe/enum.CoreError.html#variant.Timeout
     fn core_halted(&self) -> Result<bool, error::Error>;
 
+    /// Checks if the core is running, or why it is halted.
+    fn status(&self) -> Result<CoreStatus, error::Error>;
+
     /// Try to halt the core. This function ensures the core is actually halted, and
     /// returns a [`CoreError::Timeout`] otherwise.
     ///
@@ -167,6 +267,24 @@ pub trait CoreInterface {
 
     fn write_core_reg(&self, address: CoreRegisterAddress, value: u32) -> Result<(), error::Error>;
 
+    /// Reads a core register with its full width, which is 64 bits on RV64 cores.
+    fn read_core_reg_64(&self, address: CoreRegisterAddress) -> Result<u64, error::Error> {
+        self.read_core_reg(address).map(u64::from)
+    }
+
+    /// Writes a core register with its full width, which is 64 bits on RV64 cores.
+    ///
+    /// Cores with 32 bit registers return an error if the value does not fit.
+    fn write_core_reg_64(
+        &self,
+        address: CoreRegisterAddress,
+        value: u64,
+    ) -> Result<(), error::Error> {
+        let value = u32::try_from(value).map_err(|_| error::Error::RegisterValueTooLarge(value))?;
+
+        self.write_core_reg(address, value)
+    }
+
     fn get_available_breakpoint_units(&self) -> Result<u32, error::Error>;
 
     fn enable_breakpoints(&mut self, state: bool) -> Result<(), error::Error>;
@@ -177,6 +295,21 @@ pub trait CoreInterface {
 
     fn registers(&self) -> &'static RegisterFile;
 
+    /// Checks if the floating point registers of the register file are available.
+    fn fpu_present(&self) -> Result<bool, error::Error> {
+        Ok(false)
+    }
+
+    /// The width of the general purpose registers in bits, which is 64 on RV64 cores.
+    fn register_bits(&self) -> Result<u32, error::Error> {
+        Ok(32)
+    }
+
+    /// The width of the floating point registers in bits, if the FPU is present.
+    fn fpu_register_bits(&self) -> Result<u32, error::Error> {
+        Ok(32)
+    }
+
     fn memory(&self) -> Memory;
     fn hw_breakpoints_enabled(&self) -> bool;
 
@@ -202,6 +335,9 @@ impl MemoryInterface for Core {
     fn write32(&mut self, addr: u32, data: u32) -> Result<(), Error> {
         self.memory().write32(addr, data)
     }
+    fn write16(&mut self, addr: u32, data: u16) -> Result<(), Error> {
+        self.memory().write16(addr, data)
+    }
     fn write8(&mut self, addr: u32, data: u8) -> Result<(), Error> {
         self.memory().write8(addr, data)
     }
@@ -312,6 +448,11 @@ impl CoreType {
 pub struct Core {
     inner: Rc<RefCell<dyn CoreInterface>>,
     breakpoints: Vec<Breakpoint>,
+    software_breakpoints: Vec<SoftwareBreakpoint>,
+    breakpoint_manager: RefCell<BreakpointManager>,
+    /// Used to find out if a software breakpoint can be set at an address.
+    memory_map: Vec<MemoryRegion>,
+    debug_sequence: Option<Arc<dyn DebugSequence>>,
 }
 
 impl Core {
@@ -319,9 +460,32 @@ impl Core {
         Self {
             inner: Rc::new(RefCell::new(core)),
             breakpoints: Vec::new(),
+            software_breakpoints: Vec::new(),
+            breakpoint_manager: RefCell::new(BreakpointManager::default()),
+            memory_map: Vec::new(),
+            debug_sequence: None,
         }
     }
 
+    /// Sets the memory map of the target, software breakpoints can only be set in its RAM regions.
+    pub(crate) fn set_memory_map(&mut self, memory_map: Vec<MemoryRegion>) {
+        self.memory_map = memory_map;
+    }
+
+    /// Use the given debug sequence for this core.
+    ///
+    /// This runs the `DebugCoreStart` hook of the sequence, and the sequence is
+    /// used for all following resets of the core.
+    pub(crate) fn set_debug_sequence(
+        &mut self,
+        sequence: Arc<dyn DebugSequence>,
+    ) -> Result<(), error::Error> {
+        sequence.debug_core_start(&self.memory())?;
+        self.debug_sequence = Some(sequence);
+
+        Ok(())
+    }
+
     pub fn auto_attach(target: impl Into<TargetSelector>) -> Result<Core, error::Error> {
         // Get a list of all available debug probes.
         let probes = Probe::list_all();
@@ -352,15 +516,29 @@ impl Core {
         self.inner.borrow().core_halted()
     }
 
+    /// Checks if the core is running, or why it is halted.
+    pub fn status(&self) -> Result<CoreStatus, error::Error> {
+        self.inner.borrow().status()
+    }
+
     /// Try to halt the core. This function ensures the core is actually halted, and
     /// returns a [`CoreError::Timeout`] otherwise.
     ///
     /// [`CoreError::Timeout`]: ../probe/debug_probe/enum.CoreError.html#variant.Timeout
     pub fn halt(&self) -> Result<CoreInformation, error::Error> {
+        // A halt requested by the user is not a breakpoint hit.
+        self.breakpoint_manager.borrow_mut().halt_checked = true;
+
         self.inner.borrow().halt()
     }
 
+    /// Resumes the core. If it is halted at a breakpoint, the instruction at the
+    /// breakpoint is executed first, so the core does not halt at it again at once.
     pub fn run(&self) -> Result<(), error::Error> {
+        self.step_over_breakpoint()?;
+
+        self.breakpoint_manager.borrow_mut().halt_checked = false;
+
         self.inner.borrow().run()
     }
 
@@ -369,7 +547,10 @@ impl Core {
     ///
     /// [`reset_and_halt`]: trait.Core.html#tymethod.reset_and_halt
     pub fn reset(&self) -> Result<(), error::Error> {
-        self.inner.borrow().reset()
+        match &self.debug_sequence {
+            Some(sequence) => sequence.reset_system(&*self.inner.borrow()),
+            None => self.inner.borrow().reset(),
+        }
     }
 
     /// Reset the core, and then immediately halt. To continue execution after
@@ -377,12 +558,27 @@ impl Core {
     ///
     /// [`reset`]: trait.Core.html#tymethod.reset
     pub fn reset_and_halt(&self) -> Result<CoreInformation, error::Error> {
-        self.inner.borrow().reset_and_halt()
+        self.breakpoint_manager.borrow_mut().halt_checked = true;
+
+        let info = self.inner.borrow().reset_and_halt()?;
+
+        if let Some(sequence) = &self.debug_sequence {
+            sequence.debug_core_start(&self.memory())?;
+        }
+
+        Ok(info)
     }
 
     /// Steps one instruction and then enters halted state again.
+    ///
+    /// If the core is halted at a breakpoint, the instruction at the breakpoint is executed.
     pub fn step(&self) -> Result<CoreInformation, error::Error> {
-        self.inner.borrow().step()
+        self.breakpoint_manager.borrow_mut().halt_checked = true;
+
+        match self.step_over_breakpoint()? {
+            Some(info) => Ok(info),
+            None => self.inner.borrow().step(),
+        }
     }
 
     pub fn read_core_reg(
@@ -392,6 +588,21 @@ impl Core {
         self.inner.borrow().read_core_reg(address.into())
     }
 
+    pub fn read_core_reg_64(
+        &self,
+        address: impl Into<CoreRegisterAddress>,
+    ) -> Result<u64, error::Error> {
+        self.inner.borrow().read_core_reg_64(address.into())
+    }
+
+    pub fn write_core_reg_64(
+        &self,
+        address: impl Into<CoreRegisterAddress>,
+        value: u64,
+    ) -> Result<(), error::Error> {
+        self.inner.borrow().write_core_reg_64(address.into(), value)
+    }
+
     pub fn write_core_reg(
         &self,
         address: CoreRegisterAddress,
@@ -404,6 +615,22 @@ impl Core {
         self.inner.borrow().get_available_breakpoint_units()
     }
 
+    /// The number of breakpoint units which are not used.
+    pub(crate) fn free_breakpoint_units(&self) -> Result<usize, error::Error> {
+        let units = self.get_available_breakpoint_units()? as usize;
+
+        Ok(units.saturating_sub(self.breakpoints.len()))
+    }
+
+    /// Checks if a hardware or software breakpoint is set at `address`.
+    pub(crate) fn has_breakpoint(&self, address: u32) -> bool {
+        self.breakpoints.iter().any(|bp| bp.address == address)
+            || self
+                .software_breakpoints
+                .iter()
+                .any(|bp| bp.address == address)
+    }
+
     fn enable_breakpoints(&self, state: bool) -> Result<(), error::Error> {
         self.inner.borrow_mut().enable_breakpoints(state)
     }
@@ -412,6 +639,60 @@ impl Core {
         self.inner.borrow().registers()
     }
 
+    /// Checks if the core has an FPU, so its floating point registers can be accessed.
+    pub fn fpu_present(&self) -> Result<bool, error::Error> {
+        self.inner.borrow().fpu_present()
+    }
+
+    /// The width of the general purpose registers in bits, which is 64 on RV64 cores.
+    ///
+    /// The width of a RISC-V hart can only be detected while it is halted.
+    pub fn register_bits(&self) -> Result<u32, error::Error> {
+        self.inner.borrow().register_bits()
+    }
+
+    /// The width of the floating point registers in bits, which is 64 on RV64
+    /// cores with the D extension.
+    pub fn fpu_register_bits(&self) -> Result<u32, error::Error> {
+        self.inner.borrow().fpu_register_bits()
+    }
+
+    pub fn architecture(&self) -> Architecture {
+        self.inner.borrow().architecture()
+    }
+
+    /// Dumps the registers and all RAM regions of the target memory map.
+    ///
+    /// The core has to be halted.
+    pub fn dump(&self) -> Result<CoreDump, error::Error> {
+        self.dump_ranges(&dump::ram_ranges(&self.memory_map))
+    }
+
+    /// Dumps the registers and the memory in `ranges`.
+    ///
+    /// The core has to be halted.
+    pub fn dump_ranges(&self, ranges: &[Range<u32>]) -> Result<CoreDump, error::Error> {
+        CoreDump::read(self, ranges)
+    }
+
+    /// Calls the function at `address` with the arguments, and returns the values
+    /// of the result registers, `r0` and `r1` on ARM or `a0` and `a1` on RISC-V.
+    ///
+    /// The stack of the call starts at `stack`, or below the current stack pointer
+    /// if it is `None`. The function returns to a breakpoint instruction on the
+    /// stack, so the stack has to be in RAM from which code can be executed.
+    /// The core has to be halted, and its registers are restored after the call.
+    /// The core is halted again if the function does not return within `timeout`.
+    pub fn call_function(
+        &self,
+        address: u32,
+        arguments: &[u32],
+        stack: Option<u32>,
+        timeout: Duration,
+    ) -> Result<Vec<u32>, error::Error> {
+        call::call_function(self, address, arguments, stack, timeout)
+    }
+
     pub fn memory(&self) -> Memory {
         self.inner.borrow().memory()
     }
@@ -503,6 +784,252 @@ impl Core {
         }
     }
 
+    /// Sets a breakpoint, using a breakpoint unit if one is free.
+    ///
+    /// If all units are used, a software breakpoint is set if the address is in RAM.
+    pub fn set_breakpoint(&mut self, address: u32) -> Result<(), error::Error> {
+        if self.breakpoints.len() < self.get_available_breakpoint_units()? as usize {
+            self.set_hw_breakpoint(address)
+        } else {
+            self.set_sw_breakpoint(address)
+        }
+    }
+
+    /// Clears a breakpoint which was set by [`set_breakpoint`](Self::set_breakpoint).
+    pub fn clear_breakpoint(&mut self, address: u32) -> Result<(), error::Error> {
+        if self
+            .software_breakpoints
+            .iter()
+            .any(|bp| bp.address == address)
+        {
+            self.clear_sw_breakpoint(address)
+        } else {
+            self.clear_hw_breakpoint(address)
+        }
+    }
+
+    /// Set a software breakpoint
+    ///
+    /// The instruction at `address` is replaced by a breakpoint instruction,
+    /// so this only works for code in RAM. The original instruction is
+    /// restored when the breakpoint is cleared.
+    pub fn set_sw_breakpoint(&mut self, address: u32) -> Result<(), error::Error> {
+        log::debug!("Trying to set SW breakpoint at address {:#08x}", address);
+
+        if self
+            .software_breakpoints
+            .iter()
+            .any(|bp| bp.address == address)
+        {
+            return Ok(());
+        }
+
+        let in_ram = self.memory_map.iter().any(|region| match region {
+            MemoryRegion::Ram(ram) => ram.range.contains(&address),
+            _ => false,
+        });
+
+        if !in_ram {
+            return Err(error::Error::BreakpointNotInRam(address));
+        }
+
+        let mut code = [0; 4];
+        self.read_8(address, &mut code)?;
+
+        let breakpoint = SoftwareBreakpoint::new(self.architecture(), address, code);
+        self.write_8(address, breakpoint.patch)?;
+
+        self.software_breakpoints.push(breakpoint);
+
+        Ok(())
+    }
+
+    /// Checks if a software breakpoint is set at `address`.
+    pub fn has_sw_breakpoint(&self, address: u32) -> bool {
+        self.software_breakpoints
+            .iter()
+            .any(|bp| bp.address == address)
+    }
+
+    /// Clears a software breakpoint, and restores the original instruction.
+    pub fn clear_sw_breakpoint(&mut self, address: u32) -> Result<(), error::Error> {
+        let bp_position = self
+            .software_breakpoints
+            .iter()
+            .position(|bp| bp.address == address)
+            .ok_or(error::Error::BreakpointNotFound(address))?;
+
+        self.write_8(address, &self.software_breakpoints[bp_position].original)?;
+
+        self.software_breakpoints.swap_remove(bp_position);
+
+        Ok(())
+    }
+
+    /// Adds a breakpoint, which only keeps the core halted if its options allow it.
+    ///
+    /// The breakpoint is set with [`set_breakpoint`](Self::set_breakpoint). The options
+    /// are checked by [`poll_halted`](Self::poll_halted) when the core halts at it.
+    pub fn add_breakpoint(
+        &mut self,
+        address: u32,
+        options: BreakpointOptions,
+    ) -> Result<BreakpointId, error::Error> {
+        if self.breakpoint_at(address).is_some() {
+            return Err(error::Error::BreakpointExists(address));
+        }
+
+        self.set_breakpoint(address)?;
+
+        let mut manager = self.breakpoint_manager.borrow_mut();
+        let id = manager.next_id();
+        manager
+            .breakpoints
+            .push(ManagedBreakpoint::new(id, address, options));
+
+        Ok(id)
+    }
+
+    /// Removes a breakpoint which was added by [`add_breakpoint`](Self::add_breakpoint).
+    pub fn remove_breakpoint(&mut self, id: BreakpointId) -> Result<(), error::Error> {
+        let position = self
+            .breakpoint_manager
+            .borrow()
+            .breakpoints
+            .iter()
+            .position(|bp| bp.id() == id)
+            .ok_or(error::Error::UnknownBreakpoint(id))?;
+
+        let address = self.breakpoint_manager.borrow().breakpoints[position].address();
+        self.clear_breakpoint(address)?;
+
+        self.breakpoint_manager
+            .borrow_mut()
+            .breakpoints
+            .remove(position);
+
+        Ok(())
+    }
+
+    /// The id of the breakpoint at `address`, if one was added.
+    pub fn breakpoint_at(&self, address: u32) -> Option<BreakpointId> {
+        self.breakpoint_manager
+            .borrow()
+            .breakpoints
+            .iter()
+            .find(|bp| bp.address() == address)
+            .map(|bp| bp.id())
+    }
+
+    /// The breakpoints which were added by [`add_breakpoint`](Self::add_breakpoint),
+    /// with their hit statistics.
+    pub fn managed_breakpoints(&self) -> Vec<ManagedBreakpoint> {
+        self.breakpoint_manager.borrow().breakpoints.clone()
+    }
+
+    /// Checks if the core is halted, and handles a halt at a breakpoint.
+    ///
+    /// If the core halted at a breakpoint whose condition is not met, or whose hit
+    /// is ignored, it is resumed and `false` is returned. Each halt is only checked
+    /// once, so this can be polled while waiting for the core to halt.
+    pub fn poll_halted(&self) -> Result<bool, error::Error> {
+        if !self.core_halted()? {
+            return Ok(false);
+        }
+
+        if self.breakpoint_manager.borrow().halt_checked {
+            return Ok(true);
+        }
+        self.breakpoint_manager.borrow_mut().halt_checked = true;
+
+        let pc = self.read_core_reg(self.registers().program_counter())?;
+
+        let (position, condition) = match self
+            .breakpoint_manager
+            .borrow()
+            .breakpoints
+            .iter()
+            .position(|bp| bp.address() == pc)
+        {
+            Some(position) => (
+                position,
+                self.breakpoint_manager.borrow().breakpoints[position]
+                    .options()
+                    .condition
+                    .clone(),
+            ),
+            None => return Ok(true),
+        };
+
+        let stay_halted = match condition {
+            Some(condition) if !self.condition_met(&condition)? => false,
+            _ => self.breakpoint_manager.borrow_mut().breakpoints[position].hit(),
+        };
+
+        if !stay_halted {
+            log::debug!("Resuming from breakpoint at {:#010x}", pc);
+            self.run()?;
+        }
+
+        Ok(stay_halted)
+    }
+
+    fn condition_met(&self, condition: &BreakpointCondition) -> Result<bool, error::Error> {
+        Ok(match *condition {
+            BreakpointCondition::Register {
+                register,
+                comparison,
+                value,
+            } => comparison.compare(self.read_core_reg(register)?, value),
+            BreakpointCondition::Memory {
+                address,
+                comparison,
+                value,
+            } => comparison.compare(self.read_word_32(address)?, value),
+        })
+    }
+
+    /// If the core is halted at a breakpoint, the instruction at it is executed.
+    ///
+    /// For a software breakpoint the original instruction is restored and the
+    /// breakpoint instruction is written again afterwards. The unit of a hardware
+    /// breakpoint is disabled while stepping, because it halts the core before the
+    /// instruction is executed.
+    ///
+    /// Returns `None` if the core is not halted at a breakpoint.
+    fn step_over_breakpoint(&self) -> Result<Option<CoreInformation>, error::Error> {
+        if (self.breakpoints.is_empty() && self.software_breakpoints.is_empty())
+            || !self.core_halted()?
+        {
+            return Ok(None);
+        }
+
+        let pc = self.read_core_reg(self.registers().program_counter())?;
+
+        if let Some(breakpoint) = self.software_breakpoints.iter().find(|bp| bp.address == pc) {
+            log::debug!("Stepping over SW breakpoint at address {:#08x}", pc);
+
+            self.write_8(pc, &breakpoint.original)?;
+            let info = self.inner.borrow().step();
+            self.write_8(pc, breakpoint.patch)?;
+
+            return info.map(Some);
+        }
+
+        if let Some(breakpoint) = self.breakpoints.iter().find(|bp| bp.address == pc) {
+            log::debug!("Stepping over HW breakpoint at address {:#08x}", pc);
+
+            let inner = self.inner.borrow();
+            inner.clear_breakpoint(breakpoint.register_hw)?;
+            let info = inner.step();
+            inner.set_breakpoint(breakpoint.register_hw, pc)?;
+
+            return info.map(Some);
+        }
+
+        Ok(None)
+    }
+
     fn find_free_breakpoint_unit(&self) -> usize {
         let mut used_bp: Vec<_> = self.breakpoints.iter().map(|bp| bp.register_hw).collect();
         used_bp.sort();
@@ -545,13 +1072,58 @@ impl BreakpointId {
     }
 }
 
+impl std::fmt::Display for BreakpointId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 #[derive(Clone)]
 pub struct Breakpoint {
     address: u32,
     register_hw: usize,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Architecture {
     ARM,
     RISCV,
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::MockCore;
+    use crate::{BreakpointOptions, Memory};
+
+    #[test]
+    fn ignored_hits_resume_past_a_hardware_breakpoint() {
+        // A loop from 0x108 back to 0x100, which moves the stack pointer on each pass.
+        let (mut core, state) = MockCore::start(&[(0x108, (0x100, -4))], Memory::new_dummy());
+
+        let options = BreakpointOptions {
+            ignore_count: 2,
+            ..Default::default()
+        };
+        core.add_breakpoint(0x108, options).unwrap();
+
+        core.run().unwrap();
+        assert!(!core.poll_halted().unwrap());
+        assert!(!core.poll_halted().unwrap());
+        assert!(core.poll_halted().unwrap());
+
+        // The core passed the breakpoint twice, instead of halting at it again at once.
+        let state = state.borrow();
+        assert_eq!(state.pc(), 0x108);
+        assert_eq!(state.sp(), 0x2000_0100 - 8);
+        assert_eq!(state.units[0], Some(0x108));
+    }
+
+    #[test]
+    fn step_from_a_hardware_breakpoint() {
+        let (mut core, state) = MockCore::start(&[], Memory::new_dummy());
+        core.set_hw_breakpoint(0x100).unwrap();
+
+        assert_eq!(core.step().unwrap().pc, 0x104);
+        assert_eq!(state.borrow().units[0], Some(0x100));
+    }
+}
@@ -0,0 +1,513 @@
+//! Evaluation of DWARF expressions, which describe where variables are, the
+//! frame base of a function and how to unwind a stack frame.
+
+use super::{unit_entry, DebugError, DebugInfo, Registers, R};
+use crate::core::{Architecture, Core, CoreRegisterAddress};
+
+use gimli::{EvaluationResult, Location, Piece};
+
+/// Expressions can contain loops, so the number of operations is limited.
+const MAX_ITERATIONS: u32 = 10_000;
+
+/// The RISC-V thread pointer `tp`, which points to the thread local storage.
+const RISCV_THREAD_POINTER: u16 = 4;
+
+/// The registers and memory an expression is evaluated with.
+pub(crate) struct ExpressionContext<'a> {
+    pub debug_info: &'a DebugInfo,
+    /// The unit of the expression, which is needed for the base types and the
+    /// locations it refers to. Expressions of the call frame information do not
+    /// have one.
+    pub unit: Option<&'a gimli::Unit<R>>,
+    pub encoding: gimli::Encoding,
+    pub core: &'a Core,
+    /// The registers of the frame. Registers which are not known are read
+    /// from the core in the innermost frame.
+    pub registers: &'a Registers,
+    pub innermost: bool,
+    pub frame_base: Option<u64>,
+    pub cfa: Option<u64>,
+    /// The registers of the caller, which have the values the registers had
+    /// at the entry of the function.
+    pub entry_registers: Option<&'a Registers>,
+}
+
+impl<'a> ExpressionContext<'a> {
+    /// Evaluates an expression into the pieces of a location.
+    pub fn evaluate(
+        &self,
+        expression: gimli::Expression<R>,
+    ) -> Result<Vec<Piece<R, usize>>, DebugError> {
+        self.evaluate_with(expression, None)
+    }
+
+    /// Evaluates an expression which results in an address, like a frame
+    /// base or a CFA. `initial` is pushed on the stack before the evaluation.
+    pub fn evaluate_address(
+        &self,
+        expression: gimli::Expression<R>,
+        initial: Option<u64>,
+    ) -> Result<u64, DebugError> {
+        let pieces = self.evaluate_with(expression, initial)?;
+
+        match pieces.as_slice() {
+            [Piece {
+                location: Location::Address { address },
+                ..
+            }] => Ok(*address),
+            [Piece {
+                location: Location::Register { register },
+                ..
+            }] => self.register(register.0).map(u64::from),
+            [Piece {
+                location: Location::Value { value },
+                ..
+            }] => Ok(value.to_u64(self.address_mask())?),
+            _ => Err(DebugError::Unsupported(format!(
+                "Expected an address, but the expression resulted in {:?}",
+                pieces
+            ))),
+        }
+    }
+
+    fn evaluate_with(
+        &self,
+        expression: gimli::Expression<R>,
+        initial: Option<u64>,
+    ) -> Result<Vec<Piece<R, usize>>, DebugError> {
+        let mut evaluation = expression.evaluation(self.encoding);
+        evaluation.set_max_iterations(MAX_ITERATIONS);
+        if let Some(initial) = initial {
+            evaluation.set_initial_value(initial);
+        }
+
+        let mut result = evaluation.evaluate()?;
+
+        loop {
+            result = match result {
+                EvaluationResult::Complete => break,
+                EvaluationResult::RequiresMemory {
+                    address,
+                    size,
+                    space,
+                    base_type,
+                } => {
+                    if let Some(space) = space {
+                        return Err(DebugError::Unsupported(format!(
+                            "Memory access in address space {}",
+                            space
+                        )));
+                    }
+
+                    let mut buffer = [0u8; 8];
+                    let size = usize::from(size).min(buffer.len());
+                    self.core
+                        .memory()
+                        .read_block8(address as u32, &mut buffer[..size])?;
+
+                    let value = self.typed_value(base_type, u64::from_le_bytes(buffer))?;
+                    evaluation.resume_with_memory(value)?
+                }
+                EvaluationResult::RequiresRegister {
+                    register,
+                    base_type,
+                } => {
+                    let value = self.register(register.0)?;
+                    let value = self.typed_value(base_type, u64::from(value))?;
+                    evaluation.resume_with_register(value)?
+                }
+                EvaluationResult::RequiresFrameBase => {
+                    let frame_base = self.frame_base.ok_or_else(|| {
+                        DebugError::Unsupported("The function has no frame base".to_string())
+                    })?;
+                    evaluation.resume_with_frame_base(frame_base)?
+                }
+                EvaluationResult::RequiresCallFrameCfa => {
+                    let cfa = self.cfa.ok_or_else(|| {
+                        DebugError::Unsupported("The CFA of the frame is unknown".to_string())
+                    })?;
+                    evaluation.resume_with_call_frame_cfa(cfa)?
+                }
+                EvaluationResult::RequiresTls(offset) => {
+                    let thread_pointer = match self.core.architecture() {
+                        Architecture::RISCV => self.register(RISCV_THREAD_POINTER)?,
+                        Architecture::ARM => {
+                            return Err(DebugError::Unsupported(
+                                "Thread local storage on ARM cores".to_string(),
+                            ))
+                        }
+                    };
+                    evaluation.resume_with_tls(u64::from(thread_pointer).wrapping_add(offset))?
+                }
+                EvaluationResult::RequiresEntryValue(expression) => {
+                    let value = self.entry_value(expression)?;
+                    evaluation.resume_with_entry_value(gimli::Value::Generic(value))?
+                }
+                EvaluationResult::RequiresAtLocation(reference) => {
+                    let location = self.at_location(reference)?;
+                    evaluation.resume_with_at_location(location)?
+                }
+                EvaluationResult::RequiresRelocatedAddress(address) => {
+                    // Executables are not relocated.
+                    evaluation.resume_with_relocated_address(address)?
+                }
+                EvaluationResult::RequiresIndexedAddress { index, .. } => {
+                    let address = self.debug_info.dwarf.address(self.unit()?, index)?;
+                    evaluation.resume_with_indexed_address(address)?
+                }
+                EvaluationResult::RequiresBaseType(offset) => {
+                    let value_type = self.value_type(offset)?;
+                    evaluation.resume_with_base_type(value_type)?
+                }
+                EvaluationResult::RequiresParameterRef(_) => {
+                    return Err(DebugError::Unsupported(
+                        "Parameters of the caller (DW_OP_GNU_parameter_ref)".to_string(),
+                    ))
+                }
+            }
+        }
+
+        Ok(evaluation.result())
+    }
+
+    /// Reads a register by its DWARF register number.
+    pub fn register(&self, register: u16) -> Result<u32, DebugError> {
+        if let Some(value) = self.registers.get(register) {
+            return Ok(value);
+        }
+
+        // Only the innermost frame has the registers of the core.
+        if self.innermost {
+            if let Some(address) = dwarf_register(self.core, register) {
+                return Ok(self.core.read_core_reg(address)?);
+            }
+        }
+
+        Err(DebugError::UnknownRegister(register))
+    }
+
+    /// Evaluates the expression of `DW_OP_entry_value` with the registers at
+    /// the entry of the function. These are only known for the registers which
+    /// the function has to preserve, and which are restored by unwinding.
+    fn entry_value(&self, expression: gimli::Expression<R>) -> Result<u64, DebugError> {
+        let registers = self.entry_registers.ok_or_else(|| {
+            DebugError::Unsupported("The registers at the entry of the function".to_string())
+        })?;
+
+        let context = ExpressionContext {
+            registers,
+            innermost: false,
+            frame_base: None,
+            cfa: None,
+            entry_registers: None,
+            ..*self
+        };
+
+        let pieces = context.evaluate(expression)?;
+        match pieces.as_slice() {
+            [Piece {
+                location: Location::Register { register },
+                ..
+            }] => context.register(register.0).map(u64::from),
+            [Piece {
+                location: Location::Address { address },
+                ..
+            }] => Ok(*address),
+            [Piece {
+                location: Location::Value { value },
+                ..
+            }] => Ok(value.to_u64(self.address_mask())?),
+            _ => Err(DebugError::Unsupported(format!(
+                "Entry value with the location {:?}",
+                pieces
+            ))),
+        }
+    }
+
+    /// The location expression of the entry `DW_OP_call*` refers to.
+    fn at_location(&self, reference: gimli::DieReference<usize>) -> Result<R, DebugError> {
+        let unit = self.unit()?;
+
+        let offset = match reference {
+            gimli::DieReference::UnitRef(offset) => offset,
+            gimli::DieReference::DebugInfoRef(_) => {
+                return Err(DebugError::Unsupported(
+                    "A call of an entry in another unit".to_string(),
+                ))
+            }
+        };
+
+        let location = match unit_entry(unit, offset)? {
+            Some(entry) => entry.attr_value(gimli::DW_AT_location)?,
+            None => None,
+        };
+
+        match location {
+            Some(gimli::AttributeValue::Exprloc(expression)) => Ok(expression.0),
+            // An entry without a location does nothing.
+            _ => Ok(R::new(std::rc::Rc::from(&[][..]), gimli::LittleEndian)),
+        }
+    }
+
+    fn value_type(&self, offset: gimli::UnitOffset<usize>) -> Result<gimli::ValueType, DebugError> {
+        let entry = unit_entry(self.unit()?, offset)?
+            .ok_or_else(|| DebugError::Unsupported(format!("The base type at {:?}", offset)))?;
+
+        let encoding = match entry.attr_value(gimli::DW_AT_encoding)? {
+            Some(gimli::AttributeValue::Encoding(encoding)) => Some(encoding),
+            _ => None,
+        };
+        let byte_size = entry
+            .attr_value(gimli::DW_AT_byte_size)?
+            .and_then(|size| size.udata_value());
+
+        encoding
+            .zip(byte_size)
+            .and_then(|(encoding, byte_size)| gimli::ValueType::from_encoding(encoding, byte_size))
+            .ok_or_else(|| DebugError::Unsupported(format!("The base type at {:?}", offset)))
+    }
+
+    /// Converts a raw value to the base type at `offset`, or to a generic value
+    /// if the offset is 0.
+    fn typed_value(
+        &self,
+        offset: gimli::UnitOffset<usize>,
+        value: u64,
+    ) -> Result<gimli::Value, DebugError> {
+        if offset == gimli::UnitOffset(0) {
+            return Ok(gimli::Value::Generic(value & self.address_mask()));
+        }
+
+        Ok(gimli::Value::from_u64(self.value_type(offset)?, value)?)
+    }
+
+    fn unit(&self) -> Result<&'a gimli::Unit<R>, DebugError> {
+        self.unit.ok_or_else(|| {
+            DebugError::Unsupported("A reference to a unit in call frame information".to_string())
+        })
+    }
+
+    fn address_mask(&self) -> u64 {
+        u64::MAX >> (64 - 8 * u32::from(self.encoding.address_size))
+    }
+}
+
+/// Finds the core register of a DWARF register number. Only the general
+/// purpose registers are supported.
+pub(crate) fn dwarf_register(core: &Core, register: u16) -> Option<CoreRegisterAddress> {
+    match core.architecture() {
+        // r0 to r15
+        Architecture::ARM if register < 16 => Some(CoreRegisterAddress(register)),
+        // x0 to x31
+        Architecture::RISCV if register < 32 => Some(CoreRegisterAddress(0x1000 + register)),
+        _ => None,
+    }
+}
+
+/// The number of general purpose registers which have a DWARF register number.
+pub(crate) fn dwarf_register_count(architecture: Architecture) -> u16 {
+    match architecture {
+        Architecture::ARM => 16,
+        Architecture::RISCV => 32,
+    }
+}
+
+/// The DWARF register number of the stack pointer, which is `sp` (r13) on ARM
+/// and `sp` (x2) on RISC-V.
+pub(crate) fn dwarf_stack_pointer(architecture: Architecture) -> u16 {
+    match architecture {
+        Architecture::ARM => 13,
+        Architecture::RISCV => 2,
+    }
+}
+
+/// The DWARF register number of the return address, which is `lr` (r14) on
+/// ARM and `ra` (x1) on RISC-V.
+pub(crate) fn dwarf_return_address(architecture: Architecture) -> u16 {
+    match architecture {
+        Architecture::ARM => 14,
+        Architecture::RISCV => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{unwind_register, DebugError, DebugInfo, Registers, R};
+    use super::{dwarf_register, dwarf_return_address, dwarf_stack_pointer, ExpressionContext};
+    use crate::{Architecture, Core, CoreDump, CoreRegisterAddress, MemorySegment};
+    use gimli::{LittleEndian, Location};
+    use std::rc::Rc;
+
+    const STACK_POINTER: u32 = 0x2000_0010;
+
+    fn reader(bytes: &[u8]) -> R {
+        R::new(Rc::from(bytes), LittleEndian)
+    }
+
+    fn debug_info() -> DebugInfo {
+        DebugInfo {
+            dwarf: gimli::Dwarf::load(
+                |_| Ok::<_, gimli::Error>(reader(&[])),
+                |_| Ok::<_, gimli::Error>(reader(&[])),
+            )
+            .unwrap(),
+            frame_section: gimli::DebugFrame::from(reader(&[])),
+            address_size: 4,
+        }
+    }
+
+    /// An ARM core with r4 = 4, the stack pointer at `STACK_POINTER` and
+    /// 0x0800_1234 in the word above it.
+    fn core() -> Core {
+        let mut registers: Vec<u32> = (0..18).collect();
+        registers[13] = STACK_POINTER;
+
+        let mut data = vec![0; 32];
+        data[0x18..0x1c].copy_from_slice(&0x0800_1234u32.to_le_bytes());
+
+        Core::from_dump(CoreDump::new(
+            Architecture::ARM,
+            registers,
+            vec![MemorySegment {
+                address: 0x2000_0000,
+                data,
+            }],
+        ))
+        .unwrap()
+    }
+
+    fn context<'a>(
+        debug_info: &'a DebugInfo,
+        core: &'a Core,
+        registers: &'a Registers,
+    ) -> ExpressionContext<'a> {
+        ExpressionContext {
+            debug_info,
+            unit: None,
+            encoding: debug_info.frame_encoding(),
+            core,
+            registers,
+            innermost: true,
+            frame_base: None,
+            cfa: None,
+            entry_registers: None,
+        }
+    }
+
+    #[test]
+    fn register_and_memory() {
+        let (debug_info, core) = (debug_info(), core());
+        let registers = Registers::from_core(&core);
+        let context = context(&debug_info, &core, &registers);
+
+        // DW_OP_breg13 8, DW_OP_deref
+        let expression = gimli::Expression(reader(&[0x7d, 0x08, 0x06]));
+
+        assert_eq!(
+            context.evaluate_address(expression, None).unwrap(),
+            0x0800_1234
+        );
+    }
+
+    #[test]
+    fn missing_values_are_errors() {
+        let (debug_info, core) = (debug_info(), core());
+        let registers = Registers::from_core(&core);
+        let context = context(&debug_info, &core, &registers);
+
+        // DW_OP_call_frame_cfa
+        let cfa = gimli::Expression(reader(&[0x9c]));
+        assert!(matches!(
+            context.evaluate(cfa.clone()),
+            Err(DebugError::Unsupported(_))
+        ));
+
+        let context = ExpressionContext {
+            cfa: Some(0x2000_0020),
+            ..context
+        };
+        assert_eq!(context.evaluate_address(cfa, None).unwrap(), 0x2000_0020);
+
+        // DW_OP_fbreg 0
+        let frame_base = gimli::Expression(reader(&[0x91, 0x00]));
+        assert!(matches!(
+            context.evaluate(frame_base),
+            Err(DebugError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn entry_value() {
+        let (debug_info, core) = (debug_info(), core());
+        let registers = Registers::from_core(&core);
+
+        let mut entry_registers = Registers(vec![None; 16]);
+        entry_registers[4] = Some(0x1234);
+
+        let context = ExpressionContext {
+            entry_registers: Some(&entry_registers),
+            ..context(&debug_info, &core, &registers)
+        };
+
+        // DW_OP_entry_value(DW_OP_reg4), DW_OP_stack_value
+        let pieces = context
+            .evaluate(gimli::Expression(reader(&[0xa3, 0x01, 0x54, 0x9f])))
+            .unwrap();
+        assert_eq!(
+            pieces[0].location,
+            Location::Value {
+                value: gimli::Value::Generic(0x1234)
+            }
+        );
+
+        // r0 is not preserved by calls, so its entry value is not known.
+        let result = context.evaluate(gimli::Expression(reader(&[0xa3, 0x01, 0x50, 0x9f])));
+        assert!(matches!(result, Err(DebugError::UnknownRegister(0))));
+    }
+
+    #[test]
+    fn unwind_rules() {
+        let (debug_info, core) = (debug_info(), core());
+        let registers = Registers::from_core(&core);
+        let context = context(&debug_info, &core, &registers);
+        let cfa = u64::from(STACK_POINTER) + 0x10;
+
+        let saved = unwind_register(&context, 14, gimli::RegisterRule::Offset(-8), cfa);
+        assert_eq!(saved.unwrap(), Some(0x0800_1234));
+
+        let same = unwind_register(&context, 4, gimli::RegisterRule::SameValue, cfa);
+        assert_eq!(same.unwrap(), Some(4));
+
+        let architectural = unwind_register(&context, 4, gimli::RegisterRule::Architectural, cfa);
+        assert!(matches!(architectural, Err(DebugError::Unsupported(_))));
+    }
+
+    #[test]
+    fn stack_pointer_and_return_address_numbers() {
+        for architecture in [Architecture::ARM, Architecture::RISCV].iter().copied() {
+            let core = Core::from_dump(CoreDump::new(architecture, vec![0; 18], vec![])).unwrap();
+            let registers = core.registers();
+
+            assert_eq!(
+                dwarf_register(&core, dwarf_stack_pointer(architecture)).map(|address| address.0),
+                Some(CoreRegisterAddress::from(registers.stack_pointer()).0)
+            );
+            assert_eq!(
+                dwarf_register(&core, dwarf_return_address(architecture)).map(|address| address.0),
+                Some(CoreRegisterAddress::from(registers.return_address()).0)
+            );
+        }
+    }
+
+    #[test]
+    fn riscv_registers() {
+        // The program counter, then x1 to x31.
+        let dump = CoreDump::new(Architecture::RISCV, (0..32).collect(), vec![]);
+        let core = Core::from_dump(dump).unwrap();
+        let registers = Registers::from_core(&core);
+
+        assert_eq!(registers.len(), 32);
+        assert_eq!(registers.get(31), Some(31));
+        assert_eq!(registers.get(32), None);
+    }
+}
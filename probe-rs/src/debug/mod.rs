@@ -3,22 +3,30 @@
 //! The `debug` module contains various debug functionality, which can be
 //! used to implement a debugger based on `probe-rs`.
 
+mod expression;
+mod stepping;
 mod typ;
 mod variable;
 
 use crate::core::Core;
-use typ::Type;
-use variable::Variable;
+use expression::{
+    dwarf_register, dwarf_register_count, dwarf_return_address, dwarf_stack_pointer,
+    ExpressionContext,
+};
+pub use typ::{BaseType, Enumerator, Member, Type, Value, VariableValue, Variant};
+pub use variable::{StaticVariable, Variable};
 
 use std::{
-    borrow, io,
+    borrow,
+    collections::VecDeque,
+    io,
     path::{Path, PathBuf},
     rc::Rc,
     str::{from_utf8, Utf8Error},
 };
 
 use gimli::{FileEntry, LineProgramHeader};
-use log::{debug, error, info};
+use log::{debug, info};
 use object::read::{Object, ObjectSection};
 use thiserror::Error;
 
@@ -34,6 +42,14 @@ pub enum DebugError {
     NonUtf8(#[from] Utf8Error),
     #[error("Error using the probe: {0}")]
     Probe(#[from] crate::Error),
+    #[error("Unsupported debug information: {0}")]
+    Unsupported(String),
+    #[error("The value of DWARF register {0} is not known in this frame")]
+    UnknownRegister(u16),
+    #[error("There is no line information for the address {0:#010x}")]
+    NoLineInformation(u32),
+    #[error("The core did not halt within {0:?}")]
+    StepTimeout(std::time::Duration),
 }
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum ColumnType {
@@ -54,6 +70,9 @@ impl From<gimli::ColumnType> for ColumnType {
 pub struct StackFrame {
     pub id: u64,
     pub function_name: String,
+    /// The function is inlined into the function of the next frame, which
+    /// has the same registers and program counter.
+    pub is_inlined: bool,
     pub source_location: Option<SourceLocation>,
     registers: Registers,
     pc: u32,
@@ -62,7 +81,11 @@ pub struct StackFrame {
 
 impl std::fmt::Display for StackFrame {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        writeln!(f, "{}: {}", self.id, self.function_name)?;
+        if self.is_inlined {
+            writeln!(f, "{}: {} (inlined)", self.id, self.function_name)?;
+        } else {
+            writeln!(f, "{}: {}", self.id, self.function_name)?;
+        }
         if let Some(si) = &self.source_location {
             write!(
                 f,
@@ -88,7 +111,7 @@ impl std::fmt::Display for StackFrame {
         for variable in &self.variables {
             writeln!(
                 f,
-                "\t\t{}: {}:{} = 0x{:08x}",
+                "\t\t{}: {}:{} = {}",
                 variable.name, variable.file, variable.line, variable.value
             )?;
         }
@@ -96,24 +119,33 @@ impl std::fmt::Display for StackFrame {
     }
 }
 
+/// The values of the registers of a frame, by their DWARF register number.
 #[derive(Debug, Clone)]
-struct Registers([Option<u32>; 16]);
+struct Registers(Vec<Option<u32>>);
 
 impl Registers {
+    /// Reads all registers which have a DWARF register number, which are
+    /// r0 to r15 on ARM and x0 to x31 on RISC-V.
     pub fn from_core(core: &Core) -> Self {
-        let mut registers = Registers([None; 16]);
-        for i in 0..16 {
-            registers[i as usize] = Some(core.read_core_reg(i).unwrap());
-        }
-        registers
+        let count = dwarf_register_count(core.architecture());
+
+        Registers(
+            (0..count)
+                .map(|i| {
+                    dwarf_register(core, i).and_then(|address| core.read_core_reg(address).ok())
+                })
+                .collect(),
+        )
     }
 
-    pub fn get_call_frame_address(&self) -> Option<u32> {
-        self.0[13]
+    /// The number of registers, which are numbered from zero.
+    pub fn len(&self) -> u16 {
+        self.0.len() as u16
     }
 
-    pub fn set_call_frame_address(&mut self, value: Option<u32>) {
-        self.0[13] = value;
+    /// The value of a register by its DWARF register number, if it is known.
+    pub fn get(&self, register: u16) -> Option<u32> {
+        self.0.get(usize::from(register)).copied().flatten()
     }
 }
 
@@ -160,6 +192,9 @@ pub struct StackFrameIterator<'a, 'b> {
     frame_count: u64,
     pc: Option<u64>,
     registers: Registers,
+    /// The frames of functions which are inlined into the last physical frame.
+    inlined_frames: VecDeque<StackFrame>,
+    next_id: u64,
 }
 
 impl<'a, 'b> StackFrameIterator<'a, 'b> {
@@ -173,6 +208,8 @@ impl<'a, 'b> StackFrameIterator<'a, 'b> {
             frame_count: 0,
             pc: Some(pc),
             registers,
+            inlined_frames: VecDeque::new(),
+            next_id: 0,
         }
     }
 }
@@ -181,6 +218,10 @@ impl<'a, 'b> Iterator for StackFrameIterator<'a, 'b> {
     type Item = StackFrame;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if let Some(frame) = self.inlined_frames.pop_front() {
+            return Some(frame);
+        }
+
         use gimli::UnwindSection;
         let mut ctx = gimli::UninitializedUnwindContext::new();
         let bases = gimli::BaseAddresses::default();
@@ -211,70 +252,134 @@ impl<'a, 'b> Iterator for StackFrameIterator<'a, 'b> {
             }
         };
 
-        let current_cfa = match unwind_info.cfa() {
-            gimli::CfaRule::RegisterAndOffset { register, offset } => {
-                let reg_val = self.registers[register.0 as usize];
+        let frame_registers = self.registers.clone();
+        let context = ExpressionContext {
+            debug_info: self.debug_info,
+            unit: None,
+            encoding: self.debug_info.frame_encoding(),
+            core: self.core,
+            registers: &frame_registers,
+            innermost: self.frame_count == 0,
+            frame_base: None,
+            cfa: None,
+            entry_registers: None,
+        };
 
-                Some((i64::from(reg_val.unwrap()) + offset) as u32)
+        let cfa = match unwind_info.cfa() {
+            gimli::CfaRule::RegisterAndOffset { register, offset } => context
+                .register(register.0)
+                .map(|value| (i64::from(value) + offset) as u64),
+            gimli::CfaRule::Expression(expression) => {
+                context.evaluate_address(expression.clone(), None)
             }
-            gimli::CfaRule::Expression(_) => unimplemented!(),
         };
 
-        if let Some(ref cfa) = &current_cfa {
-            debug!("Current CFA: {:#x}", cfa);
-        }
-
-        // generate previous registers
-        for i in 0..16 {
-            if i == 13 {
-                continue;
+        let cfa = match cfa {
+            Ok(cfa) => cfa,
+            Err(e) => {
+                log::warn!("Failed to compute the CFA at {:#x}: {}", pc, e);
+                return None;
             }
+        };
 
-            use gimli::read::RegisterRule::*;
+        debug!("Current CFA: {:#x}", cfa);
 
-            self.registers[i] = match unwind_info.register(gimli::Register(i as u16)) {
-                Undefined => None,
-                SameValue => self.registers[i],
-                Offset(o) => {
-                    let addr = i64::from(current_cfa.unwrap()) + o;
-                    let mut buff = [0u8; 4];
-                    self.core.read_8(addr as u32, &mut buff).unwrap();
+        let context = ExpressionContext {
+            cfa: Some(cfa),
+            ..context
+        };
 
-                    let val = u32::from_le_bytes(buff);
+        let stack_pointer = dwarf_stack_pointer(self.core.architecture());
+        let return_address = dwarf_return_address(self.core.architecture());
 
-                    debug!("reg[{: >}]={:#08x}", i, val);
+        // generate previous registers
+        for i in 0..self.registers.len() {
+            if i == stack_pointer {
+                continue;
+            }
 
-                    Some(val)
+            let rule = unwind_info.register(gimli::Register(i));
+            self.registers[usize::from(i)] = match unwind_register(&context, i, rule, cfa) {
+                Ok(value) => value,
+                Err(e) => {
+                    debug!("Failed to unwind register {}: {}", i, e);
+                    None
                 }
-                _ => unimplemented!(),
+            };
+
+            if let Some(value) = self.registers[usize::from(i)] {
+                debug!("reg[{: >}]={:#08x}", i, value);
             }
         }
 
-        self.registers.set_call_frame_address(current_cfa);
-
-        let return_frame = match self.debug_info.get_stackframe_info(
-            &self.core,
-            pc,
-            self.frame_count,
-            self.registers.clone(),
-        ) {
-            Ok(frame) => Some(frame),
-            Err(e) => {
-                log::warn!("Unable to get stack frame information: {}", e);
-                None
-            }
-        };
+        // The stack pointer of the caller is the CFA.
+        self.registers[usize::from(stack_pointer)] = Some(cfa as u32);
+
+        let return_frame =
+            match self
+                .debug_info
+                .get_stackframe_info(&context, pc, self.next_id, &self.registers)
+            {
+                Ok(frames) => {
+                    self.next_id += frames.len() as u64;
+                    self.inlined_frames.extend(frames);
+                    self.inlined_frames.pop_front()
+                }
+                Err(e) => {
+                    log::warn!("Unable to get stack frame information: {}", e);
+                    None
+                }
+            };
 
         self.frame_count += 1;
 
         // Next function is where our current return register is pointing to.
         // We just have to remove the lowest bit (indicator for Thumb mode).
-        self.pc = self.registers[14].map(|pc| u64::from(pc & !1));
+        self.pc = self.registers[usize::from(return_address)].map(|pc| u64::from(pc & !1));
 
         return_frame
     }
 }
 
+/// Finds the value a register had in the caller, with its unwind rule.
+fn unwind_register(
+    context: &ExpressionContext,
+    register: u16,
+    rule: gimli::RegisterRule<R>,
+    cfa: u64,
+) -> Result<Option<u32>, DebugError> {
+    use gimli::RegisterRule::*;
+
+    let value = match rule {
+        Undefined => None,
+        SameValue => context.register(register).ok(),
+        Offset(offset) => {
+            let address = (cfa as i64).wrapping_add(offset) as u32;
+            Some(context.core.memory().read32(address)?)
+        }
+        ValOffset(offset) => Some((cfa as i64).wrapping_add(offset) as u32),
+        Register(other) => Some(context.register(other.0)?),
+        Expression(expression) => {
+            let address = context.evaluate_address(expression, Some(cfa))?;
+            Some(context.core.memory().read32(address as u32)?)
+        }
+        ValExpression(expression) => Some(context.evaluate_address(expression, Some(cfa))? as u32),
+        Architectural => {
+            return Err(DebugError::Unsupported(
+                "Architectural register rules".to_string(),
+            ))
+        }
+    };
+
+    Ok(value)
+}
+
+/// The deepest nesting of types which is resolved.
+const MAX_TYPE_DEPTH: usize = 32;
+
+/// The most bytes which are read for the value of a variable.
+const MAX_VARIABLE_SIZE: usize = 4096;
+
 type R = gimli::EndianReader<gimli::LittleEndian, std::rc::Rc<[u8]>>;
 type DwarfReader = gimli::read::EndianRcSlice<gimli::LittleEndian>;
 type FunctionDie<'a, 'u> = gimli::DebuggingInformationEntry<
@@ -283,8 +388,6 @@ type FunctionDie<'a, 'u> = gimli::DebuggingInformationEntry<
     gimli::EndianReader<gimli::LittleEndian, std::rc::Rc<[u8]>>,
     usize,
 >;
-type EntriesCursor<'a, 'u> =
-    gimli::EntriesCursor<'a, 'u, gimli::EndianReader<gimli::LittleEndian, std::rc::Rc<[u8]>>>;
 type UnitIter =
     gimli::CompilationUnitHeadersIter<gimli::EndianReader<gimli::LittleEndian, std::rc::Rc<[u8]>>>;
 
@@ -292,6 +395,7 @@ type UnitIter =
 pub struct DebugInfo {
     dwarf: gimli::Dwarf<DwarfReader>,
     frame_section: gimli::DebugFrame<DwarfReader>,
+    address_size: u8,
 }
 
 impl DebugInfo {
@@ -332,15 +436,28 @@ impl DebugInfo {
 
         use gimli::Section;
 
-        let frame_section = gimli::DebugFrame::load(load_section).unwrap();
+        let address_size = if object.is_64() { 8 } else { 4 };
+
+        let mut frame_section = gimli::DebugFrame::load(load_section).unwrap();
+        frame_section.set_address_size(address_size);
 
         Ok(DebugInfo {
             //object,
             dwarf: dwarf_cow,
             frame_section,
+            address_size,
         })
     }
 
+    /// The encoding of the expressions in the call frame information.
+    fn frame_encoding(&self) -> gimli::Encoding {
+        gimli::Encoding {
+            format: gimli::Format::Dwarf32,
+            version: 4,
+            address_size: self.address_size,
+        }
+    }
+
     pub fn get_source_location(&self, address: u64) -> Option<SourceLocation> {
         let mut units = self.dwarf.units();
 
@@ -452,48 +569,85 @@ impl DebugInfo {
         None
     }
 
+    /// Finds the function and the variables of a frame, and the frames of the
+    /// functions which are inlined at the address, from the innermost one.
+    /// `caller_registers` are the registers of the frame after unwinding it.
     fn get_stackframe_info(
         &self,
-        core: &Core,
+        context: &ExpressionContext,
         address: u64,
-        frame_count: u64,
-        registers: Registers,
-    ) -> Result<StackFrame, DebugError> {
+        first_id: u64,
+        caller_registers: &Registers,
+    ) -> Result<Vec<StackFrame>, DebugError> {
         let mut units = self.get_units();
-        let unknown_function = format!("<unknown_function_{}>", frame_count);
         while let Some(unit_info) = self.get_next_unit_info(&mut units) {
-            if let Some(die_cursor_state) = &mut unit_info.get_function_die(address) {
-                let function_name = unit_info
-                    .get_function_name(&die_cursor_state.function_die)
-                    .unwrap_or(unknown_function);
+            let functions = unit_info.get_function_dies(address)?;
+
+            let subprogram = match functions.first() {
+                Some(subprogram) => subprogram,
+                None => continue,
+            };
+
+            let mut context = ExpressionContext {
+                unit: Some(&unit_info.unit),
+                encoding: unit_info.unit.encoding(),
+                entry_registers: Some(caller_registers),
+                ..*context
+            };
+
+            // Inlined functions use the frame base of the function they are inlined into.
+            context.frame_base = match subprogram.attr_value(gimli::DW_AT_frame_base)? {
+                Some(gimli::AttributeValue::Exprloc(expression)) => {
+                    match context.evaluate_address(expression, None) {
+                        Ok(frame_base) => Some(frame_base),
+                        Err(e) => {
+                            debug!("Failed to compute the frame base: {}", e);
+                            None
+                        }
+                    }
+                }
+                _ => None,
+            };
+
+            // The innermost function is at the address, and each function it is
+            // inlined into is at the call site of the inlined function.
+            let mut source_location = self.get_source_location(address);
+            let mut frames = Vec::with_capacity(functions.len());
 
-                let variables = unit_info.get_variables(
-                    core,
-                    die_cursor_state,
-                    u64::from(registers.get_call_frame_address().unwrap()),
-                )?;
+            for function in functions.iter().rev() {
+                let id = first_id + frames.len() as u64;
+                let function_name = unit_info
+                    .get_function_name(function)?
+                    .unwrap_or_else(|| format!("<unknown_function_{}>", id));
 
-                // dbg!(&variables);
+                let variables = unit_info.get_variables(&context, address, function.offset())?;
+                let call_site = unit_info.get_call_site(function)?;
 
-                return Ok(StackFrame {
-                    id: frame_count,
+                frames.push(StackFrame {
+                    id,
                     function_name,
-                    source_location: self.get_source_location(address),
-                    registers,
+                    is_inlined: function.tag() == gimli::DW_TAG_inlined_subroutine,
+                    source_location,
+                    registers: context.registers.clone(),
                     pc: address as u32,
                     variables,
                 });
+
+                source_location = call_site;
             }
+
+            return Ok(frames);
         }
 
-        Ok(StackFrame {
-            id: frame_count,
-            function_name: unknown_function,
+        Ok(vec![StackFrame {
+            id: first_id,
+            function_name: format!("<unknown_function_{}>", first_id),
+            is_inlined: false,
             source_location: self.get_source_location(address),
-            registers,
+            registers: context.registers.clone(),
             pc: address as u32,
             variables: vec![],
-        })
+        }])
     }
 
     pub fn try_unwind<'a, 'b>(
@@ -501,7 +655,151 @@ impl DebugInfo {
         core: &'b Core,
         address: u64,
     ) -> StackFrameIterator<'a, 'b> {
-        StackFrameIterator::new(&self, core, address)
+        StackFrameIterator::new(self, core, address)
+    }
+
+    /// Find the address of a function, given its name or its linkage name.
+    pub fn get_function_address(&self, name: &str) -> Result<Option<u64>, DebugError> {
+        let mut units = self.dwarf.units();
+
+        while let Some(header) = units.next()? {
+            let unit = self.dwarf.unit(header)?;
+            let mut entries = unit.entries();
+
+            while let Some((_, entry)) = entries.next_dfs()? {
+                if entry.tag() != gimli::DW_TAG_subprogram {
+                    continue;
+                }
+
+                // Declarations and inlined functions have no address.
+                let address = match entry.attr_value(gimli::DW_AT_low_pc)? {
+                    Some(gimli::AttributeValue::Addr(address)) => address,
+                    _ => continue,
+                };
+
+                let found = [gimli::DW_AT_name, gimli::DW_AT_linkage_name]
+                    .iter()
+                    .filter_map(|attribute| entry.attr_value(*attribute).ok().flatten())
+                    .filter_map(|value| self.dwarf.attr_string(&unit, value).ok())
+                    .any(|function_name| &*function_name == name.as_bytes());
+
+                if found {
+                    return Ok(Some(address));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Find a variable with a fixed address, given its name or its linkage name.
+    pub fn get_static_variable(&self, name: &str) -> Result<Option<StaticVariable>, DebugError> {
+        let mut units = self.dwarf.units();
+
+        while let Some(header) = units.next()? {
+            let unit = self.dwarf.unit(header)?;
+            let mut entries = unit.entries();
+
+            while let Some((_, entry)) = entries.next_dfs()? {
+                if entry.tag() != gimli::DW_TAG_variable {
+                    continue;
+                }
+
+                // Local variables and declarations have no fixed address.
+                let address = match entry.attr_value(gimli::DW_AT_location)? {
+                    Some(gimli::AttributeValue::Exprloc(expression)) => {
+                        let mut bytes = expression.0.clone();
+                        match gimli::Operation::parse(&mut bytes, &expression.0, unit.encoding())? {
+                            gimli::Operation::Address { address } => address as u32,
+                            _ => continue,
+                        }
+                    }
+                    _ => continue,
+                };
+
+                // The definition of a variable can refer to its declaration for the name and type.
+                let declaration = match entry.attr_value(gimli::DW_AT_specification)? {
+                    Some(gimli::AttributeValue::UnitRef(offset)) => unit_entry(&unit, offset)?,
+                    _ => None,
+                };
+
+                let found = [gimli::DW_AT_name, gimli::DW_AT_linkage_name]
+                    .iter()
+                    .filter_map(|attribute| {
+                        let value = match entry.attr_value(*attribute).ok().flatten() {
+                            Some(value) => value,
+                            None => declaration.as_ref()?.attr_value(*attribute).ok()??,
+                        };
+                        self.dwarf.attr_string(&unit, value).ok()
+                    })
+                    .any(|variable_name| &*variable_name == name.as_bytes());
+
+                if !found {
+                    continue;
+                }
+
+                let typ = match entry.attr_value(gimli::DW_AT_type)? {
+                    Some(typ) => Some(typ),
+                    None => match &declaration {
+                        Some(declaration) => declaration.attr_value(gimli::DW_AT_type)?,
+                        None => None,
+                    },
+                };
+
+                return Ok(Some(StaticVariable {
+                    name: name.to_owned(),
+                    address,
+                    typ: match typ {
+                        Some(typ) => self.base_type(&unit, typ)?,
+                        None => None,
+                    },
+                }));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Resolves a type to a base type, through typedefs and qualifiers.
+    fn base_type(
+        &self,
+        unit: &gimli::Unit<DwarfReader>,
+        mut typ: gimli::AttributeValue<DwarfReader>,
+    ) -> Result<Option<BaseType>, DebugError> {
+        loop {
+            let entry = match typ {
+                gimli::AttributeValue::UnitRef(offset) => unit_entry(unit, offset)?,
+                _ => return Ok(None),
+            };
+
+            let entry = match entry {
+                Some(entry) => entry,
+                None => return Ok(None),
+            };
+
+            match entry.tag() {
+                gimli::DW_TAG_base_type => {
+                    let encoding = match entry.attr_value(gimli::DW_AT_encoding)? {
+                        Some(gimli::AttributeValue::Encoding(encoding)) => encoding,
+                        _ => return Ok(None),
+                    };
+                    let byte_size = match entry.attr_value(gimli::DW_AT_byte_size)? {
+                        Some(byte_size) => byte_size.udata_value(),
+                        None => None,
+                    };
+
+                    return Ok(byte_size.and_then(|size| BaseType::from_encoding(encoding, size)));
+                }
+                gimli::DW_TAG_typedef
+                | gimli::DW_TAG_const_type
+                | gimli::DW_TAG_volatile_type
+                | gimli::DW_TAG_atomic_type => match entry.attr_value(gimli::DW_AT_type)? {
+                    Some(next) => typ = next,
+                    None => return Ok(None),
+                },
+                _ => return Ok(None),
+            }
+        }
     }
 
     /// Find the program counter where a breakpoint should be set,
@@ -541,14 +839,14 @@ impl DebugInfo {
                 for file_name in header.file_names() {
                     let combined_path = comp_dir
                         .as_ref()
-                        .and_then(|dir| self.get_path(&dir, &unit, &header, file_name));
+                        .and_then(|dir| self.get_path(dir, &unit, header, file_name));
 
                     if combined_path.map(|p| p == path).unwrap_or(false) {
                         let mut rows = line_program.clone().rows();
 
                         while let Some((header, row)) = rows.next_row()? {
                             let row_path = comp_dir.as_ref().and_then(|dir| {
-                                self.get_path(&dir, &unit, &header, row.file(&header)?)
+                                self.get_path(dir, &unit, header, row.file(header)?)
                             });
 
                             if row_path.map(|p| p != path).unwrap_or(true) {
@@ -645,251 +943,656 @@ impl DebugInfo {
     }
 }
 
-struct DieCursorState<'a, 'u> {
-    entries_cursor: EntriesCursor<'a, 'u>,
-    _depth: isize,
-    function_die: FunctionDie<'a, 'u>,
-}
-
 struct UnitInfo<'a> {
     debug_info: &'a DebugInfo,
     unit: gimli::Unit<gimli::EndianReader<gimli::LittleEndian, std::rc::Rc<[u8]>>, usize>,
 }
 
 impl<'a> UnitInfo<'a> {
-    fn get_function_die(&self, address: u64) -> Option<DieCursorState> {
-        let mut entries_cursor = self.unit.entries();
+    /// Finds the function at `address` and the functions which are inlined
+    /// at the address, from the outermost to the innermost one.
+    fn get_function_dies(&self, address: u64) -> Result<Vec<FunctionDie<'_, '_>>, DebugError> {
+        let mut tree = self.unit.entries_tree(None)?;
+        let mut functions = vec![];
+
+        self.find_function_dies(tree.root()?, address, &mut functions)?;
 
-        while let Some((depth, current)) = entries_cursor.next_dfs().unwrap() {
-            match current.tag() {
+        Ok(functions)
+    }
+
+    /// Searches the children of `node` for the functions at `address`, and
+    /// returns if one was found.
+    fn find_function_dies<'abbrev, 'unit>(
+        &self,
+        node: gimli::EntriesTreeNode<'abbrev, 'unit, '_, R>,
+        address: u64,
+        functions: &mut Vec<FunctionDie<'abbrev, 'unit>>,
+    ) -> Result<bool, DebugError> {
+        let mut children = node.children();
+        while let Some(child) = children.next()? {
+            let entry = child.entry();
+
+            match entry.tag() {
                 gimli::DW_TAG_subprogram | gimli::DW_TAG_inlined_subroutine => {
-                    let mut ranges = self
-                        .debug_info
-                        .dwarf
-                        .die_ranges(&self.unit, &current)
-                        .unwrap();
-
-                    while let Some(ranges) = ranges.next().unwrap() {
-                        if (ranges.begin <= address) && (address < ranges.end) {
-                            return Some(DieCursorState {
-                                _depth: depth,
-                                function_die: current.clone(),
-                                entries_cursor,
-                            });
-                        }
+                    if self.contains_address(entry, address)? {
+                        functions.push(entry.clone());
+                        self.find_function_dies(child, address, functions)?;
+                        return Ok(true);
                     }
                 }
-                _ => (),
-            };
+                // Functions are nested in namespaces and types, and inlined
+                // functions in lexical blocks.
+                _ => {
+                    if self.find_function_dies(child, address, functions)? {
+                        return Ok(true);
+                    }
+                }
+            }
         }
-        None
+
+        Ok(false)
     }
 
-    fn get_function_name(&self, function_die: &FunctionDie) -> Option<String> {
-        if let Some(fn_name_attr) = function_die
-            .attr(gimli::DW_AT_name)
-            .expect(" Failed to parse entry")
-        {
-            if let gimli::AttributeValue::DebugStrRef(fn_name_ref) = fn_name_attr.value() {
-                let fn_name_raw = self.debug_info.dwarf.string(fn_name_ref).unwrap();
+    fn contains_address(&self, entry: &FunctionDie, address: u64) -> Result<bool, DebugError> {
+        let mut ranges = self.debug_info.dwarf.die_ranges(&self.unit, entry)?;
 
-                return Some(String::from_utf8_lossy(&fn_name_raw).to_string());
+        while let Some(range) = ranges.next()? {
+            if range.begin <= address && address < range.end {
+                return Ok(true);
             }
         }
 
-        None
+        Ok(false)
     }
 
-    fn expr_to_piece(
-        &self,
-        core: &Core,
-        expression: gimli::Expression<R>,
-        frame_base: u64,
-    ) -> Result<Vec<gimli::Piece<R, usize>>, DebugError> {
-        let mut evaluation = expression.evaluation(self.unit.encoding());
+    /// The name of a function, which an inlined function or the definition of
+    /// a method takes from the entry it refers to.
+    fn get_function_name(&self, function_die: &FunctionDie) -> Result<Option<String>, DebugError> {
+        if let Some(name) = self.entry_name(function_die)? {
+            return Ok(Some(name));
+        }
 
-        // go for evaluation
-        let mut result = evaluation.evaluate()?;
+        for attribute in &[gimli::DW_AT_abstract_origin, gimli::DW_AT_specification] {
+            if let Some(gimli::AttributeValue::UnitRef(offset)) =
+                function_die.attr_value(*attribute)?
+            {
+                if let Some(origin) = unit_entry(&self.unit, offset)? {
+                    return self.get_function_name(&origin);
+                }
+            }
+        }
 
-        loop {
-            use gimli::EvaluationResult::*;
-
-            result = match result {
-                Complete => break,
-                RequiresMemory { address, size, .. } => {
-                    let mut buff = vec![0u8; size as usize];
-                    core.memory()
-                        .read_block8(address as u32, &mut buff)
-                        .expect("Failed to read memory");
-                    match size {
-                        1 => evaluation.resume_with_memory(gimli::Value::U8(buff[0]))?,
-                        2 => {
-                            let val = (u16::from(buff[0]) << 8) | (u16::from(buff[1]) as u16);
-                            evaluation.resume_with_memory(gimli::Value::U16(val))?
+        Ok(None)
+    }
+
+    /// The source location an inlined function is called at.
+    fn get_call_site(
+        &self,
+        function_die: &FunctionDie,
+    ) -> Result<Option<SourceLocation>, DebugError> {
+        let file_index = match function_die.attr_value(gimli::DW_AT_call_file)? {
+            Some(gimli::AttributeValue::FileIndex(index)) => index,
+            Some(value) => match value.udata_value() {
+                Some(index) => index,
+                None => return Ok(None),
+            },
+            None => return Ok(None),
+        };
+
+        let line = function_die
+            .attr_value(gimli::DW_AT_call_line)?
+            .and_then(|line| line.udata_value());
+        let column = function_die
+            .attr_value(gimli::DW_AT_call_column)?
+            .and_then(|column| column.udata_value())
+            .map(|column| match column {
+                0 => ColumnType::LeftEdge,
+                column => ColumnType::Column(column),
+            });
+
+        let (file, directory) = match self.unit.line_program.as_ref() {
+            Some(program) => {
+                let header = program.header();
+                match header.file(file_index) {
+                    Some(file_entry) => {
+                        let file = extract_name(self.debug_info, file_entry.path_name());
+                        let mut directory = file_entry
+                            .directory(header)
+                            .and_then(|directory| extract_name(self.debug_info, directory))
+                            .map(PathBuf::from);
+
+                        if let (Some(relative), Some(comp_dir)) = (&directory, &self.unit.comp_dir)
+                        {
+                            if relative.is_relative() {
+                                let comp_dir = String::from_utf8_lossy(comp_dir);
+                                directory = Some(Path::new(comp_dir.as_ref()).join(relative));
+                            }
                         }
-                        4 => {
-                            let val = (u32::from(buff[0]) << 24)
-                                | (u32::from(buff[1]) << 16)
-                                | (u32::from(buff[2]) << 8)
-                                | u32::from(buff[3]);
-                            evaluation.resume_with_memory(gimli::Value::U32(val))?
+
+                        (file, directory)
+                    }
+                    None => (None, None),
+                }
+            }
+            None => (None, None),
+        };
+
+        Ok(Some(SourceLocation {
+            line,
+            column,
+            file,
+            directory,
+        }))
+    }
+
+    /// Resolves the type entry at `offset`, and the types it contains.
+    fn extract_type(&self, offset: gimli::UnitOffset, depth: usize) -> Result<Type, DebugError> {
+        let mut tree = self.unit.entries_tree(Some(offset))?;
+        let node = tree.root()?;
+        let entry = node.entry().clone();
+
+        let name = self.entry_name(&entry)?;
+        let size = entry
+            .attr_value(gimli::DW_AT_byte_size)?
+            .and_then(|size| size.udata_value())
+            .map(|size| size as usize);
+
+        let unknown = |name: Option<String>| Type::Unknown {
+            name: name.unwrap_or_else(|| "<unnamed type>".to_string()),
+            size: size.unwrap_or(0),
+        };
+
+        if depth > MAX_TYPE_DEPTH {
+            return Ok(unknown(name));
+        }
+
+        let typ = match entry.tag() {
+            gimli::DW_TAG_base_type => {
+                let base = match entry.attr_value(gimli::DW_AT_encoding)? {
+                    Some(gimli::AttributeValue::Encoding(encoding)) => {
+                        size.and_then(|size| BaseType::from_encoding(encoding, size as u64))
+                    }
+                    _ => None,
+                };
+
+                match base {
+                    Some(base) => Type::Base {
+                        name: name.unwrap_or_else(|| "<unnamed type>".to_string()),
+                        base,
+                    },
+                    None => unknown(name),
+                }
+            }
+            gimli::DW_TAG_pointer_type
+            | gimli::DW_TAG_reference_type
+            | gimli::DW_TAG_rvalue_reference_type => {
+                // The pointee is not resolved, because it can contain this pointer.
+                let name = match name {
+                    Some(name) => name,
+                    None => {
+                        let pointee = match entry.attr_value(gimli::DW_AT_type)? {
+                            Some(gimli::AttributeValue::UnitRef(pointee)) => {
+                                match unit_entry(&self.unit, pointee)? {
+                                    Some(pointee) => self.entry_name(&pointee)?,
+                                    None => None,
+                                }
+                            }
+                            _ => None,
+                        };
+                        format!("*{}", pointee.unwrap_or_else(|| "void".to_string()))
+                    }
+                };
+
+                Type::Pointer {
+                    name,
+                    size: size.unwrap_or_else(|| self.unit.encoding().address_size as usize),
+                }
+            }
+            gimli::DW_TAG_structure_type | gimli::DW_TAG_class_type => {
+                let name = name.unwrap_or_else(|| "<unnamed type>".to_string());
+                let size = size.unwrap_or(0);
+                let mut members = vec![];
+
+                let mut children = node.children();
+                while let Some(child) = children.next()? {
+                    match child.entry().tag() {
+                        gimli::DW_TAG_member => {
+                            members.push(self.extract_member(child.entry(), depth)?)
                         }
-                        x => {
-                            error!(
-                                "Requested memory with size {}, which is not supported yet.",
-                                x
-                            );
-                            unimplemented!();
+                        // A Rust enum with fields is a struct with a variant part.
+                        gimli::DW_TAG_variant_part => {
+                            return self.extract_variant_part(child, name, size, depth)
                         }
+                        _ => (),
                     }
                 }
-                RequiresFrameBase => evaluation.resume_with_frame_base(frame_base).unwrap(),
-                RequiresRegister {
-                    register,
-                    base_type,
-                } => {
-                    let raw_value = core.read_core_reg(register.0 as u16)?;
-
-                    if base_type != gimli::UnitOffset(0) {
-                        unimplemented!(
-                            "Support for units in RequiresRegister request is not yet implemented."
-                        )
+
+                Type::Struct {
+                    name,
+                    size,
+                    members,
+                }
+            }
+            gimli::DW_TAG_array_type => {
+                let element = match entry.attr_value(gimli::DW_AT_type)? {
+                    Some(gimli::AttributeValue::UnitRef(element)) => {
+                        self.extract_type(element, depth + 1)?
+                    }
+                    _ => return Ok(unknown(name)),
+                };
+
+                let mut counts = vec![];
+                let mut children = node.children();
+                while let Some(child) = children.next()? {
+                    let entry = child.entry();
+                    if entry.tag() != gimli::DW_TAG_subrange_type {
+                        continue;
                     }
 
-                    evaluation.resume_with_register(gimli::Value::Generic(raw_value as u64))?
+                    let count = match entry.attr_value(gimli::DW_AT_count)? {
+                        Some(count) => count.udata_value(),
+                        None => {
+                            let lower_bound = entry
+                                .attr_value(gimli::DW_AT_lower_bound)?
+                                .and_then(|bound| bound.udata_value())
+                                .unwrap_or(0);
+                            entry
+                                .attr_value(gimli::DW_AT_upper_bound)?
+                                .and_then(|bound| bound.udata_value())
+                                .map(|bound| (bound + 1).saturating_sub(lower_bound))
+                        }
+                    };
+                    counts.push(count.unwrap_or(0) as usize);
                 }
-                x => {
-                    println!("{:?}", x);
-                    unimplemented!()
+
+                // The last dimension is the innermost one.
+                counts
+                    .iter()
+                    .rev()
+                    .fold(element, |element, count| Type::Array {
+                        element: Box::new(element),
+                        count: *count,
+                    })
+            }
+            gimli::DW_TAG_enumeration_type => {
+                let base = match entry.attr_value(gimli::DW_AT_type)? {
+                    Some(gimli::AttributeValue::UnitRef(base)) => {
+                        match self.extract_type(base, depth + 1)? {
+                            Type::Base { base, .. } => Some(base),
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                };
+                let base = base.or_else(|| {
+                    size.and_then(|size| {
+                        BaseType::from_encoding(gimli::DW_ATE_unsigned, size as u64)
+                    })
+                });
+
+                let base = match base {
+                    Some(base) => base,
+                    None => return Ok(unknown(name)),
+                };
+
+                let mut enumerators = vec![];
+                let mut children = node.children();
+                while let Some(child) = children.next()? {
+                    let entry = child.entry();
+                    if entry.tag() != gimli::DW_TAG_enumerator {
+                        continue;
+                    }
+
+                    let value = match entry.attr_value(gimli::DW_AT_const_value)? {
+                        Some(gimli::AttributeValue::Sdata(value)) => Some(value),
+                        Some(value) => value.udata_value().map(|value| value as i64),
+                        None => None,
+                    };
+
+                    if let (Some(name), Some(value)) = (self.entry_name(entry)?, value) {
+                        enumerators.push(Enumerator { name, value });
+                    }
+                }
+
+                Type::Enum {
+                    name: name.unwrap_or_else(|| "<unnamed type>".to_string()),
+                    base,
+                    enumerators,
                 }
             }
+            gimli::DW_TAG_typedef
+            | gimli::DW_TAG_const_type
+            | gimli::DW_TAG_volatile_type
+            | gimli::DW_TAG_atomic_type
+            | gimli::DW_TAG_restrict_type => match entry.attr_value(gimli::DW_AT_type)? {
+                Some(gimli::AttributeValue::UnitRef(typ)) => self.extract_type(typ, depth + 1)?,
+                _ => unknown(name),
+            },
+            _ => unknown(name),
+        };
+
+        Ok(typ)
+    }
+
+    fn extract_member(
+        &self,
+        entry: &gimli::DebuggingInformationEntry<R>,
+        depth: usize,
+    ) -> Result<Member, DebugError> {
+        let name = self
+            .entry_name(entry)?
+            .unwrap_or_else(|| "<unnamed>".to_string());
+        let offset = entry
+            .attr_value(gimli::DW_AT_data_member_location)?
+            .and_then(|offset| offset.udata_value())
+            .unwrap_or(0) as usize;
+        let typ = match entry.attr_value(gimli::DW_AT_type)? {
+            Some(gimli::AttributeValue::UnitRef(typ)) => self.extract_type(typ, depth + 1)?,
+            _ => Type::Unknown {
+                name: "<undefined>".to_string(),
+                size: 0,
+            },
+        };
+
+        Ok(Member { name, offset, typ })
+    }
+
+    /// Resolves the variants of an enum with fields. The discriminant is a
+    /// member of the variant part, and each variant contains one member
+    /// with the fields of the variant.
+    fn extract_variant_part(
+        &self,
+        node: gimli::EntriesTreeNode<R>,
+        name: String,
+        size: usize,
+        depth: usize,
+    ) -> Result<Type, DebugError> {
+        let discriminant_offset = match node.entry().attr_value(gimli::DW_AT_discr)? {
+            Some(gimli::AttributeValue::UnitRef(offset)) => Some(offset),
+            _ => None,
+        };
+
+        let mut discriminant = None;
+        let mut variants = vec![];
+
+        let mut children = node.children();
+        while let Some(child) = children.next()? {
+            let entry = child.entry();
+            match entry.tag() {
+                gimli::DW_TAG_member if Some(entry.offset()) == discriminant_offset => {
+                    discriminant = Some(Box::new(self.extract_member(entry, depth)?));
+                }
+                gimli::DW_TAG_variant => {
+                    let value = entry
+                        .attr_value(gimli::DW_AT_discr_value)?
+                        .and_then(|value| value.udata_value());
+
+                    let mut members = child.children();
+                    while let Some(member) = members.next()? {
+                        if member.entry().tag() == gimli::DW_TAG_member {
+                            variants.push(Variant {
+                                discriminant: value,
+                                member: self.extract_member(member.entry(), depth)?,
+                            });
+                            break;
+                        }
+                    }
+                }
+                _ => (),
+            }
         }
 
-        Ok(evaluation.result())
+        Ok(Type::Variant {
+            name,
+            size,
+            discriminant,
+            variants,
+        })
+    }
+
+    fn entry_name(
+        &self,
+        entry: &gimli::DebuggingInformationEntry<R>,
+    ) -> Result<Option<String>, DebugError> {
+        Ok(entry
+            .attr_value(gimli::DW_AT_name)?
+            .and_then(|name| extract_name(self.debug_info, name)))
     }
 
+    /// The variables and parameters of a function. The variables of lexical
+    /// blocks are included if the block contains the program counter, and
+    /// the ones of inlined functions are part of the frame of the inlined function.
     fn get_variables(
         &self,
-        core: &Core,
-        die_cursor_state: &mut DieCursorState,
-        frame_base: u64,
+        context: &ExpressionContext,
+        pc: u64,
+        function: gimli::UnitOffset,
     ) -> Result<Vec<Variable>, DebugError> {
+        let mut tree = self.unit.entries_tree(Some(function))?;
         let mut variables = vec![];
 
-        while let Some((depth, current)) = die_cursor_state.entries_cursor.next_dfs()? {
-            if depth != 0 && depth != 1 {
-                break;
-            }
-            if let gimli::DW_TAG_variable = current.tag() {
-                let mut variable = Variable {
-                    name: String::new(),
-                    file: String::new(),
-                    line: u64::max_value(),
-                    value: 0,
-                    ..Default::default()
-                };
-                let mut attrs = current.attrs();
-                while let Ok(Some(attr)) = attrs.next() {
-                    match attr.name() {
-                        gimli::DW_AT_name => {
-                            variable.name = extract_name(&self.debug_info, attr.value())
-                                .unwrap_or_else(|| "<undefined>".to_string());
-                        }
-                        gimli::DW_AT_decl_file => {
-                            variable.file =
-                                extract_file(&self.debug_info, &self.unit, attr.value())
-                                    .unwrap_or_else(|| "<undefined>".to_string());
-                        }
-                        gimli::DW_AT_decl_line => {
-                            variable.line = extract_line(&self.debug_info, attr.value())
-                                .unwrap_or_else(u64::max_value);
-                        }
-                        gimli::DW_AT_type => {
-                            variable.typ =
-                                extract_type(&self, attr.value()).unwrap_or_else(|| Type {
-                                    name: "<undefined>".to_string(),
-                                    named_children: None,
-                                    indexed_children: None,
-                                });
-                        }
-                        gimli::DW_AT_location => {
-                            variable.value =
-                                extract_location(&self, core, frame_base, attr.value())?
-                                    .unwrap_or_else(u64::max_value);
-                        }
-                        _ => (),
+        self.collect_variables(context, pc, tree.root()?, &mut variables)?;
+
+        Ok(variables)
+    }
+
+    fn collect_variables(
+        &self,
+        context: &ExpressionContext,
+        pc: u64,
+        node: gimli::EntriesTreeNode<R>,
+        variables: &mut Vec<Variable>,
+    ) -> Result<(), DebugError> {
+        let mut children = node.children();
+        while let Some(child) = children.next()? {
+            let entry = child.entry();
+
+            match entry.tag() {
+                gimli::DW_TAG_variable | gimli::DW_TAG_formal_parameter => {
+                    variables.push(self.get_variable(context, pc, entry)?);
+                }
+                gimli::DW_TAG_lexical_block => {
+                    // A block without addresses covers the whole function.
+                    let has_ranges = self
+                        .debug_info
+                        .dwarf
+                        .die_ranges(&self.unit, entry)?
+                        .next()?
+                        .is_some();
+
+                    if !has_ranges || self.contains_address(entry, pc)? {
+                        self.collect_variables(context, pc, child, variables)?;
                     }
                 }
-                variables.push(variable);
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn get_variable(
+        &self,
+        context: &ExpressionContext,
+        pc: u64,
+        entry: &FunctionDie,
+    ) -> Result<Variable, DebugError> {
+        let mut variable = Variable {
+            name: String::new(),
+            file: String::new(),
+            line: u64::max_value(),
+            typ: Type::Unknown {
+                name: "<undefined>".to_string(),
+                size: 0,
+            },
+            value: VariableValue::Unavailable,
+        };
+        let mut location = None;
+
+        // The variables of inlined functions take their name and type from
+        // the variables of the abstract function.
+        if let Some(gimli::AttributeValue::UnitRef(origin)) =
+            entry.attr_value(gimli::DW_AT_abstract_origin)?
+        {
+            if let Some(origin) = unit_entry(&self.unit, origin)? {
+                self.read_variable_attributes(&origin, &mut variable, &mut location)?;
+            }
+        }
+        self.read_variable_attributes(entry, &mut variable, &mut location)?;
+
+        // The type is needed to read the value, and can come after the location.
+        if let Some(location) = location {
+            variable.value = match extract_location(self, context, pc, location, &variable.typ) {
+                Ok(value) => value,
+                Err(e) => {
+                    debug!("Failed to read the variable {}: {}", variable.name, e);
+                    VariableValue::Unavailable
+                }
             };
         }
 
-        Ok(variables)
+        Ok(variable)
+    }
+
+    fn read_variable_attributes(
+        &self,
+        entry: &FunctionDie,
+        variable: &mut Variable,
+        location: &mut Option<gimli::AttributeValue<R>>,
+    ) -> Result<(), DebugError> {
+        let mut attrs = entry.attrs();
+        while let Ok(Some(attr)) = attrs.next() {
+            match attr.name() {
+                gimli::DW_AT_name => {
+                    variable.name = extract_name(self.debug_info, attr.value())
+                        .unwrap_or_else(|| "<undefined>".to_string());
+                }
+                gimli::DW_AT_decl_file => {
+                    variable.file = extract_file(self.debug_info, &self.unit, attr.value())
+                        .unwrap_or_else(|| "<undefined>".to_string());
+                }
+                gimli::DW_AT_decl_line => {
+                    variable.line =
+                        extract_line(self.debug_info, attr.value()).unwrap_or_else(u64::max_value);
+                }
+                gimli::DW_AT_type => {
+                    if let gimli::AttributeValue::UnitRef(offset) = attr.value() {
+                        variable.typ = self.extract_type(offset, 0)?;
+                    }
+                }
+                gimli::DW_AT_location => *location = Some(attr.value()),
+                _ => (),
+            }
+        }
+
+        Ok(())
     }
 }
 
+/// Reads the entry at an offset in a unit.
+fn unit_entry(
+    unit: &gimli::Unit<DwarfReader>,
+    offset: gimli::UnitOffset,
+) -> Result<Option<gimli::DebuggingInformationEntry<'_, '_, DwarfReader>>, DebugError> {
+    let mut entries = unit.entries_at_offset(offset)?;
+    entries.next_entry()?;
+
+    Ok(entries.current().cloned())
+}
+
 fn extract_location(
     unit_info: &UnitInfo,
-    core: &Core,
-    frame_base: u64,
+    context: &ExpressionContext,
+    pc: u64,
     attribute_value: gimli::AttributeValue<R>,
-) -> Result<Option<u64>, DebugError> {
-    match attribute_value {
-        gimli::AttributeValue::Exprloc(expression) => {
-            let piece = unit_info.expr_to_piece(core, expression, frame_base)?;
-
-            let value = get_piece_value(core, &piece[0]);
-            Ok(value.map(u64::from))
+    typ: &Type,
+) -> Result<VariableValue, DebugError> {
+    let dwarf = &unit_info.debug_info.dwarf;
+
+    let locations = match attribute_value {
+        gimli::AttributeValue::Exprloc(expression) => return read_value(context, expression, typ),
+        gimli::AttributeValue::LocationListsRef(offset) => {
+            dwarf.locations(&unit_info.unit, offset)?
+        }
+        gimli::AttributeValue::DebugLocListsIndex(index) => {
+            let offset = dwarf.locations_offset(&unit_info.unit, index)?;
+            dwarf.locations(&unit_info.unit, offset)?
+        }
+        _ => return Ok(VariableValue::Unavailable),
+    };
+
+    // The location of the variable depends on the program counter.
+    let mut locations = locations;
+    while let Some(location) = locations.next()? {
+        if location.range.begin <= pc && pc < location.range.end {
+            return read_value(context, location.data, typ);
         }
-        _ => Ok(None),
     }
+
+    Ok(VariableValue::Unavailable)
 }
 
-fn extract_type(unit_info: &UnitInfo, attribute_value: gimli::AttributeValue<R>) -> Option<Type> {
-    match attribute_value {
-        gimli::AttributeValue::UnitRef(unit_ref) => {
-            if let Ok(mut tree) = unit_info.unit.entries_tree(Some(unit_ref)) {
-                let node = tree.root().unwrap();
-
-                // Examine the entry attributes.
-                let entry = node.entry();
-                if let gimli::DW_TAG_structure_type = entry.tag() {
-                    let type_name = extract_name(
-                        &unit_info.debug_info,
-                        entry.attr(gimli::DW_AT_name).unwrap().unwrap().value(),
-                    );
-                    let mut named_children = std::collections::HashMap::new();
-
-                    let mut children = node.children();
-                    while let Ok(Some(child)) = children.next() {
-                        // Recursively process a child.
-                        let entry = child.entry();
-                        if let gimli::DW_TAG_member = entry.tag() {
-                            let member_name = extract_name(
-                                &unit_info.debug_info,
-                                entry.attr(gimli::DW_AT_name).unwrap().unwrap().value(),
-                            );
-                            named_children.insert(
-                                member_name?,
-                                extract_type(
-                                    unit_info,
-                                    (entry.attr(gimli::DW_AT_type).ok()?)?.value(),
-                                )?,
-                            );
-                        };
-                    }
+fn read_value(
+    context: &ExpressionContext,
+    expression: gimli::Expression<R>,
+    typ: &Type,
+) -> Result<VariableValue, DebugError> {
+    let pieces = context.evaluate(expression)?;
+
+    let size = typ.size().min(MAX_VARIABLE_SIZE);
+    Ok(match read_pieces(context, &pieces, size)? {
+        Some(bytes) => typ.decode(&bytes),
+        None => VariableValue::Unavailable,
+    })
+}
 
-                    return Some(Type {
-                        name: type_name.unwrap_or_else(|| "<unnamed type>".to_string()),
-                        named_children: Some(named_children),
-                        indexed_children: Some(vec![]),
-                    });
-                };
-            }
-            None
+/// Reads the bytes of a value which is split into pieces. Returns `None` if a
+/// piece is optimized out or can not be read.
+fn read_pieces(
+    context: &ExpressionContext,
+    pieces: &[gimli::Piece<R>],
+    size: usize,
+) -> Result<Option<Vec<u8>>, DebugError> {
+    use gimli::Location;
+
+    let mut bytes = Vec::with_capacity(size);
+
+    for piece in pieces {
+        if bytes.len() >= size {
+            break;
         }
-        _ => None,
+
+        let piece_size = match piece.size_in_bits {
+            Some(bits) => (bits as usize).div_ceil(8),
+            None => size - bytes.len(),
+        };
+
+        let mut data = match &piece.location {
+            Location::Address { address } => {
+                let mut data = vec![0; piece_size.min(size - bytes.len())];
+                if let Err(error) = context
+                    .core
+                    .memory()
+                    .read_block8(*address as u32, &mut data)
+                {
+                    debug!("Failed to read a variable at {:#010x}: {}", address, error);
+                    return Ok(None);
+                }
+                data
+            }
+            Location::Register { register } => context.register(register.0)?.to_le_bytes().to_vec(),
+            Location::Value { value } => value.to_u64(u64::MAX)?.to_le_bytes().to_vec(),
+            Location::Bytes { value } => gimli::Reader::to_slice(value)?.to_vec(),
+            _ => return Ok(None),
+        };
+
+        data.resize(piece_size, 0);
+        bytes.extend(data);
     }
+
+    bytes.truncate(size);
+
+    Ok(Some(bytes))
 }
 
 fn extract_file(
@@ -934,23 +1637,6 @@ fn extract_name(
     }
 }
 
-fn get_piece_value(core: &Core, p: &gimli::Piece<DwarfReader>) -> Option<u32> {
-    use gimli::Location;
-
-    match &p.location {
-        Location::Empty => None,
-        Location::Address { address } => Some(*address as u32),
-        Location::Value { value } => Some(value.to_u64(0xff_ff_ff_ff).unwrap() as u32),
-        Location::Register { register } => {
-            let val = core
-                .read_core_reg(register.0 as u16)
-                .expect("Failed to read register from target");
-            Some(val)
-        }
-        l => unimplemented!("Location {:?} not implemented", l),
-    }
-}
-
 pub(crate) fn _print_all_attributes(
     core: Core,
     frame_base: Option<u32>,
@@ -1040,3 +1726,173 @@ pub(crate) fn _print_all_attributes(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{expression::ExpressionContext, DebugInfo, Registers, R};
+    use crate::{Architecture, Core, CoreDump};
+    use gimli::write::{
+        Address, AttributeValue, DwarfUnit, EndianVec, LineProgram, LineString, Sections,
+    };
+    use gimli::LittleEndian;
+    use std::path::PathBuf;
+    use std::rc::Rc;
+
+    /// A function `outer` at 0x100..0x200, with `inner` inlined at 0x120..0x130.
+    fn inlined_debug_info() -> DebugInfo {
+        let encoding = gimli::Encoding {
+            format: gimli::Format::Dwarf32,
+            version: 4,
+            address_size: 4,
+        };
+        let mut dwarf = DwarfUnit::new(encoding);
+
+        let mut program = LineProgram::new(
+            encoding,
+            Default::default(),
+            LineString::String(b"/src".to_vec()),
+            LineString::String(b"main.rs".to_vec()),
+            None,
+        );
+        let directory = program.default_directory();
+        let file = program.add_file(LineString::String(b"lib.rs".to_vec()), directory, None);
+        dwarf.unit.line_program = program;
+
+        let root = dwarf.unit.root();
+        let unit = &mut dwarf.unit;
+        unit.get_mut(root).set(
+            gimli::DW_AT_comp_dir,
+            AttributeValue::String(b"/src".to_vec()),
+        );
+
+        let inner = unit.add(root, gimli::DW_TAG_subprogram);
+        unit.get_mut(inner)
+            .set(gimli::DW_AT_name, AttributeValue::String(b"inner".to_vec()));
+        let inner_variable = unit.add(inner, gimli::DW_TAG_variable);
+        unit.get_mut(inner_variable)
+            .set(gimli::DW_AT_name, AttributeValue::String(b"x".to_vec()));
+
+        let outer = unit.add(root, gimli::DW_TAG_subprogram);
+        let entry = unit.get_mut(outer);
+        entry.set(gimli::DW_AT_name, AttributeValue::String(b"outer".to_vec()));
+        entry.set(
+            gimli::DW_AT_low_pc,
+            AttributeValue::Address(Address::Constant(0x100)),
+        );
+        entry.set(gimli::DW_AT_high_pc, AttributeValue::Udata(0x100));
+        let outer_variable = unit.add(outer, gimli::DW_TAG_variable);
+        unit.get_mut(outer_variable)
+            .set(gimli::DW_AT_name, AttributeValue::String(b"a".to_vec()));
+
+        let inlined = unit.add(outer, gimli::DW_TAG_inlined_subroutine);
+        let entry = unit.get_mut(inlined);
+        entry.set(
+            gimli::DW_AT_abstract_origin,
+            AttributeValue::ThisUnitEntryRef(inner),
+        );
+        entry.set(
+            gimli::DW_AT_low_pc,
+            AttributeValue::Address(Address::Constant(0x120)),
+        );
+        entry.set(gimli::DW_AT_high_pc, AttributeValue::Udata(0x10));
+        entry.set(
+            gimli::DW_AT_call_file,
+            AttributeValue::FileIndex(Some(file)),
+        );
+        entry.set(gimli::DW_AT_call_line, AttributeValue::Udata(42));
+        entry.set(gimli::DW_AT_call_column, AttributeValue::Udata(5));
+        let inlined_variable = unit.add(inlined, gimli::DW_TAG_variable);
+        unit.get_mut(inlined_variable).set(
+            gimli::DW_AT_abstract_origin,
+            AttributeValue::ThisUnitEntryRef(inner_variable),
+        );
+
+        let mut sections = Sections::new(EndianVec::new(LittleEndian));
+        dwarf.write(&mut sections).unwrap();
+
+        let mut data = std::collections::HashMap::new();
+        sections
+            .for_each(|id, section| {
+                data.insert(id, section.slice().to_vec());
+                Ok::<_, gimli::Error>(())
+            })
+            .unwrap();
+
+        let reader = |bytes: &[u8]| R::new(Rc::from(bytes), LittleEndian);
+        DebugInfo {
+            dwarf: gimli::Dwarf::load(
+                |id| Ok::<_, gimli::Error>(reader(data.get(&id).map_or(&[][..], |d| &d[..]))),
+                |_| Ok::<_, gimli::Error>(reader(&[])),
+            )
+            .unwrap(),
+            frame_section: gimli::DebugFrame::from(reader(&[])),
+            address_size: 4,
+        }
+    }
+
+    #[test]
+    fn inlined_functions() {
+        let debug_info = inlined_debug_info();
+        let unit_info = debug_info
+            .get_next_unit_info(&mut debug_info.get_units())
+            .unwrap();
+
+        let names = |address| -> Vec<String> {
+            unit_info
+                .get_function_dies(address)
+                .unwrap()
+                .iter()
+                .map(|die| unit_info.get_function_name(die).unwrap().unwrap())
+                .collect()
+        };
+
+        assert_eq!(names(0x104), vec!["outer"]);
+        assert_eq!(names(0x124), vec!["outer", "inner"]);
+        assert!(names(0x200).is_empty());
+
+        let functions = unit_info.get_function_dies(0x124).unwrap();
+        let call_site = unit_info.get_call_site(&functions[1]).unwrap().unwrap();
+        assert_eq!(call_site.line, Some(42));
+        assert_eq!(call_site.column, Some(super::ColumnType::Column(5)));
+        assert_eq!(call_site.file.as_deref(), Some("lib.rs"));
+        assert_eq!(call_site.directory, Some(PathBuf::from("/src")));
+        assert!(unit_info.get_call_site(&functions[0]).unwrap().is_none());
+    }
+
+    #[test]
+    fn variables_of_inlined_functions() {
+        let debug_info = inlined_debug_info();
+        let unit_info = debug_info
+            .get_next_unit_info(&mut debug_info.get_units())
+            .unwrap();
+
+        let core = Core::from_dump(CoreDump::new(Architecture::ARM, vec![0; 18], vec![])).unwrap();
+        let registers = Registers::from_core(&core);
+        let context = ExpressionContext {
+            debug_info: &debug_info,
+            unit: Some(&unit_info.unit),
+            encoding: unit_info.unit.encoding(),
+            core: &core,
+            registers: &registers,
+            innermost: true,
+            frame_base: None,
+            cfa: None,
+            entry_registers: None,
+        };
+
+        let functions = unit_info.get_function_dies(0x124).unwrap();
+        let names = |function: &super::FunctionDie| -> Vec<String> {
+            unit_info
+                .get_variables(&context, 0x124, function.offset())
+                .unwrap()
+                .into_iter()
+                .map(|variable| variable.name)
+                .collect()
+        };
+
+        // Each frame only has its own variables, and the inlined variable
+        // takes its name from the abstract function.
+        assert_eq!(names(&functions[0]), vec!["a"]);
+        assert_eq!(names(&functions[1]), vec!["x"]);
+    }
+}
@@ -0,0 +1,557 @@
+//! Source level stepping with the line tables.
+//!
+//! The core runs to temporary hardware breakpoints at the line table rows of
+//! the current function and at its return address. If there are not enough
+//! free breakpoint units, or a step has to stop in called functions, the core
+//! is stepped by instructions instead.
+
+use super::expression::dwarf_stack_pointer;
+use super::{DebugError, DebugInfo, R};
+use crate::core::{Architecture, Core, CoreInformation};
+
+use std::ops::Range;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// How long to wait between checks if the core has halted.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Where a step stops.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StepMode {
+    /// At the next statement of the line table, even if it has the same line.
+    Row,
+    /// At the next line of the current function.
+    Over,
+    /// At the next line, which can be in a called function.
+    Into,
+}
+
+/// The statements of the function the program counter is in.
+#[derive(Debug, Default)]
+struct FunctionLines {
+    ranges: Vec<Range<u64>>,
+    /// The address, file index and line of the statements.
+    rows: Vec<(u64, u64, u64)>,
+}
+
+impl FunctionLines {
+    fn contains(&self, address: u64) -> bool {
+        self.ranges.iter().any(|range| range.contains(&address))
+    }
+
+    /// The addresses the step in `mode` stops at, when it starts at `pc`.
+    fn stops(&self, pc: u64, mode: StepMode) -> Vec<u32> {
+        let current = self
+            .rows
+            .iter()
+            .filter(|(address, _, _)| *address <= pc)
+            .max_by_key(|(address, _, _)| *address);
+
+        let mut stops: Vec<u32> = self
+            .rows
+            .iter()
+            .filter(|(address, file, line)| match (current, mode) {
+                (None, _) => *address != pc,
+                (Some(current), StepMode::Row) => *address != current.0,
+                (Some(current), _) => (*file, *line) != (current.1, current.2),
+            })
+            .map(|(address, _, _)| *address as u32)
+            .collect();
+
+        stops.sort_unstable();
+        stops.dedup();
+        stops
+    }
+}
+
+/// The address a function returns to, and the stack pointer after the return.
+#[derive(Debug, Clone, Copy)]
+struct ReturnSite {
+    address: u32,
+    stack_pointer: Option<u32>,
+}
+
+impl DebugInfo {
+    /// Steps to the next statement of the line table, even if it is on the
+    /// same line. Calls are stepped over.
+    pub fn step_line(
+        &self,
+        core: &mut Core,
+        timeout: Duration,
+    ) -> Result<CoreInformation, DebugError> {
+        self.step(core, StepMode::Row, timeout)
+    }
+
+    /// Steps to the next line of the current function, or to the caller if the
+    /// function returns. Calls are stepped over.
+    pub fn step_over(
+        &self,
+        core: &mut Core,
+        timeout: Duration,
+    ) -> Result<CoreInformation, DebugError> {
+        self.step(core, StepMode::Over, timeout)
+    }
+
+    /// Steps to the next line, which is the first line of a called function if
+    /// the function has line information.
+    pub fn step_into(
+        &self,
+        core: &mut Core,
+        timeout: Duration,
+    ) -> Result<CoreInformation, DebugError> {
+        self.step(core, StepMode::Into, timeout)
+    }
+
+    /// Runs until the current function returns to its caller.
+    pub fn step_out(
+        &self,
+        core: &mut Core,
+        timeout: Duration,
+    ) -> Result<CoreInformation, DebugError> {
+        let deadline = Instant::now() + timeout;
+        let pc = program_counter(core)?;
+
+        let return_site = self.return_site(core, pc).ok_or_else(|| {
+            DebugError::Unsupported(format!("The return address at {:#010x} is unknown", pc))
+        })?;
+
+        run_to_return(core, return_site, deadline, timeout)
+    }
+
+    fn step(
+        &self,
+        core: &mut Core,
+        mode: StepMode,
+        timeout: Duration,
+    ) -> Result<CoreInformation, DebugError> {
+        let deadline = Instant::now() + timeout;
+        let pc = program_counter(core)?;
+
+        let function = self.function_lines(pc)?;
+        if function.rows.is_empty() {
+            return Err(DebugError::NoLineInformation(pc as u32));
+        }
+
+        let stops = function.stops(pc, mode);
+        let return_site = self.return_site(core, pc);
+
+        let mut targets = stops.clone();
+        targets.extend(return_site.map(|site| site.address));
+
+        // The first instruction is stepped, so the core leaves a breakpoint at the program counter.
+        let mut info = core.step()?;
+
+        if mode != StepMode::Into
+            && !targets.contains(&info.pc)
+            && function.contains(u64::from(info.pc))
+        {
+            if let Some(halted) = run_to(core, &targets, deadline, timeout)? {
+                info = halted;
+            }
+        }
+
+        loop {
+            let pc = info.pc;
+
+            if stops.contains(&pc) {
+                return Ok(info);
+            }
+
+            if let Some(site) = return_site {
+                if pc == site.address && returned(core, site)? {
+                    return Ok(info);
+                }
+            }
+
+            if core.has_breakpoint(pc) {
+                return Ok(info);
+            }
+
+            if !function.contains(u64::from(pc)) && (targets.contains(&pc) || self.has_line(pc)) {
+                // A function with line information is called, or the
+                // function returned to a caller without a breakpoint.
+                if mode == StepMode::Into || !self.called_from(core, &function)? {
+                    return Ok(info);
+                }
+            }
+
+            if Instant::now() > deadline {
+                return Err(DebugError::StepTimeout(timeout));
+            }
+
+            if !function.contains(u64::from(pc)) && self.called_from(core, &function)? {
+                // A function without line information, or one which is
+                // stepped over, returns to the current function.
+                let site = ReturnSite {
+                    address: return_address(core)?,
+                    stack_pointer: Some(stack_pointer(core)?),
+                };
+                info = run_to_return(core, site, deadline, timeout)?;
+                continue;
+            }
+
+            info = core.step()?;
+        }
+    }
+
+    /// Checks if the current function was called from the function of `lines`,
+    /// by the return address the call left.
+    fn called_from(&self, core: &Core, lines: &FunctionLines) -> Result<bool, DebugError> {
+        Ok(lines.contains(u64::from(return_address(core)?)))
+    }
+
+    fn has_line(&self, address: u32) -> bool {
+        self.get_source_location(u64::from(address))
+            .and_then(|location| location.line)
+            .is_some()
+    }
+
+    /// The return site of the function at `pc`, which is the first frame of
+    /// the backtrace with another program counter.
+    fn return_site(&self, core: &Core, pc: u64) -> Option<ReturnSite> {
+        let frame = self
+            .try_unwind(core, pc)
+            .take(64)
+            .find(|frame| u64::from(frame.pc) != pc)?;
+
+        Some(ReturnSite {
+            address: frame.pc,
+            stack_pointer: frame
+                .registers
+                .get(dwarf_stack_pointer(core.architecture())),
+        })
+    }
+
+    /// Finds the ranges of the function at `pc`, and its statements.
+    fn function_lines(&self, pc: u64) -> Result<FunctionLines, DebugError> {
+        let mut units = self.get_units();
+
+        while let Some(unit_info) = self.get_next_unit_info(&mut units) {
+            let functions = unit_info.get_function_dies(pc)?;
+            let subprogram = match functions.first() {
+                Some(subprogram) => subprogram,
+                None => continue,
+            };
+
+            let mut lines = FunctionLines::default();
+
+            let mut ranges = self.dwarf.die_ranges(&unit_info.unit, subprogram)?;
+            while let Some(range) = ranges.next()? {
+                lines.ranges.push(range.begin..range.end);
+            }
+
+            if let Some(program) = unit_info.unit.line_program.clone() {
+                lines.rows = statements(program, &lines.ranges)?;
+            }
+
+            return Ok(lines);
+        }
+
+        Ok(FunctionLines::default())
+    }
+}
+
+/// The address, file index and line of the statements in `ranges`.
+fn statements(
+    program: gimli::IncompleteLineProgram<R>,
+    ranges: &[Range<u64>],
+) -> Result<Vec<(u64, u64, u64)>, DebugError> {
+    let mut statements = vec![];
+
+    let mut rows = program.rows();
+    while let Some((_, row)) = rows.next_row()? {
+        if row.end_sequence() || !row.is_stmt() {
+            continue;
+        }
+
+        let line = match row.line() {
+            Some(line) => line,
+            None => continue,
+        };
+
+        if ranges.iter().any(|range| range.contains(&row.address())) {
+            statements.push((row.address(), row.file_index(), line));
+        }
+    }
+
+    Ok(statements)
+}
+
+/// Runs to the return site, with a breakpoint or by stepping instructions.
+fn run_to_return(
+    core: &mut Core,
+    site: ReturnSite,
+    deadline: Instant,
+    timeout: Duration,
+) -> Result<CoreInformation, DebugError> {
+    loop {
+        // A breakpoint at the address the core is halted at would halt it
+        // again at once, so the instruction is executed first.
+        if program_counter(core)? == u64::from(site.address) {
+            core.step()?;
+        }
+
+        let info = match run_to(core, &[site.address], deadline, timeout)? {
+            Some(info) => info,
+            None => core.step()?,
+        };
+
+        if info.pc != site.address {
+            // A breakpoint of the user was hit.
+            if core.has_breakpoint(info.pc) {
+                return Ok(info);
+            }
+        } else if returned(core, site)? {
+            return Ok(info);
+        }
+
+        if Instant::now() > deadline {
+            return Err(DebugError::StepTimeout(timeout));
+        }
+    }
+}
+
+/// Runs the core until it halts at one of `addresses`, which get temporary
+/// breakpoints, or at another breakpoint. Returns `None` without running the
+/// core if there are not enough free breakpoint units.
+fn run_to(
+    core: &mut Core,
+    addresses: &[u32],
+    deadline: Instant,
+    timeout: Duration,
+) -> Result<Option<CoreInformation>, DebugError> {
+    // Addresses with a breakpoint of the user halt the core anyway.
+    let mut temporary: Vec<u32> = addresses
+        .iter()
+        .copied()
+        .filter(|address| !core.has_breakpoint(*address))
+        .collect();
+    temporary.sort_unstable();
+    temporary.dedup();
+
+    if temporary.len() > core.free_breakpoint_units()? {
+        return Ok(None);
+    }
+
+    let mut result = Ok(());
+    let mut set = Vec::with_capacity(temporary.len());
+
+    for address in &temporary {
+        match core.set_hw_breakpoint(*address) {
+            Ok(()) => set.push(*address),
+            Err(e) => {
+                result = Err(e.into());
+                break;
+            }
+        }
+    }
+
+    if result.is_ok() {
+        result = wait_for_halt(core, deadline, timeout);
+    }
+
+    // All breakpoints which were set are cleared, even if the core did not
+    // halt or clearing one of them failed. The first error is returned.
+    for address in &set {
+        if let Err(e) = core.clear_hw_breakpoint(*address) {
+            if result.is_ok() {
+                result = Err(e.into());
+            }
+        }
+    }
+
+    result?;
+
+    Ok(Some(CoreInformation {
+        pc: program_counter(core)? as u32,
+    }))
+}
+
+fn wait_for_halt(core: &Core, deadline: Instant, timeout: Duration) -> Result<(), DebugError> {
+    core.run()?;
+
+    while !core.poll_halted()? {
+        if Instant::now() > deadline {
+            core.halt()?;
+            return Err(DebugError::StepTimeout(timeout));
+        }
+
+        sleep(POLL_INTERVAL);
+    }
+
+    Ok(())
+}
+
+/// Checks if the core is at the return site in the frame of the caller, and
+/// not in a recursive call of the function.
+fn returned(core: &Core, site: ReturnSite) -> Result<bool, DebugError> {
+    Ok(match site.stack_pointer {
+        Some(expected) => stack_pointer(core)? >= expected,
+        None => true,
+    })
+}
+
+fn program_counter(core: &Core) -> Result<u64, DebugError> {
+    Ok(u64::from(
+        core.read_core_reg(core.registers().program_counter())?,
+    ))
+}
+
+fn stack_pointer(core: &Core) -> Result<u32, DebugError> {
+    Ok(core.read_core_reg(core.registers().stack_pointer())?)
+}
+
+/// The return address register, without the Thumb bit on ARM cores.
+fn return_address(core: &Core) -> Result<u32, DebugError> {
+    let address = core.read_core_reg(core.registers().return_address())?;
+
+    Ok(match core.architecture() {
+        Architecture::ARM => address & !1,
+        Architecture::RISCV => address,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run_to, run_to_return, FunctionLines, ReturnSite, StepMode};
+    use crate::debug::DebugError;
+    use crate::test_support::{MockCore, MockState};
+    use crate::{Core, Memory};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::{Duration, Instant};
+
+    const TIMEOUT: Duration = Duration::from_millis(50);
+
+    fn core(jumps: &[(u32, (u32, i32))]) -> (Core, Rc<RefCell<MockState>>) {
+        MockCore::start(jumps, Memory::new_dummy())
+    }
+
+    fn deadline() -> Instant {
+        Instant::now() + TIMEOUT
+    }
+
+    /// Line 10 at 0x100, line 11 at 0x104, 0x106 and 0x10c, line 12 at 0x108,
+    /// in two ranges.
+    fn lines() -> FunctionLines {
+        FunctionLines {
+            ranges: vec![0x100..0x108, 0x108..0x110],
+            rows: vec![
+                (0x100, 1, 10),
+                (0x104, 1, 11),
+                (0x106, 1, 11),
+                (0x108, 1, 12),
+                (0x10c, 1, 11),
+            ],
+        }
+    }
+
+    #[test]
+    fn stops_at_other_lines() {
+        let lines = lines();
+
+        assert_eq!(lines.stops(0x104, StepMode::Over), vec![0x100, 0x108]);
+        assert_eq!(lines.stops(0x106, StepMode::Into), vec![0x100, 0x108]);
+        assert_eq!(
+            lines.stops(0x10a, StepMode::Over),
+            vec![0x100, 0x104, 0x106, 0x10c]
+        );
+        assert!(lines.contains(0x10e));
+        assert!(!lines.contains(0x110));
+    }
+
+    #[test]
+    fn stops_at_other_rows() {
+        let lines = lines();
+
+        assert_eq!(
+            lines.stops(0x105, StepMode::Row),
+            vec![0x100, 0x106, 0x108, 0x10c]
+        );
+    }
+
+    #[test]
+    fn run_to_halts_at_the_first_address() {
+        let (mut core, state) = core(&[]);
+
+        let info = run_to(&mut core, &[0x10c, 0x108], deadline(), TIMEOUT)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(info.pc, 0x108);
+        assert_eq!(state.borrow().units, vec![None; 4]);
+    }
+
+    #[test]
+    fn run_to_steps_without_enough_units() {
+        let (mut core, state) = core(&[]);
+
+        let addresses = [0x104, 0x108, 0x10c, 0x110, 0x114];
+        assert!(run_to(&mut core, &addresses, deadline(), TIMEOUT)
+            .unwrap()
+            .is_none());
+        assert_eq!(state.borrow().pc(), 0x100);
+    }
+
+    #[test]
+    fn run_to_clears_breakpoints_if_setting_one_fails() {
+        let (mut core, state) = core(&[]);
+        state.borrow_mut().failing_set = Some(2);
+
+        assert!(run_to(&mut core, &[0x104, 0x108, 0x10c], deadline(), TIMEOUT).is_err());
+
+        let state = state.borrow();
+        assert_eq!(state.units, vec![None; 4]);
+        assert_eq!(state.pc(), 0x100);
+        assert!(!core.has_breakpoint(0x104));
+    }
+
+    #[test]
+    fn run_to_clears_remaining_breakpoints_if_clearing_one_fails() {
+        let (mut core, state) = core(&[]);
+        state.borrow_mut().failing_clear = Some(1);
+
+        assert!(run_to(&mut core, &[0x104, 0x108, 0x10c], deadline(), TIMEOUT).is_err());
+
+        let state = state.borrow();
+        assert_eq!(state.clear_calls, 3);
+        assert_eq!(state.units.iter().flatten().count(), 1);
+    }
+
+    #[test]
+    fn run_to_clears_breakpoints_on_timeout() {
+        // An endless loop.
+        let (mut core, state) = core(&[(0x100, (0x100, 0))]);
+
+        assert!(matches!(
+            run_to(&mut core, &[0x104], deadline(), TIMEOUT),
+            Err(DebugError::StepTimeout(_))
+        ));
+
+        let state = state.borrow();
+        assert!(state.halted);
+        assert_eq!(state.units, vec![None; 4]);
+    }
+
+    #[test]
+    fn return_of_recursive_call_is_skipped() {
+        // The function at 0x100 returns to 0x200 in a recursive call of
+        // itself first, which then returns to 0x200 again.
+        let (mut core, state) = core(&[
+            (0x100, (0x200, 8)),
+            (0x200, (0x104, 0)),
+            (0x104, (0x200, 8)),
+        ]);
+        state.borrow_mut().set_sp(0x2000_00f0);
+
+        let site = ReturnSite {
+            address: 0x200,
+            stack_pointer: Some(0x2000_0100),
+        };
+        let info = run_to_return(&mut core, site, deadline(), TIMEOUT).unwrap();
+
+        assert_eq!(info.pc, 0x200);
+        assert_eq!(state.borrow().sp(), 0x2000_0100);
+    }
+}
@@ -1,6 +1,542 @@
-#[derive(Debug, Default)]
-pub struct Type {
+/// The type of a variable, resolved from its DWARF type entries.
+///
+/// Typedefs and qualifiers like `const` are resolved to the type they refer to.
+/// Pointers do not contain the type they point to, so recursive types end.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Base {
+        name: String,
+        base: BaseType,
+    },
+    /// A pointer or a reference.
+    Pointer {
+        name: String,
+        size: usize,
+    },
+    Struct {
+        name: String,
+        size: usize,
+        members: Vec<Member>,
+    },
+    Array {
+        element: Box<Type>,
+        count: usize,
+    },
+    /// An enumeration with named values, like a C enum or a Rust enum without fields.
+    Enum {
+        name: String,
+        base: BaseType,
+        enumerators: Vec<Enumerator>,
+    },
+    /// An enum with fields, whose variant is selected by a discriminant.
+    Variant {
+        name: String,
+        size: usize,
+        /// The member which holds the discriminant, if there is more than one variant.
+        discriminant: Option<Box<Member>>,
+        variants: Vec<Variant>,
+    },
+    /// A type which can not be decoded, like a 128 bit integer or a union.
+    Unknown {
+        name: String,
+        size: usize,
+    },
+}
+
+/// A member of a struct, at an offset from the start of the struct.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Member {
+    pub name: String,
+    pub offset: usize,
+    pub typ: Type,
+}
+
+/// A named value of an [`Enum`](Type::Enum).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Enumerator {
     pub name: String,
-    pub named_children: Option<std::collections::HashMap<String, Type>>,
-    pub indexed_children: Option<Vec<Type>>,
+    pub value: i64,
+}
+
+/// A variant of a [`Variant`](Type::Variant) type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variant {
+    /// The value of the discriminant which selects the variant. The variant
+    /// without one is selected by all other values.
+    pub discriminant: Option<u64>,
+    pub member: Member,
+}
+
+impl Type {
+    pub fn name(&self) -> String {
+        match self {
+            Type::Base { name, .. }
+            | Type::Pointer { name, .. }
+            | Type::Struct { name, .. }
+            | Type::Enum { name, .. }
+            | Type::Variant { name, .. }
+            | Type::Unknown { name, .. } => name.clone(),
+            Type::Array { element, count } => format!("[{}; {}]", element.name(), count),
+        }
+    }
+
+    /// The size of a value in bytes.
+    pub fn size(&self) -> usize {
+        match self {
+            Type::Base { base, .. } | Type::Enum { base, .. } => base.size(),
+            Type::Pointer { size, .. }
+            | Type::Struct { size, .. }
+            | Type::Variant { size, .. }
+            | Type::Unknown { size, .. } => *size,
+            Type::Array { element, count } => element.size().saturating_mul(*count),
+        }
+    }
+
+    /// Decodes a little endian value of this type. The parts of the value
+    /// which are outside of `bytes` are unavailable.
+    pub fn decode(&self, bytes: &[u8]) -> VariableValue {
+        match self {
+            Type::Struct { members, .. } => VariableValue::Struct(
+                members
+                    .iter()
+                    .map(|member| (member.name.clone(), member.decode(bytes)))
+                    .collect(),
+            ),
+            Type::Array { element, count } => {
+                let size = element.size();
+                if size == 0 {
+                    return VariableValue::Array(vec![]);
+                }
+
+                // Large arrays are only read in part.
+                let available = (bytes.len() / size).min(*count);
+                VariableValue::Array(
+                    bytes
+                        .chunks_exact(size)
+                        .take(available)
+                        .map(|chunk| element.decode(chunk))
+                        .collect(),
+                )
+            }
+            _ if bytes.len() < self.size() => VariableValue::Unavailable,
+            Type::Base { base, .. } => VariableValue::Base(base.decode(bytes)),
+            Type::Pointer { size, .. } => {
+                let mut buffer = [0; 8];
+                let size = (*size).min(8);
+                buffer[..size].copy_from_slice(&bytes[..size]);
+                VariableValue::Pointer(u64::from_le_bytes(buffer))
+            }
+            Type::Enum {
+                base, enumerators, ..
+            } => {
+                let value = base.decode(bytes);
+
+                // The enumerator values can be stored with or without sign,
+                // so only the bits of the type are compared.
+                let mask = u64::MAX >> (64 - 8 * base.size());
+                let name = value.as_i64().and_then(|value| {
+                    enumerators
+                        .iter()
+                        .find(|enumerator| (enumerator.value ^ value) as u64 & mask == 0)
+                        .map(|enumerator| enumerator.name.clone())
+                });
+
+                VariableValue::Enum { name, value }
+            }
+            Type::Variant {
+                discriminant,
+                variants,
+                ..
+            } => {
+                let selected = match discriminant {
+                    Some(discriminant) => {
+                        let value = match discriminant.decode(bytes) {
+                            VariableValue::Base(value) | VariableValue::Enum { value, .. } => {
+                                value.as_i64().map(|value| value as u64)
+                            }
+                            _ => None,
+                        };
+
+                        match value {
+                            Some(value) => variants
+                                .iter()
+                                .find(|variant| variant.discriminant == Some(value))
+                                .or_else(|| {
+                                    variants
+                                        .iter()
+                                        .find(|variant| variant.discriminant.is_none())
+                                }),
+                            None => None,
+                        }
+                    }
+                    None => variants.first(),
+                };
+
+                match selected {
+                    Some(variant) => VariableValue::Variant {
+                        name: variant.member.name.clone(),
+                        value: Box::new(variant.member.decode(bytes)),
+                    },
+                    None => VariableValue::Unavailable,
+                }
+            }
+            Type::Unknown { .. } => VariableValue::Unavailable,
+        }
+    }
+}
+
+impl Member {
+    fn decode(&self, bytes: &[u8]) -> VariableValue {
+        match bytes.get(self.offset..) {
+            Some(bytes) => self.typ.decode(bytes),
+            None => VariableValue::Unavailable,
+        }
+    }
+}
+
+/// The value of a variable, decoded according to its [`Type`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum VariableValue {
+    Base(Value),
+    /// The address a pointer or a reference points to.
+    Pointer(u64),
+    /// The names and values of the members of a struct.
+    Struct(Vec<(String, VariableValue)>),
+    Array(Vec<VariableValue>),
+    /// The value of an enumeration, and the name of its enumerator if it has one.
+    Enum {
+        name: Option<String>,
+        value: Value,
+    },
+    /// The selected variant of an enum with fields, and the value of its fields.
+    Variant {
+        name: String,
+        value: Box<VariableValue>,
+    },
+    /// The value is optimized out, could not be read or has an unknown type.
+    Unavailable,
+}
+
+impl std::fmt::Display for VariableValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            VariableValue::Base(value) => write!(f, "{}", value),
+            VariableValue::Pointer(address) => write!(f, "{:#010x}", address),
+            VariableValue::Struct(members) => {
+                write!(f, "{{")?;
+                for (index, (name, value)) in members.iter().enumerate() {
+                    let separator = if index == 0 { " " } else { ", " };
+                    write!(f, "{}{}: {}", separator, name, value)?;
+                }
+                if members.is_empty() {
+                    write!(f, "}}")
+                } else {
+                    write!(f, " }}")
+                }
+            }
+            VariableValue::Array(elements) => {
+                write!(f, "[")?;
+                for (index, element) in elements.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                write!(f, "]")
+            }
+            VariableValue::Enum {
+                name: Some(name), ..
+            } => write!(f, "{}", name),
+            VariableValue::Enum { name: None, value } => write!(f, "{}", value),
+            VariableValue::Variant { name, value } => match value.as_ref() {
+                VariableValue::Struct(members) if members.is_empty() => write!(f, "{}", name),
+                value => write!(f, "{} {}", name, value),
+            },
+            VariableValue::Unavailable => write!(f, "<unavailable>"),
+        }
+    }
+}
+
+/// The type of a scalar value, which is a DWARF base type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BaseType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    I64,
+    U64,
+    F32,
+    F64,
+}
+
+impl BaseType {
+    /// Finds the base type with the DWARF encoding and size.
+    pub fn from_encoding(encoding: gimli::DwAte, byte_size: u64) -> Option<Self> {
+        let base_type = match (encoding, byte_size) {
+            (gimli::DW_ATE_signed, 1) | (gimli::DW_ATE_signed_char, 1) => BaseType::I8,
+            (gimli::DW_ATE_signed, 2) => BaseType::I16,
+            (gimli::DW_ATE_signed, 4) => BaseType::I32,
+            (gimli::DW_ATE_signed, 8) => BaseType::I64,
+            (gimli::DW_ATE_unsigned, 1)
+            | (gimli::DW_ATE_unsigned_char, 1)
+            | (gimli::DW_ATE_boolean, 1) => BaseType::U8,
+            (gimli::DW_ATE_unsigned, 2) => BaseType::U16,
+            (gimli::DW_ATE_unsigned, 4) => BaseType::U32,
+            (gimli::DW_ATE_unsigned, 8) => BaseType::U64,
+            (gimli::DW_ATE_float, 4) => BaseType::F32,
+            (gimli::DW_ATE_float, 8) => BaseType::F64,
+            _ => return None,
+        };
+
+        Some(base_type)
+    }
+
+    /// The size of a value in bytes.
+    pub fn size(self) -> usize {
+        match self {
+            BaseType::I8 | BaseType::U8 => 1,
+            BaseType::I16 | BaseType::U16 => 2,
+            BaseType::I32 | BaseType::U32 | BaseType::F32 => 4,
+            BaseType::I64 | BaseType::U64 | BaseType::F64 => 8,
+        }
+    }
+
+    /// Decodes a little endian value of this type, which has [`size`](Self::size) bytes.
+    pub fn decode(self, bytes: &[u8]) -> Value {
+        let mut buffer = [0; 8];
+        buffer[..self.size()].copy_from_slice(&bytes[..self.size()]);
+        let raw = u64::from_le_bytes(buffer);
+
+        match self {
+            BaseType::I8 => Value::Signed(i64::from(raw as u8 as i8)),
+            BaseType::I16 => Value::Signed(i64::from(raw as u16 as i16)),
+            BaseType::I32 => Value::Signed(i64::from(raw as u32 as i32)),
+            BaseType::I64 => Value::Signed(raw as i64),
+            BaseType::U8 | BaseType::U16 | BaseType::U32 | BaseType::U64 => Value::Unsigned(raw),
+            BaseType::F32 => Value::Float(f64::from(f32::from_bits(raw as u32))),
+            BaseType::F64 => Value::Float(f64::from_bits(raw)),
+        }
+    }
+}
+
+impl std::str::FromStr for BaseType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "i8" => Ok(BaseType::I8),
+            "u8" => Ok(BaseType::U8),
+            "i16" => Ok(BaseType::I16),
+            "u16" => Ok(BaseType::U16),
+            "i32" => Ok(BaseType::I32),
+            "u32" => Ok(BaseType::U32),
+            "i64" => Ok(BaseType::I64),
+            "u64" => Ok(BaseType::U64),
+            "f32" => Ok(BaseType::F32),
+            "f64" => Ok(BaseType::F64),
+            other => Err(format!("Unknown type '{}'", other)),
+        }
+    }
+}
+
+/// A value of a [`BaseType`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Signed(i64),
+    Unsigned(u64),
+    Float(f64),
+}
+
+impl Value {
+    /// The value as a signed integer, if it is an integer. Unsigned values
+    /// are converted bit for bit.
+    pub fn as_i64(self) -> Option<i64> {
+        match self {
+            Value::Signed(value) => Some(value),
+            Value::Unsigned(value) => Some(value as i64),
+            Value::Float(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Value::Signed(value) => write!(f, "{}", value),
+            Value::Unsigned(value) => write!(f, "{}", value),
+            Value::Float(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BaseType, Enumerator, Member, Type, Value, VariableValue, Variant};
+
+    fn base(base: BaseType) -> Type {
+        Type::Base {
+            name: String::new(),
+            base,
+        }
+    }
+
+    fn member(name: &str, offset: usize, typ: Type) -> Member {
+        Member {
+            name: name.to_owned(),
+            offset,
+            typ,
+        }
+    }
+
+    #[test]
+    fn decode_values() {
+        assert_eq!(BaseType::I8.decode(&[0xff]), Value::Signed(-1));
+        assert_eq!(BaseType::U16.decode(&[0x34, 0x12]), Value::Unsigned(0x1234));
+        assert_eq!(
+            BaseType::I32.decode(&[0xfe, 0xff, 0xff, 0xff]),
+            Value::Signed(-2)
+        );
+        assert_eq!(
+            BaseType::F32.decode(&1.5f32.to_le_bytes()),
+            Value::Float(1.5)
+        );
+        assert_eq!(
+            BaseType::F64.decode(&(-0.25f64).to_le_bytes()),
+            Value::Float(-0.25)
+        );
+    }
+
+    #[test]
+    fn dwarf_encodings() {
+        assert_eq!(
+            BaseType::from_encoding(gimli::DW_ATE_signed, 2),
+            Some(BaseType::I16)
+        );
+        assert_eq!(
+            BaseType::from_encoding(gimli::DW_ATE_boolean, 1),
+            Some(BaseType::U8)
+        );
+        assert_eq!(BaseType::from_encoding(gimli::DW_ATE_float, 2), None);
+    }
+
+    #[test]
+    fn decode_struct_with_array() {
+        let typ = Type::Struct {
+            name: "Sample".to_owned(),
+            size: 12,
+            members: vec![
+                member("flag", 0, base(BaseType::U8)),
+                member(
+                    "values",
+                    2,
+                    Type::Array {
+                        element: Box::new(base(BaseType::I16)),
+                        count: 3,
+                    },
+                ),
+                member(
+                    "next",
+                    8,
+                    Type::Pointer {
+                        name: "*const Sample".to_owned(),
+                        size: 4,
+                    },
+                ),
+            ],
+        };
+
+        let bytes = [1, 0, 0xff, 0xff, 2, 0, 3, 0, 0x00, 0x01, 0x00, 0x20];
+        let value = typ.decode(&bytes);
+
+        assert_eq!(
+            value,
+            VariableValue::Struct(vec![
+                ("flag".to_owned(), VariableValue::Base(Value::Unsigned(1))),
+                (
+                    "values".to_owned(),
+                    VariableValue::Array(vec![
+                        VariableValue::Base(Value::Signed(-1)),
+                        VariableValue::Base(Value::Signed(2)),
+                        VariableValue::Base(Value::Signed(3)),
+                    ])
+                ),
+                ("next".to_owned(), VariableValue::Pointer(0x2000_0100)),
+            ])
+        );
+        assert_eq!(
+            value.to_string(),
+            "{ flag: 1, values: [-1, 2, 3], next: 0x20000100 }"
+        );
+
+        // The members which were not read are unavailable.
+        assert_eq!(
+            typ.decode(&bytes[..4]).to_string(),
+            "{ flag: 1, values: [-1], next: <unavailable> }"
+        );
+    }
+
+    #[test]
+    fn decode_enum() {
+        let typ = Type::Enum {
+            name: "Level".to_owned(),
+            base: BaseType::I8,
+            enumerators: vec![
+                Enumerator {
+                    name: "Low".to_owned(),
+                    value: 0,
+                },
+                Enumerator {
+                    name: "Negative".to_owned(),
+                    value: 0xff,
+                },
+            ],
+        };
+
+        assert_eq!(typ.decode(&[0]).to_string(), "Low");
+        assert_eq!(typ.decode(&[0xff]).to_string(), "Negative");
+        assert_eq!(typ.decode(&[7]).to_string(), "7");
+    }
+
+    #[test]
+    fn decode_variant() {
+        // An `Option<u16>`, with the discriminant in the first byte.
+        let typ = Type::Variant {
+            name: "Option<u16>".to_owned(),
+            size: 4,
+            discriminant: Some(Box::new(member("__tag", 0, base(BaseType::U8)))),
+            variants: vec![
+                Variant {
+                    discriminant: Some(0),
+                    member: member(
+                        "None",
+                        0,
+                        Type::Struct {
+                            name: "None".to_owned(),
+                            size: 4,
+                            members: vec![],
+                        },
+                    ),
+                },
+                Variant {
+                    discriminant: Some(1),
+                    member: member(
+                        "Some",
+                        0,
+                        Type::Struct {
+                            name: "Some".to_owned(),
+                            size: 4,
+                            members: vec![member("__0", 2, base(BaseType::U16))],
+                        },
+                    ),
+                },
+            ],
+        };
+
+        assert_eq!(typ.decode(&[0, 0, 0, 0]).to_string(), "None");
+        assert_eq!(typ.decode(&[1, 0, 5, 0]).to_string(), "Some { __0: 5 }");
+        assert_eq!(typ.decode(&[2, 0, 5, 0]), VariableValue::Unavailable);
+    }
 }
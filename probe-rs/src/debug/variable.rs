@@ -1,10 +1,20 @@
 use super::*;
 
-#[derive(Debug, Default)]
+/// A variable of a stack frame, with its value read from the target.
+#[derive(Debug, Clone, PartialEq)]
 pub struct Variable {
     pub name: String,
     pub file: String,
     pub line: u64,
-    pub value: u64,
+    pub value: VariableValue,
     pub typ: Type,
 }
+
+/// A variable with a fixed address, like a global or a `static`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaticVariable {
+    pub name: String,
+    pub address: u32,
+    /// The type of the variable, if it is a scalar.
+    pub typ: Option<BaseType>,
+}
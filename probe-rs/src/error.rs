@@ -1,5 +1,7 @@
 use crate::config::RegistryError;
-use crate::DebugProbeError;
+use crate::rtt::RttError;
+use crate::svd::SvdError;
+use crate::{BreakpointId, CallError, CoreDumpError, DebugProbeError};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -14,6 +16,24 @@ pub enum Error {
     CoreNotFound(usize),
     #[error("Unable to load specification for chip: {0}")]
     ChipNotFound(#[from] RegistryError),
+    #[error("The value {0:#x} does not fit into a 32 bit register")]
+    RegisterValueTooLarge(u64),
+    #[error("No software breakpoint can be set at {0:#010x}, because it is not in RAM")]
+    BreakpointNotInRam(u32),
+    #[error("There is no breakpoint at {0:#010x}")]
+    BreakpointNotFound(u32),
+    #[error("There is already a breakpoint at {0:#010x}")]
+    BreakpointExists(u32),
+    #[error("The breakpoint {0:?} does not exist")]
+    UnknownBreakpoint(BreakpointId),
+    #[error("An error with the core dump occured: {0}")]
+    CoreDump(#[from] CoreDumpError),
+    #[error("A function call on the target failed: {0}")]
+    Call(#[from] CallError),
+    #[error("An RTT error occured: {0}")]
+    Rtt(#[from] RttError),
+    #[error("An SVD error occured: {0}")]
+    Svd(#[from] SvdError),
 }
 
 impl Error {
@@ -1,7 +1,7 @@
 use super::FlashProgress;
 use super::{FlashBuilder, FlashError, FlashFill, FlashLayout, FlashPage};
 use crate::config::{FlashAlgorithm, FlashRegion, MemoryRange};
-use crate::core::{Core, RegisterFile};
+use crate::core::{Architecture, Core, RegisterFile};
 use crate::error;
 use crate::memory::MemoryInterface;
 use crate::{session::Session, DebugProbeError};
@@ -545,6 +545,14 @@ impl<'a, O: Operation> ActiveFlasher<'a, O> {
         let algo = &self.flash_algorithm;
         let regs: &'static RegisterFile = self.core.registers();
 
+        // The functions return to the header of the algorithm, which halts the core.
+        let (static_base, return_address) = match self.core.architecture() {
+            // The static base is R9, and the return address needs the Thumb bit.
+            Architecture::ARM => (regs.platform_register(9), algo.load_address + 1),
+            // The static base is gp (x3), and the header is an `ebreak`.
+            Architecture::RISCV => (regs.platform_register(3), algo.load_address),
+        };
+
         [
             (regs.program_counter(), Some(pc)),
             (regs.argument_register(0), r0),
@@ -552,14 +560,14 @@ impl<'a, O: Operation> ActiveFlasher<'a, O> {
             (regs.argument_register(2), r2),
             (regs.argument_register(3), r3),
             (
-                regs.platform_register(9),
+                static_base,
                 if init { Some(algo.static_base) } else { None },
             ),
             (
                 regs.stack_pointer(),
                 if init { Some(algo.begin_stack) } else { None },
             ),
-            (regs.return_address(), Some(algo.load_address + 1)),
+            (regs.return_address(), Some(return_address)),
         ]
         .iter()
         .map(|(description, value)| {
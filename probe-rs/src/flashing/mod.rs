@@ -10,6 +10,7 @@ mod download;
 mod error;
 mod flasher;
 mod loader;
+pub mod option_bytes;
 mod progress;
 mod visualizer;
 
@@ -0,0 +1,352 @@
+//! Reading and programming of the option bytes of STM32 chips.
+//!
+//! The option bytes configure chip wide settings like the read protection level,
+//! the brown out reset level, the watchdog and the boot configuration. Their layout
+//! and the way they are programmed differ between the STM32 families, so each
+//! supported family has its own module with a typed representation.
+//!
+//! The family of a target is selected by the `option_bytes` field of a chip family
+//! definition.
+//!
+//! Most changes only take effect after the next reset of the chip. Be careful when
+//! changing the read protection: lowering it triggers a mass erase, and read
+//! protection level 2 disables the debug interface permanently.
+
+pub mod stm32f0;
+pub mod stm32f1;
+pub mod stm32f4;
+pub mod stm32g0;
+pub mod stm32h7;
+pub mod stm32l4;
+
+use crate::{error, Memory};
+use std::fmt;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// The keys which unlock the `FLASH_KEYR` register.
+const FLASH_KEYS: [u32; 2] = [0x4567_0123, 0xCDEF_89AB];
+
+/// The keys which unlock the `FLASH_OPTKEYR` register, except on the STM32F0 and STM32F1.
+const OPTION_KEYS: [u32; 2] = [0x0819_2A3B, 0x4C5D_6E7F];
+
+/// The maximum time programming the option bytes is allowed to take.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Error)]
+pub enum OptionBytesError {
+    #[error("Accessing the flash interface failed: {0}")]
+    Memory(#[source] error::Error),
+    #[error("The option bytes of this chip are not supported.")]
+    Unsupported,
+    #[error("The option byte field '{0}' does not exist for this chip.")]
+    UnknownField(String),
+    #[error("The value {value:#x} is not valid for the option byte field '{field}'.")]
+    InvalidValue { field: String, value: u32 },
+    #[error("The flash interface could not be unlocked.")]
+    Locked,
+    #[error("Programming the option bytes did not finish in time.")]
+    Timeout,
+    #[error("Programming the option bytes failed, the flash status is {0:#010x}.")]
+    ProgrammingFailed(u32),
+}
+
+/// The STM32 families whose option bytes can be accessed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionBytesFamily {
+    Stm32F0,
+    Stm32F1,
+    Stm32F4,
+    Stm32G0,
+    Stm32H7,
+    Stm32L4,
+}
+
+impl OptionBytesFamily {
+    /// Returns the family with the given name, as used in the `option_bytes`
+    /// field of the target descriptions, e.g. `stm32f4`.
+    pub fn from_name(name: impl AsRef<str>) -> Option<Self> {
+        match &name.as_ref().to_ascii_lowercase()[..] {
+            "stm32f0" => Some(Self::Stm32F0),
+            "stm32f1" => Some(Self::Stm32F1),
+            "stm32f4" => Some(Self::Stm32F4),
+            "stm32g0" => Some(Self::Stm32G0),
+            "stm32h7" => Some(Self::Stm32H7),
+            "stm32l4" => Some(Self::Stm32L4),
+            _ => None,
+        }
+    }
+
+    /// Reads the current option bytes of a chip of this family.
+    pub fn read(self, memory: &Memory) -> Result<OptionBytes, OptionBytesError> {
+        Ok(match self {
+            Self::Stm32F0 => OptionBytes::Stm32F0(stm32f0::OptionBytes::read(memory)?),
+            Self::Stm32F1 => OptionBytes::Stm32F1(stm32f1::OptionBytes::read(memory)?),
+            Self::Stm32F4 => OptionBytes::Stm32F4(stm32f4::OptionBytes::read(memory)?),
+            Self::Stm32G0 => OptionBytes::Stm32G0(stm32g0::OptionBytes::read(memory)?),
+            Self::Stm32H7 => OptionBytes::Stm32H7(stm32h7::OptionBytes::read(memory)?),
+            Self::Stm32L4 => OptionBytes::Stm32L4(stm32l4::OptionBytes::read(memory)?),
+        })
+    }
+}
+
+/// The option bytes of a chip of any of the supported families.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptionBytes {
+    Stm32F0(stm32f0::OptionBytes),
+    Stm32F1(stm32f1::OptionBytes),
+    Stm32F4(stm32f4::OptionBytes),
+    Stm32G0(stm32g0::OptionBytes),
+    Stm32H7(stm32h7::OptionBytes),
+    Stm32L4(stm32l4::OptionBytes),
+}
+
+impl OptionBytes {
+    /// Programs the option bytes into the chip.
+    pub fn write(&self, memory: &Memory) -> Result<(), OptionBytesError> {
+        match self {
+            Self::Stm32F0(option_bytes) => option_bytes.write(memory),
+            Self::Stm32F1(option_bytes) => option_bytes.write(memory),
+            Self::Stm32F4(option_bytes) => option_bytes.write(memory),
+            Self::Stm32G0(option_bytes) => option_bytes.write(memory),
+            Self::Stm32H7(option_bytes) => option_bytes.write(memory),
+            Self::Stm32L4(option_bytes) => option_bytes.write(memory),
+        }
+    }
+
+    /// Returns all fields of the option bytes, with their current values.
+    pub fn fields(&self) -> Vec<OptionBytesField> {
+        match self {
+            Self::Stm32F0(option_bytes) => option_bytes.fields(),
+            Self::Stm32F1(option_bytes) => option_bytes.fields(),
+            Self::Stm32F4(option_bytes) => option_bytes.fields(),
+            Self::Stm32G0(option_bytes) => option_bytes.fields(),
+            Self::Stm32H7(option_bytes) => option_bytes.fields(),
+            Self::Stm32L4(option_bytes) => option_bytes.fields(),
+        }
+    }
+
+    /// Sets the field called `name` to `value`.
+    ///
+    /// The change is only applied to the chip with [`OptionBytes::write`].
+    ///
+    /// [`OptionBytes::write`]: enum.OptionBytes.html#method.write
+    pub fn set_field(&mut self, name: &str, value: u32) -> Result<(), OptionBytesError> {
+        match self {
+            Self::Stm32F0(option_bytes) => option_bytes.set_field(name, value),
+            Self::Stm32F1(option_bytes) => option_bytes.set_field(name, value),
+            Self::Stm32F4(option_bytes) => option_bytes.set_field(name, value),
+            Self::Stm32G0(option_bytes) => option_bytes.set_field(name, value),
+            Self::Stm32H7(option_bytes) => option_bytes.set_field(name, value),
+            Self::Stm32L4(option_bytes) => option_bytes.set_field(name, value),
+        }
+    }
+
+    /// Returns the read protection level.
+    pub fn read_protection(&self) -> ReadProtection {
+        match self {
+            Self::Stm32F0(option_bytes) => option_bytes.read_protection,
+            Self::Stm32F1(option_bytes) => option_bytes.read_protection,
+            Self::Stm32F4(option_bytes) => option_bytes.read_protection,
+            Self::Stm32G0(option_bytes) => option_bytes.read_protection,
+            Self::Stm32H7(option_bytes) => option_bytes.read_protection,
+            Self::Stm32L4(option_bytes) => option_bytes.read_protection,
+        }
+    }
+}
+
+impl fmt::Display for OptionBytes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for field in self.fields() {
+            writeln!(
+                f,
+                "{:<20} {:#6x}  {}",
+                field.name, field.value, field.description
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A single field of the option bytes, used to list and modify the
+/// option bytes without knowing the family of the chip.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptionBytesField {
+    /// The name of the field, e.g. `rdp`.
+    pub name: &'static str,
+    /// The current value of the field.
+    pub value: u32,
+    /// A short description of the field.
+    pub description: &'static str,
+}
+
+impl OptionBytesField {
+    fn new(name: &'static str, value: impl Into<u32>, description: &'static str) -> Self {
+        Self {
+            name,
+            value: value.into(),
+            description,
+        }
+    }
+}
+
+/// The read protection level of the flash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadProtection {
+    /// No protection.
+    Level0,
+    /// The flash cannot be read by a debugger. Going back to level 0 mass erases the flash.
+    Level1,
+    /// The debug interface is disabled permanently. This cannot be undone.
+    Level2,
+}
+
+impl ReadProtection {
+    /// Decodes the `RDP` byte, which uses `0xAA` for level 0 and `0xCC` for level 2.
+    fn from_byte(value: u8) -> Self {
+        match value {
+            0xAA => ReadProtection::Level0,
+            0xCC => ReadProtection::Level2,
+            _ => ReadProtection::Level1,
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            ReadProtection::Level0 => 0xAA,
+            ReadProtection::Level1 => 0xBB,
+            ReadProtection::Level2 => 0xCC,
+        }
+    }
+
+    fn from_level(field: &str, level: u32) -> Result<Self, OptionBytesError> {
+        match level {
+            0 => Ok(ReadProtection::Level0),
+            1 => Ok(ReadProtection::Level1),
+            2 => Ok(ReadProtection::Level2),
+            value => Err(invalid_value(field, value)),
+        }
+    }
+
+    /// The number of the protection level.
+    pub fn level(self) -> u32 {
+        match self {
+            ReadProtection::Level0 => 0,
+            ReadProtection::Level1 => 1,
+            ReadProtection::Level2 => 2,
+        }
+    }
+}
+
+fn invalid_value(field: &str, value: u32) -> OptionBytesError {
+    OptionBytesError::InvalidValue {
+        field: field.to_owned(),
+        value,
+    }
+}
+
+/// Converts `value` into a single bit flag.
+fn bit(field: &str, value: u32) -> Result<bool, OptionBytesError> {
+    match value {
+        0 => Ok(false),
+        1 => Ok(true),
+        value => Err(invalid_value(field, value)),
+    }
+}
+
+/// Checks that `value` fits into a field which is `width` bits wide.
+fn bits(field: &str, value: u32, width: u32) -> Result<u32, OptionBytesError> {
+    if value >> width == 0 {
+        Ok(value)
+    } else {
+        Err(invalid_value(field, value))
+    }
+}
+
+fn read32(memory: &Memory, address: u32) -> Result<u32, OptionBytesError> {
+    memory.read32(address).map_err(OptionBytesError::Memory)
+}
+
+fn write32(memory: &Memory, address: u32, value: u32) -> Result<(), OptionBytesError> {
+    memory
+        .write32(address, value)
+        .map_err(OptionBytesError::Memory)
+}
+
+/// Sets `bits` in the register at `address`.
+fn set_bits(memory: &Memory, address: u32, bits: u32) -> Result<(), OptionBytesError> {
+    let value = read32(memory, address)?;
+    write32(memory, address, value | bits)
+}
+
+/// Clears `bits` in the register at `address`.
+fn clear_bits(memory: &Memory, address: u32, bits: u32) -> Result<(), OptionBytesError> {
+    let value = read32(memory, address)?;
+    write32(memory, address, value & !bits)
+}
+
+/// Writes the two `keys` to the key register at `key_address`, if `lock_bit`
+/// is set in the register at `lock_address`.
+fn unlock(
+    memory: &Memory,
+    lock_address: u32,
+    lock_bit: u32,
+    key_address: u32,
+    keys: [u32; 2],
+) -> Result<(), OptionBytesError> {
+    if read32(memory, lock_address)? & lock_bit == 0 {
+        return Ok(());
+    }
+
+    write32(memory, key_address, keys[0])?;
+    write32(memory, key_address, keys[1])?;
+
+    if read32(memory, lock_address)? & lock_bit != 0 {
+        return Err(OptionBytesError::Locked);
+    }
+
+    Ok(())
+}
+
+/// Waits until `busy_mask` is cleared in the register at `address`,
+/// and returns the last value of the register.
+fn wait_while_busy(memory: &Memory, address: u32, busy_mask: u32) -> Result<u32, OptionBytesError> {
+    let start = Instant::now();
+
+    loop {
+        let status = read32(memory, address)?;
+        if status & busy_mask == 0 {
+            return Ok(status);
+        }
+        if start.elapsed() > TIMEOUT {
+            return Err(OptionBytesError::Timeout);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OptionBytesFamily, ReadProtection};
+
+    #[test]
+    fn family_from_name() {
+        assert_eq!(
+            OptionBytesFamily::from_name("STM32F4"),
+            Some(OptionBytesFamily::Stm32F4)
+        );
+        assert_eq!(OptionBytesFamily::from_name("stm32l0"), None);
+    }
+
+    #[test]
+    fn read_protection_encoding() {
+        assert_eq!(ReadProtection::from_byte(0xAA), ReadProtection::Level0);
+        assert_eq!(ReadProtection::from_byte(0xCC), ReadProtection::Level2);
+        // Every other value is level 1.
+        assert_eq!(ReadProtection::from_byte(0x00), ReadProtection::Level1);
+        assert_eq!(ReadProtection::from_byte(0xFF), ReadProtection::Level1);
+        assert_eq!(
+            ReadProtection::from_byte(ReadProtection::Level1.to_byte()),
+            ReadProtection::Level1
+        );
+    }
+}
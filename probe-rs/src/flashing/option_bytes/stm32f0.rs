@@ -0,0 +1,260 @@
+//! Option bytes of the STM32F0 family.
+//!
+//! The option bytes are stored in a separate flash area, where each byte is
+//! followed by its complement. They are programmed by erasing the whole area
+//! and writing it again, one half word at a time.
+
+use super::{
+    bit, clear_bits, read32, set_bits, unlock, wait_while_busy, write32, OptionBytesError,
+    OptionBytesField, ReadProtection, FLASH_KEYS,
+};
+use crate::Memory;
+
+/// The address of the option bytes area.
+pub(super) const OPTION_BYTES: u32 = 0x1FFF_F800;
+
+const FLASH_KEYR: u32 = 0x4002_2004;
+const FLASH_OPTKEYR: u32 = 0x4002_2008;
+const FLASH_SR: u32 = 0x4002_200C;
+const FLASH_CR: u32 = 0x4002_2010;
+
+const SR_BSY: u32 = 1 << 0;
+const SR_PGERR: u32 = 1 << 2;
+const SR_WRPRTERR: u32 = 1 << 4;
+const SR_EOP: u32 = 1 << 5;
+
+const CR_OPTPG: u32 = 1 << 4;
+const CR_OPTER: u32 = 1 << 5;
+const CR_STRT: u32 = 1 << 6;
+const CR_LOCK: u32 = 1 << 7;
+const CR_OPTWRE: u32 = 1 << 9;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptionBytes {
+    pub read_protection: ReadProtection,
+    /// Software watchdog, the IWDG is only started by software.
+    pub wdg_sw: bool,
+    /// No reset is generated when entering stop mode.
+    pub nrst_stop: bool,
+    /// No reset is generated when entering standby mode.
+    pub nrst_stdby: bool,
+    /// Replaces the BOOT0 pin if `boot_sel` is cleared. Only on STM32F04x and STM32F09x.
+    pub nboot0: bool,
+    /// Selects the boot mode together with BOOT0.
+    pub nboot1: bool,
+    /// The VDDA power supply supervisor is enabled.
+    pub vdda_monitor: bool,
+    /// The RAM parity check is disabled.
+    pub ram_parity_check: bool,
+    /// The BOOT0 pin is used instead of `nboot0`. Only on STM32F04x and STM32F09x.
+    pub boot_sel: bool,
+    /// User data, not used by the chip.
+    pub data0: u8,
+    /// User data, not used by the chip.
+    pub data1: u8,
+    /// The write protection of the flash, one bit per group of sectors.
+    /// A cleared bit enables the protection.
+    pub wrp: u32,
+}
+
+impl OptionBytes {
+    fn from_bytes(bytes: [u8; 8]) -> Self {
+        let user = bytes[1];
+
+        Self {
+            read_protection: ReadProtection::from_byte(bytes[0]),
+            wdg_sw: user & (1 << 0) != 0,
+            nrst_stop: user & (1 << 1) != 0,
+            nrst_stdby: user & (1 << 2) != 0,
+            nboot0: user & (1 << 3) != 0,
+            nboot1: user & (1 << 4) != 0,
+            vdda_monitor: user & (1 << 5) != 0,
+            ram_parity_check: user & (1 << 6) != 0,
+            boot_sel: user & (1 << 7) != 0,
+            data0: bytes[2],
+            data1: bytes[3],
+            wrp: u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+        }
+    }
+
+    fn to_bytes(&self) -> [u8; 8] {
+        let user = self.wdg_sw as u8
+            | (self.nrst_stop as u8) << 1
+            | (self.nrst_stdby as u8) << 2
+            | (self.nboot0 as u8) << 3
+            | (self.nboot1 as u8) << 4
+            | (self.vdda_monitor as u8) << 5
+            | (self.ram_parity_check as u8) << 6
+            | (self.boot_sel as u8) << 7;
+
+        let wrp = self.wrp.to_le_bytes();
+
+        [
+            self.read_protection.to_byte(),
+            user,
+            self.data0,
+            self.data1,
+            wrp[0],
+            wrp[1],
+            wrp[2],
+            wrp[3],
+        ]
+    }
+
+    /// Reads the option bytes from the option bytes area.
+    pub fn read(memory: &Memory) -> Result<Self, OptionBytesError> {
+        Ok(Self::from_bytes(read_bytes(memory)?))
+    }
+
+    /// Erases the option bytes area and programs these option bytes.
+    pub fn write(&self, memory: &Memory) -> Result<(), OptionBytesError> {
+        program(memory, self.to_bytes())
+    }
+
+    pub fn fields(&self) -> Vec<OptionBytesField> {
+        vec![
+            OptionBytesField::new("rdp", self.read_protection.level(), "Read protection level"),
+            OptionBytesField::new("wdg_sw", self.wdg_sw, "Software watchdog"),
+            OptionBytesField::new("nrst_stop", self.nrst_stop, "No reset in stop mode"),
+            OptionBytesField::new("nrst_stdby", self.nrst_stdby, "No reset in standby mode"),
+            OptionBytesField::new("nboot0", self.nboot0, "Software BOOT0"),
+            OptionBytesField::new("nboot1", self.nboot1, "Boot mode selection"),
+            OptionBytesField::new("vdda_monitor", self.vdda_monitor, "VDDA supervisor enabled"),
+            OptionBytesField::new(
+                "ram_parity_check",
+                self.ram_parity_check,
+                "RAM parity check disabled",
+            ),
+            OptionBytesField::new("boot_sel", self.boot_sel, "Use the BOOT0 pin"),
+            OptionBytesField::new("data0", self.data0, "User data"),
+            OptionBytesField::new("data1", self.data1, "User data"),
+            OptionBytesField::new("wrp", self.wrp, "Write protection (0 = protected)"),
+        ]
+    }
+
+    pub fn set_field(&mut self, name: &str, value: u32) -> Result<(), OptionBytesError> {
+        match name {
+            "rdp" => self.read_protection = ReadProtection::from_level(name, value)?,
+            "wdg_sw" => self.wdg_sw = bit(name, value)?,
+            "nrst_stop" => self.nrst_stop = bit(name, value)?,
+            "nrst_stdby" => self.nrst_stdby = bit(name, value)?,
+            "nboot0" => self.nboot0 = bit(name, value)?,
+            "nboot1" => self.nboot1 = bit(name, value)?,
+            "vdda_monitor" => self.vdda_monitor = bit(name, value)?,
+            "ram_parity_check" => self.ram_parity_check = bit(name, value)?,
+            "boot_sel" => self.boot_sel = bit(name, value)?,
+            "data0" => self.data0 = byte(name, value)?,
+            "data1" => self.data1 = byte(name, value)?,
+            "wrp" => self.wrp = value,
+            _ => return Err(OptionBytesError::UnknownField(name.to_owned())),
+        }
+
+        Ok(())
+    }
+}
+
+pub(super) fn byte(field: &str, value: u32) -> Result<u8, OptionBytesError> {
+    super::bits(field, value, 8).map(|value| value as u8)
+}
+
+/// Reads the eight option bytes, without their complements.
+pub(super) fn read_bytes(memory: &Memory) -> Result<[u8; 8], OptionBytesError> {
+    let mut bytes = [0; 8];
+
+    for (i, pair) in bytes.chunks_mut(2).enumerate() {
+        let word = read32(memory, OPTION_BYTES + 4 * i as u32)?;
+        pair[0] = word as u8;
+        pair[1] = (word >> 16) as u8;
+    }
+
+    Ok(bytes)
+}
+
+/// Programs the eight option bytes, which is the same for the STM32F0 and STM32F1.
+pub(super) fn program(memory: &Memory, bytes: [u8; 8]) -> Result<(), OptionBytesError> {
+    unlock(memory, FLASH_CR, CR_LOCK, FLASH_KEYR, FLASH_KEYS)?;
+
+    // The option bytes are writable when OPTWRE is set.
+    if read32(memory, FLASH_CR)? & CR_OPTWRE == 0 {
+        write32(memory, FLASH_OPTKEYR, FLASH_KEYS[0])?;
+        write32(memory, FLASH_OPTKEYR, FLASH_KEYS[1])?;
+
+        if read32(memory, FLASH_CR)? & CR_OPTWRE == 0 {
+            return Err(OptionBytesError::Locked);
+        }
+    }
+
+    let result = erase_and_program(memory, bytes);
+
+    // Lock the flash again, even if programming failed.
+    clear_bits(memory, FLASH_CR, CR_OPTPG | CR_OPTER | CR_OPTWRE)?;
+    set_bits(memory, FLASH_CR, CR_LOCK)?;
+
+    result
+}
+
+fn erase_and_program(memory: &Memory, bytes: [u8; 8]) -> Result<(), OptionBytesError> {
+    // Clear the flags of earlier operations.
+    write32(memory, FLASH_SR, SR_PGERR | SR_WRPRTERR | SR_EOP)?;
+
+    set_bits(memory, FLASH_CR, CR_OPTER)?;
+    set_bits(memory, FLASH_CR, CR_STRT)?;
+    check_status(wait_while_busy(memory, FLASH_SR, SR_BSY)?)?;
+    clear_bits(memory, FLASH_CR, CR_OPTER)?;
+
+    set_bits(memory, FLASH_CR, CR_OPTPG)?;
+
+    for (i, byte) in bytes.iter().enumerate() {
+        // Each byte is stored together with its complement.
+        let half_word = u16::from(*byte) | u16::from(!*byte) << 8;
+
+        memory
+            .write16(OPTION_BYTES + 2 * i as u32, half_word)
+            .map_err(OptionBytesError::Memory)?;
+
+        check_status(wait_while_busy(memory, FLASH_SR, SR_BSY)?)?;
+    }
+
+    Ok(())
+}
+
+fn check_status(status: u32) -> Result<(), OptionBytesError> {
+    if status & (SR_PGERR | SR_WRPRTERR) != 0 {
+        Err(OptionBytesError::ProgrammingFailed(status))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OptionBytes;
+    use crate::flashing::option_bytes::ReadProtection;
+
+    #[test]
+    fn decode_default_option_bytes() {
+        let option_bytes =
+            OptionBytes::from_bytes([0xAA, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+
+        assert_eq!(option_bytes.read_protection, ReadProtection::Level0);
+        assert!(option_bytes.wdg_sw);
+        assert!(option_bytes.boot_sel);
+        assert_eq!(option_bytes.wrp, 0xFFFF_FFFF);
+    }
+
+    #[test]
+    fn set_field() {
+        let mut option_bytes =
+            OptionBytes::from_bytes([0xAA, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+
+        option_bytes.set_field("nboot1", 0).unwrap();
+        option_bytes.set_field("data0", 0x12).unwrap();
+
+        assert_eq!(
+            option_bytes.to_bytes(),
+            [0xAA, 0xEF, 0x12, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]
+        );
+        assert!(option_bytes.set_field("data1", 0x100).is_err());
+        assert!(option_bytes.set_field("bor_lev", 0).is_err());
+    }
+}
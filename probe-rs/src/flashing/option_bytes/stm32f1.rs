@@ -0,0 +1,140 @@
+//! Option bytes of the STM32F1 family.
+//!
+//! The option bytes use the same layout and programming sequence as the STM32F0,
+//! but have fewer user options and no read protection level 2.
+
+use super::stm32f0::{byte, program, read_bytes};
+use super::{bit, invalid_value, OptionBytesError, OptionBytesField, ReadProtection};
+use crate::Memory;
+
+/// The value of the `RDP` byte which disables the read protection.
+const RDP_LEVEL0: u8 = 0xA5;
+
+/// The bits of the `USER` byte which have a field.
+const USER_MASK: u8 = 0b111;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptionBytes {
+    /// Either level 0 or level 1, level 2 is not supported.
+    pub read_protection: ReadProtection,
+    /// Software watchdog, the IWDG is only started by software.
+    pub wdg_sw: bool,
+    /// No reset is generated when entering stop mode.
+    pub nrst_stop: bool,
+    /// No reset is generated when entering standby mode.
+    pub nrst_stdby: bool,
+    /// User data, not used by the chip.
+    pub data0: u8,
+    /// User data, not used by the chip.
+    pub data1: u8,
+    /// The write protection of the flash, one bit per group of pages.
+    /// A cleared bit enables the protection.
+    pub wrp: u32,
+    /// The `USER` byte as read, so reserved bits are kept.
+    user: u8,
+}
+
+impl OptionBytes {
+    fn from_bytes(bytes: [u8; 8]) -> Self {
+        let user = bytes[1];
+
+        Self {
+            read_protection: if bytes[0] == RDP_LEVEL0 {
+                ReadProtection::Level0
+            } else {
+                ReadProtection::Level1
+            },
+            wdg_sw: user & (1 << 0) != 0,
+            nrst_stop: user & (1 << 1) != 0,
+            nrst_stdby: user & (1 << 2) != 0,
+            data0: bytes[2],
+            data1: bytes[3],
+            wrp: u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+            user,
+        }
+    }
+
+    fn to_bytes(&self) -> [u8; 8] {
+        let user = self.user & !USER_MASK
+            | self.wdg_sw as u8
+            | (self.nrst_stop as u8) << 1
+            | (self.nrst_stdby as u8) << 2;
+
+        let rdp = match self.read_protection {
+            ReadProtection::Level0 => RDP_LEVEL0,
+            _ => ReadProtection::Level1.to_byte(),
+        };
+
+        let wrp = self.wrp.to_le_bytes();
+
+        [
+            rdp, user, self.data0, self.data1, wrp[0], wrp[1], wrp[2], wrp[3],
+        ]
+    }
+
+    /// Reads the option bytes from the option bytes area.
+    pub fn read(memory: &Memory) -> Result<Self, OptionBytesError> {
+        Ok(Self::from_bytes(read_bytes(memory)?))
+    }
+
+    /// Erases the option bytes area and programs these option bytes.
+    pub fn write(&self, memory: &Memory) -> Result<(), OptionBytesError> {
+        program(memory, self.to_bytes())
+    }
+
+    pub fn fields(&self) -> Vec<OptionBytesField> {
+        vec![
+            OptionBytesField::new("rdp", self.read_protection.level(), "Read protection level"),
+            OptionBytesField::new("wdg_sw", self.wdg_sw, "Software watchdog"),
+            OptionBytesField::new("nrst_stop", self.nrst_stop, "No reset in stop mode"),
+            OptionBytesField::new("nrst_stdby", self.nrst_stdby, "No reset in standby mode"),
+            OptionBytesField::new("data0", self.data0, "User data"),
+            OptionBytesField::new("data1", self.data1, "User data"),
+            OptionBytesField::new("wrp", self.wrp, "Write protection (0 = protected)"),
+        ]
+    }
+
+    pub fn set_field(&mut self, name: &str, value: u32) -> Result<(), OptionBytesError> {
+        match name {
+            "rdp" => {
+                self.read_protection = match ReadProtection::from_level(name, value)? {
+                    ReadProtection::Level2 => return Err(invalid_value(name, value)),
+                    level => level,
+                }
+            }
+            "wdg_sw" => self.wdg_sw = bit(name, value)?,
+            "nrst_stop" => self.nrst_stop = bit(name, value)?,
+            "nrst_stdby" => self.nrst_stdby = bit(name, value)?,
+            "data0" => self.data0 = byte(name, value)?,
+            "data1" => self.data1 = byte(name, value)?,
+            "wrp" => self.wrp = value,
+            _ => return Err(OptionBytesError::UnknownField(name.to_owned())),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OptionBytes;
+    use crate::flashing::option_bytes::ReadProtection;
+
+    #[test]
+    fn read_protection() {
+        let mut option_bytes =
+            OptionBytes::from_bytes([0xA5, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(option_bytes.read_protection, ReadProtection::Level0);
+
+        // Level 2 does not exist on the STM32F1.
+        assert!(option_bytes.set_field("rdp", 2).is_err());
+
+        option_bytes.set_field("rdp", 1).unwrap();
+        option_bytes.set_field("wdg_sw", 0).unwrap();
+
+        let bytes = option_bytes.to_bytes();
+        assert_ne!(bytes[0], 0xA5);
+        // The reserved bits of the USER byte are kept.
+        assert_eq!(bytes[1], 0xFE);
+    }
+}
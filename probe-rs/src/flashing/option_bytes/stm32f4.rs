@@ -0,0 +1,157 @@
+//! Option bytes of the STM32F4 family.
+//!
+//! The option bytes are accessed through the `FLASH_OPTCR` register, which is
+//! programmed into the option bytes by setting `OPTSTRT`.
+
+use super::{
+    bit, bits, read32, set_bits, unlock, wait_while_busy, write32, OptionBytesError,
+    OptionBytesField, ReadProtection, OPTION_KEYS,
+};
+use crate::Memory;
+
+const FLASH_OPTKEYR: u32 = 0x4002_3C08;
+const FLASH_SR: u32 = 0x4002_3C0C;
+const FLASH_OPTCR: u32 = 0x4002_3C14;
+
+const SR_ERRORS: u32 = 0b1111 << 4;
+const SR_BSY: u32 = 1 << 16;
+
+const OPTCR_OPTLOCK: u32 = 1 << 0;
+const OPTCR_OPTSTRT: u32 = 1 << 1;
+
+/// The bits of `FLASH_OPTCR` which have a field.
+const OPTCR_MASK: u32 = 0x0FFF_FFEC;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptionBytes {
+    pub read_protection: ReadProtection,
+    /// The brown out reset level, from `0` (level 3, highest threshold) to `3` (off).
+    pub bor_level: u8,
+    /// Software watchdog, the IWDG is only started by software.
+    pub wdg_sw: bool,
+    /// No reset is generated when entering stop mode.
+    pub nrst_stop: bool,
+    /// No reset is generated when entering standby mode.
+    pub nrst_stdby: bool,
+    /// The write protection of the sectors, one bit per sector.
+    /// A cleared bit enables the protection.
+    pub nwrp: u16,
+    /// The register as read, so bits of other chips in the family are kept.
+    optcr: u32,
+}
+
+impl OptionBytes {
+    fn from_optcr(optcr: u32) -> Self {
+        Self {
+            read_protection: ReadProtection::from_byte((optcr >> 8) as u8),
+            bor_level: ((optcr >> 2) & 0b11) as u8,
+            wdg_sw: optcr & (1 << 5) != 0,
+            nrst_stop: optcr & (1 << 6) != 0,
+            nrst_stdby: optcr & (1 << 7) != 0,
+            nwrp: ((optcr >> 16) & 0xFFF) as u16,
+            optcr,
+        }
+    }
+
+    fn to_optcr(&self) -> u32 {
+        self.optcr & !OPTCR_MASK & !(OPTCR_OPTLOCK | OPTCR_OPTSTRT)
+            | u32::from(self.bor_level) << 2
+            | (self.wdg_sw as u32) << 5
+            | (self.nrst_stop as u32) << 6
+            | (self.nrst_stdby as u32) << 7
+            | u32::from(self.read_protection.to_byte()) << 8
+            | u32::from(self.nwrp) << 16
+    }
+
+    /// Reads the option bytes from `FLASH_OPTCR`.
+    pub fn read(memory: &Memory) -> Result<Self, OptionBytesError> {
+        Ok(Self::from_optcr(read32(memory, FLASH_OPTCR)?))
+    }
+
+    /// Programs these option bytes.
+    pub fn write(&self, memory: &Memory) -> Result<(), OptionBytesError> {
+        unlock(
+            memory,
+            FLASH_OPTCR,
+            OPTCR_OPTLOCK,
+            FLASH_OPTKEYR,
+            OPTION_KEYS,
+        )?;
+
+        // Clear the flags of earlier operations.
+        write32(memory, FLASH_SR, SR_ERRORS)?;
+
+        write32(memory, FLASH_OPTCR, self.to_optcr())?;
+        set_bits(memory, FLASH_OPTCR, OPTCR_OPTSTRT)?;
+        let status = wait_while_busy(memory, FLASH_SR, SR_BSY);
+
+        set_bits(memory, FLASH_OPTCR, OPTCR_OPTLOCK)?;
+
+        let status = status?;
+        if status & SR_ERRORS != 0 {
+            return Err(OptionBytesError::ProgrammingFailed(status));
+        }
+
+        Ok(())
+    }
+
+    pub fn fields(&self) -> Vec<OptionBytesField> {
+        vec![
+            OptionBytesField::new("rdp", self.read_protection.level(), "Read protection level"),
+            OptionBytesField::new("bor_lev", self.bor_level, "Brown out reset level (3 = off)"),
+            OptionBytesField::new("wdg_sw", self.wdg_sw, "Software watchdog"),
+            OptionBytesField::new("nrst_stop", self.nrst_stop, "No reset in stop mode"),
+            OptionBytesField::new("nrst_stdby", self.nrst_stdby, "No reset in standby mode"),
+            OptionBytesField::new("nwrp", self.nwrp, "Write protection (0 = protected)"),
+        ]
+    }
+
+    pub fn set_field(&mut self, name: &str, value: u32) -> Result<(), OptionBytesError> {
+        match name {
+            "rdp" => self.read_protection = ReadProtection::from_level(name, value)?,
+            "bor_lev" => self.bor_level = bits(name, value, 2)? as u8,
+            "wdg_sw" => self.wdg_sw = bit(name, value)?,
+            "nrst_stop" => self.nrst_stop = bit(name, value)?,
+            "nrst_stdby" => self.nrst_stdby = bit(name, value)?,
+            "nwrp" => self.nwrp = bits(name, value, 12)? as u16,
+            _ => return Err(OptionBytesError::UnknownField(name.to_owned())),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OptionBytes;
+    use crate::flashing::option_bytes::ReadProtection;
+
+    /// The value of `FLASH_OPTCR` after a reset, with the default option bytes.
+    const OPTCR_RESET: u32 = 0x0FFF_AAED;
+
+    #[test]
+    fn decode_reset_value() {
+        let option_bytes = OptionBytes::from_optcr(OPTCR_RESET);
+
+        assert_eq!(option_bytes.read_protection, ReadProtection::Level0);
+        assert_eq!(option_bytes.bor_level, 3);
+        assert!(option_bytes.wdg_sw);
+        assert!(option_bytes.nrst_stop);
+        assert!(option_bytes.nrst_stdby);
+        assert_eq!(option_bytes.nwrp, 0xFFF);
+
+        // The lock bit is not written back.
+        assert_eq!(option_bytes.to_optcr(), OPTCR_RESET & !1);
+    }
+
+    #[test]
+    fn set_field() {
+        let mut option_bytes = OptionBytes::from_optcr(OPTCR_RESET);
+
+        option_bytes.set_field("bor_lev", 1).unwrap();
+        option_bytes.set_field("rdp", 1).unwrap();
+
+        assert_eq!(option_bytes.to_optcr(), 0x0FFF_BBE4);
+        assert!(option_bytes.set_field("bor_lev", 4).is_err());
+    }
+}
@@ -0,0 +1,195 @@
+//! Option bytes of the STM32G0 family.
+//!
+//! The flash interface is the same as on the STM32L4, only the
+//! layout of the `FLASH_OPTR` register differs.
+
+use super::stm32l4::{program_optr, FLASH_OPTR};
+use super::{bit, bits, read32, OptionBytesError, OptionBytesField, ReadProtection};
+use crate::Memory;
+
+/// The bits of `FLASH_OPTR` which have a field.
+const OPTR_MASK: u32 = 0x3F4F_FFFF;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptionBytes {
+    pub read_protection: ReadProtection,
+    /// The brown out reset is enabled.
+    pub bor_en: bool,
+    /// The falling threshold of the brown out reset, from `0` to `3`.
+    pub borf_level: u8,
+    /// The rising threshold of the brown out reset, from `0` to `3`.
+    pub borr_level: u8,
+    /// No reset is generated when entering stop mode.
+    pub nrst_stop: bool,
+    /// No reset is generated when entering standby mode.
+    pub nrst_stdby: bool,
+    /// No reset is generated when entering shutdown mode.
+    pub nrst_shdw: bool,
+    /// Software independent watchdog.
+    pub iwdg_sw: bool,
+    /// The independent watchdog keeps running in stop mode.
+    pub iwdg_stop: bool,
+    /// The independent watchdog keeps running in standby mode.
+    pub iwdg_stdby: bool,
+    /// Software window watchdog.
+    pub wwdg_sw: bool,
+    /// The SRAM parity check is disabled.
+    pub ram_parity_check: bool,
+    /// The BOOT0 pin is used instead of `nboot0`.
+    pub nboot_sel: bool,
+    /// Selects the boot mode together with BOOT0.
+    pub nboot1: bool,
+    /// Replaces the BOOT0 pin if `nboot_sel` is cleared.
+    pub nboot0: bool,
+    /// The mode of the NRST pin: `1` reset input only, `2` GPIO, `3` bidirectional reset.
+    pub nrst_mode: u8,
+    /// The internal reset holder is enabled.
+    pub irhen: bool,
+    /// The register as read, so bits without a field are kept.
+    optr: u32,
+}
+
+impl OptionBytes {
+    fn from_optr(optr: u32) -> Self {
+        let flag = |bit: u32| optr & (1 << bit) != 0;
+
+        Self {
+            read_protection: ReadProtection::from_byte(optr as u8),
+            bor_en: flag(8),
+            borf_level: ((optr >> 9) & 0b11) as u8,
+            borr_level: ((optr >> 11) & 0b11) as u8,
+            nrst_stop: flag(13),
+            nrst_stdby: flag(14),
+            nrst_shdw: flag(15),
+            iwdg_sw: flag(16),
+            iwdg_stop: flag(17),
+            iwdg_stdby: flag(18),
+            wwdg_sw: flag(19),
+            ram_parity_check: flag(22),
+            nboot_sel: flag(24),
+            nboot1: flag(25),
+            nboot0: flag(26),
+            nrst_mode: ((optr >> 27) & 0b11) as u8,
+            irhen: flag(29),
+            optr,
+        }
+    }
+
+    fn to_optr(&self) -> u32 {
+        self.optr & !OPTR_MASK
+            | u32::from(self.read_protection.to_byte())
+            | (self.bor_en as u32) << 8
+            | u32::from(self.borf_level) << 9
+            | u32::from(self.borr_level) << 11
+            | (self.nrst_stop as u32) << 13
+            | (self.nrst_stdby as u32) << 14
+            | (self.nrst_shdw as u32) << 15
+            | (self.iwdg_sw as u32) << 16
+            | (self.iwdg_stop as u32) << 17
+            | (self.iwdg_stdby as u32) << 18
+            | (self.wwdg_sw as u32) << 19
+            | (self.ram_parity_check as u32) << 22
+            | (self.nboot_sel as u32) << 24
+            | (self.nboot1 as u32) << 25
+            | (self.nboot0 as u32) << 26
+            | u32::from(self.nrst_mode) << 27
+            | (self.irhen as u32) << 29
+    }
+
+    /// Reads the option bytes from `FLASH_OPTR`.
+    pub fn read(memory: &Memory) -> Result<Self, OptionBytesError> {
+        Ok(Self::from_optr(read32(memory, FLASH_OPTR)?))
+    }
+
+    /// Programs these option bytes.
+    pub fn write(&self, memory: &Memory) -> Result<(), OptionBytesError> {
+        program_optr(memory, self.to_optr())
+    }
+
+    pub fn fields(&self) -> Vec<OptionBytesField> {
+        vec![
+            OptionBytesField::new("rdp", self.read_protection.level(), "Read protection level"),
+            OptionBytesField::new("bor_en", self.bor_en, "Brown out reset enabled"),
+            OptionBytesField::new("borf_lev", self.borf_level, "Brown out falling threshold"),
+            OptionBytesField::new("borr_lev", self.borr_level, "Brown out rising threshold"),
+            OptionBytesField::new("nrst_stop", self.nrst_stop, "No reset in stop mode"),
+            OptionBytesField::new("nrst_stdby", self.nrst_stdby, "No reset in standby mode"),
+            OptionBytesField::new("nrst_shdw", self.nrst_shdw, "No reset in shutdown mode"),
+            OptionBytesField::new("iwdg_sw", self.iwdg_sw, "Software independent watchdog"),
+            OptionBytesField::new("iwdg_stop", self.iwdg_stop, "IWDG running in stop mode"),
+            OptionBytesField::new(
+                "iwdg_stdby",
+                self.iwdg_stdby,
+                "IWDG running in standby mode",
+            ),
+            OptionBytesField::new("wwdg_sw", self.wwdg_sw, "Software window watchdog"),
+            OptionBytesField::new(
+                "ram_parity_check",
+                self.ram_parity_check,
+                "RAM parity check disabled",
+            ),
+            OptionBytesField::new("nboot_sel", self.nboot_sel, "Use the BOOT0 pin"),
+            OptionBytesField::new("nboot1", self.nboot1, "Boot mode selection"),
+            OptionBytesField::new("nboot0", self.nboot0, "Software BOOT0"),
+            OptionBytesField::new("nrst_mode", self.nrst_mode, "NRST pin mode"),
+            OptionBytesField::new("irhen", self.irhen, "Internal reset holder enabled"),
+        ]
+    }
+
+    pub fn set_field(&mut self, name: &str, value: u32) -> Result<(), OptionBytesError> {
+        match name {
+            "rdp" => self.read_protection = ReadProtection::from_level(name, value)?,
+            "bor_en" => self.bor_en = bit(name, value)?,
+            "borf_lev" => self.borf_level = bits(name, value, 2)? as u8,
+            "borr_lev" => self.borr_level = bits(name, value, 2)? as u8,
+            "nrst_stop" => self.nrst_stop = bit(name, value)?,
+            "nrst_stdby" => self.nrst_stdby = bit(name, value)?,
+            "nrst_shdw" => self.nrst_shdw = bit(name, value)?,
+            "iwdg_sw" => self.iwdg_sw = bit(name, value)?,
+            "iwdg_stop" => self.iwdg_stop = bit(name, value)?,
+            "iwdg_stdby" => self.iwdg_stdby = bit(name, value)?,
+            "wwdg_sw" => self.wwdg_sw = bit(name, value)?,
+            "ram_parity_check" => self.ram_parity_check = bit(name, value)?,
+            "nboot_sel" => self.nboot_sel = bit(name, value)?,
+            "nboot1" => self.nboot1 = bit(name, value)?,
+            "nboot0" => self.nboot0 = bit(name, value)?,
+            "nrst_mode" => self.nrst_mode = bits(name, value, 2)? as u8,
+            "irhen" => self.irhen = bit(name, value)?,
+            _ => return Err(OptionBytesError::UnknownField(name.to_owned())),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OptionBytes;
+    use crate::flashing::option_bytes::ReadProtection;
+
+    /// The factory value of `FLASH_OPTR`.
+    const OPTR_DEFAULT: u32 = 0xFFFF_FEAA;
+
+    #[test]
+    fn decode_default_value() {
+        let option_bytes = OptionBytes::from_optr(OPTR_DEFAULT);
+
+        assert_eq!(option_bytes.read_protection, ReadProtection::Level0);
+        assert!(!option_bytes.bor_en);
+        assert_eq!(option_bytes.borf_level, 3);
+        assert_eq!(option_bytes.nrst_mode, 3);
+        assert!(option_bytes.nboot_sel);
+
+        assert_eq!(option_bytes.to_optr(), OPTR_DEFAULT);
+    }
+
+    #[test]
+    fn set_field() {
+        let mut option_bytes = OptionBytes::from_optr(OPTR_DEFAULT);
+
+        option_bytes.set_field("nboot_sel", 0).unwrap();
+        option_bytes.set_field("nrst_mode", 2).unwrap();
+
+        assert_eq!(option_bytes.to_optr(), 0xF6FF_FEAA);
+    }
+}
@@ -0,0 +1,207 @@
+//! Option bytes of the STM32H7 family.
+//!
+//! The current option bytes are read from `FLASH_OPTSR_CUR` and `FLASH_BOOT_CURR`.
+//! New values are written to the `_PRG` registers and programmed by setting
+//! `OPTSTART`, after which they take effect immediately.
+
+use super::{
+    bit, bits, read32, set_bits, unlock, wait_while_busy, write32, OptionBytesError,
+    OptionBytesField, ReadProtection, OPTION_KEYS,
+};
+use crate::Memory;
+
+const FLASH_OPTKEYR: u32 = 0x5200_2008;
+const FLASH_OPTCR: u32 = 0x5200_2018;
+const FLASH_OPTSR_CUR: u32 = 0x5200_201C;
+const FLASH_OPTSR_PRG: u32 = 0x5200_2020;
+const FLASH_OPTCCR: u32 = 0x5200_2024;
+const FLASH_BOOT_CURR: u32 = 0x5200_2040;
+const FLASH_BOOT_PRGR: u32 = 0x5200_2044;
+
+const OPTCR_OPTLOCK: u32 = 1 << 0;
+const OPTCR_OPTSTART: u32 = 1 << 1;
+
+const OPTSR_OPT_BUSY: u32 = 1 << 0;
+const OPTSR_OPTCHANGEERR: u32 = 1 << 30;
+
+const OPTCCR_CLR_OPTCHANGEERR: u32 = 1 << 30;
+
+/// The bits of `FLASH_OPTSR` which have a field.
+const OPTSR_MASK: u32 = 0x2006_FFDC;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptionBytes {
+    pub read_protection: ReadProtection,
+    /// The brown out reset level, from `0` (off) to `3` (highest threshold).
+    pub bor_level: u8,
+    /// Software independent watchdog.
+    pub iwdg1_sw: bool,
+    /// No reset is generated when the D1 domain enters stop mode.
+    pub nrst_stop_d1: bool,
+    /// No reset is generated when the D1 domain enters standby mode.
+    pub nrst_stby_d1: bool,
+    /// The independent watchdog keeps running in stop mode.
+    pub fz_iwdg_stop: bool,
+    /// The independent watchdog keeps running in standby mode.
+    pub fz_iwdg_sdby: bool,
+    /// The I/O high speed at low voltage is enabled.
+    pub io_hslv: bool,
+    /// The upper 16 bits of the boot address when BOOT0 is low.
+    pub boot_add0: u16,
+    /// The upper 16 bits of the boot address when BOOT0 is high.
+    pub boot_add1: u16,
+    /// The register as read, so bits without a field are kept.
+    optsr: u32,
+}
+
+impl OptionBytes {
+    fn from_registers(optsr: u32, boot: u32) -> Self {
+        let flag = |bit: u32| optsr & (1 << bit) != 0;
+
+        Self {
+            read_protection: ReadProtection::from_byte((optsr >> 8) as u8),
+            bor_level: ((optsr >> 2) & 0b11) as u8,
+            iwdg1_sw: flag(4),
+            nrst_stop_d1: flag(6),
+            nrst_stby_d1: flag(7),
+            fz_iwdg_stop: flag(17),
+            fz_iwdg_sdby: flag(18),
+            io_hslv: flag(29),
+            boot_add0: boot as u16,
+            boot_add1: (boot >> 16) as u16,
+            optsr,
+        }
+    }
+
+    fn to_optsr(&self) -> u32 {
+        self.optsr & !OPTSR_MASK & !(OPTSR_OPT_BUSY | OPTSR_OPTCHANGEERR)
+            | u32::from(self.bor_level) << 2
+            | (self.iwdg1_sw as u32) << 4
+            | (self.nrst_stop_d1 as u32) << 6
+            | (self.nrst_stby_d1 as u32) << 7
+            | u32::from(self.read_protection.to_byte()) << 8
+            | (self.fz_iwdg_stop as u32) << 17
+            | (self.fz_iwdg_sdby as u32) << 18
+            | (self.io_hslv as u32) << 29
+    }
+
+    fn to_boot(&self) -> u32 {
+        u32::from(self.boot_add1) << 16 | u32::from(self.boot_add0)
+    }
+
+    /// Reads the current option bytes.
+    pub fn read(memory: &Memory) -> Result<Self, OptionBytesError> {
+        Ok(Self::from_registers(
+            read32(memory, FLASH_OPTSR_CUR)?,
+            read32(memory, FLASH_BOOT_CURR)?,
+        ))
+    }
+
+    /// Programs these option bytes.
+    pub fn write(&self, memory: &Memory) -> Result<(), OptionBytesError> {
+        unlock(
+            memory,
+            FLASH_OPTCR,
+            OPTCR_OPTLOCK,
+            FLASH_OPTKEYR,
+            OPTION_KEYS,
+        )?;
+
+        // Clear the error of earlier operations.
+        write32(memory, FLASH_OPTCCR, OPTCCR_CLR_OPTCHANGEERR)?;
+
+        write32(memory, FLASH_OPTSR_PRG, self.to_optsr())?;
+        write32(memory, FLASH_BOOT_PRGR, self.to_boot())?;
+
+        set_bits(memory, FLASH_OPTCR, OPTCR_OPTSTART)?;
+        let status = wait_while_busy(memory, FLASH_OPTSR_CUR, OPTSR_OPT_BUSY);
+
+        set_bits(memory, FLASH_OPTCR, OPTCR_OPTLOCK)?;
+
+        let status = status?;
+        if status & OPTSR_OPTCHANGEERR != 0 {
+            return Err(OptionBytesError::ProgrammingFailed(status));
+        }
+
+        Ok(())
+    }
+
+    pub fn fields(&self) -> Vec<OptionBytesField> {
+        vec![
+            OptionBytesField::new("rdp", self.read_protection.level(), "Read protection level"),
+            OptionBytesField::new("bor_lev", self.bor_level, "Brown out reset level (0 = off)"),
+            OptionBytesField::new("iwdg1_sw", self.iwdg1_sw, "Software independent watchdog"),
+            OptionBytesField::new(
+                "nrst_stop_d1",
+                self.nrst_stop_d1,
+                "No reset in D1 stop mode",
+            ),
+            OptionBytesField::new(
+                "nrst_stby_d1",
+                self.nrst_stby_d1,
+                "No reset in D1 standby mode",
+            ),
+            OptionBytesField::new(
+                "fz_iwdg_stop",
+                self.fz_iwdg_stop,
+                "IWDG running in stop mode",
+            ),
+            OptionBytesField::new(
+                "fz_iwdg_sdby",
+                self.fz_iwdg_sdby,
+                "IWDG running in standby mode",
+            ),
+            OptionBytesField::new("io_hslv", self.io_hslv, "I/O high speed at low voltage"),
+            OptionBytesField::new("boot_add0", self.boot_add0, "Boot address with BOOT0 low"),
+            OptionBytesField::new("boot_add1", self.boot_add1, "Boot address with BOOT0 high"),
+        ]
+    }
+
+    pub fn set_field(&mut self, name: &str, value: u32) -> Result<(), OptionBytesError> {
+        match name {
+            "rdp" => self.read_protection = ReadProtection::from_level(name, value)?,
+            "bor_lev" => self.bor_level = bits(name, value, 2)? as u8,
+            "iwdg1_sw" => self.iwdg1_sw = bit(name, value)?,
+            "nrst_stop_d1" => self.nrst_stop_d1 = bit(name, value)?,
+            "nrst_stby_d1" => self.nrst_stby_d1 = bit(name, value)?,
+            "fz_iwdg_stop" => self.fz_iwdg_stop = bit(name, value)?,
+            "fz_iwdg_sdby" => self.fz_iwdg_sdby = bit(name, value)?,
+            "io_hslv" => self.io_hslv = bit(name, value)?,
+            "boot_add0" => self.boot_add0 = bits(name, value, 16)? as u16,
+            "boot_add1" => self.boot_add1 = bits(name, value, 16)? as u16,
+            _ => return Err(OptionBytesError::UnknownField(name.to_owned())),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OptionBytes;
+    use crate::flashing::option_bytes::ReadProtection;
+
+    #[test]
+    fn decode_registers() {
+        // SWAP_BANK_OPT is set and has no field.
+        let option_bytes = OptionBytes::from_registers(0x8006_AAD0, 0x1FF0_0800);
+
+        assert_eq!(option_bytes.read_protection, ReadProtection::Level0);
+        assert_eq!(option_bytes.bor_level, 0);
+        assert!(option_bytes.iwdg1_sw);
+        assert!(option_bytes.fz_iwdg_stop);
+        assert_eq!(option_bytes.boot_add0, 0x0800);
+        assert_eq!(option_bytes.boot_add1, 0x1FF0);
+
+        assert_eq!(option_bytes.to_optsr(), 0x8006_AAD0);
+        assert_eq!(option_bytes.to_boot(), 0x1FF0_0800);
+    }
+
+    #[test]
+    fn busy_flag_is_not_written() {
+        let mut option_bytes = OptionBytes::from_registers(0x0006_AAD1, 0x1FF0_0800);
+        option_bytes.set_field("bor_lev", 3).unwrap();
+
+        assert_eq!(option_bytes.to_optsr(), 0x0006_AADC);
+    }
+}
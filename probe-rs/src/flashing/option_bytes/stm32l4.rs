@@ -0,0 +1,221 @@
+//! Option bytes of the STM32L4 family.
+//!
+//! The user options are accessed through the `FLASH_OPTR` register, which is
+//! programmed into the option bytes by setting `OPTSTRT`. The STM32G0 uses the
+//! same flash interface.
+//!
+//! The new option bytes are loaded after a power-on reset, or when `OBL_LAUNCH`
+//! is set, which also resets the chip.
+
+use super::{
+    bit, invalid_value, read32, set_bits, unlock, wait_while_busy, write32, OptionBytesError,
+    OptionBytesField, ReadProtection, FLASH_KEYS, OPTION_KEYS,
+};
+use crate::Memory;
+
+const FLASH_KEYR: u32 = 0x4002_2008;
+const FLASH_OPTKEYR: u32 = 0x4002_200C;
+const FLASH_SR: u32 = 0x4002_2010;
+const FLASH_CR: u32 = 0x4002_2014;
+pub(super) const FLASH_OPTR: u32 = 0x4002_2020;
+
+const SR_ERRORS: u32 = 0x0000_C3FA;
+const SR_BSY: u32 = 1 << 16;
+
+const CR_OPTSTRT: u32 = 1 << 17;
+const CR_OPTLOCK: u32 = 1 << 30;
+const CR_LOCK: u32 = 1 << 31;
+
+/// The bits of `FLASH_OPTR` which have a field.
+const OPTR_MASK: u32 = 0x0F8F_77FF;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptionBytes {
+    pub read_protection: ReadProtection,
+    /// The brown out reset level, from `0` (1.7 V) to `4` (2.8 V).
+    pub bor_level: u8,
+    /// No reset is generated when entering stop mode.
+    pub nrst_stop: bool,
+    /// No reset is generated when entering standby mode.
+    pub nrst_stdby: bool,
+    /// No reset is generated when entering shutdown mode.
+    pub nrst_shdw: bool,
+    /// Software independent watchdog.
+    pub iwdg_sw: bool,
+    /// The independent watchdog keeps running in stop mode.
+    pub iwdg_stop: bool,
+    /// The independent watchdog keeps running in standby mode.
+    pub iwdg_stdby: bool,
+    /// Software window watchdog.
+    pub wwdg_sw: bool,
+    /// Selects the boot mode together with BOOT0.
+    pub nboot1: bool,
+    /// The SRAM2 parity check is disabled.
+    pub sram2_pe: bool,
+    /// SRAM2 is not erased on a system reset.
+    pub sram2_rst: bool,
+    /// The BOOT0 pin is used instead of `nboot0`.
+    pub nswboot0: bool,
+    /// Replaces the BOOT0 pin if `nswboot0` is cleared.
+    pub nboot0: bool,
+    /// The register as read, so bits without a field are kept.
+    optr: u32,
+}
+
+impl OptionBytes {
+    fn from_optr(optr: u32) -> Self {
+        let flag = |bit: u32| optr & (1 << bit) != 0;
+
+        Self {
+            read_protection: ReadProtection::from_byte(optr as u8),
+            bor_level: ((optr >> 8) & 0b111) as u8,
+            nrst_stop: flag(12),
+            nrst_stdby: flag(13),
+            nrst_shdw: flag(14),
+            iwdg_sw: flag(16),
+            iwdg_stop: flag(17),
+            iwdg_stdby: flag(18),
+            wwdg_sw: flag(19),
+            nboot1: flag(23),
+            sram2_pe: flag(24),
+            sram2_rst: flag(25),
+            nswboot0: flag(26),
+            nboot0: flag(27),
+            optr,
+        }
+    }
+
+    fn to_optr(&self) -> u32 {
+        self.optr & !OPTR_MASK
+            | u32::from(self.read_protection.to_byte())
+            | u32::from(self.bor_level) << 8
+            | (self.nrst_stop as u32) << 12
+            | (self.nrst_stdby as u32) << 13
+            | (self.nrst_shdw as u32) << 14
+            | (self.iwdg_sw as u32) << 16
+            | (self.iwdg_stop as u32) << 17
+            | (self.iwdg_stdby as u32) << 18
+            | (self.wwdg_sw as u32) << 19
+            | (self.nboot1 as u32) << 23
+            | (self.sram2_pe as u32) << 24
+            | (self.sram2_rst as u32) << 25
+            | (self.nswboot0 as u32) << 26
+            | (self.nboot0 as u32) << 27
+    }
+
+    /// Reads the option bytes from `FLASH_OPTR`.
+    pub fn read(memory: &Memory) -> Result<Self, OptionBytesError> {
+        Ok(Self::from_optr(read32(memory, FLASH_OPTR)?))
+    }
+
+    /// Programs these option bytes.
+    pub fn write(&self, memory: &Memory) -> Result<(), OptionBytesError> {
+        program_optr(memory, self.to_optr())
+    }
+
+    pub fn fields(&self) -> Vec<OptionBytesField> {
+        vec![
+            OptionBytesField::new("rdp", self.read_protection.level(), "Read protection level"),
+            OptionBytesField::new("bor_lev", self.bor_level, "Brown out reset level"),
+            OptionBytesField::new("nrst_stop", self.nrst_stop, "No reset in stop mode"),
+            OptionBytesField::new("nrst_stdby", self.nrst_stdby, "No reset in standby mode"),
+            OptionBytesField::new("nrst_shdw", self.nrst_shdw, "No reset in shutdown mode"),
+            OptionBytesField::new("iwdg_sw", self.iwdg_sw, "Software independent watchdog"),
+            OptionBytesField::new("iwdg_stop", self.iwdg_stop, "IWDG running in stop mode"),
+            OptionBytesField::new(
+                "iwdg_stdby",
+                self.iwdg_stdby,
+                "IWDG running in standby mode",
+            ),
+            OptionBytesField::new("wwdg_sw", self.wwdg_sw, "Software window watchdog"),
+            OptionBytesField::new("nboot1", self.nboot1, "Boot mode selection"),
+            OptionBytesField::new("sram2_pe", self.sram2_pe, "SRAM2 parity check disabled"),
+            OptionBytesField::new("sram2_rst", self.sram2_rst, "SRAM2 not erased on reset"),
+            OptionBytesField::new("nswboot0", self.nswboot0, "Use the BOOT0 pin"),
+            OptionBytesField::new("nboot0", self.nboot0, "Software BOOT0"),
+        ]
+    }
+
+    pub fn set_field(&mut self, name: &str, value: u32) -> Result<(), OptionBytesError> {
+        match name {
+            "rdp" => self.read_protection = ReadProtection::from_level(name, value)?,
+            "bor_lev" => {
+                if value > 4 {
+                    return Err(invalid_value(name, value));
+                }
+                self.bor_level = value as u8;
+            }
+            "nrst_stop" => self.nrst_stop = bit(name, value)?,
+            "nrst_stdby" => self.nrst_stdby = bit(name, value)?,
+            "nrst_shdw" => self.nrst_shdw = bit(name, value)?,
+            "iwdg_sw" => self.iwdg_sw = bit(name, value)?,
+            "iwdg_stop" => self.iwdg_stop = bit(name, value)?,
+            "iwdg_stdby" => self.iwdg_stdby = bit(name, value)?,
+            "wwdg_sw" => self.wwdg_sw = bit(name, value)?,
+            "nboot1" => self.nboot1 = bit(name, value)?,
+            "sram2_pe" => self.sram2_pe = bit(name, value)?,
+            "sram2_rst" => self.sram2_rst = bit(name, value)?,
+            "nswboot0" => self.nswboot0 = bit(name, value)?,
+            "nboot0" => self.nboot0 = bit(name, value)?,
+            _ => return Err(OptionBytesError::UnknownField(name.to_owned())),
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes `optr` to `FLASH_OPTR` and programs it into the option bytes.
+pub(super) fn program_optr(memory: &Memory, optr: u32) -> Result<(), OptionBytesError> {
+    unlock(memory, FLASH_CR, CR_LOCK, FLASH_KEYR, FLASH_KEYS)?;
+    unlock(memory, FLASH_CR, CR_OPTLOCK, FLASH_OPTKEYR, OPTION_KEYS)?;
+
+    // Clear the flags of earlier operations.
+    write32(memory, FLASH_SR, SR_ERRORS)?;
+
+    write32(memory, FLASH_OPTR, optr)?;
+    set_bits(memory, FLASH_CR, CR_OPTSTRT)?;
+    let status = wait_while_busy(memory, FLASH_SR, SR_BSY);
+
+    // Setting LOCK also sets OPTLOCK.
+    set_bits(memory, FLASH_CR, CR_LOCK)?;
+
+    let status = status?;
+    if status & SR_ERRORS != 0 {
+        return Err(OptionBytesError::ProgrammingFailed(status));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OptionBytes;
+    use crate::flashing::option_bytes::ReadProtection;
+
+    /// The factory value of `FLASH_OPTR`.
+    const OPTR_DEFAULT: u32 = 0xFFEF_F8AA;
+
+    #[test]
+    fn decode_default_value() {
+        let option_bytes = OptionBytes::from_optr(OPTR_DEFAULT);
+
+        assert_eq!(option_bytes.read_protection, ReadProtection::Level0);
+        assert_eq!(option_bytes.bor_level, 0);
+        assert!(option_bytes.iwdg_sw);
+        assert!(option_bytes.nswboot0);
+        assert!(option_bytes.nboot0);
+
+        assert_eq!(option_bytes.to_optr(), OPTR_DEFAULT);
+    }
+
+    #[test]
+    fn set_field() {
+        let mut option_bytes = OptionBytes::from_optr(OPTR_DEFAULT);
+
+        option_bytes.set_field("nswboot0", 0).unwrap();
+        option_bytes.set_field("bor_lev", 4).unwrap();
+
+        assert_eq!(option_bytes.to_optr(), 0xFBEF_FCAA);
+        assert!(option_bytes.set_field("bor_lev", 5).is_err());
+    }
+}
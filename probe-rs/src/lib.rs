@@ -75,13 +75,20 @@ mod error;
 pub mod flashing;
 mod memory;
 mod probe;
+pub mod rtt;
+pub mod semihosting;
 mod session;
+pub mod svd;
+#[cfg(test)]
+pub(crate) mod test_support;
 
 pub use crate::config::Target;
 pub use crate::core::CoreType;
 pub use crate::core::{
-    Breakpoint, BreakpointId, CommunicationInterface, Core, CoreInterface, CoreList,
-    CoreRegisterAddress,
+    Architecture, Breakpoint, BreakpointCondition, BreakpointId, BreakpointOptions,
+    BreakpointStatistics, CallError, CommunicationInterface, Comparison, Core, CoreDump,
+    CoreDumpError, CoreInterface, CoreList, CoreRegisterAddress, CoreStatus, HaltReason,
+    ManagedBreakpoint, MemorySegment, WatchKind,
 };
 pub use crate::error::Error;
 pub use crate::memory::{Memory, MemoryInterface, MemoryList};
@@ -28,6 +28,12 @@ pub trait MemoryInterface {
     /// Returns `AccessPortError::MemoryNotAligned` if this does not hold true.
     fn write32(&mut self, addr: u32, data: u32) -> Result<(), error::Error>;
 
+    /// Write a 16bit word at `addr`.
+    ///
+    /// The address where the write should be performed at has to be half word aligned.
+    /// Returns `AccessPortError::MemoryNotAligned` if this does not hold true.
+    fn write16(&mut self, addr: u32, data: u16) -> Result<(), error::Error>;
+
     /// Write an 8bit word at `addr`.
     fn write8(&mut self, addr: u32, data: u8) -> Result<(), error::Error>;
 
@@ -66,6 +72,10 @@ where
         (*self).write32(addr, data)
     }
 
+    fn write16(&mut self, addr: u32, data: u16) -> Result<(), error::Error> {
+        (*self).write16(addr, data)
+    }
+
     fn write8(&mut self, addr: u32, data: u8) -> Result<(), error::Error> {
         (*self).write8(addr, data)
     }
@@ -97,6 +107,9 @@ impl MemoryInterface for MemoryDummy {
     fn write32(&mut self, _address: u32, _data: u32) -> Result<(), error::Error> {
         unimplemented!()
     }
+    fn write16(&mut self, _address: u32, _data: u16) -> Result<(), error::Error> {
+        unimplemented!()
+    }
     fn write8(&mut self, _address: u32, _data: u8) -> Result<(), error::Error> {
         unimplemented!()
     }
@@ -152,6 +165,10 @@ impl Memory {
         self.inner.borrow_mut().write32(addr, data)
     }
 
+    pub fn write16(&self, addr: u32, data: u16) -> Result<(), error::Error> {
+        self.inner.borrow_mut().write16(addr, data)
+    }
+
     pub fn write8(&self, addr: u32, data: u8) -> Result<(), error::Error> {
         self.inner.borrow_mut().write8(addr, data)
     }
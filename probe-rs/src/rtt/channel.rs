@@ -0,0 +1,323 @@
+use super::RttError;
+use crate::{Error, Memory};
+use std::cmp;
+
+/// The offset of the write offset in a channel descriptor, which is followed by the read offset.
+const WRITE_OFFSET_OFFSET: u32 = 12;
+
+/// The offset of the read offset in a channel descriptor.
+const READ_OFFSET_OFFSET: u32 = 16;
+
+/// Channel names longer than this are cut off.
+const MAX_NAME_LENGTH: usize = 32;
+
+/// The parts of a channel descriptor which do not change while the target runs.
+struct Channel {
+    memory: Memory,
+    number: u32,
+    descriptor: u32,
+    name: Option<String>,
+    buffer: u32,
+    size: u32,
+}
+
+impl Channel {
+    /// Reads a channel descriptor, and returns `None` if the channel is not in use.
+    fn read(memory: Memory, number: u32, descriptor: u32) -> Result<Option<Self>, Error> {
+        let mut words = [0; 3];
+        memory.read_block32(descriptor, &mut words)?;
+        let [name, buffer, size] = words;
+
+        if buffer == 0 {
+            return Ok(None);
+        }
+
+        if size < 2 {
+            return Err(RttError::ControlBlockCorrupted(format!(
+                "The buffer of channel {} has a size of {} bytes",
+                number, size
+            ))
+            .into());
+        }
+
+        // The name is only informational, so an unreadable name is not an error.
+        let name = if name != 0 {
+            read_name(&memory, name).ok()
+        } else {
+            None
+        };
+
+        Ok(Some(Self {
+            memory,
+            number,
+            descriptor,
+            name,
+            buffer,
+            size,
+        }))
+    }
+
+    /// Reads the write and the read offset, and checks that they are inside the buffer.
+    fn offsets(&self) -> Result<(u32, u32), Error> {
+        let mut offsets = [0; 2];
+        self.memory
+            .read_block32(self.descriptor + WRITE_OFFSET_OFFSET, &mut offsets)?;
+        let [write, read] = offsets;
+
+        if write >= self.size || read >= self.size {
+            return Err(RttError::ControlBlockCorrupted(format!(
+                "The offsets {} and {} of channel {} are outside of its buffer",
+                write, read, self.number
+            ))
+            .into());
+        }
+
+        Ok((write, read))
+    }
+}
+
+/// A channel which carries data from the target to the host.
+pub struct UpChannel(Channel);
+
+impl UpChannel {
+    pub(super) fn read(
+        memory: Memory,
+        number: u32,
+        descriptor: u32,
+    ) -> Result<Option<Self>, Error> {
+        Ok(Channel::read(memory, number, descriptor)?.map(Self))
+    }
+
+    /// The number of the channel. Channel 0 is usually the terminal.
+    pub fn number(&self) -> u32 {
+        self.0.number
+    }
+
+    /// The name of the channel, if the target gave it one.
+    pub fn name(&self) -> Option<&str> {
+        self.0.name.as_deref()
+    }
+
+    /// The size of the ring buffer in bytes.
+    pub fn buffer_size(&self) -> u32 {
+        self.0.size
+    }
+
+    /// Reads the data the target has written to the channel into `data`, and
+    /// returns the number of bytes which were read.
+    ///
+    /// Returns 0 if there is no new data.
+    pub fn read_data(&self, data: &mut [u8]) -> Result<usize, Error> {
+        let channel = &self.0;
+        let (write, mut read) = channel.offsets()?;
+
+        let mut total = 0;
+        while total < data.len() && read != write {
+            // The data up to the end of the buffer, if it wraps around.
+            let end = if write > read { write } else { channel.size };
+            let count = cmp::min((end - read) as usize, data.len() - total);
+
+            channel
+                .memory
+                .read_block8(channel.buffer + read, &mut data[total..total + count])?;
+
+            total += count;
+            read += count as u32;
+            if read == channel.size {
+                read = 0;
+            }
+        }
+
+        if total > 0 {
+            // Frees the space for the target.
+            channel
+                .memory
+                .write32(channel.descriptor + READ_OFFSET_OFFSET, read)?;
+        }
+
+        Ok(total)
+    }
+}
+
+/// A channel which carries data from the host to the target.
+pub struct DownChannel(Channel);
+
+impl DownChannel {
+    pub(super) fn read(
+        memory: Memory,
+        number: u32,
+        descriptor: u32,
+    ) -> Result<Option<Self>, Error> {
+        Ok(Channel::read(memory, number, descriptor)?.map(Self))
+    }
+
+    /// The number of the channel. Channel 0 is usually the terminal.
+    pub fn number(&self) -> u32 {
+        self.0.number
+    }
+
+    /// The name of the channel, if the target gave it one.
+    pub fn name(&self) -> Option<&str> {
+        self.0.name.as_deref()
+    }
+
+    /// The size of the ring buffer in bytes.
+    pub fn buffer_size(&self) -> u32 {
+        self.0.size
+    }
+
+    /// Writes as much of `data` as fits into the free space of the buffer, and
+    /// returns the number of bytes which were written.
+    ///
+    /// One byte of the buffer always stays free, so a full buffer can be told
+    /// apart from an empty one.
+    pub fn write_data(&self, data: &[u8]) -> Result<usize, Error> {
+        let channel = &self.0;
+        let (mut write, read) = channel.offsets()?;
+
+        let mut total = 0;
+        while total < data.len() {
+            let end = if read > write {
+                read - 1
+            } else if read == 0 {
+                channel.size - 1
+            } else {
+                channel.size
+            };
+            let count = cmp::min((end - write) as usize, data.len() - total);
+
+            if count == 0 {
+                break;
+            }
+
+            channel
+                .memory
+                .write_block8(channel.buffer + write, &data[total..total + count])?;
+
+            total += count;
+            write += count as u32;
+            if write == channel.size {
+                write = 0;
+            }
+        }
+
+        if total > 0 {
+            // Makes the data visible to the target.
+            channel
+                .memory
+                .write32(channel.descriptor + WRITE_OFFSET_OFFSET, write)?;
+        }
+
+        Ok(total)
+    }
+}
+
+/// Reads the null terminated name of a channel.
+fn read_name(memory: &Memory, address: u32) -> Result<String, Error> {
+    let mut name = Vec::new();
+
+    for offset in 0..MAX_NAME_LENGTH as u32 {
+        match memory.read8(address + offset)? {
+            0 => break,
+            byte => name.push(byte),
+        }
+    }
+
+    Ok(String::from_utf8_lossy(&name).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rtt::tests::{
+        memory, memory_map, BUFFER_SIZE, CONTROL_BLOCK, DOWN_BUFFER, UP_BUFFER,
+    };
+    use crate::rtt::{Rtt, ScanRegion};
+    use crate::Memory;
+
+    /// The address of the write offset of the first up channel, followed by its read offset.
+    const UP_OFFSETS: u32 = CONTROL_BLOCK + 24 + 12;
+    /// The address of the write offset of the down channel, which is the third descriptor.
+    const DOWN_OFFSETS: u32 = CONTROL_BLOCK + 24 + 2 * 24 + 12;
+
+    fn attach(memory: &Memory) -> Rtt {
+        Rtt::attach(memory.clone(), &memory_map(), &ScanRegion::Ram).unwrap()
+    }
+
+    #[test]
+    fn read_up_channel() {
+        let memory = memory();
+        let rtt = attach(&memory);
+        let channel = &rtt.up_channels()[0];
+
+        let mut data = [0; 8];
+        assert_eq!(channel.read_data(&mut data).unwrap(), 0);
+
+        memory.write_block8(UP_BUFFER, b"hello").unwrap();
+        memory.write32(UP_OFFSETS, 5).unwrap();
+
+        assert_eq!(channel.read_data(&mut data).unwrap(), 5);
+        assert_eq!(&data[..5], b"hello");
+        assert_eq!(memory.read32(UP_OFFSETS + 4).unwrap(), 5);
+
+        assert_eq!(channel.read_data(&mut data).unwrap(), 0);
+    }
+
+    #[test]
+    fn read_wrapped_up_channel() {
+        let memory = memory();
+        let rtt = attach(&memory);
+        let channel = &rtt.up_channels()[0];
+
+        // "abcdef" starts 3 bytes before the end of the buffer.
+        memory
+            .write_block8(UP_BUFFER + BUFFER_SIZE - 3, b"abc")
+            .unwrap();
+        memory.write_block8(UP_BUFFER, b"def").unwrap();
+        memory.write32(UP_OFFSETS, 3).unwrap();
+        memory.write32(UP_OFFSETS + 4, BUFFER_SIZE - 3).unwrap();
+
+        // A short buffer stops the read in the middle.
+        let mut data = [0; 4];
+        assert_eq!(channel.read_data(&mut data).unwrap(), 4);
+        assert_eq!(&data, b"abcd");
+        assert_eq!(memory.read32(UP_OFFSETS + 4).unwrap(), 1);
+
+        assert_eq!(channel.read_data(&mut data).unwrap(), 2);
+        assert_eq!(&data[..2], b"ef");
+    }
+
+    #[test]
+    fn write_down_channel() {
+        let memory = memory();
+        let rtt = attach(&memory);
+        let channel = rtt.down_channel(0).unwrap();
+
+        assert_eq!(channel.write_data(b"hi").unwrap(), 2);
+        assert_eq!(memory.read32(DOWN_OFFSETS).unwrap(), 2);
+
+        let mut data = [0; 2];
+        memory.read_block8(DOWN_BUFFER, &mut data).unwrap();
+        assert_eq!(&data, b"hi");
+
+        // One byte stays free, so only 13 of the remaining 14 bytes fit.
+        let long = [b'x'; 20];
+        assert_eq!(channel.write_data(&long).unwrap(), 13);
+        assert_eq!(channel.write_data(&long).unwrap(), 0);
+
+        // The target reads everything, so the write wraps around.
+        memory.write32(DOWN_OFFSETS + 4, 15).unwrap();
+        assert_eq!(channel.write_data(&long).unwrap(), 15);
+        assert_eq!(memory.read32(DOWN_OFFSETS).unwrap(), 14);
+    }
+
+    #[test]
+    fn corrupted_offsets() {
+        let memory = memory();
+        let rtt = attach(&memory);
+
+        memory.write32(UP_OFFSETS, BUFFER_SIZE).unwrap();
+
+        let mut data = [0; 4];
+        assert!(rtt.up_channels()[0].read_data(&mut data).is_err());
+    }
+}
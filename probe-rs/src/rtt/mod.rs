@@ -0,0 +1,321 @@
+//! Host side of SEGGER RTT (Real Time Transfer).
+//!
+//! The target places a control block in its RAM, which starts with the ID
+//! `SEGGER RTT` and describes a number of ring buffers. The up channels carry
+//! data from the target to the host, and the down channels from the host to the
+//! target. The buffers are read and written through [`Memory`] while the core
+//! keeps running, so the core is never halted.
+//!
+//! The control block can be found by scanning the RAM of the target, or by
+//! looking up the `_SEGGER_RTT` symbol of the firmware with [`control_block_address`].
+
+mod channel;
+
+pub use channel::{DownChannel, UpChannel};
+
+use crate::config::MemoryRegion;
+use crate::{Error, Memory};
+use goblin::elf::Elf;
+use std::ops::Range;
+use thiserror::Error;
+
+/// The ID at the start of the control block, including its null terminator.
+const CONTROL_BLOCK_ID: &[u8] = b"SEGGER RTT\0";
+
+/// The size of the control block header: the 16 byte ID and the channel counts.
+const HEADER_SIZE: u32 = 24;
+
+/// The size of a channel descriptor.
+const DESCRIPTOR_SIZE: u32 = 24;
+
+/// More channels than this are taken as a sign of a corrupted control block.
+const MAX_CHANNELS: u32 = 255;
+
+/// The name of the control block symbol of the SEGGER RTT implementation.
+const CONTROL_BLOCK_SYMBOL: &str = "_SEGGER_RTT";
+
+#[derive(Error, Debug)]
+pub enum RttError {
+    #[error("No RTT control block was found")]
+    ControlBlockNotFound,
+    #[error("RTT control blocks were found at multiple addresses: {0:x?}")]
+    MultipleControlBlocksFound(Vec<u32>),
+    #[error("The RTT control block is corrupted: {0}")]
+    ControlBlockCorrupted(String),
+    #[error("The ELF file could not be parsed: {0}")]
+    InvalidElf(#[from] goblin::error::Error),
+}
+
+/// Where to look for the control block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanRegion {
+    /// Scan all RAM regions of the memory map.
+    Ram,
+    /// Scan the given range.
+    Range(Range<u32>),
+    /// The control block is at this address.
+    Exact(u32),
+}
+
+/// A control block on the target, and the channels it describes.
+pub struct Rtt {
+    address: u32,
+    up_channels: Vec<UpChannel>,
+    down_channels: Vec<DownChannel>,
+}
+
+impl Rtt {
+    /// Finds the control block in `region`, and reads its channel descriptors.
+    ///
+    /// The control block is only found once the target has initialized it, so
+    /// attaching right after a reset can fail.
+    pub fn attach(
+        memory: Memory,
+        memory_map: &[MemoryRegion],
+        region: &ScanRegion,
+    ) -> Result<Self, Error> {
+        let address = match region {
+            ScanRegion::Exact(address) => *address,
+            ScanRegion::Range(range) => find_control_block(&memory, std::slice::from_ref(range))?,
+            ScanRegion::Ram => {
+                let ranges: Vec<_> = memory_map
+                    .iter()
+                    .filter_map(|region| match region {
+                        MemoryRegion::Ram(ram) => Some(ram.range.clone()),
+                        _ => None,
+                    })
+                    .collect();
+
+                find_control_block(&memory, &ranges)?
+            }
+        };
+
+        Self::attach_at(memory, address)
+    }
+
+    fn attach_at(memory: Memory, address: u32) -> Result<Self, Error> {
+        let mut header = [0; HEADER_SIZE as usize];
+        memory.read_block8(address, &mut header)?;
+
+        if !header.starts_with(CONTROL_BLOCK_ID) {
+            return Err(RttError::ControlBlockNotFound.into());
+        }
+
+        let max_up = read_count(&header[16..20])?;
+        let max_down = read_count(&header[20..24])?;
+
+        let descriptor = |index: u32| address + HEADER_SIZE + index * DESCRIPTOR_SIZE;
+
+        let mut up_channels = Vec::new();
+        for number in 0..max_up {
+            if let Some(channel) = UpChannel::read(memory.clone(), number, descriptor(number))? {
+                up_channels.push(channel);
+            }
+        }
+
+        let mut down_channels = Vec::new();
+        for number in 0..max_down {
+            let descriptor = descriptor(max_up + number);
+            if let Some(channel) = DownChannel::read(memory.clone(), number, descriptor)? {
+                down_channels.push(channel);
+            }
+        }
+
+        log::debug!(
+            "Found an RTT control block at {:#010x} with {} up and {} down channels",
+            address,
+            up_channels.len(),
+            down_channels.len()
+        );
+
+        Ok(Self {
+            address,
+            up_channels,
+            down_channels,
+        })
+    }
+
+    /// The address of the control block.
+    pub fn address(&self) -> u32 {
+        self.address
+    }
+
+    /// The channels which carry data from the target to the host.
+    pub fn up_channels(&self) -> &[UpChannel] {
+        &self.up_channels
+    }
+
+    /// The channels which carry data from the host to the target.
+    pub fn down_channels(&self) -> &[DownChannel] {
+        &self.down_channels
+    }
+
+    /// Returns the up channel with the given number, if it is in use.
+    pub fn up_channel(&self, number: u32) -> Option<&UpChannel> {
+        self.up_channels
+            .iter()
+            .find(|channel| channel.number() == number)
+    }
+
+    /// Returns the down channel with the given number, if it is in use.
+    pub fn down_channel(&self, number: u32) -> Option<&DownChannel> {
+        self.down_channels
+            .iter()
+            .find(|channel| channel.number() == number)
+    }
+}
+
+/// Looks up the address of the `_SEGGER_RTT` control block in an ELF file.
+pub fn control_block_address(elf: &[u8]) -> Result<Option<u32>, RttError> {
+    let elf = Elf::parse(elf)?;
+
+    let address = elf
+        .syms
+        .iter()
+        .find(|symbol| elf.strtab.get_unsafe(symbol.st_name) == Some(CONTROL_BLOCK_SYMBOL))
+        .map(|symbol| symbol.st_value as u32);
+
+    Ok(address)
+}
+
+fn read_count(bytes: &[u8]) -> Result<u32, RttError> {
+    let count = i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+
+    if count < 0 || count as u32 > MAX_CHANNELS {
+        return Err(RttError::ControlBlockCorrupted(format!(
+            "{} is not a valid number of channels",
+            count
+        )));
+    }
+
+    Ok(count as u32)
+}
+
+/// Scans the ranges for the ID of a control block.
+fn find_control_block(memory: &Memory, ranges: &[Range<u32>]) -> Result<u32, Error> {
+    let mut addresses = Vec::new();
+
+    for range in ranges {
+        let mut data = vec![0; (range.end - range.start) as usize];
+        memory.read_block8(range.start, &mut data)?;
+
+        addresses.extend(
+            data.windows(CONTROL_BLOCK_ID.len())
+                .enumerate()
+                .filter(|(_, window)| *window == CONTROL_BLOCK_ID)
+                .map(|(offset, _)| range.start + offset as u32),
+        );
+    }
+
+    match addresses.len() {
+        0 => Err(RttError::ControlBlockNotFound.into()),
+        1 => Ok(addresses[0]),
+        _ => Err(RttError::MultipleControlBlocksFound(addresses).into()),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{Rtt, RttError, ScanRegion};
+    use crate::config::{MemoryRegion, RamRegion};
+    use crate::test_support::RamImage;
+    use crate::{Error, Memory};
+
+    pub(crate) const RAM_BASE: u32 = 0x2000_0000;
+    pub(crate) const CONTROL_BLOCK: u32 = RAM_BASE + 0x100;
+    pub(crate) const UP_BUFFER: u32 = RAM_BASE + 0x200;
+    pub(crate) const DOWN_BUFFER: u32 = RAM_BASE + 0x300;
+    pub(crate) const BUFFER_SIZE: u32 = 16;
+    const NAME: u32 = RAM_BASE + 0x400;
+
+    fn put(data: &mut [u8], address: u32, bytes: &[u8]) {
+        let offset = (address - RAM_BASE) as usize;
+        data[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// A RAM image with a control block, which has two up channels of which only
+    /// the first is in use, and one down channel.
+    pub(crate) fn memory() -> Memory {
+        let mut data = vec![0; 0x800];
+
+        let mut control_block = Vec::new();
+        control_block.extend_from_slice(b"SEGGER RTT\0\0\0\0\0\0");
+        control_block.extend_from_slice(&2u32.to_le_bytes());
+        control_block.extend_from_slice(&1u32.to_le_bytes());
+
+        let descriptors = [
+            [NAME, UP_BUFFER, BUFFER_SIZE, 0, 0, 0],
+            [0, 0, 0, 0, 0, 0],
+            [NAME, DOWN_BUFFER, BUFFER_SIZE, 0, 0, 0],
+        ];
+        for word in descriptors.iter().flatten() {
+            control_block.extend_from_slice(&word.to_le_bytes());
+        }
+
+        put(&mut data, CONTROL_BLOCK, &control_block);
+        put(&mut data, NAME, b"Terminal\0");
+
+        Memory::new(RamImage {
+            base: RAM_BASE,
+            data,
+        })
+    }
+
+    pub(crate) fn memory_map() -> Vec<MemoryRegion> {
+        vec![MemoryRegion::Ram(RamRegion {
+            range: RAM_BASE..RAM_BASE + 0x800,
+            is_boot_memory: false,
+        })]
+    }
+
+    #[test]
+    fn scan_ram_for_control_block() {
+        let rtt = Rtt::attach(memory(), &memory_map(), &ScanRegion::Ram).unwrap();
+
+        assert_eq!(rtt.address(), CONTROL_BLOCK);
+
+        assert_eq!(rtt.up_channels().len(), 1);
+        assert_eq!(rtt.up_channels()[0].number(), 0);
+        assert_eq!(rtt.up_channels()[0].name(), Some("Terminal"));
+        assert_eq!(rtt.up_channels()[0].buffer_size(), BUFFER_SIZE);
+
+        assert_eq!(rtt.down_channels().len(), 1);
+        assert!(rtt.down_channel(0).is_some());
+        assert!(rtt.up_channel(1).is_none());
+    }
+
+    #[test]
+    fn attach_at_exact_address() {
+        let rtt = Rtt::attach(memory(), &[], &ScanRegion::Exact(CONTROL_BLOCK)).unwrap();
+        assert_eq!(rtt.up_channels().len(), 1);
+
+        let result = Rtt::attach(memory(), &[], &ScanRegion::Exact(RAM_BASE));
+        assert!(matches!(
+            result,
+            Err(Error::Rtt(RttError::ControlBlockNotFound))
+        ));
+    }
+
+    #[test]
+    fn missing_control_block() {
+        let range = RAM_BASE..CONTROL_BLOCK;
+        let result = Rtt::attach(memory(), &[], &ScanRegion::Range(range));
+
+        assert!(matches!(
+            result,
+            Err(Error::Rtt(RttError::ControlBlockNotFound))
+        ));
+    }
+
+    #[test]
+    fn corrupted_channel_count() {
+        let memory = memory();
+        memory.write32(CONTROL_BLOCK + 16, 0xffff_ffff).unwrap();
+
+        let result = Rtt::attach(memory, &[], &ScanRegion::Exact(CONTROL_BLOCK));
+        assert!(matches!(
+            result,
+            Err(Error::Rtt(RttError::ControlBlockCorrupted(_)))
+        ));
+    }
+}
@@ -0,0 +1,587 @@
+//! Semihosting, which lets the target use the console and the files of the host.
+//!
+//! A semihosting call halts the core with the operation in `r0` and a pointer
+//! to its parameters in `r1`, or `a0` and `a1` on RISC-V. [`Semihosting::handle`]
+//! performs the operation on the host, writes the result to `r0` and resumes the core.
+//!
+//! ARM cores use `BKPT 0xAB` for a call. On RISC-V the call is an `ebreak` between
+//! `slli x0, x0, 0x1f` and `srai x0, x0, 7`, which tells it apart from a breakpoint.
+//!
+//! The target can open any file the host can access, so only firmware which is
+//! trusted should be run with semihosting.
+
+use crate::{Architecture, Core, CoreStatus, Error, HaltReason};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::time::Instant;
+
+const SYS_OPEN: u32 = 0x01;
+const SYS_CLOSE: u32 = 0x02;
+const SYS_WRITEC: u32 = 0x03;
+const SYS_WRITE0: u32 = 0x04;
+const SYS_WRITE: u32 = 0x05;
+const SYS_READ: u32 = 0x06;
+const SYS_CLOCK: u32 = 0x10;
+const SYS_EXIT: u32 = 0x18;
+
+/// The reason passed to `SYS_EXIT` when the application exits normally.
+pub const ADP_STOPPED_APPLICATION_EXIT: u32 = 0x2_0026;
+
+/// `BKPT 0xAB`
+const ARM_CALL: [u8; 2] = [0xab, 0xbe];
+
+/// `slli x0, x0, 0x1f`, `ebreak` and `srai x0, x0, 7`.
+const RISCV_CALL: [u8; 12] = [
+    0x13, 0x10, 0xf0, 0x01, 0x73, 0x00, 0x10, 0x00, 0x13, 0x50, 0x70, 0x40,
+];
+
+/// The special file name which opens the console.
+const CONSOLE: &[u8] = b":tt";
+
+/// The result of a failed operation, `-1`.
+const FAILED: u32 = u32::MAX;
+
+/// Console output is read from the target in chunks of this size.
+const STRING_CHUNK_SIZE: u32 = 64;
+
+/// `SYS_READ` and `SYS_WRITE` transfer data in chunks of this size, so the
+/// target cannot make the host allocate an arbitrary amount of memory.
+const TRANSFER_CHUNK_SIZE: u32 = 0x1_0000;
+
+/// The longest file name `SYS_OPEN` accepts.
+const MAX_NAME_LENGTH: u32 = 4096;
+
+/// What happened to the core after a semihosting call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SemihostingOutcome {
+    /// The call was performed, and the core is running again.
+    Resumed,
+    /// The target called `SYS_EXIT`, and the core stays halted.
+    ///
+    /// The reason is [`ADP_STOPPED_APPLICATION_EXIT`] if the application exited normally.
+    Exited { reason: u32 },
+}
+
+/// A file which is opened by the target.
+enum Handle {
+    Stdin,
+    Stdout,
+    Stderr,
+    File(File),
+}
+
+/// Performs the semihosting calls of a core.
+pub struct Semihosting {
+    console: Box<dyn Write>,
+    handles: HashMap<u32, Handle>,
+    next_handle: u32,
+    start: Instant,
+}
+
+impl Semihosting {
+    /// Creates a semihosting host, which writes the console output to stdout.
+    pub fn new() -> Self {
+        Self::with_console(io::stdout())
+    }
+
+    /// Creates a semihosting host, which writes the console output to `console`.
+    pub fn with_console(console: impl Write + 'static) -> Self {
+        Self {
+            console: Box::new(console),
+            handles: HashMap::new(),
+            next_handle: 1,
+            start: Instant::now(),
+        }
+    }
+
+    /// Performs the semihosting call the core is halted at.
+    ///
+    /// Returns `None` if the core is running, or halted for another reason.
+    pub fn handle(&mut self, core: &Core) -> Result<Option<SemihostingOutcome>, Error> {
+        if core.status()? != CoreStatus::Halted(HaltReason::Breakpoint) {
+            return Ok(None);
+        }
+
+        let architecture = core.architecture();
+        let registers = core.registers();
+        let pc = core.read_core_reg(registers.program_counter())?;
+
+        let (call_address, mut code) = match architecture {
+            Architecture::ARM => (pc, vec![0; ARM_CALL.len()]),
+            Architecture::RISCV => match pc.checked_sub(4) {
+                Some(address) => (address, vec![0; RISCV_CALL.len()]),
+                None => return Ok(None),
+            },
+        };
+        core.read_8(call_address, &mut code)?;
+
+        if !is_call(architecture, &code) {
+            return Ok(None);
+        }
+
+        let operation = core.read_core_reg(registers.argument_register(0))?;
+        let parameter = core.read_core_reg(registers.argument_register(1))?;
+
+        log::debug!(
+            "Semihosting operation {:#x} with parameter {:#010x}",
+            operation,
+            parameter
+        );
+
+        if operation == SYS_EXIT {
+            // 32 bit targets pass the reason instead of a pointer to it.
+            return Ok(Some(SemihostingOutcome::Exited { reason: parameter }));
+        }
+
+        if let Some(result) = self.perform(core, operation, parameter)? {
+            core.write_core_reg(registers.result_register(0).into(), result)?;
+        }
+
+        // Continue after the breakpoint instruction.
+        let call_size = match architecture {
+            Architecture::ARM => 2,
+            Architecture::RISCV => 4,
+        };
+        core.write_core_reg(registers.program_counter().into(), pc + call_size)?;
+        core.run()?;
+
+        Ok(Some(SemihostingOutcome::Resumed))
+    }
+
+    /// Performs an operation, and returns the value for `r0` if it has a result.
+    fn perform(
+        &mut self,
+        core: &Core,
+        operation: u32,
+        parameter: u32,
+    ) -> Result<Option<u32>, Error> {
+        let result = match operation {
+            SYS_OPEN => {
+                let mut parameters = [0; 3];
+                core.read_32(parameter, &mut parameters)?;
+                let [name, mode, length] = parameters;
+
+                if length > MAX_NAME_LENGTH {
+                    log::warn!("Semihosting file name of {} bytes is too long", length);
+                    return Ok(Some(FAILED));
+                }
+
+                let mut name_bytes = vec![0; length as usize];
+                core.read_8(name, &mut name_bytes)?;
+
+                self.open(&name_bytes, mode)
+            }
+            SYS_CLOSE => {
+                let handle = core.read_word_32(parameter)?;
+
+                match self.handles.remove(&handle) {
+                    Some(_) => 0,
+                    None => FAILED,
+                }
+            }
+            SYS_WRITEC => {
+                let character = core.read_word_8(parameter)?;
+                let _ = self.console.write_all(&[character]);
+                let _ = self.console.flush();
+
+                return Ok(None);
+            }
+            SYS_WRITE0 => {
+                let string = read_string(core, parameter)?;
+                let _ = self.console.write_all(&string);
+                let _ = self.console.flush();
+
+                return Ok(None);
+            }
+            SYS_WRITE => {
+                let mut parameters = [0; 3];
+                core.read_32(parameter, &mut parameters)?;
+                let [handle, address, length] = parameters;
+
+                let mut written = 0;
+                while written < length {
+                    let chunk_size = (length - written).min(TRANSFER_CHUNK_SIZE);
+                    let chunk_address = match address.checked_add(written) {
+                        Some(chunk_address) => chunk_address,
+                        None => break,
+                    };
+
+                    let mut data = vec![0; chunk_size as usize];
+                    core.read_8(chunk_address, &mut data)?;
+
+                    if self.write(handle, &data).is_err() {
+                        break;
+                    }
+                    written += chunk_size;
+                }
+
+                // The number of bytes which were not written.
+                length - written
+            }
+            SYS_READ => {
+                let mut parameters = [0; 3];
+                core.read_32(parameter, &mut parameters)?;
+                let [handle, address, length] = parameters;
+
+                let mut read = 0;
+                while read < length {
+                    let chunk_size = (length - read).min(TRANSFER_CHUNK_SIZE);
+                    let chunk_address = match address.checked_add(read) {
+                        Some(chunk_address) => chunk_address,
+                        None => break,
+                    };
+
+                    let mut data = vec![0; chunk_size as usize];
+                    let count = match self.read(handle, &mut data) {
+                        Ok(count) => count as u32,
+                        Err(_) => break,
+                    };
+
+                    if count > 0 {
+                        core.write_8(chunk_address, &data[..count as usize])?;
+                    }
+                    read += count;
+
+                    // A short read is the end of the file, or all input
+                    // which is available on stdin.
+                    if count < chunk_size {
+                        break;
+                    }
+                }
+
+                // The number of bytes which were not read.
+                length - read
+            }
+            SYS_CLOCK => (self.start.elapsed().as_millis() / 10) as u32,
+            operation => {
+                log::warn!(
+                    "The semihosting operation {:#x} is not supported",
+                    operation
+                );
+                FAILED
+            }
+        };
+
+        Ok(Some(result))
+    }
+
+    /// Opens a file with the mode of `fopen`, given as an index into
+    /// `r`, `rb`, `r+`, `r+b`, `w`, `wb`, `w+`, `w+b`, `a`, `ab`, `a+` and `a+b`.
+    fn open(&mut self, name: &[u8], mode: u32) -> u32 {
+        let handle = if name == CONSOLE {
+            match console_handle(mode) {
+                Some(handle) => handle,
+                None => return FAILED,
+            }
+        } else {
+            let options = match open_options(mode) {
+                Some(options) => options,
+                None => return FAILED,
+            };
+
+            match options.open(String::from_utf8_lossy(name).as_ref()) {
+                Ok(file) => Handle::File(file),
+                Err(e) => {
+                    log::debug!("Semihosting failed to open a file: {}", e);
+                    return FAILED;
+                }
+            }
+        };
+
+        let number = self.next_handle;
+        self.next_handle += 1;
+        self.handles.insert(number, handle);
+
+        number
+    }
+
+    fn write(&mut self, handle: u32, data: &[u8]) -> io::Result<()> {
+        match self.handles.get_mut(&handle) {
+            Some(Handle::Stdout) => {
+                self.console.write_all(data)?;
+                self.console.flush()
+            }
+            Some(Handle::Stderr) => io::stderr().write_all(data),
+            Some(Handle::File(file)) => file.write_all(data),
+            Some(Handle::Stdin) | None => Err(io::ErrorKind::InvalidInput.into()),
+        }
+    }
+
+    fn read(&mut self, handle: u32, data: &mut [u8]) -> io::Result<usize> {
+        match self.handles.get_mut(&handle) {
+            Some(Handle::Stdin) => io::stdin().read(data),
+            Some(Handle::File(file)) => file.read(data),
+            Some(Handle::Stdout) | Some(Handle::Stderr) | None => {
+                Err(io::ErrorKind::InvalidInput.into())
+            }
+        }
+    }
+}
+
+impl Default for Semihosting {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Checks if `code` is a semihosting call, which starts at the program counter
+/// on ARM and one instruction before it on RISC-V.
+fn is_call(architecture: Architecture, code: &[u8]) -> bool {
+    match architecture {
+        Architecture::ARM => code == ARM_CALL,
+        Architecture::RISCV => code == RISCV_CALL,
+    }
+}
+
+/// The console is opened for reading with the modes `r` to `r+b`, for writing
+/// with `w` to `w+b`, and as stderr with the append modes.
+fn console_handle(mode: u32) -> Option<Handle> {
+    match mode {
+        0..=3 => Some(Handle::Stdin),
+        4..=7 => Some(Handle::Stdout),
+        8..=11 => Some(Handle::Stderr),
+        _ => None,
+    }
+}
+
+fn open_options(mode: u32) -> Option<OpenOptions> {
+    let mut options = OpenOptions::new();
+
+    // The binary flag makes no difference on the host.
+    match mode / 2 {
+        0 => options.read(true),
+        1 => options.read(true).write(true),
+        2 => options.write(true).create(true).truncate(true),
+        3 => options.read(true).write(true).create(true).truncate(true),
+        4 => options.append(true).create(true),
+        5 => options.read(true).append(true).create(true),
+        _ => return None,
+    };
+
+    Some(options)
+}
+
+/// Reads a null terminated string.
+fn read_string(core: &Core, mut address: u32) -> Result<Vec<u8>, Error> {
+    let mut string = Vec::new();
+
+    loop {
+        // The chunks are aligned, so no memory after the end of a region is read.
+        let chunk_size = STRING_CHUNK_SIZE - address % STRING_CHUNK_SIZE;
+        let mut chunk = vec![0; chunk_size as usize];
+        core.read_8(address, &mut chunk)?;
+
+        match chunk.iter().position(|&byte| byte == 0) {
+            Some(end) => {
+                string.extend_from_slice(&chunk[..end]);
+                return Ok(string);
+            }
+            None => string.extend_from_slice(&chunk),
+        }
+
+        address += chunk_size;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        console_handle, is_call, Handle, Semihosting, SemihostingOutcome,
+        ADP_STOPPED_APPLICATION_EXIT, ARM_CALL, FAILED, SYS_EXIT, SYS_OPEN, SYS_READ, SYS_WRITE,
+        SYS_WRITE0,
+    };
+    use crate::test_support::{MockCore, RamImage};
+    use crate::{Architecture, Core, HaltReason, Memory};
+    use std::cell::RefCell;
+    use std::io::{self, Write};
+    use std::rc::Rc;
+
+    const RAM_BASE: u32 = 0x2000_0000;
+
+    /// A console which can be read after it is moved into [`Semihosting`].
+    #[derive(Clone, Default)]
+    struct Console(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Console {
+        fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(data)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn core() -> Core {
+        let memory = Memory::new(RamImage {
+            base: RAM_BASE,
+            data: vec![0; 0x400],
+        });
+
+        MockCore::start(&[], memory).0
+    }
+
+    /// Writes the parameter block and the name for `SYS_OPEN`, and opens the file.
+    fn open(semihosting: &mut Semihosting, core: &mut Core, name: &[u8], mode: u32) -> u32 {
+        core.write_8(RAM_BASE + 0x100, name).unwrap();
+        core.write_32(RAM_BASE, &[RAM_BASE + 0x100, mode, name.len() as u32])
+            .unwrap();
+
+        semihosting
+            .perform(core, SYS_OPEN, RAM_BASE)
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn detect_calls() {
+        assert!(is_call(Architecture::ARM, &[0xab, 0xbe]));
+        // BKPT 0
+        assert!(!is_call(Architecture::ARM, &[0x00, 0xbe]));
+
+        let riscv_call = [
+            0x13, 0x10, 0xf0, 0x01, 0x73, 0x00, 0x10, 0x00, 0x13, 0x50, 0x70, 0x40,
+        ];
+        assert!(is_call(Architecture::RISCV, &riscv_call));
+
+        // An ebreak without the surrounding instructions is a breakpoint.
+        let mut breakpoint = riscv_call;
+        breakpoint[..4].copy_from_slice(&[0x13, 0x00, 0x00, 0x00]);
+        assert!(!is_call(Architecture::RISCV, &breakpoint));
+    }
+
+    #[test]
+    fn console_modes() {
+        assert!(matches!(console_handle(0), Some(Handle::Stdin)));
+        assert!(matches!(console_handle(4), Some(Handle::Stdout)));
+        assert!(matches!(console_handle(9), Some(Handle::Stderr)));
+        assert!(console_handle(12).is_none());
+    }
+
+    #[test]
+    fn write_to_console() {
+        let console = Console::default();
+        let mut semihosting = Semihosting::with_console(console.clone());
+        let mut core = core();
+
+        let handle = open(&mut semihosting, &mut core, b":tt", 4);
+        assert_ne!(handle, FAILED);
+
+        core.write_8(RAM_BASE + 0x200, b"Hello").unwrap();
+        core.write_32(RAM_BASE, &[handle, RAM_BASE + 0x200, 5])
+            .unwrap();
+
+        let result = semihosting.perform(&core, SYS_WRITE, RAM_BASE).unwrap();
+        assert_eq!(result, Some(0));
+        assert_eq!(console.0.borrow().as_slice(), b"Hello");
+
+        // The console cannot be read after it is opened for writing.
+        core.write_32(RAM_BASE, &[handle, RAM_BASE + 0x200, 5])
+            .unwrap();
+        let result = semihosting.perform(&core, SYS_READ, RAM_BASE).unwrap();
+        assert_eq!(result, Some(5));
+    }
+
+    #[test]
+    fn read_from_file() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"semihosting").unwrap();
+
+        let mut semihosting = Semihosting::with_console(Console::default());
+        let mut core = core();
+
+        let name = file.path().to_str().unwrap().as_bytes();
+        let handle = open(&mut semihosting, &mut core, name, 0);
+        assert_ne!(handle, FAILED);
+
+        // The target asks for much more than the file contains, which is
+        // read in chunks instead of being allocated at once.
+        core.write_32(RAM_BASE, &[handle, RAM_BASE + 0x200, u32::MAX])
+            .unwrap();
+
+        let result = semihosting.perform(&core, SYS_READ, RAM_BASE).unwrap();
+        assert_eq!(result, Some(u32::MAX - 11));
+
+        let mut data = [0; 11];
+        core.read_8(RAM_BASE + 0x200, &mut data).unwrap();
+        assert_eq!(&data, b"semihosting");
+    }
+
+    #[test]
+    fn open_fails() {
+        let mut semihosting = Semihosting::with_console(Console::default());
+        let mut core = core();
+
+        // An invalid mode
+        assert_eq!(open(&mut semihosting, &mut core, b":tt", 12), FAILED);
+
+        // A name which is too long is not read.
+        core.write_32(RAM_BASE, &[RAM_BASE + 0x100, 0, u32::MAX])
+            .unwrap();
+        let result = semihosting.perform(&core, SYS_OPEN, RAM_BASE).unwrap();
+        assert_eq!(result, Some(FAILED));
+    }
+
+    #[test]
+    fn unsupported_operation_fails() {
+        let mut semihosting = Semihosting::with_console(Console::default());
+        let core = core();
+
+        // SYS_SYSTEM
+        let result = semihosting.perform(&core, 0x12, RAM_BASE).unwrap();
+        assert_eq!(result, Some(FAILED));
+    }
+
+    #[test]
+    fn handle_calls() {
+        const CALL: u32 = RAM_BASE + 0x300;
+
+        let mut data = vec![0; 0x400];
+        data[0x300..0x302].copy_from_slice(&ARM_CALL);
+        data[0x100..0x107].copy_from_slice(b"Hello\n\0");
+
+        // The core spins after the call, so it is found there after it is resumed.
+        let memory = Memory::new(RamImage {
+            base: RAM_BASE,
+            data,
+        });
+        let (core, state) = MockCore::start(&[(CALL + 2, (CALL + 2, 0))], memory);
+
+        let console = Console::default();
+        let mut semihosting = Semihosting::with_console(console.clone());
+
+        // A halt which is not caused by a breakpoint is no call.
+        state.borrow_mut().set_register(15, CALL);
+        assert_eq!(semihosting.handle(&core).unwrap(), None);
+
+        state.borrow_mut().halt_reason = Some(HaltReason::Breakpoint);
+        state.borrow_mut().set_register(0, SYS_WRITE0);
+        state.borrow_mut().set_register(1, RAM_BASE + 0x100);
+
+        let outcome = semihosting.handle(&core).unwrap();
+        assert_eq!(outcome, Some(SemihostingOutcome::Resumed));
+        assert_eq!(console.0.borrow().as_slice(), b"Hello\n");
+        assert!(!state.borrow().halted);
+        assert_eq!(state.borrow().pc(), CALL + 2);
+
+        {
+            let mut state = state.borrow_mut();
+            state.halted = true;
+            state.set_register(15, CALL);
+            state.set_register(0, SYS_EXIT);
+            state.set_register(1, ADP_STOPPED_APPLICATION_EXIT);
+        }
+
+        // The core stays halted at the call after an exit.
+        let outcome = semihosting.handle(&core).unwrap();
+        assert_eq!(
+            outcome,
+            Some(SemihostingOutcome::Exited {
+                reason: ADP_STOPPED_APPLICATION_EXIT
+            })
+        );
+        assert!(state.borrow().halted);
+        assert_eq!(state.borrow().pc(), CALL);
+    }
+}
@@ -1,11 +1,16 @@
 use crate::architecture::{
-    arm::{memory::ADIMemoryInterface, ArmChipInfo, ArmCommunicationInterface},
-    riscv::communication_interface::RiscvCommunicationInterface,
+    arm::{
+        memory::ADIMemoryInterface,
+        sequences::nrf::{self, NrfError},
+        ArmChipInfo, ArmCommunicationInterface,
+    },
+    riscv::{communication_interface::RiscvCommunicationInterface, RiscvChipInfo},
 };
 use crate::config::{
-    ChipInfo, MemoryRegion, RawFlashAlgorithm, RegistryError, Target, TargetSelector,
+    ChipInfo, FlmError, MemoryRegion, RawFlashAlgorithm, RegistryError, Target, TargetSelector,
 };
 use crate::core::Architecture;
+use crate::flashing::option_bytes::OptionBytesFamily;
 use crate::{Core, CoreList, Error, Memory, MemoryList, Probe};
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -44,11 +49,18 @@ impl Session {
                 let (returned_probe, found_chip) =
                     try_arm_autodetect(generic_probe.take().unwrap());
 
-                // Ignore errors during autodetect
-                let found_chip = found_chip.unwrap_or_else(|e| {
-                    log::debug!("Error during autodetect: {}", e);
-                    None
-                });
+                let mut found_chip = match found_chip {
+                    Ok(found_chip) => found_chip,
+                    // A locked chip is reported, so the user knows how to unlock it.
+                    Err(Error::ArchitectureSpecific(e)) if e.is::<NrfError>() => {
+                        return Err(Error::ArchitectureSpecific(e))
+                    }
+                    // Ignore other errors during autodetect
+                    Err(e) => {
+                        log::debug!("Error during autodetect: {}", e);
+                        None
+                    }
+                };
 
                 generic_probe = Some(returned_probe);
 
@@ -56,11 +68,13 @@ impl Session {
                     let riscv_interface =
                         RiscvCommunicationInterface::new(generic_probe.take().unwrap())?;
 
-                    let idcode = riscv_interface.read_idcode();
-
-                    log::debug!("ID Code read over JTAG: {:x?}", idcode);
-
-                    // TODO: Implement autodetect for RISC-V
+                    match RiscvChipInfo::read(&riscv_interface) {
+                        Ok(chip_info) => {
+                            log::debug!("RISC-V chip info: {:x?}", chip_info);
+                            found_chip = Some(chip_info.into());
+                        }
+                        Err(e) => log::debug!("Error during RISC-V autodetect: {}", e),
+                    }
 
                     // This will always work, the interface is created and used only in this function
                     generic_probe = Some(riscv_interface.close().unwrap());
@@ -77,8 +91,14 @@ impl Session {
 
         let session = match target.architecture() {
             Architecture::ARM => {
-                let arm_interface = ArmCommunicationInterface::new(generic_probe.unwrap())
+                let mut arm_interface = ArmCommunicationInterface::new(generic_probe.unwrap())
                     .map_err(|(_probe, err)| err)?;
+
+                if let Some(sequence) = &target.debug_sequence {
+                    sequence.debug_port_setup(&mut arm_interface)?;
+                    sequence.debug_device_unlock(&mut arm_interface)?;
+                }
+
                 ArchitectureSession::Arm(arm_interface)
             }
             Architecture::RISCV => {
@@ -95,8 +115,16 @@ impl Session {
         })
     }
 
+    /// Lists the cores of the target. Each hart of a RISC-V target is a separate core.
     pub fn list_cores(&self) -> CoreList {
-        CoreList::new(vec![self.inner.borrow().target.core_type])
+        let inner = self.inner.borrow();
+
+        let num_cores = match inner.architecture_session {
+            ArchitectureSession::Arm(_) => 1,
+            ArchitectureSession::Riscv(ref riscv_interface) => riscv_interface.num_harts(),
+        };
+
+        CoreList::new(vec![inner.target.core_type; num_cores as usize])
     }
 
     pub fn attach_to_core(&self, n: usize) -> Result<Core, Error> {
@@ -105,10 +133,24 @@ impl Session {
             .get(n)
             .ok_or_else(|| Error::CoreNotFound(n))?;
 
-        match self.inner.borrow().architecture_session {
-            ArchitectureSession::Arm(ref arm_interface) => core.attach_arm(arm_interface.clone()),
+        let inner = self.inner.borrow();
+
+        match inner.architecture_session {
+            ArchitectureSession::Arm(ref arm_interface) => {
+                let mut core = core.attach_arm(arm_interface.clone())?;
+                core.set_memory_map(inner.target.memory_map.clone());
+
+                if let Some(sequence) = &inner.target.debug_sequence {
+                    core.set_debug_sequence(sequence.clone())?;
+                }
+
+                Ok(core)
+            }
             ArchitectureSession::Riscv(ref riscv_interface) => {
-                core.attach_riscv(riscv_interface.clone())
+                let mut core = core.attach_riscv(riscv_interface.for_hart(n as u32))?;
+                core.set_memory_map(inner.target.memory_map.clone());
+
+                Ok(core)
             }
         }
     }
@@ -142,9 +184,36 @@ impl Session {
         self.inner.borrow().target.flash_algorithms.clone()
     }
 
+    /// Adds a flash algorithm, which is used instead of the built-in
+    /// algorithms of the target for the same flash.
+    ///
+    /// See [`Target::add_flash_algorithm`] for details.
+    ///
+    /// [`Target::add_flash_algorithm`]: config/struct.Target.html#method.add_flash_algorithm
+    pub fn add_flash_algorithm(&self, algorithm: RawFlashAlgorithm) -> Result<(), FlmError> {
+        self.inner
+            .borrow_mut()
+            .target
+            .add_flash_algorithm(algorithm)
+    }
+
+    /// Returns the option bytes layout of the target, if its option bytes are supported.
+    pub fn option_bytes_family(&self) -> Option<OptionBytesFamily> {
+        self.inner.borrow().target.option_bytes
+    }
+
+    pub fn architecture(&self) -> Architecture {
+        self.inner.borrow().target.architecture()
+    }
+
     pub fn memory_map(&self) -> Vec<MemoryRegion> {
         self.inner.borrow().target.memory_map.clone()
     }
+
+    /// Returns the path of the SVD file of the target, if its description references one.
+    pub fn svd_path(&self) -> Option<String> {
+        self.inner.borrow().target.svd.clone()
+    }
 }
 
 fn try_arm_autodetect(probe: Probe) -> (Probe, Result<Option<ChipInfo>, Error>) {
@@ -161,9 +230,20 @@ fn try_arm_autodetect(probe: Probe) -> (Probe, Result<Option<ChipInfo>, Error>)
                         None
                     });
 
+                // A chip locked by APPROTECT hides its ROM table, so check for it if nothing was found.
+                let locked =
+                    found_chip.is_none() && nrf::is_locked(&mut arm_interface).unwrap_or(false);
+
                 // This will always work, the interface is created and used only in this function
                 let probe = arm_interface.close().unwrap();
 
+                if locked {
+                    return (
+                        probe,
+                        Err(Error::architecture_specific(NrfError::ApProtected)),
+                    );
+                }
+
                 let found_chip = found_chip.map(ChipInfo::from);
 
                 (probe, Ok(found_chip))
@@ -0,0 +1,768 @@
+//! Peripheral registers, which are described by a CMSIS-SVD file.
+//!
+//! A [`Device`] is parsed from the SVD file of a chip, and its registers are
+//! read and written by name through [`Memory`]. The fields of a register are
+//! decoded with the names of their enumerated values.
+//!
+//! Register arrays are expanded into a register for each element. Clusters are
+//! not supported, and are skipped.
+
+use crate::{Error, Memory};
+use roxmltree::Node;
+use std::path::Path;
+use thiserror::Error;
+
+/// The register size of devices which do not specify one.
+const DEFAULT_REGISTER_SIZE: u32 = 32;
+
+#[derive(Error, Debug)]
+pub enum SvdError {
+    #[error("The SVD file could not be read: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("The SVD file is not valid XML: {0}")]
+    Xml(#[from] roxmltree::Error),
+    #[error("The element <{element}> is missing in <{parent}>")]
+    MissingElement {
+        element: &'static str,
+        parent: String,
+    },
+    #[error("'{0}' is not a valid number")]
+    InvalidNumber(String),
+    #[error("The peripheral {0} derives from the unknown peripheral {1}")]
+    UnknownBase(String, String),
+    #[error("There is no peripheral {0}")]
+    UnknownPeripheral(String),
+    #[error("There is no register {0}")]
+    UnknownRegister(String),
+    #[error("There is no field {0}")]
+    UnknownField(String),
+    #[error("The field {field} has no value {value}")]
+    UnknownValue { field: String, value: String },
+    #[error("The value {value:#x} does not fit into the {width} bits of {field}")]
+    ValueTooLarge {
+        field: String,
+        value: u32,
+        width: u32,
+    },
+    #[error("The register {0} has the unsupported size of {1} bits")]
+    UnsupportedSize(String, u32),
+    #[error("The bit range of the field {0} does not fit into a 32 bit register")]
+    InvalidBitRange(String),
+    #[error("The register {0} is outside of the 32 bit address space")]
+    AddressOutOfRange(String),
+    #[error("The register array {0} has an invalid dimIndex")]
+    InvalidDimIndex(String),
+}
+
+/// A device with its peripherals.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Device {
+    pub name: String,
+    pub peripherals: Vec<Peripheral>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Peripheral {
+    pub name: String,
+    pub description: Option<String>,
+    pub base_address: u32,
+    pub registers: Vec<Register>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Register {
+    pub name: String,
+    pub description: Option<String>,
+    /// The address of the register, which is the base address of its peripheral
+    /// plus the address offset.
+    pub address: u32,
+    pub address_offset: u32,
+    /// The size in bits.
+    pub size: u32,
+    pub fields: Vec<Field>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    pub name: String,
+    pub description: Option<String>,
+    pub bit_offset: u32,
+    pub bit_width: u32,
+    pub enumerated_values: Vec<EnumeratedValue>,
+}
+
+/// The name of a value of a field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnumeratedValue {
+    pub name: String,
+    pub description: Option<String>,
+    pub value: u32,
+}
+
+impl Device {
+    /// Loads a device from an SVD file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, SvdError> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Parses the content of an SVD file.
+    pub fn parse(svd: &str) -> Result<Self, SvdError> {
+        let document = roxmltree::Document::parse(svd)?;
+        let device = document.root_element();
+
+        let name = required_text(device, "name")?;
+        let size = optional_number(device, "size")?.unwrap_or(DEFAULT_REGISTER_SIZE);
+
+        let mut peripherals = Vec::new();
+        let mut derived = Vec::new();
+
+        for node in required_child(device, "peripherals")?
+            .children()
+            .filter(|node| node.has_tag_name("peripheral"))
+        {
+            let peripheral = parse_peripheral(node, size)?;
+
+            if let Some(base) = node.attribute("derivedFrom") {
+                derived.push((peripherals.len(), base.to_owned()));
+            }
+
+            peripherals.push(peripheral);
+        }
+
+        // A derived peripheral has the registers of its base at its own address.
+        for (index, base) in derived {
+            let base = peripherals
+                .iter()
+                .find(|peripheral| peripheral.name == base)
+                .cloned()
+                .ok_or_else(|| SvdError::UnknownBase(peripherals[index].name.clone(), base))?;
+
+            let peripheral = &mut peripherals[index];
+
+            if peripheral.description.is_none() {
+                peripheral.description = base.description;
+            }
+
+            if peripheral.registers.is_empty() {
+                peripheral.registers = base.registers;
+
+                for register in &mut peripheral.registers {
+                    register.address = register_address(
+                        &register.name,
+                        peripheral.base_address,
+                        register.address_offset,
+                    )?;
+                }
+            }
+        }
+
+        Ok(Device { name, peripherals })
+    }
+
+    /// Finds a peripheral by its name, which is not case sensitive.
+    pub fn peripheral(&self, name: &str) -> Option<&Peripheral> {
+        self.peripherals
+            .iter()
+            .find(|peripheral| peripheral.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Finds a register by its path, like `RCC.CR`.
+    pub fn find_register(&self, path: &str) -> Result<&Register, SvdError> {
+        let mut names = path.splitn(2, '.');
+        let peripheral_name = names.next().unwrap_or_default();
+
+        let peripheral = self
+            .peripheral(peripheral_name)
+            .ok_or_else(|| SvdError::UnknownPeripheral(peripheral_name.to_owned()))?;
+
+        names
+            .next()
+            .and_then(|register| peripheral.register(register))
+            .ok_or_else(|| SvdError::UnknownRegister(path.to_owned()))
+    }
+
+    /// Finds a field by its path, like `RCC.CR.HSEON`.
+    pub fn find_field(&self, path: &str) -> Result<(&Register, &Field), SvdError> {
+        let split = path
+            .rfind('.')
+            .ok_or_else(|| SvdError::UnknownField(path.to_owned()))?;
+
+        let register = self.find_register(&path[..split])?;
+        let field = register
+            .field(&path[split + 1..])
+            .ok_or_else(|| SvdError::UnknownField(path.to_owned()))?;
+
+        Ok((register, field))
+    }
+}
+
+impl Peripheral {
+    /// Finds a register by its name, which is not case sensitive.
+    pub fn register(&self, name: &str) -> Option<&Register> {
+        self.registers
+            .iter()
+            .find(|register| register.name.eq_ignore_ascii_case(name))
+    }
+}
+
+impl Register {
+    /// Finds a field by its name, which is not case sensitive.
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        self.fields
+            .iter()
+            .find(|field| field.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Reads the register with an access of its size.
+    pub fn read(&self, memory: &Memory) -> Result<u32, Error> {
+        match self.size {
+            8 => Ok(u32::from(memory.read8(self.address)?)),
+            16 => {
+                let mut bytes = [0; 2];
+                memory.read_block8(self.address, &mut bytes)?;
+                Ok(u32::from(u16::from_le_bytes(bytes)))
+            }
+            32 => memory.read32(self.address),
+            size => Err(SvdError::UnsupportedSize(self.name.clone(), size).into()),
+        }
+    }
+
+    /// Writes the register with an access of its size.
+    pub fn write(&self, memory: &Memory, value: u32) -> Result<(), Error> {
+        match self.size {
+            8 => memory.write8(self.address, value as u8),
+            16 => memory.write16(self.address, value as u16),
+            32 => memory.write32(self.address, value),
+            size => Err(SvdError::UnsupportedSize(self.name.clone(), size).into()),
+        }
+    }
+
+    /// Writes a field, and keeps the other fields of the register.
+    pub fn write_field(&self, memory: &Memory, field: &Field, value: u32) -> Result<(), Error> {
+        let register_value = self.read(memory)?;
+        self.write(memory, field.insert(register_value, value)?)
+    }
+}
+
+impl Field {
+    /// The bits of the field in its register.
+    pub fn mask(&self) -> u32 {
+        let bits = if self.bit_width >= 32 {
+            u32::MAX
+        } else {
+            (1 << self.bit_width) - 1
+        };
+
+        bits.checked_shl(self.bit_offset).unwrap_or(0)
+    }
+
+    /// Extracts the value of the field from the value of its register.
+    pub fn extract(&self, register_value: u32) -> u32 {
+        (register_value & self.mask())
+            .checked_shr(self.bit_offset)
+            .unwrap_or(0)
+    }
+
+    /// Replaces the field in the value of its register.
+    pub fn insert(&self, register_value: u32, value: u32) -> Result<u32, SvdError> {
+        let shifted = value.checked_shl(self.bit_offset).unwrap_or(0);
+
+        if shifted >> self.bit_offset != value || shifted & !self.mask() != 0 {
+            return Err(SvdError::ValueTooLarge {
+                field: self.name.clone(),
+                value,
+                width: self.bit_width,
+            });
+        }
+
+        Ok((register_value & !self.mask()) | shifted)
+    }
+
+    /// The enumerated value with the given value.
+    pub fn enumerated_value(&self, value: u32) -> Option<&EnumeratedValue> {
+        self.enumerated_values
+            .iter()
+            .find(|enumerated_value| enumerated_value.value == value)
+    }
+
+    /// Parses a value of the field, which is a number or the name of an enumerated value.
+    pub fn parse_value(&self, value: &str) -> Result<u32, SvdError> {
+        if let Some(enumerated_value) = self
+            .enumerated_values
+            .iter()
+            .find(|enumerated_value| enumerated_value.name.eq_ignore_ascii_case(value))
+        {
+            return Ok(enumerated_value.value);
+        }
+
+        parse_number(value).map_err(|_| SvdError::UnknownValue {
+            field: self.name.clone(),
+            value: value.to_owned(),
+        })
+    }
+}
+
+fn parse_peripheral(node: Node, size: u32) -> Result<Peripheral, SvdError> {
+    let name = required_text(node, "name")?;
+    let base_address = required_number(node, "baseAddress")?;
+    let size = optional_number(node, "size")?.unwrap_or(size);
+
+    let mut registers = Vec::new();
+
+    if let Some(registers_node) = child(node, "registers") {
+        for register in registers_node.children().filter(Node::is_element) {
+            if register.has_tag_name("register") {
+                registers.extend(parse_register(register, base_address, size)?);
+            } else if register.has_tag_name("cluster") {
+                log::warn!(
+                    "Skipping the cluster {} in the peripheral {}, clusters are not supported",
+                    text(register, "name").unwrap_or_default(),
+                    name
+                );
+            }
+        }
+    }
+
+    Ok(Peripheral {
+        name,
+        description: text(node, "description"),
+        base_address,
+        registers,
+    })
+}
+
+/// Parses a register, or the registers of a register array.
+fn parse_register(node: Node, base_address: u32, size: u32) -> Result<Vec<Register>, SvdError> {
+    let name = required_text(node, "name")?;
+    let description = text(node, "description");
+    let address_offset = required_number(node, "addressOffset")?;
+    let size = optional_number(node, "size")?.unwrap_or(size);
+
+    let mut fields = Vec::new();
+    if let Some(fields_node) = child(node, "fields") {
+        for field in fields_node
+            .children()
+            .filter(|node| node.has_tag_name("field"))
+        {
+            fields.push(parse_field(field)?);
+        }
+    }
+
+    let dim = match optional_number(node, "dim")? {
+        Some(dim) => dim,
+        None => {
+            return Ok(vec![Register {
+                address: register_address(&name, base_address, address_offset)?,
+                name,
+                description,
+                address_offset,
+                size,
+                fields,
+            }])
+        }
+    };
+
+    let increment = required_number(node, "dimIncrement")?;
+    let indices = match text(node, "dimIndex") {
+        Some(indices) => {
+            dim_indices(&indices).ok_or_else(|| SvdError::InvalidDimIndex(name.clone()))?
+        }
+        None => (0..dim).map(|index| index.to_string()).collect(),
+    };
+
+    if indices.len() != dim as usize {
+        return Err(SvdError::InvalidDimIndex(name));
+    }
+
+    // The name of an array is `NAME[%s]`, the names of a list of registers contain `%s`.
+    let pattern = name.replace("[%s]", "%s");
+
+    indices
+        .iter()
+        .enumerate()
+        .map(|(position, index)| {
+            let name = pattern.replace("%s", index);
+            let address_offset = (position as u32)
+                .checked_mul(increment)
+                .and_then(|offset| offset.checked_add(address_offset))
+                .ok_or_else(|| SvdError::AddressOutOfRange(name.clone()))?;
+
+            Ok(Register {
+                address: register_address(&name, base_address, address_offset)?,
+                description: description
+                    .as_ref()
+                    .map(|description| description.replace("%s", index)),
+                name,
+                address_offset,
+                size,
+                fields: fields.clone(),
+            })
+        })
+        .collect()
+}
+
+/// The names of the elements of a register array, from a `dimIndex` like
+/// `0-3`, `A-D` or `A,B,C`.
+fn dim_indices(text: &str) -> Option<Vec<String>> {
+    if text.contains(',') {
+        return Some(
+            text.split(',')
+                .map(|index| index.trim().to_owned())
+                .collect(),
+        );
+    }
+
+    let mut bounds = text.splitn(2, '-');
+    let (first, last) = (bounds.next()?.trim(), bounds.next()?.trim());
+
+    if let (Ok(first), Ok(last)) = (first.parse::<u32>(), last.parse::<u32>()) {
+        return Some((first..=last).map(|index| index.to_string()).collect());
+    }
+
+    match (first.as_bytes(), last.as_bytes()) {
+        ([first], [last]) if first.is_ascii_uppercase() && last.is_ascii_uppercase() => Some(
+            (*first..=*last)
+                .map(|index| char::from(index).to_string())
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+fn register_address(name: &str, base_address: u32, address_offset: u32) -> Result<u32, SvdError> {
+    base_address
+        .checked_add(address_offset)
+        .ok_or_else(|| SvdError::AddressOutOfRange(name.to_owned()))
+}
+
+fn parse_field(node: Node) -> Result<Field, SvdError> {
+    let name = required_text(node, "name")?;
+
+    // The position is given in one of three ways.
+    let (bit_offset, bit_width) = if let Some(offset) = optional_number(node, "bitOffset")? {
+        (offset, optional_number(node, "bitWidth")?.unwrap_or(1))
+    } else if let Some(lsb) = optional_number(node, "lsb")? {
+        let msb = required_number(node, "msb")?;
+        let width = msb
+            .checked_sub(lsb)
+            .ok_or_else(|| SvdError::InvalidBitRange(name.clone()))?;
+
+        (lsb, width + 1)
+    } else {
+        let range = required_text(node, "bitRange")?;
+        let invalid = || SvdError::InvalidNumber(range.clone());
+
+        let mut bits = range
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .splitn(2, ':');
+        let msb = parse_number(bits.next().ok_or_else(invalid)?)?;
+        let lsb = parse_number(bits.next().ok_or_else(invalid)?)?;
+
+        let width = msb
+            .checked_sub(lsb)
+            .ok_or_else(|| SvdError::InvalidBitRange(name.clone()))?;
+
+        (lsb, width + 1)
+    };
+
+    // The shifts of `Field::mask` need the field to be inside of the register.
+    let fits = matches!(bit_offset.checked_add(bit_width), Some(end) if end <= 32);
+    if bit_width == 0 || !fits {
+        return Err(SvdError::InvalidBitRange(name));
+    }
+
+    let mut enumerated_values = Vec::new();
+    for value in node
+        .children()
+        .filter(|node| node.has_tag_name("enumeratedValues"))
+        .flat_map(|values| values.children())
+        .filter(|node| node.has_tag_name("enumeratedValue"))
+    {
+        // Default values and values with "don't care" bits have no single value.
+        let number = match text(value, "value").map(|number| parse_number(&number)) {
+            Some(Ok(number)) => number,
+            _ => continue,
+        };
+
+        enumerated_values.push(EnumeratedValue {
+            name: required_text(value, "name")?,
+            description: text(value, "description"),
+            value: number,
+        });
+    }
+
+    Ok(Field {
+        name,
+        description: text(node, "description"),
+        bit_offset,
+        bit_width,
+        enumerated_values,
+    })
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(name))
+}
+
+fn required_child<'a, 'input>(
+    node: Node<'a, 'input>,
+    name: &'static str,
+) -> Result<Node<'a, 'input>, SvdError> {
+    child(node, name).ok_or_else(|| SvdError::MissingElement {
+        element: name,
+        parent: node.tag_name().name().to_owned(),
+    })
+}
+
+/// The text of a child element, with the whitespace of descriptions collapsed.
+fn text(node: Node, name: &str) -> Option<String> {
+    let text = child(node, name)?.text()?;
+    Some(text.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+fn required_text(node: Node, name: &'static str) -> Result<String, SvdError> {
+    text(node, name).ok_or_else(|| SvdError::MissingElement {
+        element: name,
+        parent: node.tag_name().name().to_owned(),
+    })
+}
+
+fn optional_number(node: Node, name: &str) -> Result<Option<u32>, SvdError> {
+    text(node, name).map(|text| parse_number(&text)).transpose()
+}
+
+fn required_number(node: Node, name: &'static str) -> Result<u32, SvdError> {
+    parse_number(&required_text(node, name)?)
+}
+
+/// Parses a decimal, a `0x` prefixed hexadecimal or a `#` prefixed binary number.
+fn parse_number(text: &str) -> Result<u32, SvdError> {
+    let text = text.trim();
+
+    let result = if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        u32::from_str_radix(hex, 16)
+    } else if let Some(binary) = text.strip_prefix('#') {
+        u32::from_str_radix(binary, 2)
+    } else {
+        text.parse()
+    };
+
+    result.map_err(|_| SvdError::InvalidNumber(text.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_number, Device, SvdError};
+    use crate::test_support::RamImage;
+    use crate::Memory;
+
+    const SVD: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<device schemaVersion="1.1">
+  <name>TEST</name>
+  <size>32</size>
+  <peripherals>
+    <peripheral>
+      <name>GPIOA</name>
+      <description>General purpose
+        I/O</description>
+      <baseAddress>0x20000000</baseAddress>
+      <registers>
+        <register>
+          <name>MODER</name>
+          <addressOffset>0x0</addressOffset>
+          <fields>
+            <field>
+              <name>MODER1</name>
+              <bitOffset>2</bitOffset>
+              <bitWidth>2</bitWidth>
+              <enumeratedValues>
+                <enumeratedValue><name>Input</name><value>0</value></enumeratedValue>
+                <enumeratedValue><name>Output</name><value>0b01</value></enumeratedValue>
+                <enumeratedValue><name>Alternate</name><value>#10</value></enumeratedValue>
+                <enumeratedValue><name>Unused</name><isDefault>true</isDefault></enumeratedValue>
+              </enumeratedValues>
+            </field>
+            <field>
+              <name>MODER0</name>
+              <bitRange>[1:0]</bitRange>
+            </field>
+          </fields>
+        </register>
+        <register>
+          <name>ODR</name>
+          <addressOffset>0x4</addressOffset>
+          <size>16</size>
+          <fields>
+            <field><name>ODR15</name><lsb>15</lsb><msb>15</msb></field>
+          </fields>
+        </register>
+        <register>
+          <dim>2</dim>
+          <dimIncrement>0x4</dimIncrement>
+          <name>AFR[%s]</name>
+          <description>Alternate function %s</description>
+          <addressOffset>0x20</addressOffset>
+        </register>
+        <register>
+          <dim>2</dim>
+          <dimIncrement>0x8</dimIncrement>
+          <dimIndex>L,H</dimIndex>
+          <name>LCK%s</name>
+          <addressOffset>0x30</addressOffset>
+        </register>
+        <cluster>
+          <name>SKIPPED</name>
+          <addressOffset>0x10</addressOffset>
+        </cluster>
+      </registers>
+    </peripheral>
+    <peripheral derivedFrom="GPIOA">
+      <name>GPIOB</name>
+      <baseAddress>0x20000100</baseAddress>
+    </peripheral>
+  </peripherals>
+</device>"#;
+
+    #[test]
+    fn parse_device() {
+        let device = Device::parse(SVD).unwrap();
+
+        assert_eq!(device.name, "TEST");
+        assert_eq!(device.peripherals.len(), 2);
+
+        let gpioa = device.peripheral("gpioa").unwrap();
+        assert_eq!(gpioa.description.as_deref(), Some("General purpose I/O"));
+        assert_eq!(gpioa.registers.len(), 6);
+
+        let moder = gpioa.register("MODER").unwrap();
+        let moder1 = moder.field("MODER1").unwrap();
+        assert_eq!((moder1.bit_offset, moder1.bit_width), (2, 2));
+        // "0b01" is not an SVD number, so only two values remain.
+        assert_eq!(moder1.enumerated_values.len(), 2);
+        assert_eq!(moder1.enumerated_value(2).unwrap().name, "Alternate");
+
+        let moder0 = moder.field("MODER0").unwrap();
+        assert_eq!((moder0.bit_offset, moder0.bit_width), (0, 2));
+
+        let odr = gpioa.register("ODR").unwrap();
+        assert_eq!(odr.size, 16);
+        assert_eq!(odr.field("ODR15").unwrap().bit_width, 1);
+    }
+
+    #[test]
+    fn register_arrays() {
+        let device = Device::parse(SVD).unwrap();
+
+        let afr1 = device.find_register("GPIOA.AFR1").unwrap();
+        assert_eq!(afr1.address, 0x2000_0024);
+        assert_eq!(afr1.description.as_deref(), Some("Alternate function 1"));
+
+        let lckh = device.find_register("GPIOB.LCKH").unwrap();
+        assert_eq!(lckh.address, 0x2000_0138);
+    }
+
+    #[test]
+    fn invalid_bit_ranges() {
+        let field = |position: &str| {
+            let svd = SVD.replace("<bitRange>[1:0]</bitRange>", position);
+            Device::parse(&svd)
+        };
+
+        assert!(matches!(
+            field("<lsb>3</lsb><msb>1</msb>"),
+            Err(SvdError::InvalidBitRange(_))
+        ));
+        assert!(matches!(
+            field("<bitOffset>32</bitOffset>"),
+            Err(SvdError::InvalidBitRange(_))
+        ));
+        assert!(matches!(
+            field("<bitOffset>30</bitOffset><bitWidth>4</bitWidth>"),
+            Err(SvdError::InvalidBitRange(_))
+        ));
+        assert!(field("<bitOffset>0</bitOffset><bitWidth>32</bitWidth>").is_ok());
+    }
+
+    #[test]
+    fn dim_indices() {
+        assert_eq!(super::dim_indices("1-3").unwrap(), ["1", "2", "3"]);
+        assert_eq!(super::dim_indices("A-C").unwrap(), ["A", "B", "C"]);
+        assert_eq!(super::dim_indices("TX, RX").unwrap(), ["TX", "RX"]);
+        assert!(super::dim_indices("A-3").is_none());
+    }
+
+    #[test]
+    fn derived_peripheral() {
+        let device = Device::parse(SVD).unwrap();
+
+        let odr = device.find_register("GPIOB.ODR").unwrap();
+        assert_eq!(odr.address, 0x2000_0104);
+        assert_eq!(
+            device.peripheral("GPIOB").unwrap().description.as_deref(),
+            Some("General purpose I/O")
+        );
+    }
+
+    #[test]
+    fn find_by_path() {
+        let device = Device::parse(SVD).unwrap();
+
+        let (register, field) = device.find_field("GPIOA.MODER.MODER1").unwrap();
+        assert_eq!(register.name, "MODER");
+        assert_eq!(field.name, "MODER1");
+
+        assert!(matches!(
+            device.find_register("GPIOC.MODER"),
+            Err(SvdError::UnknownPeripheral(_))
+        ));
+        assert!(matches!(
+            device.find_field("GPIOA.MODER.MODER9"),
+            Err(SvdError::UnknownField(_))
+        ));
+    }
+
+    #[test]
+    fn field_values() {
+        let device = Device::parse(SVD).unwrap();
+        let (_, field) = device.find_field("GPIOA.MODER.MODER1").unwrap();
+
+        assert_eq!(field.mask(), 0b1100);
+        assert_eq!(field.extract(0b1011), 0b10);
+        assert_eq!(field.insert(0b1111, 0b01).unwrap(), 0b0111);
+        assert!(matches!(
+            field.insert(0, 4),
+            Err(SvdError::ValueTooLarge { .. })
+        ));
+
+        assert_eq!(field.parse_value("alternate").unwrap(), 2);
+        assert_eq!(field.parse_value("0x1").unwrap(), 1);
+        assert!(field.parse_value("Analog").is_err());
+    }
+
+    #[test]
+    fn read_and_write_registers() {
+        let device = Device::parse(SVD).unwrap();
+        let memory = Memory::new(RamImage {
+            base: 0x2000_0000,
+            data: vec![0; 0x200],
+        });
+
+        let (moder, moder1) = device.find_field("GPIOB.MODER.MODER1").unwrap();
+        moder.write(&memory, 0b0011).unwrap();
+        moder.write_field(&memory, moder1, 0b10).unwrap();
+        assert_eq!(memory.read32(0x2000_0100).unwrap(), 0b1011);
+
+        let odr = device.find_register("GPIOA.ODR").unwrap();
+        odr.write(&memory, 0x8001).unwrap();
+        assert_eq!(odr.read(&memory).unwrap(), 0x8001);
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(parse_number("0x1F").unwrap(), 31);
+        assert_eq!(parse_number("#101").unwrap(), 5);
+        assert_eq!(parse_number(" 12 ").unwrap(), 12);
+        assert!(parse_number("1x0").is_err());
+    }
+}
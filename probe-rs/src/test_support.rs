@@ -0,0 +1,302 @@
+//! Fakes of cores and memory which are shared by the tests of several modules.
+
+use crate::architecture::arm::core::ARM_REGISTER_FILE;
+use crate::core::{CoreInformation, CoreRegisterAddress, CoreStatus, HaltReason, RegisterFile};
+use crate::{error, Architecture, Core, CoreInterface, DebugProbeError, Memory, MemoryInterface};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// How many instructions `run` executes before the core counts as running
+/// without ever halting.
+const MAX_INSTRUCTIONS: usize = 1000;
+
+const STACK_POINTER: u16 = 13;
+const PROGRAM_COUNTER: u16 = 15;
+
+/// The state of a [`MockCore`], which tests can inspect and change.
+#[derive(Default)]
+pub(crate) struct MockState {
+    /// The registers hold 64 bits, like the registers of RV64 cores.
+    pub(crate) registers: HashMap<u16, u64>,
+    /// The next program counter, and the change of the stack pointer, by
+    /// the address of an instruction. Other instructions are 4 bytes long.
+    pub(crate) jumps: HashMap<u32, (u32, i32)>,
+    pub(crate) units: Vec<Option<u32>>,
+    pub(crate) halted: bool,
+    /// The reason `status` reports while the core is halted, `Unknown` if it is not set.
+    pub(crate) halt_reason: Option<HaltReason>,
+    /// The number of the call to `set_breakpoint` which fails.
+    pub(crate) failing_set: Option<usize>,
+    pub(crate) set_calls: usize,
+    /// The number of the call to `clear_breakpoint` which fails.
+    pub(crate) failing_clear: Option<usize>,
+    pub(crate) clear_calls: usize,
+}
+
+impl MockState {
+    pub(crate) fn register(&self, register: u16) -> u32 {
+        self.register_64(register) as u32
+    }
+
+    pub(crate) fn register_64(&self, register: u16) -> u64 {
+        self.registers.get(&register).copied().unwrap_or(0)
+    }
+
+    pub(crate) fn set_register(&mut self, register: u16, value: u32) {
+        self.registers.insert(register, u64::from(value));
+    }
+
+    pub(crate) fn pc(&self) -> u32 {
+        self.register(PROGRAM_COUNTER)
+    }
+
+    pub(crate) fn sp(&self) -> u32 {
+        self.register(STACK_POINTER)
+    }
+
+    pub(crate) fn set_sp(&mut self, value: u32) {
+        self.set_register(STACK_POINTER, value);
+    }
+
+    fn execute(&mut self) {
+        let pc = self.pc();
+        let (next, sp_change) = self.jumps.get(&pc).copied().unwrap_or((pc + 4, 0));
+
+        self.set_register(PROGRAM_COUNTER, next);
+        self.set_sp((self.sp() as i32 + sp_change) as u32);
+    }
+}
+
+/// A simulated ARM core, which executes a program of jumps.
+///
+/// It starts halted with four breakpoint units, at 0x100 with the stack pointer
+/// at 0x2000_0100.
+pub(crate) struct MockCore {
+    state: Rc<RefCell<MockState>>,
+    memory: Memory,
+}
+
+impl MockCore {
+    /// Creates a core, and the state which the tests use to control it.
+    pub(crate) fn start(
+        jumps: &[(u32, (u32, i32))],
+        memory: Memory,
+    ) -> (Core, Rc<RefCell<MockState>>) {
+        let mut state = MockState {
+            jumps: jumps.iter().copied().collect(),
+            units: vec![None; 4],
+            halted: true,
+            ..Default::default()
+        };
+        state.set_register(PROGRAM_COUNTER, 0x100);
+        state.set_sp(0x2000_0100);
+
+        let state = Rc::new(RefCell::new(state));
+        let core = Core::new(MockCore {
+            state: state.clone(),
+            memory,
+        });
+
+        (core, state)
+    }
+}
+
+fn failure() -> error::Error {
+    error::Error::Probe(DebugProbeError::Unknown)
+}
+
+impl CoreInterface for MockCore {
+    fn wait_for_core_halted(&self) -> Result<(), error::Error> {
+        Ok(())
+    }
+
+    fn core_halted(&self) -> Result<bool, error::Error> {
+        Ok(self.state.borrow().halted)
+    }
+
+    fn status(&self) -> Result<CoreStatus, error::Error> {
+        let state = self.state.borrow();
+
+        if state.halted {
+            Ok(CoreStatus::Halted(
+                state.halt_reason.unwrap_or(HaltReason::Unknown),
+            ))
+        } else {
+            Ok(CoreStatus::Running)
+        }
+    }
+
+    fn halt(&self) -> Result<CoreInformation, error::Error> {
+        let mut state = self.state.borrow_mut();
+        state.halted = true;
+        Ok(CoreInformation { pc: state.pc() })
+    }
+
+    fn run(&self) -> Result<(), error::Error> {
+        let mut state = self.state.borrow_mut();
+
+        for _ in 0..MAX_INSTRUCTIONS {
+            // Like a comparator, a unit halts the core before the
+            // instruction at its address is executed.
+            if state.units.contains(&Some(state.pc())) {
+                return Ok(());
+            }
+            state.execute();
+        }
+
+        state.halted = false;
+        Ok(())
+    }
+
+    fn reset(&self) -> Result<(), error::Error> {
+        Err(failure())
+    }
+
+    fn reset_and_halt(&self) -> Result<CoreInformation, error::Error> {
+        Err(failure())
+    }
+
+    fn step(&self) -> Result<CoreInformation, error::Error> {
+        let mut state = self.state.borrow_mut();
+        if !state.units.contains(&Some(state.pc())) {
+            state.execute();
+        }
+        Ok(CoreInformation { pc: state.pc() })
+    }
+
+    fn read_core_reg(&self, address: CoreRegisterAddress) -> Result<u32, error::Error> {
+        Ok(self.state.borrow().register(address.0))
+    }
+
+    fn write_core_reg(&self, address: CoreRegisterAddress, value: u32) -> Result<(), error::Error> {
+        self.state.borrow_mut().set_register(address.0, value);
+        Ok(())
+    }
+
+    fn read_core_reg_64(&self, address: CoreRegisterAddress) -> Result<u64, error::Error> {
+        Ok(self.state.borrow().register_64(address.0))
+    }
+
+    fn write_core_reg_64(
+        &self,
+        address: CoreRegisterAddress,
+        value: u64,
+    ) -> Result<(), error::Error> {
+        self.state.borrow_mut().registers.insert(address.0, value);
+        Ok(())
+    }
+
+    fn get_available_breakpoint_units(&self) -> Result<u32, error::Error> {
+        Ok(self.state.borrow().units.len() as u32)
+    }
+
+    fn enable_breakpoints(&mut self, _state: bool) -> Result<(), error::Error> {
+        Ok(())
+    }
+
+    fn set_breakpoint(&self, unit: usize, address: u32) -> Result<(), error::Error> {
+        let mut state = self.state.borrow_mut();
+        state.set_calls += 1;
+        if state.failing_set == Some(state.set_calls) {
+            return Err(failure());
+        }
+        state.units[unit] = Some(address);
+        Ok(())
+    }
+
+    fn clear_breakpoint(&self, unit: usize) -> Result<(), error::Error> {
+        let mut state = self.state.borrow_mut();
+        state.clear_calls += 1;
+        if state.failing_clear == Some(state.clear_calls) {
+            return Err(failure());
+        }
+        state.units[unit] = None;
+        Ok(())
+    }
+
+    fn registers(&self) -> &'static RegisterFile {
+        &ARM_REGISTER_FILE
+    }
+
+    fn memory(&self) -> Memory {
+        self.memory.clone()
+    }
+
+    fn hw_breakpoints_enabled(&self) -> bool {
+        true
+    }
+
+    fn architecture(&self) -> Architecture {
+        Architecture::ARM
+    }
+}
+
+/// A RAM image, which fails accesses outside of it.
+pub(crate) struct RamImage {
+    pub(crate) base: u32,
+    pub(crate) data: Vec<u8>,
+}
+
+impl RamImage {
+    fn range(&self, address: u32, length: usize) -> Result<std::ops::Range<usize>, error::Error> {
+        let start = address
+            .checked_sub(self.base)
+            .map(|offset| offset as usize)
+            .filter(|offset| offset + length <= self.data.len())
+            .ok_or_else(failure)?;
+
+        Ok(start..start + length)
+    }
+}
+
+impl MemoryInterface for RamImage {
+    fn read32(&mut self, address: u32) -> Result<u32, error::Error> {
+        let mut data = [0];
+        self.read_block32(address, &mut data)?;
+        Ok(data[0])
+    }
+
+    fn read8(&mut self, address: u32) -> Result<u8, error::Error> {
+        Ok(self.data[self.range(address, 1)?][0])
+    }
+
+    fn read_block32(&mut self, address: u32, data: &mut [u32]) -> Result<(), error::Error> {
+        let range = self.range(address, data.len() * 4)?;
+        for (word, bytes) in data.iter_mut().zip(self.data[range].chunks(4)) {
+            *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        Ok(())
+    }
+
+    fn read_block8(&mut self, address: u32, data: &mut [u8]) -> Result<(), error::Error> {
+        let range = self.range(address, data.len())?;
+        data.copy_from_slice(&self.data[range]);
+        Ok(())
+    }
+
+    fn write32(&mut self, address: u32, data: u32) -> Result<(), error::Error> {
+        self.write_block8(address, &data.to_le_bytes())
+    }
+
+    fn write16(&mut self, address: u32, data: u16) -> Result<(), error::Error> {
+        self.write_block8(address, &data.to_le_bytes())
+    }
+
+    fn write8(&mut self, address: u32, data: u8) -> Result<(), error::Error> {
+        self.write_block8(address, &[data])
+    }
+
+    fn write_block32(&mut self, address: u32, data: &[u32]) -> Result<(), error::Error> {
+        for (index, word) in data.iter().enumerate() {
+            self.write32(address + index as u32 * 4, *word)?;
+        }
+        Ok(())
+    }
+
+    fn write_block8(&mut self, address: u32, data: &[u8]) -> Result<(), error::Error> {
+        let range = self.range(address, data.len())?;
+        self.data[range].copy_from_slice(data);
+        Ok(())
+    }
+}
@@ -0,0 +1,78 @@
+@ Minimal flash algorithm used as a test fixture for the FLM parser.
+@
+@ It does not program anything, but has the same layout as a Keil FLM.
+@ Build it with:
+@
+@   llvm-mc -triple=thumbv7m-none-eabi -filetype=obj TEST_64.s -o TEST_64.o
+@   rust-lld -flavor gnu -N -e 0 -T TEST_64.ld TEST_64.o -o ../TEST_64.FLM
+@
+@ A copy is used by the tests of target-gen, in target-gen/tests/fixtures/pack/Flash.
+
+    .syntax unified
+    .thumb
+
+    .section PrgCode, "ax"
+    .global Init
+    .type Init, %function
+    .thumb_func
+Init:
+    movs r0, #0
+    bx lr
+
+    .global UnInit
+    .type UnInit, %function
+    .thumb_func
+UnInit:
+    movs r0, #0
+    bx lr
+
+    .global EraseChip
+    .type EraseChip, %function
+    .thumb_func
+EraseChip:
+    movs r0, #0
+    bx lr
+
+    .global EraseSector
+    .type EraseSector, %function
+    .thumb_func
+EraseSector:
+    movs r0, #0
+    bx lr
+
+    .global ProgramPage
+    .type ProgramPage, %function
+    .thumb_func
+ProgramPage:
+    ldr r3, =counter
+    ldr r0, [r3]
+    adds r0, r0, #1
+    str r0, [r3]
+    movs r0, #0
+    bx lr
+    .ltorg
+
+    .section PrgData, "aw"
+counter:
+    .word 0
+    .word 0
+
+    .section DevDscr, "a"
+    .global FlashDevice
+    .type FlashDevice, %object
+FlashDevice:
+    .short 0x0101            @ Vers
+    .ascii "Test Flash 64kB"  @ DevName
+    .space 128 - 15
+    .short 1                 @ DevType (on chip)
+    .word 0x08000000         @ DevAdr
+    .word 0x00010000         @ szDev
+    .word 0x00000400         @ szPage
+    .word 0                  @ Res
+    .byte 0xFF               @ valEmpty
+    .space 3
+    .word 100                @ toProg
+    .word 3000               @ toErase
+    .word 0x00000400, 0x00000000   @ 1 kB sectors from 0x0
+    .word 0x00000800, 0x00008000   @ 2 kB sectors from 0x8000
+    .word 0xFFFFFFFF, 0xFFFFFFFF
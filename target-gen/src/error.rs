@@ -0,0 +1,20 @@
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum GenerateError {
+    #[error("An IO error was encountered: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Reading the pack archive failed: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("No .pdsc file was found in {0}.")]
+    PdscMissing(PathBuf),
+    #[error("Parsing the .pdsc file failed: {0}")]
+    Xml(#[from] roxmltree::Error),
+    #[error("The .pdsc file is invalid: {0}")]
+    InvalidPdsc(String),
+    #[error("Parsing the flash algorithm failed: {0}")]
+    Flm(#[from] probe_rs::config::FlmError),
+    #[error("Serializing the target description failed: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+}
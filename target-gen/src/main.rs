@@ -0,0 +1,52 @@
+mod error;
+mod pack;
+mod pdsc;
+mod yaml;
+
+use error::GenerateError;
+use pack::Pack;
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+#[structopt(
+    name = "target-gen",
+    about = "Generates probe-rs target descriptions from CMSIS-Packs"
+)]
+struct Opt {
+    /// The CMSIS-Pack to read, either a .pack archive or an extracted .pdsc file
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+    /// The directory the target descriptions are written to
+    #[structopt(parse(from_os_str))]
+    output: PathBuf,
+}
+
+fn main() {
+    pretty_env_logger::init();
+
+    let opt = Opt::from_args();
+
+    if let Err(e) = run(&opt.input, &opt.output) {
+        eprintln!("Error generating target descriptions: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(input: &Path, output: &Path) -> Result<(), GenerateError> {
+    let mut pack = Pack::open(input)?;
+
+    let families = pdsc::extract_families(&mut pack)?;
+
+    std::fs::create_dir_all(output)?;
+
+    for family in families {
+        let path = output.join(format!("{}.yaml", family.name));
+
+        println!("Writing {}", path.display());
+
+        std::fs::write(path, yaml::to_target_yaml(&family)?)?;
+    }
+
+    Ok(())
+}
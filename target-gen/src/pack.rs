@@ -0,0 +1,83 @@
+//! Access to the files of a CMSIS-Pack, either as a `.pack` archive or as an extracted `.pdsc` file.
+
+use crate::error::GenerateError;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+pub enum Pack {
+    /// An extracted pack, given by the path to its `.pdsc` file.
+    Directory { pdsc: PathBuf },
+    /// A `.pack` archive, which is a zip file.
+    Archive {
+        path: PathBuf,
+        archive: ZipArchive<File>,
+    },
+}
+
+impl Pack {
+    /// Opens the pack at `path`, which is either a `.pdsc` file or a `.pack` archive.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, GenerateError> {
+        let path = path.as_ref();
+
+        let is_pdsc = path
+            .extension()
+            .map(|ext| ext.eq_ignore_ascii_case("pdsc"))
+            .unwrap_or(false);
+
+        if is_pdsc {
+            Ok(Pack::Directory {
+                pdsc: path.to_owned(),
+            })
+        } else {
+            Ok(Pack::Archive {
+                path: path.to_owned(),
+                archive: ZipArchive::new(File::open(path)?)?,
+            })
+        }
+    }
+
+    /// Reads the contents of the `.pdsc` file of the pack.
+    pub fn read_pdsc(&mut self) -> Result<String, GenerateError> {
+        match self {
+            Pack::Directory { pdsc } => Ok(std::fs::read_to_string(pdsc)?),
+            Pack::Archive { path, archive } => {
+                // The .pdsc file is located in the root of the archive.
+                let name = (0..archive.len())
+                    .filter_map(|i| archive.by_index(i).ok().map(|f| f.name().to_owned()))
+                    .find(|name| {
+                        !name.contains('/') && name.to_ascii_lowercase().ends_with(".pdsc")
+                    })
+                    .ok_or_else(|| GenerateError::PdscMissing(path.clone()))?;
+
+                let mut contents = String::new();
+                archive.by_name(&name)?.read_to_string(&mut contents)?;
+
+                Ok(contents)
+            }
+        }
+    }
+
+    /// Reads a file of the pack, given by its path relative to the `.pdsc` file.
+    pub fn read_file(&mut self, relative_path: &str) -> Result<Vec<u8>, GenerateError> {
+        // Paths in .pdsc files often use Windows path separators.
+        let relative_path = relative_path.replace('\\', "/");
+
+        let mut contents = Vec::new();
+
+        match self {
+            Pack::Directory { pdsc } => {
+                let base = pdsc.parent().unwrap_or_else(|| Path::new("."));
+                File::open(base.join(&relative_path))?.read_to_end(&mut contents)?;
+            }
+            Pack::Archive { archive, .. } => {
+                archive
+                    .by_name(&relative_path)?
+                    .read_to_end(&mut contents)?;
+            }
+        }
+
+        Ok(contents)
+    }
+}
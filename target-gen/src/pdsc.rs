@@ -0,0 +1,405 @@
+//! Extraction of chip families from the `.pdsc` description of a CMSIS-Pack.
+
+use crate::error::GenerateError;
+use crate::pack::Pack;
+use probe_rs::config::{Chip, ChipFamily, FlashRegion, MemoryRegion, RamRegion, RawFlashAlgorithm};
+use roxmltree::{Document, Node};
+use std::borrow::Cow;
+use std::path::Path;
+
+/// The properties of a device, which are inherited from the
+/// family and sub family of the device.
+#[derive(Debug, Clone, Default)]
+struct DeviceProperties {
+    core: Option<String>,
+    /// The memories of the device, with the `id` or `name` they are identified by.
+    memories: Vec<(String, MemoryRegion)>,
+    /// The paths of the flash algorithms of the device, and if they are used by default.
+    algorithms: Vec<(String, bool)>,
+}
+
+impl DeviceProperties {
+    /// Returns the properties of `node`, with the properties of `self` as a base.
+    fn inherit(&self, node: Node) -> Result<Self, GenerateError> {
+        let mut properties = self.clone();
+
+        for child in node.children().filter(Node::is_element) {
+            match child.tag_name().name() {
+                "processor" => {
+                    if let Some(core) = child.attribute("Dcore") {
+                        properties.core = Some(core.to_owned());
+                    }
+                }
+                "memory" => {
+                    if let Some((key, region)) = parse_memory(child)? {
+                        // A memory of a device overrides the memory with the same name of its family.
+                        properties.memories.retain(|(k, _)| *k != key);
+                        properties.memories.push((key, region));
+                    }
+                }
+                "algorithm" => {
+                    let path = required_attribute(child, "name")?;
+                    let default = child.attribute("default") == Some("1");
+                    properties.algorithms.retain(|(p, _)| p != path);
+                    properties.algorithms.push((path.to_owned(), default));
+                }
+                _ => (),
+            }
+        }
+
+        Ok(properties)
+    }
+}
+
+fn required_attribute<'a>(node: Node<'a, '_>, name: &str) -> Result<&'a str, GenerateError> {
+    node.attribute(name).ok_or_else(|| {
+        GenerateError::InvalidPdsc(format!(
+            "The attribute '{}' of a <{}> element is missing.",
+            name,
+            node.tag_name().name()
+        ))
+    })
+}
+
+fn parse_number(value: &str) -> Result<u32, GenerateError> {
+    let parsed = if value.starts_with("0x") || value.starts_with("0X") {
+        u32::from_str_radix(&value[2..], 16)
+    } else {
+        value.parse()
+    };
+
+    parsed.map_err(|_| GenerateError::InvalidPdsc(format!("'{}' is not a valid number.", value)))
+}
+
+/// Parses a `<memory>` element, returning `None` for memories which are neither RAM nor flash.
+fn parse_memory(node: Node) -> Result<Option<(String, MemoryRegion)>, GenerateError> {
+    let start = parse_number(required_attribute(node, "start")?)?;
+    let size = parse_number(required_attribute(node, "size")?)?;
+    let is_boot_memory = node.attribute("startup") == Some("1");
+
+    let range = start..start.saturating_add(size);
+
+    // Older packs use an `id` like `IROM1` or `IRAM1`, newer ones a `name` and the access rights.
+    let (key, is_ram) = if let Some(id) = node.attribute("id") {
+        if id.starts_with("IRAM") {
+            (id, true)
+        } else if id.starts_with("IROM") {
+            (id, false)
+        } else {
+            return Ok(None);
+        }
+    } else {
+        let name = required_attribute(node, "name")?;
+        let access = node.attribute("access").unwrap_or("");
+
+        if access.contains('w') {
+            (name, true)
+        } else if access.contains('x') {
+            (name, false)
+        } else {
+            return Ok(None);
+        }
+    };
+
+    let region = if is_ram {
+        MemoryRegion::Ram(RamRegion {
+            range,
+            is_boot_memory,
+        })
+    } else {
+        MemoryRegion::Flash(FlashRegion {
+            range,
+            is_boot_memory,
+        })
+    };
+
+    Ok(Some((key.to_owned(), region)))
+}
+
+/// Maps a CMSIS `Dcore` to the core names used by probe-rs.
+fn core_name(core: &str) -> Option<&'static str> {
+    match core {
+        "Cortex-M0" | "Cortex-M0+" => Some("M0"),
+        "Cortex-M3" => Some("M3"),
+        "Cortex-M4" => Some("M4"),
+        "Cortex-M7" => Some("M7"),
+        "Cortex-M33" => Some("M33"),
+        _ => None,
+    }
+}
+
+/// The name of a flash algorithm, derived from the name of its file.
+fn algorithm_name(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    Path::new(&path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_else(|| path.to_ascii_lowercase())
+}
+
+/// A chip, before it is sorted into a family.
+struct ExtractedChip {
+    core: &'static str,
+    chip: Chip,
+    algorithms: Vec<(String, bool)>,
+}
+
+/// Collects all chips below `node`, which is a `<family>`, `<subFamily>` or `<device>` element.
+fn extract_chips(
+    node: Node,
+    inherited: &DeviceProperties,
+    chips: &mut Vec<ExtractedChip>,
+) -> Result<(), GenerateError> {
+    let properties = inherited.inherit(node)?;
+
+    let tag = node.tag_name().name();
+
+    if tag == "device" || tag == "variant" {
+        let name_attribute = if tag == "device" { "Dname" } else { "Dvariant" };
+
+        let variants: Vec<_> = node
+            .children()
+            .filter(|child| child.has_tag_name("variant"))
+            .collect();
+
+        // A device with variants is only described by its variants.
+        if !variants.is_empty() {
+            for variant in variants {
+                extract_chips(variant, &properties, chips)?;
+            }
+            return Ok(());
+        }
+
+        let name = required_attribute(node, name_attribute)?;
+
+        let core = match properties
+            .core
+            .as_deref()
+            .map(|core| (core, core_name(core)))
+        {
+            Some((_, Some(core))) => core,
+            Some((core, None)) => {
+                log::warn!("Skipping {}, the core {} is not supported.", name, core);
+                return Ok(());
+            }
+            None => {
+                log::warn!("Skipping {}, no core is specified.", name);
+                return Ok(());
+            }
+        };
+
+        chips.push(ExtractedChip {
+            core,
+            chip: Chip {
+                name: Cow::Owned(name.to_owned()),
+                part: None,
+                idcode: None,
+                svd: None,
+                memory_map: Cow::Owned(
+                    properties
+                        .memories
+                        .iter()
+                        .map(|(_, region)| region.clone())
+                        .collect(),
+                ),
+                flash_algorithms: Cow::Owned(
+                    properties
+                        .algorithms
+                        .iter()
+                        .map(|(path, _)| Cow::Owned(algorithm_name(path)))
+                        .collect(),
+                ),
+            },
+            algorithms: properties.algorithms.clone(),
+        });
+    } else {
+        for child in node
+            .children()
+            .filter(|child| child.has_tag_name("subFamily") || child.has_tag_name("device"))
+        {
+            extract_chips(child, &properties, chips)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Extracts all chip families described by the `.pdsc` file of `pack`.
+///
+/// If the devices of a family have different cores, a separate chip family is
+/// created for each core, as a `ChipFamily` only has a single core type.
+pub fn extract_families(pack: &mut Pack) -> Result<Vec<ChipFamily>, GenerateError> {
+    let pdsc = pack.read_pdsc()?;
+    let document = Document::parse(&pdsc)?;
+
+    let devices = document
+        .root_element()
+        .children()
+        .find(|node| node.has_tag_name("devices"))
+        .ok_or_else(|| GenerateError::InvalidPdsc("No <devices> element was found.".into()))?;
+
+    let mut families = Vec::new();
+
+    for family in devices
+        .children()
+        .filter(|node| node.has_tag_name("family"))
+    {
+        let family_name = required_attribute(family, "Dfamily")?;
+
+        let mut chips = Vec::new();
+        extract_chips(family, &DeviceProperties::default(), &mut chips)?;
+
+        let mut cores: Vec<&'static str> = Vec::new();
+        for chip in &chips {
+            if !cores.contains(&chip.core) {
+                cores.push(chip.core);
+            }
+        }
+
+        for core in &cores {
+            let name = if cores.len() > 1 {
+                format!("{} {}", family_name, core)
+            } else {
+                family_name.to_owned()
+            };
+
+            let mut algorithms: Vec<RawFlashAlgorithm> = Vec::new();
+            let mut variants = Vec::new();
+
+            for chip in chips.iter().filter(|chip| chip.core == *core) {
+                for (path, default) in &chip.algorithms {
+                    let algorithm_name = algorithm_name(path);
+                    if algorithms.iter().any(|a| a.name == algorithm_name) {
+                        continue;
+                    }
+
+                    log::info!("Parsing flash algorithm {}", path);
+                    let data = pack.read_file(path)?;
+                    algorithms.push(RawFlashAlgorithm::from_flm_data(
+                        &algorithm_name,
+                        &data,
+                        *default,
+                    )?);
+                }
+
+                variants.push(chip.chip.clone());
+            }
+
+            families.push(ChipFamily {
+                name: Cow::Owned(name),
+                manufacturer: None,
+                variants: Cow::Owned(variants),
+                flash_algorithms: Cow::Owned(algorithms),
+                core: Cow::Borrowed(core),
+                debug_sequence: None,
+                option_bytes: None,
+            });
+        }
+    }
+
+    Ok(families)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::extract_families;
+    use crate::pack::Pack;
+    use probe_rs::config::{ChipFamily, FlashRegion, MemoryRegion, RamRegion};
+    use std::fs::File;
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+    fn check_families(families: &[ChipFamily]) {
+        assert_eq!(families.len(), 2);
+
+        let m4 = &families[0];
+        assert_eq!(m4.name, "TEST Series M4");
+        assert_eq!(m4.core, "M4");
+        assert_eq!(
+            m4.variants
+                .iter()
+                .map(|chip| &chip.name[..])
+                .collect::<Vec<_>>(),
+            vec!["TEST1C4", "TEST1R4"]
+        );
+        assert_eq!(
+            &m4.variants[0].memory_map[..],
+            &[
+                MemoryRegion::Flash(FlashRegion {
+                    range: 0x0800_0000..0x0801_0000,
+                    is_boot_memory: true,
+                }),
+                MemoryRegion::Ram(RamRegion {
+                    range: 0x2000_0000..0x2000_4000,
+                    is_boot_memory: false,
+                }),
+            ]
+        );
+        assert_eq!(&m4.variants[0].flash_algorithms[..], &["test_64"]);
+        assert_eq!(m4.flash_algorithms.len(), 1);
+        assert_eq!(m4.flash_algorithms[0].name, "test_64");
+        assert!(m4.flash_algorithms[0].default);
+
+        let m0 = &families[1];
+        assert_eq!(m0.name, "TEST Series M0");
+        assert_eq!(m0.core, "M0");
+        assert_eq!(m0.variants.len(), 1);
+        assert_eq!(m0.variants[0].name, "TEST2x4");
+        assert_eq!(m0.variants[0].memory_map.len(), 3);
+    }
+
+    #[test]
+    fn extract_from_pdsc() {
+        let mut pack = Pack::open(format!("{}/pack/Test.TestDevice_DFP.pdsc", FIXTURES)).unwrap();
+
+        check_families(&extract_families(&mut pack).unwrap());
+    }
+
+    /// Packs the files of the extracted fixture into a `.pack` archive in `dir`.
+    fn create_pack(dir: &Path) -> PathBuf {
+        let path = dir.join("Test.TestDevice_DFP.1.0.0.pack");
+        let mut archive = ZipWriter::new(File::create(&path).unwrap());
+
+        for name in &["Test.TestDevice_DFP.pdsc", "Flash/TEST_64.FLM"] {
+            let contents = std::fs::read(format!("{}/pack/{}", FIXTURES, name)).unwrap();
+
+            archive.start_file(*name, FileOptions::default()).unwrap();
+            archive.write_all(&contents).unwrap();
+        }
+
+        archive.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn extract_from_pack() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut pack = Pack::open(create_pack(dir.path())).unwrap();
+
+        check_families(&extract_families(&mut pack).unwrap());
+    }
+
+    #[test]
+    fn generated_yaml_can_be_compiled() {
+        let mut pack = Pack::open(format!("{}/pack/Test.TestDevice_DFP.pdsc", FIXTURES)).unwrap();
+
+        let output = tempfile::tempdir().unwrap();
+        let targets = output.path().join("targets");
+        std::fs::create_dir_all(&targets).unwrap();
+
+        for family in extract_families(&mut pack).unwrap() {
+            let yaml = crate::yaml::to_target_yaml(&family).unwrap();
+            std::fs::write(targets.join(format!("{}.yaml", family.name)), yaml).unwrap();
+        }
+
+        // The generated descriptions have to be usable for the built-in targets.
+        probe_rs_t2rust::run(&targets, output.path().join("targets.rs"));
+
+        let generated = std::fs::read_to_string(output.path().join("targets.rs")).unwrap();
+        assert!(generated.contains("test_64"));
+    }
+}
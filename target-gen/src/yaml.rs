@@ -0,0 +1,37 @@
+//! Serialization of chip families in the format of the target descriptions in `probe-rs/targets`.
+
+use crate::error::GenerateError;
+use probe_rs::config::ChipFamily;
+use serde_yaml::{Mapping, Value};
+
+/// Serializes `family` into the YAML format which is used for the built-in targets.
+///
+/// Compared to the plain serialization of a `ChipFamily`, the flash algorithms are
+/// stored in a map with the algorithm names as keys, and empty fields are omitted.
+pub fn to_target_yaml(family: &ChipFamily) -> Result<String, GenerateError> {
+    let mut value = serde_yaml::to_value(family)?;
+
+    if let Value::Mapping(ref mut mapping) = value {
+        for key in &["manufacturer", "debug_sequence", "option_bytes"] {
+            let key = Value::from(*key);
+            if mapping.get(&key) == Some(&Value::Null) {
+                mapping.remove(&key);
+            }
+        }
+
+        let key = Value::from("flash_algorithms");
+        if let Some(Value::Sequence(algorithms)) = mapping.remove(&key) {
+            let mut by_name = Mapping::new();
+            for algorithm in algorithms {
+                let name = algorithm
+                    .get("name")
+                    .cloned()
+                    .unwrap_or_else(|| Value::from(""));
+                by_name.insert(name, algorithm);
+            }
+            mapping.insert(key, Value::Mapping(by_name));
+        }
+    }
+
+    Ok(serde_yaml::to_string(&value)?)
+}